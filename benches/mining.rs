@@ -0,0 +1,150 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use blockchainlib::transaction::{Input, OutPoint};
+use blockchainlib::{
+    transaction, transaction_root, Block, Hash256, HashAlgorithm, Hashable, MemoryHardFunction,
+    Script, Transaction,
+};
+
+const TRANSACTION_COUNT: usize = 100;
+
+/**
+ * Returns a block with 100 transactions, which its hash covers through their
+ * root.
+ */
+fn create_block() -> Block {
+    let transactions = (0..TRANSACTION_COUNT)
+        .map(|index| Transaction {
+            inputs: vec![Input::new(
+                OutPoint {
+                    transaction_id: Hash256([index as u8; 32]),
+                    index: 0,
+                },
+                transaction::Output {
+                    to_address: format!("sender-{}", index),
+                    value: index as u64 + 1,
+                    locking_script: Script::new(),
+                },
+            )],
+            outputs: vec![transaction::Output {
+                to_address: format!("recipient-{}", index),
+                value: index as u64,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        })
+        .collect();
+
+    Block::new(1, 2, Hash256([3; 32]), transactions, 0x1f00_ffff)
+}
+
+/**
+ * Compares the cost of one nonce attempt:
+ *
+ * - serialize then hash: serializing the whole block into a new vector and
+ *   hashing it (what mining used to do),
+ *
+ * - streaming: writing the whole block into the hasher,
+ *
+ * - midstate: continuing from the hash state of everything but the nonce.
+ *
+ * The throughput is reported in hashes per second.
+ */
+fn nonce_attempt(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("nonce_attempt");
+    group.throughput(Throughput::Elements(1));
+
+    for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3].iter() {
+        let mut block = create_block();
+        group.bench_function(format!("serialize_then_hash/{:?}", algorithm), |bencher| {
+            let mut nonce = 0;
+            bencher.iter(|| {
+                nonce += 1;
+                block.nonce = nonce;
+                black_box(algorithm.digest(&block.bytes()))
+            })
+        });
+
+        let mut block = create_block();
+        group.bench_function(format!("streaming/{:?}", algorithm), |bencher| {
+            let mut nonce = 0;
+            bencher.iter(|| {
+                nonce += 1;
+                block.nonce = nonce;
+                black_box(block.hash_with(*algorithm))
+            })
+        });
+
+        let block = create_block();
+        let mining_state = block.mining_state(*algorithm);
+        group.bench_function(format!("midstate/{:?}", algorithm), |bencher| {
+            let mut nonce = 0;
+            bencher.iter(|| {
+                nonce += 1;
+                black_box(mining_state.hash_with_nonce(nonce))
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/**
+ * Measures the cost of computing the transaction root of a block with 100
+ * transactions: hashing the encoding of each transaction into its id, and
+ * then the tree of ids. It's what validating a block's transactions, or
+ * updating its transaction root after changing them, costs.
+ *
+ * The throughput is reported in transactions per second.
+ */
+fn root_of_transactions(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("transaction_root");
+    group.throughput(Throughput::Elements(TRANSACTION_COUNT as u64));
+
+    let block = create_block();
+    for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3].iter() {
+        group.bench_function(format!("{:?}", algorithm), |bencher| {
+            bencher.iter(|| black_box(transaction_root(&block.transactions, *algorithm)))
+        });
+    }
+
+    group.finish();
+}
+
+/**
+ * Measures the cost of verifying the seal of a block with a memory-hard
+ * proof-of-work function: one evaluation of the function.
+ */
+fn memory_hard_verification(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("memory_hard_verification");
+    group.throughput(Throughput::Elements(1));
+
+    let functions = [
+        MemoryHardFunction::Scrypt {
+            log_n: 10,
+            r: 8,
+            p: 1,
+        },
+        MemoryHardFunction::Argon2id {
+            memory_kib: 4096,
+            iterations: 1,
+            parallelism: 1,
+        },
+    ];
+    let block_hash = create_block().hash();
+    for function in functions.iter() {
+        group.bench_function(format!("{:?}", function), |bencher| {
+            bencher.iter(|| black_box(function.check_difficulty(&block_hash, 0x1f00_ffff)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    nonce_attempt,
+    root_of_transactions,
+    memory_hard_verification
+);
+criterion_main!(benches);
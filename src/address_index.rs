@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use crate::{Address, Block, HashAlgorithm, TransactionId};
+
+/**
+ * Whether an address history event received an output or spent one.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressEventKind {
+    Received,
+    Spent,
+}
+
+/**
+ * An event in the history of an address: an output to the address that a
+ * transaction created, or an output of the address that a transaction spent,
+ * with the index (height) and the timestamp of the block that contains the
+ * transaction.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressEvent {
+    pub kind: AddressEventKind,
+    pub transaction_id: TransactionId,
+    pub value: u64,
+    pub block_index: u32,
+    pub timestamp: u128,
+}
+
+/**
+ * A page of the history of an address, and the total number of events in the
+ * history.
+ */
+#[derive(Debug, PartialEq)]
+pub struct AddressHistory {
+    pub events: Vec<AddressEvent>,
+    pub total: usize,
+}
+
+/**
+ * An index of the history of every address: its events in chain order (by
+ * block, then by transaction, and the spends of a transaction before the
+ * outputs it creates).
+ *
+ * The index is maintained as blocks are connected to the tip of the chain
+ * and disconnected from it (e.g., on a reorganization), so the events of a
+ * disconnected block are always the last ones of their addresses.
+ */
+#[derive(Debug, Default)]
+pub struct AddressIndex {
+    histories: HashMap<Address, Vec<AddressEvent>>,
+}
+
+impl AddressIndex {
+    /**
+     * Returns the history of the given address (empty if the address has
+     * none).
+     */
+    pub fn history(&self, address: &str) -> &[AddressEvent] {
+        self.histories
+            .get(address)
+            .map(|events| events.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /**
+     * Adds the events of the given block, which becomes the tip, with
+     * transaction ids computed with the given algorithm.
+     */
+    pub fn connect_block(&mut self, block: &Block, algorithm: HashAlgorithm) {
+        self.add_events(block_events(block, algorithm));
+    }
+
+    /**
+     * Adds the events of the block that becomes the tip (see block_events),
+     * which are computed before the block is handed to the chain's storage.
+     */
+    pub(crate) fn add_events(&mut self, events: Vec<(Address, AddressEvent)>) {
+        for (address, event) in events {
+            self.histories.entry(address).or_default().push(event);
+        }
+    }
+
+    /**
+     * Removes the events of the given block, which must be the tip.
+     */
+    pub fn disconnect_block(&mut self, block: &Block) {
+        let addresses = block.transactions.iter().flat_map(|transaction| {
+            let spenders = transaction
+                .inputs
+                .iter()
+                .map(|input| &input.previous_output.to_address);
+            let recipients = transaction.outputs.iter().map(|output| &output.to_address);
+
+            spenders.chain(recipients)
+        });
+
+        for address in addresses {
+            if let Some(events) = self.histories.get_mut(address) {
+                while events
+                    .last()
+                    .is_some_and(|event| event.block_index >= block.index)
+                {
+                    events.pop();
+                }
+                if events.is_empty() {
+                    self.histories.remove(address);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Returns the events of the block's transactions in chain order, with the
+ * addresses they belong to.
+ */
+pub(crate) fn block_events(
+    block: &Block,
+    algorithm: HashAlgorithm,
+) -> Vec<(Address, AddressEvent)> {
+    let mut events = vec![];
+    for transaction in &block.transactions {
+        let transaction_id = transaction.id_with(algorithm);
+        let event = |kind, value| AddressEvent {
+            kind,
+            transaction_id,
+            value,
+            block_index: block.index,
+            timestamp: block.timestamp,
+        };
+
+        for input in &transaction.inputs {
+            let output = &input.previous_output;
+            events.push((
+                output.to_address.clone(),
+                event(AddressEventKind::Spent, output.value),
+            ));
+        }
+        for output in &transaction.outputs {
+            events.push((
+                output.to_address.clone(),
+                event(AddressEventKind::Received, output.value),
+            ));
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod address_index_tests {
+    use super::{AddressEvent, AddressEventKind, AddressIndex};
+
+    use crate::test_support::{create_payment, output};
+    use crate::{Block, Hash256, HashAlgorithm, Transaction};
+
+    fn create_blocks() -> (Block, Block) {
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![output("Alice", 5)],
+            lock_time: None,
+        };
+        let payment = create_payment();
+        let block1 = Block::new(0, 10, Hash256::ZERO, vec![coinbase], 0x2100_ffff);
+        let block2 = Block::new(1, 20, Hash256::ZERO, vec![payment], 0x2100_ffff);
+
+        (block1, block2)
+    }
+
+    fn event(
+        kind: AddressEventKind,
+        transaction: &Transaction,
+        value: u64,
+        block: &Block,
+    ) -> AddressEvent {
+        AddressEvent {
+            kind,
+            transaction_id: transaction.id(),
+            value,
+            block_index: block.index,
+            timestamp: block.timestamp,
+        }
+    }
+
+    #[test]
+    fn connect_blocks() {
+        let (block1, block2) = create_blocks();
+        let mut index = AddressIndex::default();
+
+        index.connect_block(&block1, HashAlgorithm::Sha256);
+        index.connect_block(&block2, HashAlgorithm::Sha256);
+
+        let coinbase = &block1.transactions[0];
+        let payment = &block2.transactions[0];
+        assert_eq!(
+            vec![
+                event(AddressEventKind::Received, coinbase, 5, &block1),
+                event(AddressEventKind::Spent, payment, 5, &block2),
+                event(AddressEventKind::Received, payment, 2, &block2),
+            ],
+            index.history("Alice")
+        );
+        assert_eq!(
+            vec![event(AddressEventKind::Received, payment, 3, &block2)],
+            index.history("Bob")
+        );
+        assert!(index.history("Chris").is_empty());
+    }
+
+    #[test]
+    fn disconnect_block() {
+        let (block1, block2) = create_blocks();
+        let mut index = AddressIndex::default();
+        index.connect_block(&block1, HashAlgorithm::Sha256);
+        index.connect_block(&block2, HashAlgorithm::Sha256);
+
+        index.disconnect_block(&block2);
+
+        assert_eq!(
+            vec![event(
+                AddressEventKind::Received,
+                &block1.transactions[0],
+                5,
+                &block1
+            )],
+            index.history("Alice")
+        );
+        assert!(index.history("Bob").is_empty());
+        assert!(!index.histories.contains_key("Bob"));
+
+        index.disconnect_block(&block1);
+
+        assert!(index.histories.is_empty());
+    }
+
+    #[test]
+    fn transaction_ids_use_given_algorithm() {
+        let (block1, _) = create_blocks();
+        let mut index = AddressIndex::default();
+
+        index.connect_block(&block1, HashAlgorithm::Blake3);
+
+        assert_eq!(
+            block1.transactions[0].id_with(HashAlgorithm::Blake3),
+            index.history("Alice")[0].transaction_id
+        );
+    }
+}
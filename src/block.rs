@@ -1,17 +1,29 @@
 use std::fmt::{self, Debug, Formatter};
 
+use primitive_types::U256;
+
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::{ByteWriter, Hasher};
+use crate::merkle::transaction_root;
 use crate::{
-    difficulty_bytes_as_u128, u128_bytes, u32_bytes, u64_bytes, BlockHash, Hashable, Transaction,
+    u128_bytes, u32_bytes, u64_bytes, BlockHash, Hash256, HashAlgorithm, Hashable, Target,
+    Transaction,
 };
 
 /**
- * Blocks contain this information (7 basic attributes):
+ * Blocks contain this information (9 basic attributes):
  *
  * - Index: This block's location within the list of blocks.
  *
  * - Transactions: Any relevant information of events that have occurred for/in
  *   the block.
  *
+ * - Transaction root: The root of the Merkle tree of the transactions (see
+ *   transaction_root), which is what the block's hash covers instead of the
+ *   transactions themselves. A block's header (every attribute but the
+ *   transactions) is then enough to prove that a transaction is in the block
+ *   (see MerkleProof).
+ *
  * - Timestamp: Gives our blockchain a sense of time.
  *
  * - Nonce: A special number used for mining (for proof-of-work [PoW]
@@ -22,17 +34,23 @@ use crate::{
  * - Hash: A cryptographic fingerprint of all the above data concatenated
  *   together.
  *
- * - Difficulty: A measure of how difficult it is to find a hash below a given
- *   target.
+ * - Bits: The compact encoding of the target, a measure of how difficult it
+ *   is to find a hash below it.
+ *
+ * - Seal: Proof that the consensus engine accepts the block which isn't part
+ *   of its hash, such as a validator's signature of the hash in
+ *   proof-of-authority (see ConsensusEngine). It is empty in proof-of-work,
+ *   where the nonce is the proof.
  *
  * Difficulty
  * ----------
  *
- * SHA-256 generates a 32-byte hash. Difficulty (in our case) specifies the
- * unsigned 128-bit integer value that the most significant 16 bytes of the hash
- * of a block must be less than before it is considered "valid" (if those bytes
- * are interpreted as a single number instead of a series of bytes). Difficulty
- * will be stored as a field of the Block struct.
+ * SHA-256 generates a 32-byte hash. The target (in our case) specifies the
+ * unsigned 256-bit integer value that the hash of a block must be less than or
+ * equal to before it is considered "valid" (if the hash's bytes are
+ * interpreted as a single number instead of a series of bytes). The target is
+ * stored in a field of the Block struct in a compact 32-bit encoding, as
+ * Bitcoin does (see Target).
  *
  * Difficulty could also be expressed as:
  *
@@ -41,9 +59,6 @@ use crate::{
  *
  * These options are essentially different ways of expressing the same thing.
  *
- * Bitcoin stores the difficulty value more compactly than this, but this is
- * simpler and we don't have to worry about space efficiency.
- *
  * Little vs Big Endian
  * --------------------
  *
@@ -57,8 +72,8 @@ use crate::{
  * Stored in little-endian order (most-common) | 2a 00 00 00
  *
  * If we treat it like a little-endian representation of a number, the most
- * significant 16 bytes of our hash will appear at the end of our hash's 32-byte
- * vector.
+ * significant bytes of our hash will appear at the end of our hash's 32-byte
+ * array.
  *
  * See: https://crates.io/crates/byteorder
  *
@@ -89,7 +104,9 @@ pub struct Block {
     pub previous_block_hash: BlockHash,
     pub nonce: u64,
     pub transactions: Vec<Transaction>,
-    pub difficulty: u128,
+    pub transaction_root: Hash256,
+    pub bits: u32,
+    pub seal: Vec<u8>,
 }
 
 impl Debug for Block {
@@ -101,7 +118,7 @@ impl Debug for Block {
             buffer,
             "Block[{}]: hash {}, timestamp {}, {} transaction(s), nonce {}",
             &self.index,
-            &hex::encode(&self.hash),
+            &self.hash,
             &self.timestamp,
             &self.transactions.len(),
             &self.nonce
@@ -112,32 +129,53 @@ impl Debug for Block {
 impl Block {
     /**
      * Creates a block with given attributes. Initializes the hash to a
-     * vector of 32 zeros.
+     * hash of 32 zeros, the seal to an empty one and the transaction root to
+     * the root of the transactions computed with SHA-256 (see
+     * update_transaction_root for chains with another transaction algorithm).
      */
     pub fn new(
         index: u32,
         timestamp: u128,
         previous_block_hash: BlockHash,
         transactions: Vec<Transaction>,
-        difficulty: u128,
+        bits: u32,
     ) -> Self {
         Block {
             index,
             timestamp,
-            hash: vec![0; 32],
+            hash: Hash256::ZERO,
             previous_block_hash,
             nonce: 0,
+            transaction_root: transaction_root(&transactions, HashAlgorithm::default()),
             transactions,
-            difficulty,
+            bits,
+            seal: vec![],
         }
     }
 
+    /**
+     * Returns the block's proof-of-work target, or None if its compact
+     * encoding is invalid.
+     */
+    pub fn target(&self) -> Option<Target> {
+        Target::from_compact(self.bits)
+    }
+
+    /**
+     * Returns the work that the block's target represents. A block with an
+     * invalid target represents no work.
+     */
+    pub fn work(&self) -> U256 {
+        self.target()
+            .map_or_else(U256::zero, |target| target.work())
+    }
+
     /**
      * Performs a mining algorithm:
      *
      * 1. Generate a new nonce.
      * 2. Hash bytes (this is the computationally heavy step).
-     * 3. Check the hash against the difficulty.
+     * 3. Check the hash against the target.
      *
      *   a. Insufficient? Go back to step 1.
      *   b. Sufficient? Continue to step 4.
@@ -160,103 +198,256 @@ impl Block {
      *
      * Bitcoin adjusts its difficulty every 2,016 blocks such that the next
      * 2,016 blocks should take two weeks to mine.
+     *
+     * The block is hashed with SHA-256 (see mine_with).
      */
     pub fn mine(&mut self) {
-        for nonce_attempt in 0..(u64::max_value()) {
-            self.nonce = nonce_attempt;
-            let hash = self.hash();
-            if check_difficulty(&hash, self.difficulty) {
+        self.mine_with(HashAlgorithm::default());
+    }
+
+    /**
+     * Performs the mining algorithm with the given hash algorithm.
+     *
+     * Only the nonce changes between attempts, and it is the last field of the
+     * block's hashable bytes. So everything before it is hashed once, and each
+     * attempt continues from a copy of that hash state (see MiningState).
+     */
+    pub fn mine_with(&mut self, algorithm: HashAlgorithm) {
+        let target = match self.target() {
+            Some(target) => target,
+            None => return,
+        };
+        let mining_state = self.mining_state(algorithm);
+
+        for nonce_attempt in 0..(u64::MAX) {
+            let hash = mining_state.hash_with_nonce(nonce_attempt);
+            if target.is_met_by(&hash) {
+                self.nonce = nonce_attempt;
                 self.hash = hash;
 
                 return;
             }
         }
     }
+
+    /**
+     * Returns the hash state of the block's hashable bytes without the nonce.
+     */
+    pub fn mining_state(&self, algorithm: HashAlgorithm) -> MiningState {
+        let mut prefix_hasher = Hasher::new(algorithm);
+        self.write_bytes_without_nonce(&mut prefix_hasher);
+
+        MiningState { prefix_hasher }
+    }
+
+    /**
+     * Sets the transaction root to the root of the block's transactions
+     * computed with the given algorithm (the chain's transaction algorithm),
+     * which must be done after changing them (and before sealing the block).
+     */
+    pub fn update_transaction_root(&mut self, algorithm: HashAlgorithm) {
+        self.transaction_root = transaction_root(&self.transactions, algorithm);
+    }
+
+    /**
+     * Returns a flag that states whether the transaction root is the root of
+     * the block's transactions computed with the given algorithm.
+     */
+    pub fn has_valid_transaction_root(&self, algorithm: HashAlgorithm) -> bool {
+        self.transaction_root == transaction_root(&self.transactions, algorithm)
+    }
+
+    /**
+     * Returns a copy of the block's header: the block without its
+     * transactions.
+     */
+    pub fn header(&self) -> Block {
+        Block {
+            index: self.index,
+            timestamp: self.timestamp,
+            hash: self.hash,
+            previous_block_hash: self.previous_block_hash,
+            nonce: self.nonce,
+            transactions: vec![],
+            transaction_root: self.transaction_root,
+            bits: self.bits,
+            seal: self.seal.clone(),
+        }
+    }
+
+    fn write_bytes_without_nonce<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u32_bytes(self.index));
+        writer.write(&u128_bytes(self.timestamp));
+        writer.write(self.previous_block_hash.as_bytes());
+        writer.write(self.transaction_root.as_bytes());
+        writer.write(&u32_bytes(self.bits));
+    }
+
+    /**
+     * Writes the encoding of the block's header: every field but the
+     * transactions.
+     */
+    pub(crate) fn write_header_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.index.write_encoding(writer);
+        self.timestamp.write_encoding(writer);
+        self.hash.write_encoding(writer);
+        self.previous_block_hash.write_encoding(writer);
+        self.nonce.write_encoding(writer);
+        self.transaction_root.write_encoding(writer);
+        self.bits.write_encoding(writer);
+        writer.write(&u32_bytes(self.seal.len() as u32));
+        writer.write(&self.seal);
+    }
+
+    /**
+     * Reads a block header, which is a block without transactions.
+     */
+    pub(crate) fn read_header_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(Block {
+            index: reader.read_u32()?,
+            timestamp: reader.read_u128()?,
+            hash: reader.read_hash()?,
+            previous_block_hash: reader.read_hash()?,
+            nonce: reader.read_u64()?,
+            transaction_root: reader.read_hash()?,
+            bits: reader.read_u32()?,
+            seal: reader.read_length_prefixed()?.to_vec(),
+            transactions: vec![],
+        })
+    }
 }
 
 impl Hashable for Block {
     /**
-     * Returns a vector of hashable bytes that represents the block.
+     * Writes the hashable bytes that represent the block. The nonce comes
+     * last, so that mining can reuse the hash state of everything else.
      */
-    fn bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![];
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.write_bytes_without_nonce(writer);
+        writer.write(&u64_bytes(self.nonce));
+    }
+}
 
-        bytes.extend(&u32_bytes(self.index));
-        bytes.extend(&u128_bytes(self.timestamp));
-        bytes.extend(&self.previous_block_hash);
-        bytes.extend(&u64_bytes(self.nonce));
-        bytes.extend(
-            self.transactions
-                .iter()
-                .flat_map(Hashable::bytes)
-                .collect::<Vec<u8>>(),
-        );
-        bytes.extend(&u128_bytes(self.difficulty));
+impl Encodable for Block {
+    /**
+     * Writes the encoding of the block: its header followed by its
+     * transactions.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.write_header_encoding(writer);
+        self.transactions.write_encoding(writer);
+    }
+}
+
+impl Decodable for Block {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        let mut block = Block::read_header_encoding(reader)?;
+        block.transactions = Vec::read_encoding(reader)?;
 
-        bytes
+        Ok(block)
     }
 }
 
 /**
- * Checks whether the most significant 16 bytes of the block's hash is less than
- * the given difficulty value. If so, it's considered "valid".
+ * The hash state of a block's hashable bytes up to (but excluding) the nonce,
+ * the "midstate". Hashing a nonce attempt only feeds the nonce's 8 bytes to a
+ * copy of that state, instead of rehashing the header fields before it (the
+ * transactions are already covered by the transaction root).
  */
-pub fn check_difficulty(hash: &[u8], difficulty: u128) -> bool {
-    difficulty > difficulty_bytes_as_u128(&hash)
+#[derive(Clone)]
+pub struct MiningState {
+    prefix_hasher: Hasher,
+}
+
+impl MiningState {
+    /**
+     * Returns the hash the block would have with the given nonce.
+     */
+    pub fn hash_with_nonce(&self, nonce: u64) -> Hash256 {
+        let mut hasher = self.prefix_hasher.clone();
+        hasher.update(&u64_bytes(nonce));
+
+        hasher.finalize()
+    }
+}
+
+/**
+ * Checks whether the block's hash is less than or equal to the target that
+ * the given compact bits encode. If so, it's considered "valid". A hash never
+ * meets an invalid (negative or overflowing) encoding.
+ */
+pub fn check_difficulty(hash: &Hash256, bits: u32) -> bool {
+    Target::from_compact(bits).is_some_and(|target| target.is_met_by(hash))
 }
 
 #[cfg(test)]
 mod block_tests {
-    use super::{Block, Transaction};
-    use crate::transaction;
+    use primitive_types::U256;
+
+    use super::{check_difficulty, Block, Hash256, HashAlgorithm, Hashable, Target, Transaction};
+    use crate::test_support::outpoint;
+    use crate::{transaction, Script};
 
     #[test]
     fn constructor() {
         let instance = Block::new(
             1,
             2,
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
             3,
         );
 
         assert_eq!(1, instance.index);
         assert_eq!(2, instance.timestamp);
-        assert_eq!(vec![0; 32], instance.hash);
+        assert_eq!(Hash256::ZERO, instance.hash);
         assert_eq!(
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             instance.previous_block_hash
         );
         assert_eq!(0, instance.nonce);
         assert_eq!(
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    }
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
             instance.transactions
         );
-        assert_eq!(3, instance.difficulty);
+        assert_eq!(3, instance.bits);
+        assert!(instance.seal.is_empty());
     }
 
     #[test]
@@ -264,19 +455,25 @@ mod block_tests {
         let block = Block::new(
             1,
             2,
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
             3,
         );
@@ -293,25 +490,31 @@ mod block_tests {
     }
 
     #[test]
-    fn mine_with_difficulty_as_0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff() {
+    fn mine_with_bits_as_0x2100_ffff() {
         let mut block = Block::new(
             1,
             2,
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
-            0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff,
+            0x2100_ffff,
         );
 
         block.mine();
@@ -319,59 +522,68 @@ mod block_tests {
         assert_eq!(1, block.index);
         assert_eq!(2, block.timestamp);
         assert_eq!(
-            vec![
-                39, 78, 235, 119, 157, 146, 83, 4, 155, 240, 87, 117, 84, 101, 122, 41, 63, 16, 23,
-                97, 216, 185, 58, 38, 132, 121, 149, 4, 136, 153, 54, 223
-            ],
+            Hash256([
+                9, 237, 87, 124, 123, 59, 158, 143, 134, 226, 38, 193, 113, 69, 156, 90, 237, 11,
+                70, 37, 121, 1, 150, 99, 40, 108, 246, 142, 194, 85, 50, 178
+            ]),
             block.hash
         );
         assert_eq!(
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             block.previous_block_hash
         );
         assert_eq!(0, block.nonce);
         assert_eq!(
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    }
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
             block.transactions
         );
-        assert_eq!(
-            340282366920938463463374607431768211455_u128,
-            block.difficulty
-        );
+        assert_eq!(0x2100_ffff, block.bits);
     }
 
     #[test]
-    fn mine_with_difficulty_as_0x0000_ffff_ffff_ffff_ffff_ffff_ffff_ffff() {
+    fn mine_with_bits_as_0x1f00_ffff() {
         let mut block = Block::new(
             1,
             2,
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
-            0x0000_ffff_ffff_ffff_ffff_ffff_ffff_ffff,
+            0x1f00_ffff,
         );
 
         block.mine();
@@ -379,60 +591,129 @@ mod block_tests {
         assert_eq!(1, block.index);
         assert_eq!(2, block.timestamp);
         assert_eq!(
-            vec![
-                124, 78, 251, 115, 254, 29, 54, 204, 62, 7, 162, 92, 167, 96, 106, 235, 125, 214,
-                177, 227, 41, 247, 98, 147, 130, 3, 133, 225, 203, 89, 0, 0
-            ],
+            Hash256([
+                208, 202, 167, 164, 199, 36, 157, 173, 80, 248, 122, 72, 109, 213, 41, 149, 123,
+                199, 93, 105, 138, 30, 157, 185, 170, 185, 167, 168, 2, 129, 0, 0
+            ]),
             block.hash
         );
         assert_eq!(
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             block.previous_block_hash
         );
-        assert_eq!(10525, block.nonce);
+        assert_eq!(1866, block.nonce);
         assert_eq!(
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    }
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
             block.transactions
         );
-        assert_eq!(5192296858534827628530496329220095_u128, block.difficulty);
+        assert_eq!(0x1f00_ffff, block.bits);
+    }
+
+    #[test]
+    fn target() {
+        let block = Block::new(1, 2, Hash256::ZERO, vec![], 0x1d00_ffff);
+
+        let result = block.target();
+
+        assert_eq!(Target::from_compact(0x1d00_ffff), result);
+    }
+
+    #[test]
+    fn target_with_invalid_bits() {
+        let block = Block::new(1, 2, Hash256::ZERO, vec![], 0x0492_3456);
+
+        let result = block.target();
+
+        assert_eq!(None, result);
+    }
+
+    #[test]
+    fn work() {
+        let block = Block::new(1, 2, Hash256::ZERO, vec![], 0x1d00_ffff);
+
+        let result = block.work();
+
+        assert_eq!(U256::from(0x0001_0001_0001_u64), result);
+    }
+
+    #[test]
+    fn work_with_invalid_bits() {
+        let block = Block::new(1, 2, Hash256::ZERO, vec![], 0x0492_3456);
+
+        let result = block.work();
+
+        assert_eq!(U256::zero(), result);
+    }
+
+    #[test]
+    fn mine_with_invalid_bits() {
+        let mut block = Block::new(1, 2, Hash256::ZERO, vec![], 0x0492_3456);
+
+        block.mine();
+
+        assert_eq!(0, block.nonce);
+        assert_eq!(Hash256::ZERO, block.hash);
+    }
+
+    #[test]
+    fn mine_with_blake3() {
+        let mut block = Block::new(1, 2, Hash256::ZERO, vec![], 0x2000_ffff);
+
+        block.mine_with(HashAlgorithm::Blake3);
+
+        assert_eq!(block.hash_with(HashAlgorithm::Blake3), block.hash);
+        assert!(check_difficulty(&block.hash, block.bits));
     }
 }
 
 #[cfg(test)]
 mod hashable_block_tests {
-    use super::{Block, Hashable, Transaction};
-    use crate::transaction;
+    use super::{Block, Hash256, Hashable, Transaction};
+    use crate::test_support::outpoint;
+    use crate::{transaction, Script};
 
     #[test]
     fn bytes() {
         let block = Block::new(
             1,
             2,
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
             3,
         );
@@ -443,9 +724,9 @@ mod hashable_block_tests {
             vec![
                 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8,
                 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29,
-                30, 31, 32, 0, 0, 0, 0, 0, 0, 0, 0, 65, 108, 105, 99, 101, 1, 0, 0, 0, 0, 0, 0, 0,
-                66, 111, 98, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                0
+                30, 31, 32, 148, 82, 140, 207, 77, 0, 212, 192, 200, 223, 225, 159, 234, 146, 42,
+                117, 12, 207, 204, 120, 255, 163, 159, 209, 46, 159, 97, 79, 239, 19, 9, 32, 3, 0,
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 0
             ],
             result
         );
@@ -456,19 +737,25 @@ mod hashable_block_tests {
         let block = Block::new(
             1,
             2,
-            vec![
+            Hash256([
                 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
                 24, 25, 26, 27, 28, 29, 30, 31, 32,
-            ],
+            ]),
             vec![Transaction {
-                inputs: vec![transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                }],
+                inputs: vec![transaction::Input::new(
+                    outpoint(0),
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                )],
                 outputs: vec![transaction::Output {
                     to_address: "Bob".to_owned(),
                     value: 2,
+                    locking_script: Script::new(),
                 }],
+                lock_time: None,
             }],
             3,
         );
@@ -476,80 +763,192 @@ mod hashable_block_tests {
         let result = block.hash();
 
         assert_eq!(
-            vec![
-                117, 2, 120, 30, 164, 40, 67, 254, 110, 10, 42, 33, 124, 60, 170, 23, 52, 145, 230,
-                21, 127, 125, 2, 199, 114, 39, 202, 78, 118, 53, 16, 204
-            ],
+            Hash256([
+                187, 204, 60, 108, 132, 183, 254, 127, 227, 144, 23, 245, 239, 133, 170, 190, 216,
+                71, 202, 141, 97, 32, 144, 64, 54, 188, 6, 10, 241, 47, 242, 18
+            ]),
             result
         );
     }
 }
 
+#[cfg(test)]
+mod encodable_block_tests {
+    use super::{Block, Hash256, Transaction};
+    use crate::encoding::{ByteReader, Decodable, Encodable};
+    use crate::{transaction, Script};
+
+    fn create_block() -> Block {
+        let mut block = Block::new(
+            1,
+            2,
+            Hash256([7; 32]),
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_address: "Alice".to_owned(),
+                    value: 50,
+                    locking_script: Script::new(),
+                }],
+                lock_time: None,
+            }],
+            0x2100_ffff,
+        );
+        block.nonce = 9;
+        block.hash = Hash256([8; 32]);
+        block.seal = vec![1, 2, 3];
+
+        block
+    }
+
+    #[test]
+    fn round_trip() {
+        let block = create_block();
+
+        let result = Block::decode(&block.encode());
+
+        assert_eq!(Ok(block), result);
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let block = create_block();
+        let mut bytes = vec![];
+        block.write_header_encoding(&mut bytes);
+
+        let mut reader = ByteReader::new(&bytes);
+        let result = Block::read_header_encoding(&mut reader).unwrap();
+
+        assert!(reader.is_empty());
+        assert!(result.transactions.is_empty());
+        assert_eq!(block.hash, result.hash);
+        assert_eq!(block.previous_block_hash, result.previous_block_hash);
+        assert_eq!(block.seal, result.seal);
+        assert_eq!(
+            (block.index, block.timestamp, block.nonce, block.bits),
+            (result.index, result.timestamp, result.nonce, result.bits)
+        );
+    }
+}
+
+#[cfg(test)]
+mod mining_state_tests {
+    use super::{Block, Hash256, HashAlgorithm, Hashable, Transaction};
+    use crate::{transaction, Script};
+
+    fn create_block() -> Block {
+        Block::new(
+            1,
+            2,
+            Hash256([3; 32]),
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_address: "Alice".to_owned(),
+                    value: 1,
+                    locking_script: Script::new(),
+                }],
+                lock_time: None,
+            }],
+            0x2000_ffff,
+        )
+    }
+
+    #[test]
+    fn hash_with_nonce() {
+        let mut block = create_block();
+        let mining_state = block.mining_state(HashAlgorithm::Sha256);
+
+        for nonce in [0, 1, 42, u64::MAX].iter() {
+            block.nonce = *nonce;
+
+            let result = mining_state.hash_with_nonce(*nonce);
+
+            assert_eq!(block.hash(), result);
+        }
+    }
+
+    #[test]
+    fn hash_with_nonce_for_every_algorithm() {
+        let mut block = create_block();
+        block.nonce = 7;
+
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::DoubleSha256,
+            HashAlgorithm::Blake2b,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::Keccak256,
+        ]
+        .iter()
+        {
+            let mining_state = block.mining_state(*algorithm);
+
+            let result = mining_state.hash_with_nonce(7);
+
+            assert_eq!(block.hash_with(*algorithm), result, "{:?}", algorithm);
+        }
+    }
+}
+
 #[cfg(test)]
 mod check_difficulty_tests {
-    use super::{check_difficulty, BlockHash};
-    use crate::difficulty_bytes_as_u128;
+    use super::{check_difficulty, BlockHash, Hash256};
+
+    // Encodes the target 0x0100 (256).
+    const BITS: u32 = 0x0201_0000;
+
+    fn hash_with_low_bytes(byte0: u8, byte1: u8) -> BlockHash {
+        let mut bytes = [0_u8; 32];
+        bytes[0] = byte0;
+        bytes[1] = byte1;
+
+        Hash256(bytes)
+    }
+
+    #[test]
+    fn target_less_than_hash() {
+        let hash = hash_with_low_bytes(0x01, 0x01);
+
+        let result = check_difficulty(&hash, BITS);
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn target_equal_to_hash() {
+        let hash = hash_with_low_bytes(0x00, 0x01);
+
+        let result = check_difficulty(&hash, BITS);
+
+        assert!(result);
+    }
 
     #[test]
-    fn difficulty_less_than_that_of_hash() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-        let data_difficulty = difficulty_bytes_as_u128(&data);
-        let hash: BlockHash = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 1_u8,
-        ];
-        let hash_difficulty = difficulty_bytes_as_u128(&hash);
-        assert!(data_difficulty < hash_difficulty);
-
-        let result = check_difficulty(&hash, data_difficulty);
-
-        assert_eq!(false, result);
+    fn target_greater_than_hash() {
+        let hash = hash_with_low_bytes(0xff, 0x00);
+
+        let result = check_difficulty(&hash, BITS);
+
+        assert!(result);
     }
 
     #[test]
-    fn difficulty_equal_to_that_of_hash() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-        let data_difficulty = difficulty_bytes_as_u128(&data);
-        let hash: BlockHash = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-        let hash_difficulty = difficulty_bytes_as_u128(&hash);
-        assert!(data_difficulty == hash_difficulty);
-
-        let result = check_difficulty(&hash, data_difficulty);
-
-        assert_eq!(false, result);
+    fn target_less_than_hash_in_most_significant_byte() {
+        let mut bytes = [0_u8; 32];
+        bytes[31] = 1;
+
+        let result = check_difficulty(&Hash256(bytes), 0x2000_ffff);
+
+        assert!(!result);
     }
 
     #[test]
-    fn difficulty_greater_than_that_of_hash() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 1_u8,
-        ];
-        let data_difficulty = difficulty_bytes_as_u128(&data);
-        let hash: BlockHash = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-        let hash_difficulty = difficulty_bytes_as_u128(&hash);
-        assert!(data_difficulty > hash_difficulty);
-
-        let result = check_difficulty(&hash, data_difficulty);
-
-        assert_eq!(true, result);
+    fn invalid_target_encoding() {
+        let negative_bits = 0x0492_3456;
+
+        let result = check_difficulty(&Hash256::ZERO, negative_bits);
+
+        assert!(!result);
     }
 }
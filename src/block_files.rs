@@ -0,0 +1,451 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::{Block, BlockUndo, Transaction};
+
+const HEADERS_FILE_NAME: &str = "headers.dat";
+
+/**
+ * The files that store a chain's blocks in a directory: every block header,
+ * in chain order, in one append-only file (headers.dat), and the body of each
+ * block (its transactions) and its undo data in files of their own
+ * (block-<index>.dat and undo-<index>.dat), so that they can be removed when
+ * the chain is pruned (see Blockchain::enable_pruning).
+ *
+ * A block's header is written after its body and its undo data are synced,
+ * so a block is only stored once its header is. If writing a block is
+ * interrupted (e.g., by a crash), opening the block files again recovers
+ * them: the part of a header that was written is truncated, and the files of
+ * blocks that have no header are removed.
+ */
+#[derive(Debug)]
+pub struct BlockFiles {
+    directory: PathBuf,
+}
+
+impl BlockFiles {
+    /**
+     * Creates the block files in the given directory, which is created if it
+     * doesn't exist. It's an error if the directory already contains block
+     * files.
+     */
+    pub fn create<P: Into<PathBuf>>(directory: P) -> io::Result<Self> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(directory.join(HEADERS_FILE_NAME))?;
+
+        Ok(BlockFiles { directory })
+    }
+
+    /**
+     * Opens the block files in the given directory, which must contain block
+     * files, and recovers them from an interrupted write.
+     */
+    pub fn open<P: Into<PathBuf>>(directory: P) -> io::Result<Self> {
+        let block_files = BlockFiles {
+            directory: directory.into(),
+        };
+        let headers_path = block_files.directory.join(HEADERS_FILE_NAME);
+        let bytes = fs::read(&headers_path)?;
+        let mut reader = ByteReader::new(&bytes);
+
+        let mut header_count = 0;
+        let mut stored_length = 0;
+        while !reader.is_empty() {
+            match Block::read_header_encoding(&mut reader) {
+                Ok(_) => {
+                    header_count += 1;
+                    stored_length = bytes.len() - reader.remaining();
+                }
+                Err(DecodeErr::UnexpectedEnd) => break,
+                Err(error) => return Err(invalid_data(error)),
+            }
+        }
+        if stored_length < bytes.len() {
+            OpenOptions::new()
+                .write(true)
+                .open(&headers_path)?
+                .set_len(stored_length as u64)?;
+        }
+
+        block_files.remove_block_data(header_count)?;
+
+        Ok(block_files)
+    }
+
+    /**
+     * Returns the directory that contains the block files.
+     */
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /**
+     * Writes the block's body and undo data, syncs them (and the directory
+     * that they were created in), and then appends its header to the headers
+     * file, so the header is never durable before the data it refers to.
+     */
+    pub fn write_block(&self, block: &Block, undo: &BlockUndo) -> io::Result<()> {
+        self.write_body(block)?;
+        self.write_undo(block.index, undo)?;
+        sync_directory(&self.directory)?;
+
+        self.write_header(block)
+    }
+
+    /**
+     * Appends the block's header to the headers file and syncs it.
+     */
+    pub fn write_header(&self, block: &Block) -> io::Result<()> {
+        let mut bytes = vec![];
+        block.write_header_encoding(&mut bytes);
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(self.directory.join(HEADERS_FILE_NAME))?;
+        file.write_all(&bytes)?;
+
+        file.sync_data()
+    }
+
+    /**
+     * Writes the block's body to its own file, replacing the body of any
+     * block that had the same index, and syncs it.
+     */
+    pub fn write_body(&self, block: &Block) -> io::Result<()> {
+        write_synced(&self.body_path(block.index), &block.transactions.encode())
+    }
+
+    /**
+     * Writes the undo data of the block with the given index to its own file
+     * and syncs it.
+     */
+    pub fn write_undo(&self, index: u32, undo: &BlockUndo) -> io::Result<()> {
+        write_synced(&self.undo_path(index), &undo.encode())
+    }
+
+    /**
+     * Truncates the headers file to the given number of headers and removes
+     * the bodies and the undo data of the blocks after them, e.g., when blocks
+     * are disconnected.
+     */
+    pub fn truncate(&self, header_count: u32) -> io::Result<()> {
+        let headers_path = self.directory.join(HEADERS_FILE_NAME);
+        let bytes = fs::read(&headers_path)?;
+        let mut reader = ByteReader::new(&bytes);
+        for _ in 0..header_count {
+            Block::read_header_encoding(&mut reader).map_err(invalid_data)?;
+        }
+
+        OpenOptions::new()
+            .write(true)
+            .open(&headers_path)?
+            .set_len((bytes.len() - reader.remaining()) as u64)?;
+
+        self.remove_block_data(header_count)
+    }
+
+    /**
+     * Returns the stored block headers in chain order, as blocks without
+     * transactions.
+     */
+    pub fn read_headers(&self) -> io::Result<Vec<Block>> {
+        let bytes = fs::read(self.directory.join(HEADERS_FILE_NAME))?;
+        let mut reader = ByteReader::new(&bytes);
+
+        let mut headers = vec![];
+        while !reader.is_empty() {
+            headers.push(Block::read_header_encoding(&mut reader).map_err(invalid_data)?);
+        }
+
+        Ok(headers)
+    }
+
+    /**
+     * Returns the stored body of the block with the given index, or None if
+     * it isn't stored (e.g., because it was pruned).
+     */
+    pub fn read_body(&self, index: u32) -> io::Result<Option<Vec<Transaction>>> {
+        match fs::read(self.body_path(index)) {
+            Ok(bytes) => Ok(Some(Vec::decode(&bytes).map_err(invalid_data)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /**
+     * Returns the stored undo data of the block with the given index, or None
+     * if it isn't stored (e.g., because it was pruned).
+     */
+    pub fn read_undo(&self, index: u32) -> io::Result<Option<BlockUndo>> {
+        match fs::read(self.undo_path(index)) {
+            Ok(bytes) => Ok(Some(BlockUndo::decode(&bytes).map_err(invalid_data)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /**
+     * Removes the stored body of the block with the given index, if there is
+     * one.
+     */
+    pub fn remove_body(&self, index: u32) -> io::Result<()> {
+        remove_file_if_exists(&self.body_path(index))
+    }
+
+    /**
+     * Removes the stored undo data of the block with the given index, if
+     * there is one.
+     */
+    pub fn remove_undo(&self, index: u32) -> io::Result<()> {
+        remove_file_if_exists(&self.undo_path(index))
+    }
+
+    /**
+     * Removes the bodies and the undo data of the blocks with an index of at
+     * least the given one.
+     */
+    fn remove_block_data(&self, first_index: u32) -> io::Result<()> {
+        for entry in fs::read_dir(&self.directory)? {
+            let file_name = entry?.file_name();
+            let index = file_name
+                .to_str()
+                .and_then(|name| {
+                    name.strip_prefix("block-")
+                        .or_else(|| name.strip_prefix("undo-"))
+                })
+                .and_then(|name| name.strip_suffix(".dat"))
+                .and_then(|index| index.parse::<u32>().ok());
+            if index.is_some_and(|index| index >= first_index) {
+                remove_file_if_exists(&self.directory.join(file_name))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn body_path(&self, index: u32) -> PathBuf {
+        self.directory.join(format!("block-{:010}.dat", index))
+    }
+
+    fn undo_path(&self, index: u32) -> PathBuf {
+        self.directory.join(format!("undo-{:010}.dat", index))
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(bytes)?;
+
+    file.sync_all()
+}
+
+fn remove_file_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/**
+ * Syncs the directory, so that the files that were created, renamed or
+ * removed in it are durable.
+ */
+pub(crate) fn sync_directory(directory: &Path) -> io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+fn invalid_data(error: DecodeErr) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("{:?}", error))
+}
+
+#[cfg(test)]
+mod block_files_tests {
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use super::BlockFiles;
+    use crate::transaction::Output;
+    use crate::{Block, BlockUndo, Hash256, Script, Transaction};
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("block_files_tests-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    fn create_block(index: u32) -> Block {
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![Output {
+                to_address: "Alice".to_owned(),
+                value: 50 + index as u64,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        };
+        let mut block = Block::new(index, index as u128, Hash256::ZERO, vec![coinbase], 0);
+        block.hash = Hash256([index as u8; 32]);
+
+        block
+    }
+
+    #[test]
+    fn write_and_read_blocks() {
+        let directory = temporary_directory("write_and_read_blocks");
+        let block_files = BlockFiles::create(&directory).unwrap();
+        let blocks = vec![create_block(0), create_block(1)];
+
+        for block in &blocks {
+            block_files
+                .write_block(block, &BlockUndo::default())
+                .unwrap();
+        }
+
+        let headers = block_files.read_headers().unwrap();
+        assert_eq!(2, headers.len());
+        for (header, block) in headers.iter().zip(&blocks) {
+            assert_eq!(block.hash, header.hash);
+            assert!(header.transactions.is_empty());
+            assert_eq!(
+                Some(&block.transactions),
+                block_files.read_body(block.index).unwrap().as_ref()
+            );
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn remove_body() {
+        let directory = temporary_directory("remove_body");
+        let block_files = BlockFiles::create(&directory).unwrap();
+        block_files
+            .write_block(&create_block(0), &BlockUndo::default())
+            .unwrap();
+
+        block_files.remove_body(0).unwrap();
+        block_files.remove_body(0).unwrap();
+
+        assert_eq!(None, block_files.read_body(0).unwrap());
+        assert_eq!(1, block_files.read_headers().unwrap().len());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn create_twice() {
+        let directory = temporary_directory("create_twice");
+        BlockFiles::create(&directory).unwrap();
+
+        let result = BlockFiles::create(&directory);
+
+        assert_eq!(ErrorKind::AlreadyExists, result.unwrap_err().kind());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn read_corrupt_body() {
+        let directory = temporary_directory("read_corrupt_body");
+        let block_files = BlockFiles::create(&directory).unwrap();
+        block_files
+            .write_block(&create_block(0), &BlockUndo::default())
+            .unwrap();
+        fs::write(directory.join("block-0000000000.dat"), [1, 0, 0, 0]).unwrap();
+
+        let result = block_files.read_body(0);
+
+        assert_eq!(ErrorKind::InvalidData, result.unwrap_err().kind());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn write_and_read_undo() {
+        let directory = temporary_directory("write_and_read_undo");
+        let block_files = BlockFiles::create(&directory).unwrap();
+        let undo = BlockUndo::new(vec![(Hash256([1; 32]), Some(0))]);
+
+        block_files.write_block(&create_block(0), &undo).unwrap();
+
+        assert_eq!(Some(undo), block_files.read_undo(0).unwrap());
+        block_files.remove_undo(0).unwrap();
+        assert_eq!(None, block_files.read_undo(0).unwrap());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn truncate() {
+        let directory = temporary_directory("truncate");
+        let block_files = BlockFiles::create(&directory).unwrap();
+        for index in 0..3 {
+            block_files
+                .write_block(&create_block(index), &BlockUndo::default())
+                .unwrap();
+        }
+
+        block_files.truncate(1).unwrap();
+
+        let headers = block_files.read_headers().unwrap();
+        assert_eq!(1, headers.len());
+        assert_eq!(Hash256([0; 32]), headers[0].hash);
+        assert!(block_files.read_body(0).unwrap().is_some());
+        assert!(block_files.read_undo(0).unwrap().is_some());
+        for index in 1..3 {
+            assert_eq!(None, block_files.read_body(index).unwrap());
+            assert_eq!(None, block_files.read_undo(index).unwrap());
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn open_recovers_interrupted_write() {
+        let directory = temporary_directory("open_recovers_interrupted_write");
+        let block_files = BlockFiles::create(&directory).unwrap();
+        block_files
+            .write_block(&create_block(0), &BlockUndo::default())
+            .unwrap();
+        let stored_length = fs::metadata(directory.join("headers.dat")).unwrap().len();
+        block_files
+            .write_block(&create_block(1), &BlockUndo::default())
+            .unwrap();
+        let headers_file = fs::OpenOptions::new()
+            .write(true)
+            .open(directory.join("headers.dat"))
+            .unwrap();
+        headers_file.set_len(stored_length + 10).unwrap();
+
+        let result = BlockFiles::open(&directory).unwrap();
+
+        assert_eq!(1, result.read_headers().unwrap().len());
+        assert_eq!(
+            stored_length,
+            fs::metadata(directory.join("headers.dat")).unwrap().len()
+        );
+        assert!(result.read_body(0).unwrap().is_some());
+        assert_eq!(None, result.read_body(1).unwrap());
+        assert_eq!(None, result.read_undo(1).unwrap());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn open_missing_block_files() {
+        let directory = temporary_directory("open_missing_block_files");
+
+        let result = BlockFiles::open(&directory);
+
+        assert_eq!(ErrorKind::NotFound, result.unwrap_err().kind());
+    }
+}
@@ -0,0 +1,436 @@
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::ByteWriter;
+use crate::{u32_bytes, Block, BlockHash, Hash256, HashAlgorithm};
+
+/**
+ * The number of low bits of each value that a filter's Golomb-Rice coding
+ * writes as is (the rest is written in unary).
+ */
+const FILTER_P: u8 = 19;
+
+/**
+ * The inverse of a filter's false positive rate: an element that isn't in a
+ * block matches its filter with a probability of about 1 / FILTER_M.
+ */
+const FILTER_M: u64 = 784_931;
+
+const ADDRESS_TAG: u8 = 0;
+const OUTPOINT_TAG: u8 = 1;
+
+/**
+ * A compact filter of a block: a Golomb-coded set of the addresses that the
+ * block's transactions pay to and of the outputs that they spend (their
+ * hashes, see OutPoint::output_hash). A light client downloads the filters
+ * instead of telling a full node which addresses it's interested in, tests
+ * them locally (see matches_any) and only fetches the blocks that match, so
+ * the node doesn't learn its addresses.
+ *
+ * Each element is hashed, with a key taken from the block's hash, to a
+ * number below the element count times FILTER_M, and the sorted numbers are
+ * written as Golomb-Rice coded differences. An element that is in the block
+ * always matches, and one that isn't matches with a probability of about
+ * 1 / FILTER_M.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockFilter {
+    pub block_hash: BlockHash,
+    pub element_count: u32,
+    pub data: Vec<u8>,
+}
+
+impl BlockFilter {
+    /**
+     * Builds the filter of the block, whose spent outputs are hashed with
+     * the given algorithm (the chain's transaction algorithm).
+     */
+    pub fn new(block: &Block, algorithm: HashAlgorithm) -> Self {
+        let mut elements = vec![];
+        for transaction in &block.transactions {
+            for input in &transaction.inputs {
+                let output_hash = input.output_hash(algorithm);
+                elements.push(outpoint_element(&output_hash));
+            }
+            for output in &transaction.outputs {
+                elements.push(address_element(&output.to_address));
+            }
+        }
+        elements.sort_unstable();
+        elements.dedup();
+
+        let element_count = elements.len() as u32;
+        let range = element_count as u64 * FILTER_M;
+        let mut values: Vec<u64> = elements
+            .iter()
+            .map(|element| hash_to_range(&block.hash, element, range))
+            .collect();
+        values.sort_unstable();
+
+        let mut writer = BitWriter::default();
+        let mut previous_value = 0;
+        for value in values {
+            writer.write_golomb_rice(value - previous_value);
+            previous_value = value;
+        }
+
+        BlockFilter {
+            block_hash: block.hash,
+            element_count,
+            data: writer.bytes,
+        }
+    }
+
+    /**
+     * Returns the hash of the filter: the SHA-256 hash of its encoding.
+     */
+    pub fn hash(&self) -> Hash256 {
+        HashAlgorithm::Sha256.digest(&self.encode())
+    }
+
+    /**
+     * Returns the filter's header: the hash of the filter's hash and the
+     * previous block's filter header (32 zeros for the genesis block). Like
+     * block headers, filter headers form a chain, so a client that has the
+     * filter header of a block can verify any filter served for it.
+     */
+    pub fn header(&self, previous_header: &Hash256) -> Hash256 {
+        let mut bytes = self.hash().as_bytes().to_vec();
+        bytes.extend_from_slice(previous_header.as_bytes());
+
+        HashAlgorithm::Sha256.digest(&bytes)
+    }
+
+    /**
+     * Returns a flag that states whether the block may pay to the address.
+     */
+    pub fn matches_address(&self, address: &str) -> bool {
+        self.matches_any(&[address], &[])
+    }
+
+    /**
+     * Returns a flag that states whether the block may spend the output with
+     * the given hash.
+     */
+    pub fn matches_outpoint(&self, output_hash: &Hash256) -> bool {
+        self.matches_any(&[], &[*output_hash])
+    }
+
+    /**
+     * Returns a flag that states whether the block may pay to any of the
+     * addresses or spend any of the outputs with the given hashes. A filter
+     * whose data is malformed matches everything, so that a client fetches
+     * the block rather than miss a transaction.
+     */
+    pub fn matches_any(&self, addresses: &[&str], output_hashes: &[Hash256]) -> bool {
+        if self.element_count == 0 {
+            return false;
+        }
+
+        let range = self.element_count as u64 * FILTER_M;
+        let mut queries: Vec<u64> = addresses
+            .iter()
+            .map(|address| address_element(address))
+            .chain(output_hashes.iter().map(outpoint_element))
+            .map(|element| hash_to_range(&self.block_hash, &element, range))
+            .collect();
+        queries.sort_unstable();
+
+        let mut reader = BitReader::new(&self.data);
+        let mut value = 0_u64;
+        let mut queries = queries.into_iter().peekable();
+        for _ in 0..self.element_count {
+            value = match reader
+                .read_golomb_rice()
+                .and_then(|delta| value.checked_add(delta))
+            {
+                Some(value) => value,
+                None => return true,
+            };
+
+            while let Some(query) = queries.next_if(|query| *query <= value) {
+                if query == value {
+                    return true;
+                }
+            }
+            if queries.peek().is_none() {
+                return false;
+            }
+        }
+
+        false
+    }
+}
+
+impl Encodable for BlockFilter {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.block_hash.write_encoding(writer);
+        self.element_count.write_encoding(writer);
+        writer.write(&u32_bytes(self.data.len() as u32));
+        writer.write(&self.data);
+    }
+}
+
+impl Decodable for BlockFilter {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(BlockFilter {
+            block_hash: reader.read_hash()?,
+            element_count: reader.read_u32()?,
+            data: reader.read_length_prefixed()?.to_vec(),
+        })
+    }
+}
+
+/**
+ * The filters of a chain's blocks and their filter headers, which are
+ * maintained as blocks are connected to the tip of the chain and
+ * disconnected from it. The filters start at the first block after those
+ * whose bodies the chain didn't have when the filters were enabled (e.g.,
+ * because they were pruned), and so does the filter header chain.
+ */
+#[derive(Debug, Default)]
+pub(crate) struct BlockFilterIndex {
+    first_index: u32,
+    filters: Vec<BlockFilter>,
+    headers: Vec<Hash256>,
+}
+
+impl BlockFilterIndex {
+    /**
+     * Returns an empty index whose first filter will be that of the block
+     * with the given index.
+     */
+    pub(crate) fn starting_at(first_index: u32) -> Self {
+        BlockFilterIndex {
+            first_index,
+            ..BlockFilterIndex::default()
+        }
+    }
+
+    pub(crate) fn filter(&self, block_index: u32) -> Option<&BlockFilter> {
+        let position = block_index.checked_sub(self.first_index)?;
+
+        self.filters.get(position as usize)
+    }
+
+    pub(crate) fn header(&self, block_index: u32) -> Option<Hash256> {
+        let position = block_index.checked_sub(self.first_index)?;
+
+        self.headers.get(position as usize).copied()
+    }
+
+    /**
+     * Adds the filter of the block that becomes the tip.
+     */
+    pub(crate) fn connect_filter(&mut self, filter: BlockFilter) {
+        let previous_header = self.headers.last().copied().unwrap_or(Hash256::ZERO);
+        self.headers.push(filter.header(&previous_header));
+        self.filters.push(filter);
+    }
+
+    /**
+     * Removes the filter of the tip.
+     */
+    pub(crate) fn disconnect_tip(&mut self) {
+        self.filters.pop();
+        self.headers.pop();
+    }
+}
+
+fn address_element(address: &str) -> Vec<u8> {
+    let mut element = vec![ADDRESS_TAG];
+    element.extend_from_slice(address.as_bytes());
+
+    element
+}
+
+fn outpoint_element(output_hash: &Hash256) -> Vec<u8> {
+    let mut element = vec![OUTPOINT_TAG];
+    element.extend_from_slice(output_hash.as_bytes());
+
+    element
+}
+
+/**
+ * Hashes the element, with a key taken from the block's hash, to a number
+ * below the given range: the first 8 bytes of its SHA-256 hash, scaled to the
+ * range.
+ */
+fn hash_to_range(block_hash: &BlockHash, element: &[u8], range: u64) -> u64 {
+    let mut bytes = block_hash.as_bytes()[..16].to_vec();
+    bytes.extend_from_slice(element);
+    let hash = HashAlgorithm::Sha256.digest(&bytes);
+    let mut first_bytes = [0; 8];
+    first_bytes.copy_from_slice(&hash.as_bytes()[..8]);
+
+    ((u64::from_le_bytes(first_bytes) as u128 * range as u128) >> 64) as u64
+}
+
+/**
+ * Writes bits into bytes, most significant bit first.
+ */
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bit_count: usize,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.bit_count.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= 0x80 >> (self.bit_count % 8);
+        }
+        self.bit_count += 1;
+    }
+
+    /**
+     * Writes the value's quotient by 2^FILTER_P in unary (that many ones and
+     * a zero) and its remainder in FILTER_P bits.
+     */
+    fn write_golomb_rice(&mut self, value: u64) {
+        for _ in 0..(value >> FILTER_P) {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+        for bit in (0..FILTER_P).rev() {
+            self.write_bit((value >> bit) & 1 == 1);
+        }
+    }
+}
+
+/**
+ * Reads the bits that a BitWriter wrote.
+ */
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+
+        Some(bit)
+    }
+
+    fn read_golomb_rice(&mut self) -> Option<u64> {
+        let mut quotient = 0_u64;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let mut remainder = 0;
+        for _ in 0..FILTER_P {
+            remainder = (remainder << 1) | self.read_bit()? as u64;
+        }
+
+        quotient.checked_mul(1 << FILTER_P)?.checked_add(remainder)
+    }
+}
+
+#[cfg(test)]
+mod block_filter_tests {
+    use super::BlockFilter;
+    use crate::encoding::{Decodable, Encodable};
+
+    use crate::test_support::{create_payment, output, spend};
+    use crate::{Block, Hash256, HashAlgorithm, Transaction};
+
+    fn create_block() -> Block {
+        let payment = create_payment();
+        let coinbase = Transaction::coinbase(1, vec![output("Chris", 1)]);
+        let mut block = Block::new(1, 2, Hash256([1; 32]), vec![coinbase, payment], 0x2100_ffff);
+        block.mine();
+
+        block
+    }
+
+    #[test]
+    fn match_addresses_and_spent_outputs() {
+        let filter = BlockFilter::new(&create_block(), HashAlgorithm::Sha256);
+        let spent_output_hash = create_payment().inputs[0].output_hash(HashAlgorithm::Sha256);
+
+        assert_eq!(4, filter.element_count);
+        assert!(filter.matches_address("Alice"));
+        assert!(filter.matches_address("Bob"));
+        assert!(filter.matches_address("Chris"));
+        assert!(filter.matches_outpoint(&spent_output_hash));
+        assert!(filter.matches_any(&["Dave", "Bob"], &[]));
+    }
+
+    #[test]
+    fn no_match_for_other_elements() {
+        let filter = BlockFilter::new(&create_block(), HashAlgorithm::Sha256);
+        let unspent_output_hash = spend(&create_payment(), 0).output_hash(HashAlgorithm::Sha256);
+        let other_addresses: Vec<String> = (0..100).map(|n| format!("Address {}", n)).collect();
+
+        assert!(!filter.matches_outpoint(&unspent_output_hash));
+        assert!(!filter.matches_any(
+            &other_addresses
+                .iter()
+                .map(|address| address.as_str())
+                .collect::<Vec<&str>>(),
+            &[]
+        ));
+    }
+
+    #[test]
+    fn spent_outputs_are_hashed_with_given_algorithm() {
+        let filter = BlockFilter::new(&create_block(), HashAlgorithm::Blake3);
+
+        let spent_output = &create_payment().inputs[0];
+
+        assert!(filter.matches_outpoint(&spent_output.output_hash(HashAlgorithm::Blake3)));
+        assert!(!filter.matches_outpoint(&spent_output.output_hash(HashAlgorithm::Sha256)));
+    }
+
+    #[test]
+    fn empty_block_matches_nothing() {
+        let filter = BlockFilter::new(
+            &Block::new(0, 1, Hash256::ZERO, vec![], 0x2100_ffff),
+            HashAlgorithm::Sha256,
+        );
+
+        assert_eq!(0, filter.element_count);
+        assert!(filter.data.is_empty());
+        assert!(!filter.matches_address("Alice"));
+    }
+
+    #[test]
+    fn malformed_filter_matches_everything() {
+        let mut filter = BlockFilter::new(&create_block(), HashAlgorithm::Sha256);
+        filter.data.clear();
+
+        assert!(filter.matches_address("Dave"));
+    }
+
+    #[test]
+    fn header_commits_to_filter_and_previous_header() {
+        let filter = BlockFilter::new(&create_block(), HashAlgorithm::Sha256);
+        let mut other_filter = filter.clone();
+        other_filter.data[0] ^= 1;
+
+        assert_ne!(
+            filter.header(&Hash256::ZERO),
+            filter.header(&Hash256([1; 32]))
+        );
+        assert_ne!(
+            filter.header(&Hash256::ZERO),
+            other_filter.header(&Hash256::ZERO)
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let filter = BlockFilter::new(&create_block(), HashAlgorithm::Sha256);
+
+        assert_eq!(Ok(filter.clone()), BlockFilter::decode(&filter.encode()));
+    }
+}
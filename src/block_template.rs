@@ -0,0 +1,311 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::transaction::Output;
+use crate::{
+    Block, Blockchain, ChainStorage, FeeRate, HashAlgorithm, Mempool, Transaction, TransactionId,
+};
+
+/**
+ * The combined serialized size of the transactions (besides the coinbase)
+ * that a block template takes by default.
+ */
+pub const DEFAULT_MAX_BLOCK_TEMPLATE_SIZE: usize = 1_000_000;
+
+/**
+ * The transactions that a miner puts in its next block, selected from its
+ * mempool to collect the most fees in the block space it has.
+ *
+ * Selecting transactions one by one by their fee rates would leave out a
+ * transaction that pays a low fee rate even if its children pay a high one,
+ * although the children can't be mined without it. So transactions are
+ * selected in packages: a transaction together with its unconfirmed
+ * ancestors (see Mempool::ancestors) that the template doesn't have yet,
+ * scored by their combined fee rate. A child that pays for its parent lifts
+ * the parent's package along with its own, so a parent is effectively scored
+ * with its descendants. The package with the highest score that still fits
+ * is added, parents before children, and the packages of the remaining
+ * transactions are scored again, until no package fits.
+ *
+ * The mempool limits the ancestors of its transactions (see Mempool), which
+ * bounds the size of every package and the work of scoring it.
+ *
+ * The mempool validated its transactions against the chain when it accepted
+ * them, but the chain may have changed since then (e.g., its tip may have
+ * been disconnected), so every transaction is validated again as if it were
+ * in the chain's next block (see Blockchain::validate_transaction). A
+ * transaction that isn't final at the next block's index (or whose inputs'
+ * relative lock times haven't been reached), or that's invalid otherwise, is
+ * left out along with its descendants.
+ */
+#[derive(Debug)]
+pub struct BlockTemplate {
+    pub transactions: Vec<Transaction>,
+    pub fee: u64,
+    pub size: usize,
+    algorithm: HashAlgorithm,
+}
+
+impl BlockTemplate {
+    /**
+     * Selects the transactions of a template of the default maximum size
+     * for the next block of the given chain from the given mempool.
+     */
+    pub fn new<S: ChainStorage>(mempool: &Mempool, blockchain: &Blockchain<S>) -> Self {
+        BlockTemplate::with_max_size(mempool, blockchain, DEFAULT_MAX_BLOCK_TEMPLATE_SIZE)
+    }
+
+    /**
+     * Selects the transactions of a template for the next block of the given
+     * chain from the given mempool whose combined size is at most the given
+     * size.
+     */
+    pub fn with_max_size<S: ChainStorage>(
+        mempool: &Mempool,
+        blockchain: &Blockchain<S>,
+        max_size: usize,
+    ) -> Self {
+        let valid_ids: HashSet<TransactionId> = mempool
+            .transactions()
+            .filter(|(_, transaction)| {
+                blockchain
+                    .validate_transaction(transaction, &mempool.unconfirmed_outputs(transaction))
+                    .is_ok()
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        let ancestors: HashMap<TransactionId, HashSet<TransactionId>> = valid_ids
+            .iter()
+            .map(|id| (*id, mempool.ancestors(id)))
+            .filter(|(_, ancestor_ids)| ancestor_ids.is_subset(&valid_ids))
+            .collect();
+        let mut selected_ids = HashSet::new();
+        let mut template = BlockTemplate {
+            transactions: vec![],
+            fee: 0,
+            size: 0,
+            algorithm: blockchain.hash_algorithms().transaction,
+        };
+
+        loop {
+            let best_package = ancestors
+                .iter()
+                .filter(|(id, _)| !selected_ids.contains(*id))
+                .filter_map(|(id, ancestor_ids)| {
+                    let package: Vec<TransactionId> = ancestor_ids
+                        .iter()
+                        .filter(|ancestor_id| !selected_ids.contains(*ancestor_id))
+                        .chain(Some(id))
+                        .copied()
+                        .collect();
+                    let fee = package
+                        .iter()
+                        .filter_map(|id| mempool.fee(id))
+                        .fold(0u64, u64::saturating_add);
+                    let size: usize = package
+                        .iter()
+                        .filter_map(|id| mempool.transaction(id))
+                        .map(Transaction::size)
+                        .sum();
+                    if template.size + size > max_size {
+                        return None;
+                    }
+
+                    Some((FeeRate::of(fee, size), *id, package))
+                })
+                .max_by_key(|(fee_rate, id, _)| (*fee_rate, *id));
+            let mut package = match best_package {
+                Some((_, _, package)) => package,
+                None => break,
+            };
+
+            // A transaction has more ancestors than each of its parents, so
+            // this puts parents before their children.
+            package.sort_by_key(|id| (ancestors[id].len(), *id));
+            for id in package {
+                let transaction = mempool.transaction(&id).unwrap().clone();
+                template.fee = template.fee.saturating_add(mempool.fee(&id).unwrap());
+                template.size += transaction.size();
+                template.transactions.push(transaction);
+                selected_ids.insert(id);
+            }
+        }
+
+        template
+    }
+
+    /**
+     * Returns the block that follows the given block (the tip of the chain
+     * that the template was selected for) with the template's transactions,
+     * whose coinbase pays the given output plus the template's fees, and
+     * whose transaction root is computed with the chain's transaction
+     * algorithm. The block still has to be sealed (see Blockchain::seal_block).
+     */
+    pub fn into_block(
+        self,
+        previous_block: &Block,
+        timestamp: u128,
+        bits: u32,
+        mut coinbase_output: Output,
+    ) -> Block {
+        let index = previous_block.index + 1;
+        coinbase_output.value = coinbase_output.value.saturating_add(self.fee);
+        let mut transactions = vec![Transaction::coinbase(index, vec![coinbase_output])];
+        transactions.extend(self.transactions);
+
+        let mut block = Block::new(index, timestamp, previous_block.hash, transactions, bits);
+        block.update_transaction_root(self.algorithm);
+
+        block
+    }
+}
+
+#[cfg(test)]
+mod block_template_tests {
+    use super::BlockTemplate;
+
+    use crate::test_support::{add_block, coinbase, output, payment, spend, BITS};
+    use crate::transaction::{Input, LockTime, RelativeLockTime};
+    use crate::{Blockchain, Mempool, Transaction};
+
+    const PAYERS: [&str; 4] = ["Alice", "Chris", "Dave", "Eve"];
+
+    /**
+     * The genesis block pays 1,000 coins to each payer.
+     */
+    fn genesis_coinbase() -> Transaction {
+        Transaction::coinbase(0, PAYERS.iter().map(|payer| output(payer, 1_000)).collect())
+    }
+
+    fn create_blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![genesis_coinbase()]);
+
+        blockchain
+    }
+
+    /**
+     * Returns an input that spends the coins that the genesis block pays the
+     * given payer.
+     */
+    fn coin(payer: &str) -> Input {
+        let position = PAYERS.iter().position(|other| *other == payer).unwrap();
+
+        spend(&genesis_coinbase(), position)
+    }
+
+    fn create_mempool(blockchain: &Blockchain, transactions: &[&Transaction]) -> Mempool {
+        let mut mempool = Mempool::new();
+        for transaction in transactions {
+            mempool
+                .add_transaction((*transaction).clone(), blockchain)
+                .unwrap();
+        }
+
+        mempool
+    }
+
+    #[test]
+    fn select_transactions_by_fee_rate() {
+        let low = payment(coin("Alice"), "Bob", 990);
+        let high = payment(coin("Chris"), "Dave", 900);
+        let medium = payment(coin("Eve"), "Frank", 950);
+        let blockchain = create_blockchain();
+        let mempool = create_mempool(&blockchain, &[&low, &high, &medium]);
+
+        let result = BlockTemplate::new(&mempool, &blockchain);
+
+        assert_eq!(vec![high, medium, low], result.transactions);
+        assert_eq!(160, result.fee);
+        assert_eq!(
+            result
+                .transactions
+                .iter()
+                .map(Transaction::size)
+                .sum::<usize>(),
+            result.size
+        );
+    }
+
+    #[test]
+    fn child_pays_for_parent() {
+        let parent = payment(coin("Alice"), "Bob", 999);
+        let child = payment(spend(&parent, 0), "Chris", 700);
+        let unrelated = payment(coin("Dave"), "Eve", 900);
+        let blockchain = create_blockchain();
+        let mempool = create_mempool(&blockchain, &[&parent, &child, &unrelated]);
+
+        let result = BlockTemplate::new(&mempool, &blockchain);
+
+        assert!(parent.fee_rate() < unrelated.fee_rate());
+        assert_eq!(vec![parent, child, unrelated], result.transactions);
+    }
+
+    #[test]
+    fn parents_before_children() {
+        let parent = payment(coin("Alice"), "Bob", 900);
+        let child = payment(spend(&parent, 0), "Chris", 700);
+        let grandchild = payment(spend(&child, 0), "Dave", 400);
+        let blockchain = create_blockchain();
+        let mempool = create_mempool(&blockchain, &[&parent, &child, &grandchild]);
+
+        let result = BlockTemplate::new(&mempool, &blockchain);
+
+        assert_eq!(vec![parent, child, grandchild], result.transactions);
+    }
+
+    #[test]
+    fn select_packages_that_fit() {
+        let parent = payment(coin("Alice"), "Bob", 999);
+        let child = payment(spend(&parent, 0), "Chris", 500);
+        let unrelated = payment(coin("Dave"), "Eve", 900);
+        let blockchain = create_blockchain();
+        let mempool = create_mempool(&blockchain, &[&parent, &child, &unrelated]);
+        let max_size = parent.size() + unrelated.size();
+
+        let result = BlockTemplate::with_max_size(&mempool, &blockchain, max_size);
+
+        assert_eq!(vec![unrelated, parent], result.transactions);
+        assert_eq!(max_size, result.size);
+    }
+
+    #[test]
+    fn leave_out_transactions_that_are_no_longer_final() {
+        let mut blockchain = create_blockchain();
+        add_block(&mut blockchain, vec![coinbase(1, "Miner", 50)]);
+        let mut locked = payment(coin("Alice"), "Bob", 900);
+        locked.lock_time = Some(LockTime::BlockIndex(2));
+        let child = payment(spend(&locked, 0), "Chris", 800);
+        let mut relative = payment(coin("Chris"), "Dave", 900);
+        relative.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(2));
+        let unrelated = payment(coin("Dave"), "Eve", 900);
+        let mempool = create_mempool(&blockchain, &[&locked, &child, &relative, &unrelated]);
+        let template_before = BlockTemplate::new(&mempool, &blockchain);
+        blockchain.disconnect_tip().unwrap();
+
+        let result = BlockTemplate::new(&mempool, &blockchain);
+
+        assert_eq!(4, template_before.transactions.len());
+        assert_eq!(vec![unrelated], result.transactions);
+        assert_eq!(100, result.fee);
+    }
+
+    #[test]
+    fn into_block() {
+        let mut blockchain = create_blockchain();
+        let parent = payment(coin("Alice"), "Bob", 999);
+        let child = payment(spend(&parent, 0), "Dave", 700);
+        let unrelated = payment(coin("Chris"), "Eve", 900);
+        let mempool = create_mempool(&blockchain, &[&parent, &child, &unrelated]);
+        let template = BlockTemplate::new(&mempool, &blockchain);
+        let tip = blockchain.tip().unwrap();
+
+        let mut block = template.into_block(tip, tip.timestamp + 1, BITS, output("Miner", 50));
+        blockchain.seal_block(&mut block).unwrap();
+        let transactions = block.transactions.clone();
+
+        assert_eq!(Ok(()), blockchain.update_with_block(block));
+        assert_eq!(4, transactions.len());
+        assert_eq!(vec![output("Miner", 450)], transactions[0].outputs);
+        assert_eq!(vec![parent, child, unrelated], transactions[1..].to_vec());
+    }
+}
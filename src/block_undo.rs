@@ -0,0 +1,91 @@
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::ByteWriter;
+use crate::Hash256;
+
+/**
+ * The undo data of a connected block: every unspent output entry that
+ * connecting the block changed, with the value it had before. An output that
+ * the block spent had the index of the block that created it, and an output
+ * that the block created had none.
+ *
+ * Restoring the previous entries undoes the block exactly, without its
+ * transactions (see Blockchain::disconnect_tip).
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlockUndo {
+    previous_entries: Vec<(Hash256, Option<u32>)>,
+}
+
+impl BlockUndo {
+    /**
+     * Returns the undo data of the given previous entries (output hashes and
+     * the indexes of the blocks that created them, if they were unspent).
+     */
+    pub fn new<I>(previous_entries: I) -> Self
+    where
+        I: IntoIterator<Item = (Hash256, Option<u32>)>,
+    {
+        let mut previous_entries: Vec<(Hash256, Option<u32>)> =
+            previous_entries.into_iter().collect();
+        previous_entries.sort_unstable();
+        previous_entries.dedup();
+
+        BlockUndo { previous_entries }
+    }
+
+    /**
+     * Returns the previous entries, sorted by output hash.
+     */
+    pub fn previous_entries(&self) -> &[(Hash256, Option<u32>)] {
+        &self.previous_entries
+    }
+}
+
+impl Encodable for BlockUndo {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        (self.previous_entries.len() as u32).write_encoding(writer);
+        for (output_hash, block_index) in &self.previous_entries {
+            output_hash.write_encoding(writer);
+            block_index.write_encoding(writer);
+        }
+    }
+}
+
+impl Decodable for BlockUndo {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        let length = reader.read_u32()?;
+
+        let mut previous_entries = vec![];
+        for _ in 0..length {
+            previous_entries.push((reader.read_hash()?, Option::read_encoding(reader)?));
+        }
+
+        Ok(BlockUndo::new(previous_entries))
+    }
+}
+
+#[cfg(test)]
+mod block_undo_tests {
+    use super::BlockUndo;
+    use crate::encoding::{Decodable, Encodable};
+    use crate::Hash256;
+
+    #[test]
+    fn sorted_by_output_hash() {
+        let result = BlockUndo::new(vec![(Hash256([2; 32]), None), (Hash256([1; 32]), Some(3))]);
+
+        assert_eq!(
+            vec![(Hash256([1; 32]), Some(3)), (Hash256([2; 32]), None)],
+            result.previous_entries()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let undo = BlockUndo::new(vec![(Hash256([2; 32]), None), (Hash256([1; 32]), Some(3))]);
+
+        let result = BlockUndo::decode(&undo.encode());
+
+        assert_eq!(Ok(undo), result);
+    }
+}
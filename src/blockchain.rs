@@ -1,329 +1,2978 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use crate::{check_difficulty, Block, BlockHash, Hashable};
+use primitive_types::U256;
+use rayon::prelude::*;
+
+use crate::address_index::{block_events, AddressIndex};
+use crate::block_filter::BlockFilterIndex;
+use crate::genesis::genesis_previous_block_hash;
+use crate::{
+    verify_script, AddressEvent, AddressHistory, Block, BlockFilter, BlockHash, BlockUndo,
+    ChainStorage, ConsensusEngine, GenesisConfig, Hash256, HashAlgorithm, HashAlgorithms, Hashable,
+    MemoryStorage, MerkleProof, ProofOfWork, ScriptContext, ScriptErr, SealErr, SnapshotErr,
+    Transaction, TransactionId, TransactionProof, UtxoSnapshot,
+};
 
 #[derive(Debug, PartialEq)]
 pub enum BlockValidationErr {
     MismatchedIndex,
     InvalidHash,
+    InvalidSeal,
+    InvalidTransactionRoot,
     AchronologicalTimestamp,
     MismatchedPreviousHash,
     InvalidGenesisBlockFormat,
+    MismatchedGenesisBlock,
+    MismatchedCheckpoint,
+    InsufficientWork,
     InvalidInput,
+    DuplicateInput,
+    NonFinalTransaction,
+    RelativeLockTimeNotReached,
+    InvalidScript(ScriptErr),
     InsufficientInputValue,
+    ValueOverflow,
     InvalidCoinbaseTransaction,
+    MismatchedCoinbaseBlockIndex,
+    DuplicateTransaction,
     FeeExceedsCoinbaseTransactionOutputValue,
+    StorageFailure,
+}
+
+/**
+ * The errors of disconnecting the tip of a chain.
+ */
+#[derive(Debug, PartialEq)]
+pub enum DisconnectErr {
+    EmptyChain,
+    MissingUndoData,
+    StorageFailure,
+}
+
+/**
+ * Where a transaction is in the chain: the hash and the index (height) of the
+ * block that contains it, and its position among the block's transactions.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TransactionLocation {
+    pub block_hash: BlockHash,
+    pub block_index: u32,
+    pub position: usize,
+}
+
+/**
+ * A transaction found in the chain, with its location and its number of
+ * confirmations: 1 if it's in the last block, 2 if it's in the block before
+ * it, and so on.
+ */
+#[derive(Debug, PartialEq)]
+pub struct ConfirmedTransaction {
+    pub transaction: Transaction,
+    pub location: TransactionLocation,
+    pub confirmations: u32,
 }
 
 /**
  * A blockchain is just a block vector, which acts as a distributed ledger.
+ *
+ * A blockchain may be pinned to an expected genesis block hash, in which case
+ * it only accepts that genesis block.
+ *
+ * Blocks and transactions are hashed with the chain's hash algorithms
+ * (SHA-256 by default), and blocks are sealed and verified by the chain's
+ * consensus engine (proof-of-work by default).
+ *
+ * A blockchain validates blocks, and its storage backend keeps its state: the
+ * blocks, their undo data, the unspent outputs and the chain work (see
+ * ChainStorage). The constructors create a chain that keeps its state in
+ * memory (see MemoryStorage), which can then be moved to another backend,
+ * e.g., one that stores it on disk (see with_storage).
+ *
+ * A blockchain may keep a transaction index, which maps transaction ids to
+ * their locations, and an address index, which keeps the history of every
+ * address, so that looking a transaction or a history up doesn't scan every
+ * block (see get_transaction and address_history). It may also keep the
+ * compact filters of its blocks, which it serves to light clients (see
+ * enable_block_filters).
+ *
+ * A blockchain may be pruned: it keeps every block header and the unspent
+ * outputs, which is all that validating new blocks needs, but drops the bodies
+ * (transactions) of blocks deeper than the pruning depth from its storage. The
+ * bodies of the last blocks are kept so that a reorganization up to the
+ * pruning depth can disconnect them (see enable_pruning).
+ *
+ * Each connected block's undo data (the unspent outputs it changed) is stored
+ * next to the block, so that the block can be disconnected from the tip
+ * exactly (see disconnect_tip). Undo data is pruned along with the bodies.
+ *
+ * A blockchain may also start from a snapshot of the unspent outputs at some
+ * height instead of from the genesis block (see load_utxo_snapshot).
  */
-#[derive(Default)]
-pub struct Blockchain {
-    pub blocks: Vec<Block>,
-    unspent_outputs: HashSet<BlockHash>,
+pub struct Blockchain<S: ChainStorage = MemoryStorage> {
+    storage: S,
+    expected_genesis_hash: Option<BlockHash>,
+    hash_algorithms: HashAlgorithms,
+    consensus: Box<dyn ConsensusEngine>,
+    transaction_index: Option<HashMap<TransactionId, TransactionLocation>>,
+    address_index: Option<AddressIndex>,
+    block_filters: Option<BlockFilterIndex>,
+    pruning_depth: Option<u32>,
+    unconfirmed_snapshot_hash: Option<Hash256>,
+}
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Blockchain::new()
+    }
 }
 
 impl Blockchain {
     pub fn new() -> Self {
         Blockchain {
-            blocks: vec![],
-            unspent_outputs: HashSet::new(),
+            storage: MemoryStorage::default(),
+            expected_genesis_hash: None,
+            hash_algorithms: HashAlgorithms::default(),
+            consensus: Box::new(ProofOfWork),
+            transaction_index: None,
+            address_index: None,
+            block_filters: None,
+            pruning_depth: None,
+            unconfirmed_snapshot_hash: None,
         }
     }
 
     /**
-     * Block Verification
-     * ------------------
-     *
-     * Each supposed valid block has a nonce attached to it that we assume took
-     * an approximately certain amount of effort to generate. This
-     * "approximately certain amount of effort" is described by the difficulty
-     * value.
-     *
-     * We will verify four things now:
-     *
-     * 1. Actual index == stored index value (note that Bitcoin blocks don't
-     *    store their index).
-     *
-     * 2. Block's hash fits stored difficulty value (we'll just trust the
-     *    difficulty for now) (insecure).
-     *
-     * 3. Time is always increasing (in real life [IRL] network latency/sync
-     *    demands leniency here).
-     *
-     * 4. Actual previous block's hash == stored previous_block_hash value
-     *    (except for the genesis block).
-     *
-     * Security Notes
-     * --------------
-     *
-     * This is not secure! There are some things to take into account:
-     *
-     * - The difficulty stored in a block is not validated.
-     *
-     * - The value of the coinbase transaction is not validated.
-     *
-     * - "Coin ownership" is neither enforced nor existent.
-     *
-     * - Two otherwise identical outputs from different transactions are
-     *   indistinguishable.
+     * Creates an empty blockchain whose blocks are verified by the given
+     * consensus engine.
      */
-    pub fn update_with_block(&mut self, block: Block) -> Result<(), BlockValidationErr> {
-        let index = self.blocks.len();
+    pub fn with_consensus(consensus: Box<dyn ConsensusEngine>) -> Self {
+        Blockchain {
+            consensus,
+            ..Blockchain::new()
+        }
+    }
 
-        if block.index != index as u32 {
-            return Err(BlockValidationErr::MismatchedIndex);
-        } else if !check_difficulty(&block.hash(), block.difficulty) {
-            return Err(BlockValidationErr::InvalidHash);
-        } else if self.is_genesis_block(index) {
-            if block.previous_block_hash != vec![0; 32] {
-                return Err(BlockValidationErr::InvalidGenesisBlockFormat);
-            }
-        } else {
-            let previous_block = &self.blocks[index - 1];
-            if block.timestamp <= previous_block.timestamp {
-                return Err(BlockValidationErr::AchronologicalTimestamp);
-            } else if block.previous_block_hash != previous_block.hash {
-                return Err(BlockValidationErr::MismatchedPreviousHash);
-            }
+    /**
+     * Creates an empty blockchain that hashes blocks and transactions with the
+     * given algorithms.
+     */
+    pub fn with_hash_algorithms(hash_algorithms: HashAlgorithms) -> Self {
+        Blockchain {
+            hash_algorithms,
+            ..Blockchain::new()
         }
+    }
 
-        if let Some((coinbase, transactions)) = block.transactions.split_first() {
-            if !coinbase.is_coinbase() {
-                return Err(BlockValidationErr::InvalidCoinbaseTransaction);
-            }
+    /**
+     * Creates an empty blockchain that only accepts a genesis block with the
+     * given hash.
+     */
+    pub fn with_genesis_hash(genesis_hash: BlockHash) -> Self {
+        Blockchain {
+            expected_genesis_hash: Some(genesis_hash),
+            ..Blockchain::new()
+        }
+    }
 
-            let mut block_spent: HashSet<BlockHash> = HashSet::new();
-            let mut block_created: HashSet<BlockHash> = HashSet::new();
-            let mut total_fee = 0;
+    /**
+     * Creates a blockchain from chain parameters: builds the canonical genesis
+     * block, pins the chain to its hash and adds it as the first block. The
+     * chain uses the configuration's hash algorithms.
+     */
+    pub fn from_genesis(config: &GenesisConfig) -> Result<Self, BlockValidationErr> {
+        let genesis_block = config.build_block();
+        let mut blockchain = Blockchain {
+            expected_genesis_hash: Some(genesis_block.hash),
+            ..Blockchain::with_hash_algorithms(config.hash_algorithms)
+        };
+        blockchain.update_with_block(genesis_block)?;
 
-            for transaction in transactions {
-                let input_hashes = transaction.input_hashes();
-                if !(&input_hashes - &self.unspent_outputs).is_empty() {
-                    return Err(BlockValidationErr::InvalidInput);
-                }
+        Ok(blockchain)
+    }
 
-                let input_value = transaction.input_value();
-                let output_value = transaction.output_value();
-                if output_value > input_value {
-                    return Err(BlockValidationErr::InsufficientInputValue);
-                }
+    /**
+     * Creates a blockchain from chain parameters like from_genesis does, but
+     * seals the genesis block with the given consensus engine, which then
+     * verifies the chain's blocks.
+     */
+    pub fn from_genesis_with_consensus(
+        config: &GenesisConfig,
+        consensus: Box<dyn ConsensusEngine>,
+    ) -> Result<Self, BlockValidationErr> {
+        let genesis_block = config
+            .build_block_with(consensus.as_ref())
+            .map_err(|_| BlockValidationErr::InvalidSeal)?;
+        let mut blockchain = Blockchain {
+            expected_genesis_hash: Some(genesis_block.hash),
+            hash_algorithms: config.hash_algorithms,
+            ..Blockchain::with_consensus(consensus)
+        };
+        blockchain.update_with_block(genesis_block)?;
 
-                let fee = input_value - output_value;
-                total_fee += fee;
+        Ok(blockchain)
+    }
 
-                block_spent.extend(input_hashes);
-                block_created.extend(transaction.output_hashes());
-            }
+    /**
+     * Returns the chain's blocks, whose bodies are empty if they were pruned.
+     */
+    pub fn blocks(&self) -> &[Block] {
+        self.storage.blocks()
+    }
 
-            if coinbase.output_value() < total_fee {
-                return Err(BlockValidationErr::FeeExceedsCoinbaseTransactionOutputValue);
-            } else {
-                block_created.extend(coinbase.output_hashes());
+    /**
+     * Returns a chain with this chain's configuration (its genesis block pin,
+     * hash algorithms, consensus engine, indexes and pruning depth) that
+     * keeps its state in the given storage, e.g., a chain restarted from the
+     * files of a FlatFileStorage. The stored chain is trusted, so its blocks
+     * aren't validated again, but they're indexed if an index is enabled.
+     *
+     * This chain's own blocks are dropped, so it's meant to be called on a
+     * chain that has just been configured.
+     */
+    pub fn with_storage<T: ChainStorage>(
+        self,
+        storage: T,
+    ) -> Result<Blockchain<T>, BlockValidationErr> {
+        if let (Some(expected_genesis_hash), Some(genesis_block)) =
+            (&self.expected_genesis_hash, storage.header(0))
+        {
+            if &genesis_block.hash != expected_genesis_hash {
+                return Err(BlockValidationErr::MismatchedGenesisBlock);
             }
-
-            self.unspent_outputs
-                .retain(|output| !block_spent.contains(output));
-            self.unspent_outputs.extend(block_created);
         }
 
-        self.blocks.push(block);
-
-        Ok(())
-    }
+        let mut blockchain = Blockchain {
+            storage,
+            expected_genesis_hash: self.expected_genesis_hash,
+            hash_algorithms: self.hash_algorithms,
+            consensus: self.consensus,
+            transaction_index: None,
+            address_index: None,
+            block_filters: None,
+            pruning_depth: self.pruning_depth,
+            unconfirmed_snapshot_hash: None,
+        };
+        if self.transaction_index.is_some() {
+            blockchain.enable_transaction_index();
+        }
+        if self.address_index.is_some() {
+            blockchain.enable_address_index();
+        }
+        if self.block_filters.is_some() {
+            blockchain.enable_block_filters();
+        }
+        blockchain.prune();
 
-    fn is_genesis_block(&self, index: usize) -> bool {
-        index == 0
+        Ok(blockchain)
     }
 }
 
-#[cfg(test)]
-mod blockchain_constructor_tests {
-    use std::collections::HashSet;
-
-    use super::{Block, BlockHash, Blockchain};
+impl<S: ChainStorage> Blockchain<S> {
+    /**
+     * Returns the storage backend that keeps the chain's state.
+     */
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
 
-    fn assert_default_constructor(instance: Blockchain) {
-        assert_eq!(Vec::<Block>::new(), instance.blocks);
-        assert_eq!(HashSet::<BlockHash>::new(), instance.unspent_outputs);
+    /**
+     * Returns the number of blocks in the chain.
+     */
+    pub fn block_count(&self) -> u32 {
+        self.storage.block_count()
     }
 
-    #[test]
-    fn constructor_with_new() {
-        let instance = Blockchain::new();
+    /**
+     * Returns the header of the block with the given index: a block whose
+     * transactions may have been left out (see block).
+     */
+    pub fn header(&self, index: u32) -> Option<&Block> {
+        self.storage.header(index)
+    }
 
-        assert_default_constructor(instance);
+    /**
+     * Returns the header of the chain's tip, if any.
+     */
+    pub fn tip(&self) -> Option<&Block> {
+        self.storage
+            .block_count()
+            .checked_sub(1)
+            .and_then(|index| self.storage.header(index))
     }
 
-    #[test]
-    fn constructor_with_default() {
-        let instance: Blockchain = Default::default();
+    /**
+     * Returns the block with the given index with its transactions, or None
+     * if the chain doesn't have it or its body can't be read (e.g., because
+     * it was pruned).
+     */
+    pub fn block(&self, index: u32) -> Option<Block> {
+        let header = self.storage.header(index)?;
+        let transactions = self.storage.read_body(index).ok()??;
 
-        assert_default_constructor(instance);
-    }
-}
+        let mut block = header.header();
+        block.transactions = transactions;
 
-#[cfg(test)]
-mod blockchain_update_with_block_tests {
-    use crate::transaction::Output;
-    use crate::{now, Transaction};
+        Some(block)
+    }
 
-    use super::{check_difficulty, Block, BlockHash, BlockValidationErr, Blockchain, Hashable};
+    /**
+     * Returns the hash algorithms of this chain.
+     */
+    pub fn hash_algorithms(&self) -> HashAlgorithms {
+        self.hash_algorithms
+    }
 
-    const IMPOSSIBLE_DIFFICULTY: u128 = 0x0000_0000_0000_0000_0000_0000_0000_0000;
-    const DIFFICULTY: u128 = 0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    /**
+     * Returns the consensus engine of this chain.
+     */
+    pub fn consensus(&self) -> &dyn ConsensusEngine {
+        self.consensus.as_ref()
+    }
 
-    struct BlockOutputConfig {
-        unspent_output_value: u64,
-        output_value: u64,
-        expected_difference: u64,
+    /**
+     * Seals the block with the chain's consensus engine and block hash
+     * algorithm, so that the chain accepts it (if it is otherwise valid).
+     */
+    pub fn seal_block(&self, block: &mut Block) -> Result<(), SealErr> {
+        self.consensus.seal(block, self.hash_algorithms.block)
     }
 
-    fn genesis_block_hash() -> BlockHash {
-        vec![0; 32]
+    /**
+     * Returns the genesis block hash of this chain: the expected one if the
+     * chain is pinned, otherwise the hash of its first block (if any).
+     */
+    pub fn genesis_hash(&self) -> Option<&BlockHash> {
+        self.expected_genesis_hash
+            .as_ref()
+            .or_else(|| self.storage.header(0).map(|block| &block.hash))
     }
 
-    fn current_time() -> u128 {
-        now().expect("Failure to get the current time in milliseconds.")
+    /**
+     * Returns the total work of the chain: the sum of its blocks' work, as
+     * the consensus engine weighs it.
+     */
+    pub fn chain_work(&self) -> U256 {
+        self.storage.chain_work()
     }
 
-    fn create_coinbase_transaction() -> Transaction {
-        Transaction {
-            inputs: vec![],
-            outputs: vec![],
+    /**
+     * Starts keeping a transaction index: indexes the transactions of the
+     * chain's blocks and, from now on, of every block added to it.
+     */
+    pub fn enable_transaction_index(&mut self) {
+        let algorithm = self.hash_algorithms.transaction;
+        let mut index = HashMap::new();
+        for block in (0..self.block_count()).filter_map(|index| self.block(index)) {
+            index.extend(transaction_locations(&block, algorithm));
         }
-    }
 
-    fn create_block_with_impossible_difficulty(
-        index: u32,
-        timestamp: u128,
-        previous_block_hash: BlockHash,
-        transactions: Vec<Transaction>,
-    ) -> Block {
-        let block = Block::new(
-            index,
-            timestamp,
-            previous_block_hash,
-            transactions,
-            IMPOSSIBLE_DIFFICULTY,
-        );
-        assert_eq!(false, check_difficulty(&block.hash(), block.difficulty));
+        self.transaction_index = Some(index);
+    }
 
-        block
+    /**
+     * Returns a flag that states whether the chain keeps a transaction index.
+     */
+    pub fn has_transaction_index(&self) -> bool {
+        self.transaction_index.is_some()
     }
 
-    fn create_block_with_valid_difficulty(
-        index: u32,
-        timestamp: u128,
-        previous_block_hash: BlockHash,
-        transactions: Vec<Transaction>,
-    ) -> Block {
-        let block = Block::new(
-            index,
-            timestamp,
-            previous_block_hash,
-            transactions,
-            DIFFICULTY,
-        );
-        assert_eq!(true, check_difficulty(&block.hash(), block.difficulty));
+    /**
+     * Returns the transaction with the given id (see Transaction::id_with and
+     * the chain's transaction algorithm), its location and its number of
+     * confirmations, or None if no block contains it (or if its block's body
+     * has been pruned). Without a transaction index, every block is scanned.
+     */
+    pub fn get_transaction(&self, id: &TransactionId) -> Option<ConfirmedTransaction> {
+        let algorithm = self.hash_algorithms.transaction;
+        let (location, mut transactions) = match &self.transaction_index {
+            Some(index) => {
+                let location = *index.get(id)?;
 
-        block
-    }
+                (location, self.block(location.block_index)?.transactions)
+            }
+            None => (0..self.block_count())
+                .rev()
+                .filter_map(|index| self.block(index))
+                .find_map(|block| {
+                    let (_, location) = transaction_locations(&block, algorithm)
+                        .find(|(transaction_id, _)| transaction_id == id)?;
 
-    fn add_block_to_blockchain(blockchain: &mut Blockchain, block: Block) {
-        let original_length = blockchain.blocks.len();
+                    Some((location, block.transactions))
+                })?,
+        };
+        let tip_index = self.block_count() - 1;
 
-        let result = blockchain.update_with_block(block);
+        if location.position >= transactions.len() {
+            return None;
+        }
 
-        assert_eq!(true, result.is_ok());
-        assert_eq!(Ok(()), result);
-        assert_eq!(original_length + 1, blockchain.blocks.len());
+        Some(ConfirmedTransaction {
+            transaction: transactions.swap_remove(location.position),
+            location,
+            confirmations: tip_index - location.block_index + 1,
+        })
     }
 
-    fn assert_add_block_with_sufficient_inputs(config: BlockOutputConfig) {
-        let timestamp = current_time();
-        let genesis_block = create_block_with_valid_difficulty(
-            0,
-            timestamp,
-            genesis_block_hash(),
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![
-                    Output {
-                        to_address: "Alice".to_string(),
-                        value: 1,
-                    },
-                    Output {
-                        to_address: "Bob".to_string(),
-                        value: 2,
-                    },
-                ],
-            }],
-        );
-        let mut blockchain = Blockchain::new();
-        let block = create_block_with_valid_difficulty(
-            1,
-            timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![Output {
-                        to_address: "Chris".to_owned(),
-                        value: config.unspent_output_value,
-                    }],
-                },
-                Transaction {
-                    inputs: vec![
-                        Output {
-                            to_address: "Alice".to_owned(),
-                            value: 1,
-                        },
-                        Output {
-                            to_address: "Bob".to_owned(),
-                            value: 2,
-                        },
-                    ],
-                    outputs: vec![Output {
-                        to_address: "Chris".to_owned(),
-                        value: config.output_value,
-                    }],
-                },
-            ],
-        );
-        assert!(block.transactions[1].input_value() >= block.transactions[1].output_value());
-        assert_eq!(
-            block.transactions[1].input_value(),
-            block.transactions[1].output_value() + config.expected_difference
-        );
-        add_block_to_blockchain(&mut blockchain, genesis_block);
-        add_block_to_blockchain(&mut blockchain, block);
+    /**
+     * Returns up to count headers (blocks without their transactions) of the
+     * chain, starting at the given index, e.g., for a light client.
+     */
+    pub fn headers(&self, start: u32, count: u32) -> Vec<Block> {
+        (start..start.saturating_add(count))
+            .map_while(|index| self.header(index).map(Block::header))
+            .collect()
     }
 
-    #[test]
-    fn add_block_with_invalid_previous_block_hash() {
-        let wrong_block_hash = vec![];
-        let genesis_block =
-            create_block_with_valid_difficulty(0, current_time(), wrong_block_hash, vec![]);
-        let mut blockchain = Blockchain::new();
-
-        let result = blockchain.update_with_block(genesis_block);
+    /**
+     * Returns a proof that the transaction with the given id is in the
+     * chain, which a light client can check against the block's header (see
+     * LightClient), or None if no block contains it (or if its block's body
+     * has been pruned).
+     */
+    pub fn transaction_proof(&self, id: &TransactionId) -> Option<TransactionProof> {
+        let confirmed_transaction = self.get_transaction(id)?;
+        let location = confirmed_transaction.location;
+        let block = self.block(location.block_index)?;
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::InvalidGenesisBlockFormat), result);
+        Some(TransactionProof {
+            transaction: confirmed_transaction.transaction,
+            block_hash: location.block_hash,
+            block_index: location.block_index,
+            proof: MerkleProof::new(
+                &block.transactions,
+                location.position,
+                self.hash_algorithms.transaction,
+            )?,
+        })
     }
 
-    #[test]
-    fn add_block_with_index_as_one_to_empty_blockchain() {
-        let wrong_index = 1;
-        let genesis_block = Block::new(wrong_index, 2, vec![], vec![], 3);
-        let mut blockchain = Blockchain::new();
-
-        let result = blockchain.update_with_block(genesis_block);
+    /**
+     * Starts keeping an address index: indexes the history of every address
+     * in the chain's blocks and, from now on, in every block added to it.
+     */
+    pub fn enable_address_index(&mut self) {
+        let mut index = AddressIndex::default();
+        for block in (0..self.block_count()).filter_map(|index| self.block(index)) {
+            index.connect_block(&block, self.hash_algorithms.transaction);
+        }
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::MismatchedIndex), result);
+        self.address_index = Some(index);
+    }
+
+    /**
+     * Returns a flag that states whether the chain keeps an address index.
+     */
+    pub fn has_address_index(&self) -> bool {
+        self.address_index.is_some()
+    }
+
+    /**
+     * Returns a page of the history of the given address: at most limit
+     * events, starting at the given offset into the history (which is in
+     * chain order, see AddressIndex), and the total number of events. Without
+     * an address index, every block is scanned.
+     */
+    pub fn address_history(&self, address: &str, offset: usize, limit: usize) -> AddressHistory {
+        match &self.address_index {
+            Some(index) => {
+                let history = index.history(address);
+
+                AddressHistory {
+                    events: history.iter().skip(offset).take(limit).cloned().collect(),
+                    total: history.len(),
+                }
+            }
+            None => {
+                let history: Vec<AddressEvent> = (0..self.block_count())
+                    .filter_map(|index| self.block(index))
+                    .flat_map(|block| block_events(&block, self.hash_algorithms.transaction))
+                    .filter(|(event_address, _)| event_address == address)
+                    .map(|(_, event)| event)
+                    .collect();
+
+                AddressHistory {
+                    total: history.len(),
+                    events: history.into_iter().skip(offset).take(limit).collect(),
+                }
+            }
+        }
+    }
+
+    /**
+     * Starts keeping block filters: builds the compact filter (see
+     * BlockFilter) of the chain's blocks and, from now on, of every block
+     * added to it, and the chain of their filter headers. Blocks whose
+     * bodies have been pruned get no filters, so the filters start after
+     * the last of them.
+     */
+    pub fn enable_block_filters(&mut self) {
+        let algorithm = self.hash_algorithms.transaction;
+        let mut filters = BlockFilterIndex::default();
+        for index in 0..self.block_count() {
+            match self.block(index) {
+                Some(block) => filters.connect_filter(BlockFilter::new(&block, algorithm)),
+                None => filters = BlockFilterIndex::starting_at(index + 1),
+            }
+        }
+
+        self.block_filters = Some(filters);
+    }
+
+    /**
+     * Returns a flag that states whether the chain keeps block filters.
+     */
+    pub fn has_block_filters(&self) -> bool {
+        self.block_filters.is_some()
+    }
+
+    /**
+     * Returns the filter of the block with the given index, or None if the
+     * chain doesn't keep block filters or has no filter for the block.
+     */
+    pub fn block_filter(&self, index: u32) -> Option<&BlockFilter> {
+        self.block_filters.as_ref()?.filter(index)
+    }
+
+    /**
+     * Returns up to count filter headers (see BlockFilter::header), starting
+     * at the one of the block with the given index.
+     */
+    pub fn filter_headers(&self, start: u32, count: u32) -> Vec<Hash256> {
+        let filters = match &self.block_filters {
+            Some(filters) => filters,
+            None => return vec![],
+        };
+
+        (start..start.saturating_add(count))
+            .map_while(|index| filters.header(index))
+            .collect()
+    }
+
+    /**
+     * Starts pruning the chain: drops the bodies of the chain's blocks except
+     * the last depth ones (at least the tip's) and, from now on, whenever
+     * blocks are added. Pruned blocks keep their headers, but their
+     * transactions can't be looked up anymore (see get_transaction), and an
+     * address index has to be enabled before the blocks are pruned to cover
+     * them.
+     */
+    pub fn enable_pruning(&mut self, depth: u32) {
+        self.pruning_depth = Some(depth.max(1));
+
+        self.prune();
+    }
+
+    /**
+     * Returns the number of blocks whose bodies are kept when the chain is
+     * pruned, or None if it isn't.
+     */
+    pub fn pruning_depth(&self) -> Option<u32> {
+        self.pruning_depth
+    }
+
+    /**
+     * Returns the number of blocks, from the genesis block on, whose bodies
+     * have been pruned.
+     */
+    pub fn pruned_height(&self) -> u32 {
+        self.storage.pruned_height()
+    }
+
+    /**
+     * Returns a snapshot of the chain's unspent outputs at its tip, or None if
+     * the chain is empty (or its unspent outputs can't be read). To export a
+     * snapshot at a given height, take it when that block is the tip.
+     */
+    pub fn utxo_snapshot(&self) -> Option<UtxoSnapshot> {
+        let tip = self.tip()?;
+        let unspent_outputs = self.storage.unspent_outputs().ok()?;
+
+        Some(UtxoSnapshot::new(tip.index, tip.hash, unspent_outputs))
+    }
+
+    /**
+     * Starts the empty chain from the given snapshot of the unspent outputs,
+     * whose hash must be the trusted one, and the headers of the blocks up to
+     * the snapshot's block (blocks whose transactions are dropped). New blocks
+     * are then validated against the snapshot's unspent outputs, as if the
+     * chain had been replayed up to its height, and the chain is pruned below
+     * it (see pruned_height).
+     *
+     * The headers can't be verified against the consensus engine without
+     * their transactions (which the block hashes cover), so they're only
+     * checked to link up to the snapshot's block, which the trusted hash
+     * commits to. The snapshot stays unconfirmed until a chain that replayed
+     * the blocks confirms it (see confirm_utxo_snapshot).
+     */
+    pub fn load_utxo_snapshot(
+        &mut self,
+        snapshot: UtxoSnapshot,
+        mut headers: Vec<Block>,
+        trusted_hash: &Hash256,
+    ) -> Result<(), SnapshotErr> {
+        if self.storage.block_count() != 0 {
+            return Err(SnapshotErr::ChainNotEmpty);
+        }
+
+        let snapshot_hash = snapshot.hash();
+        if &snapshot_hash != trusted_hash {
+            return Err(SnapshotErr::UntrustedSnapshot);
+        }
+
+        let links_up = headers.len() == snapshot.height() as usize + 1
+            && headers.last().map(|header| &header.hash) == Some(snapshot.block_hash())
+            && headers[0].previous_block_hash == genesis_previous_block_hash()
+            && headers.iter().enumerate().all(|(index, header)| {
+                header.index == index as u32
+                    && (index == 0 || {
+                        let previous_header = &headers[index - 1];
+                        header.previous_block_hash == previous_header.hash
+                            && header.timestamp > previous_header.timestamp
+                    })
+            });
+        if !links_up {
+            return Err(SnapshotErr::InvalidHeaders);
+        }
+        if let Some(expected_genesis_hash) = &self.expected_genesis_hash {
+            if &headers[0].hash != expected_genesis_hash {
+                return Err(SnapshotErr::MismatchedGenesisBlock);
+            }
+        }
+
+        for header in &mut headers {
+            header.transactions = vec![];
+        }
+        let chain_work = headers.iter().fold(U256::zero(), |work, header| {
+            work.saturating_add(self.consensus.work(header))
+        });
+        self.storage
+            .load_snapshot(headers, snapshot.unspent_outputs(), chain_work)
+            .map_err(|_| SnapshotErr::StorageFailure)?;
+        self.unconfirmed_snapshot_hash = Some(snapshot_hash);
+        if let Some(block_filters) = &mut self.block_filters {
+            *block_filters = BlockFilterIndex::starting_at(snapshot.height() + 1);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Returns the hash of the snapshot that the chain was started from, if it
+     * hasn't been confirmed yet.
+     */
+    pub fn unconfirmed_snapshot_hash(&self) -> Option<&Hash256> {
+        self.unconfirmed_snapshot_hash.as_ref()
+    }
+
+    /**
+     * Confirms the snapshot that the chain was started from with a chain that
+     * replayed the blocks up to the snapshot's height (e.g., in the
+     * background): the replayed chain's tip must be at that height, and its
+     * unspent outputs must have the snapshot's hash.
+     */
+    pub fn confirm_utxo_snapshot<T: ChainStorage>(
+        &mut self,
+        replayed: &Blockchain<T>,
+    ) -> Result<(), SnapshotErr> {
+        let snapshot_hash = self
+            .unconfirmed_snapshot_hash
+            .ok_or(SnapshotErr::NoUnconfirmedSnapshot)?;
+
+        if replayed.utxo_snapshot().map(|snapshot| snapshot.hash()) != Some(snapshot_hash) {
+            return Err(SnapshotErr::MismatchedReplay);
+        }
+
+        self.unconfirmed_snapshot_hash = None;
+
+        Ok(())
+    }
+
+    /**
+     * Returns a flag that states whether a peer with the given genesis block
+     * hash is on the same chain. Nodes that disagree on genesis must refuse
+     * to peer.
+     */
+    pub fn shares_genesis_with(&self, peer_genesis_hash: &BlockHash) -> bool {
+        self.genesis_hash() == Some(peer_genesis_hash)
+    }
+
+    /**
+     * Block Verification
+     * ------------------
+     *
+     * Each supposed valid block has a nonce attached to it that we assume took
+     * an approximately certain amount of effort to generate. This
+     * "approximately certain amount of effort" is described by the target
+     * value (see Target). Other consensus engines seal blocks differently,
+     * e.g., with a validator's signature (see ConsensusEngine).
+     *
+     * We will verify six things now:
+     *
+     * 1. Actual index == stored index value (note that Bitcoin blocks don't
+     *    store their index).
+     *
+     * 2. Block's seal is accepted by the consensus engine, and its stored hash
+     *    is its actual hash. For proof-of-work, the block's hash fits stored
+     *    target value (we'll just trust the target for now) (insecure). The
+     *    hash covers the block's transactions through its transaction root,
+     *    which must be their root.
+     *
+     * 3. Time is always increasing (in real life [IRL] network latency/sync
+     *    demands leniency here).
+     *
+     * 4. Actual previous block's hash == stored previous_block_hash value
+     *    (except for the genesis block, which must match the expected genesis
+     *    hash if the chain is pinned to one).
+     *
+     * 5. Every transaction is final at the block's index and timestamp: its
+     *    lock time, and the relative lock times of its inputs (counted from
+     *    the blocks that created the spent outputs), have been reached (see
+     *    Transaction).
+     *
+     * 6. No two transactions have the same id, and the coinbase transaction
+     *    commits to the block's index (see Transaction::coinbase).
+     *
+     * The transactions are then validated in two passes: a sequential one
+     * over what they spend (the spent outputs are unspent and spent once,
+     * their relative lock times have been reached and the inputs cover the
+     * outputs), which is cheap, and a parallel one over their inputs'
+     * scripts, which may check signatures. A transaction may spend the outputs
+     * of a transaction that comes before it in the same block, so a child can
+     * be confirmed along with its parent (see BlockTemplate). If several
+     * transactions are invalid, the error of the first one (in block order)
+     * is returned, and the sequential checks of a transaction come before its
+     * scripts, so the error doesn't depend on how the parallel pass was
+     * scheduled.
+     *
+     * Security Notes
+     * --------------
+     *
+     * This is not secure! There are some things to take into account:
+     *
+     * - The target stored in a block is not validated.
+     *
+     * - The value of the coinbase transaction is not validated.
+     *
+     * - "Coin ownership" is only enforced for outputs with a locking script:
+     *   every input's unlocking script must satisfy the locking script of the
+     *   output it spends (see Script).
+     */
+    pub fn update_with_block(&mut self, block: Block) -> Result<(), BlockValidationErr> {
+        self.validate_header(&block)?;
+
+        if !block.has_valid_transaction_root(self.hash_algorithms.transaction) {
+            return Err(BlockValidationErr::InvalidTransactionRoot);
+        }
+
+        if block
+            .transactions
+            .iter()
+            .any(|transaction| !transaction.is_final(block.index, block.timestamp))
+        {
+            return Err(BlockValidationErr::NonFinalTransaction);
+        }
+
+        let algorithm = self.hash_algorithms.transaction;
+        let mut block_spent: HashSet<BlockHash> = HashSet::new();
+        let mut block_created: HashSet<BlockHash> = HashSet::new();
+
+        if let Some((coinbase, transactions)) = block.transactions.split_first() {
+            if !coinbase.is_coinbase() {
+                return Err(BlockValidationErr::InvalidCoinbaseTransaction);
+            } else if coinbase.coinbase_block_index() != Some(block.index) {
+                return Err(BlockValidationErr::MismatchedCoinbaseBlockIndex);
+            }
+
+            let mut ids = HashSet::new();
+            if !block
+                .transactions
+                .iter()
+                .all(|transaction| ids.insert(transaction.id_with(algorithm)))
+            {
+                return Err(BlockValidationErr::DuplicateTransaction);
+            }
+
+            let mut total_fee: u64 = 0;
+            let mut spend_failure = None;
+
+            for (position, transaction) in transactions.iter().enumerate() {
+                let result = self
+                    .validate_spends(
+                        transaction,
+                        block.index,
+                        block.timestamp,
+                        &block_spent,
+                        &block_created,
+                    )
+                    .inspect(|_| {
+                        for input_hash in transaction.input_hashes(algorithm) {
+                            if !block_created.remove(&input_hash) {
+                                block_spent.insert(input_hash);
+                            }
+                        }
+                        block_created.extend(transaction.output_hashes(algorithm));
+                    });
+                match result {
+                    Ok(fee) => total_fee = total_fee.saturating_add(fee),
+                    Err(BlockValidationErr::StorageFailure) => {
+                        return Err(BlockValidationErr::StorageFailure)
+                    }
+                    Err(error) => {
+                        spend_failure = Some((position, error));
+                        break;
+                    }
+                }
+            }
+
+            let checked_count = spend_failure
+                .as_ref()
+                .map_or(transactions.len(), |(position, _)| position + 1);
+            let script_failure =
+                verify_scripts(&transactions[..checked_count], block.index, algorithm)
+                    .map(|(position, error)| (position, BlockValidationErr::InvalidScript(error)));
+            let failure = match (spend_failure, script_failure) {
+                (Some(spend_failure), Some(script_failure)) => {
+                    if script_failure.0 < spend_failure.0 {
+                        Some(script_failure)
+                    } else {
+                        Some(spend_failure)
+                    }
+                }
+                (spend_failure, script_failure) => spend_failure.or(script_failure),
+            };
+            if let Some((_, error)) = failure {
+                return Err(error);
+            }
+
+            let coinbase_value = coinbase
+                .output_value()
+                .ok_or(BlockValidationErr::ValueOverflow)?;
+            if coinbase_value < total_fee {
+                return Err(BlockValidationErr::FeeExceedsCoinbaseTransactionOutputValue);
+            }
+            block_created.extend(coinbase.output_hashes(algorithm));
+        }
+
+        let mut previous_entries = vec![];
+        for output in block_spent.iter().chain(&block_created) {
+            previous_entries.push((*output, self.unspent_output(output)?));
+        }
+        let undo = BlockUndo::new(previous_entries);
+        let changes: Vec<(Hash256, Option<u32>)> = block_spent
+            .iter()
+            .filter(|output| !block_created.contains(output))
+            .map(|output| (*output, None))
+            .chain(
+                block_created
+                    .iter()
+                    .map(|output| (*output, Some(block.index))),
+            )
+            .collect();
+
+        let locations: Vec<(TransactionId, TransactionLocation)> = match &self.transaction_index {
+            Some(_) => transaction_locations(&block, algorithm).collect(),
+            None => vec![],
+        };
+        let events = match &self.address_index {
+            Some(_) => block_events(&block, algorithm),
+            None => vec![],
+        };
+        let filter = self
+            .block_filters
+            .as_ref()
+            .map(|_| BlockFilter::new(&block, algorithm));
+        let chain_work = self
+            .storage
+            .chain_work()
+            .saturating_add(self.consensus.work(&block));
+
+        self.storage
+            .connect_block(block, undo, &changes, chain_work)
+            .map_err(|_| BlockValidationErr::StorageFailure)?;
+
+        if let Some(transaction_index) = &mut self.transaction_index {
+            transaction_index.extend(locations);
+        }
+        if let Some(address_index) = &mut self.address_index {
+            address_index.add_events(events);
+        }
+        if let (Some(block_filters), Some(filter)) = (&mut self.block_filters, filter) {
+            block_filters.connect_filter(filter);
+        }
+
+        self.prune();
+
+        Ok(())
+    }
+
+    /**
+     * Validates the header of the chain's next block (items 1 to 4 of
+     * update_with_block), without its transactions: its index, its hash and
+     * seal, its timestamp and its link to the tip (or to the expected genesis
+     * block).
+     */
+    pub fn validate_header(&self, header: &Block) -> Result<(), BlockValidationErr> {
+        let index = self.storage.block_count() as usize;
+        let block_hash = header.hash_with(self.hash_algorithms.block);
+
+        if header.index != index as u32 {
+            return Err(BlockValidationErr::MismatchedIndex);
+        }
+
+        self.consensus.verify_seal(header, &block_hash)?;
+
+        if header.hash != block_hash {
+            return Err(BlockValidationErr::InvalidHash);
+        }
+
+        if self.is_genesis_block(index) {
+            if header.previous_block_hash != genesis_previous_block_hash() {
+                return Err(BlockValidationErr::InvalidGenesisBlockFormat);
+            } else if let Some(expected_genesis_hash) = &self.expected_genesis_hash {
+                if &block_hash != expected_genesis_hash {
+                    return Err(BlockValidationErr::MismatchedGenesisBlock);
+                }
+            }
+        } else {
+            let previous_block = self
+                .storage
+                .header(index as u32 - 1)
+                .expect("the chain has the previous block");
+            if header.timestamp <= previous_block.timestamp {
+                return Err(BlockValidationErr::AchronologicalTimestamp);
+            } else if header.previous_block_hash != previous_block.hash {
+                return Err(BlockValidationErr::MismatchedPreviousHash);
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Disconnects the tip of the chain and returns it: restores the unspent
+     * outputs to what they were before the block was connected (see
+     * BlockUndo), removes the block from the indexes and removes it from the
+     * storage.
+     *
+     * The block's undo data is required, so blocks that have been pruned, or
+     * that a UTXO snapshot covers, can't be disconnected.
+     */
+    pub fn disconnect_tip(&mut self) -> Result<Block, DisconnectErr> {
+        let tip = self.tip().ok_or(DisconnectErr::EmptyChain)?;
+        let chain_work = self
+            .storage
+            .chain_work()
+            .saturating_sub(self.consensus.work(tip));
+        match self.storage.read_undo(tip.index) {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(DisconnectErr::MissingUndoData),
+            Err(_) => return Err(DisconnectErr::StorageFailure),
+        }
+
+        let block = self
+            .storage
+            .disconnect_tip(chain_work)
+            .map_err(|_| DisconnectErr::StorageFailure)?;
+
+        if let Some(transaction_index) = &mut self.transaction_index {
+            for (id, location) in transaction_locations(&block, self.hash_algorithms.transaction) {
+                if transaction_index.get(&id) == Some(&location) {
+                    transaction_index.remove(&id);
+                }
+            }
+        }
+        if let Some(address_index) = &mut self.address_index {
+            address_index.disconnect_block(&block);
+        }
+        if let Some(block_filters) = &mut self.block_filters {
+            block_filters.disconnect_tip();
+        }
+
+        Ok(block)
+    }
+
+    /**
+     * Validates the unconfirmed transaction as if it were in the next block,
+     * after the transactions that created the given unconfirmed outputs
+     * (e.g., its parents in a mempool): it isn't a coinbase transaction, it's
+     * final and its inputs' relative lock times have been reached, every
+     * input spends an output that's unspent (or unconfirmed), the inputs
+     * cover the outputs and every input's unlocking script satisfies the
+     * locking script of the output it spends. Returns the transaction's fee.
+     *
+     * The next block's timestamp isn't known yet, so lock times are checked
+     * at the tip's timestamp, which the next block's timestamp is past, and
+     * an unconfirmed output counts as created in the next block.
+     */
+    pub fn validate_transaction(
+        &self,
+        transaction: &Transaction,
+        unconfirmed_outputs: &HashSet<Hash256>,
+    ) -> Result<u64, BlockValidationErr> {
+        let block_index = self.storage.block_count();
+        let block_timestamp = self.tip().map_or(0, |tip| tip.timestamp);
+        if transaction.is_coinbase() {
+            return Err(BlockValidationErr::InvalidCoinbaseTransaction);
+        } else if !transaction.is_final(block_index, block_timestamp) {
+            return Err(BlockValidationErr::NonFinalTransaction);
+        }
+
+        let fee = self.validate_spends(
+            transaction,
+            block_index,
+            block_timestamp,
+            &HashSet::new(),
+            unconfirmed_outputs,
+        )?;
+        let algorithm = self.hash_algorithms.transaction;
+        match verify_scripts(std::slice::from_ref(transaction), block_index, algorithm) {
+            Some((_, error)) => Err(BlockValidationErr::InvalidScript(error)),
+            None => Ok(fee),
+        }
+    }
+
+    /**
+     * Validates what the transaction spends in the block with the given index
+     * and timestamp, which is cheap and depends on the chain's state and on
+     * the transactions before it in the block, so it's done sequentially:
+     * every input spends an output that's unspent (created by an earlier
+     * block, or by an earlier transaction of the block) and that neither the
+     * block nor an earlier input of the transaction has spent yet, whose
+     * relative lock time (if any) has been reached at
+     * the block, and the inputs cover the outputs. Returns the transaction's
+     * fee. The hash of a spent output covers the input's copy of it (see
+     * OutPoint::output_hash), so the values and the locking scripts of the
+     * inputs are the ones of the outputs they spend.
+     */
+    fn validate_spends(
+        &self,
+        transaction: &Transaction,
+        block_index: u32,
+        block_timestamp: u128,
+        block_spent: &HashSet<Hash256>,
+        block_created: &HashSet<Hash256>,
+    ) -> Result<u64, BlockValidationErr> {
+        let algorithm = self.hash_algorithms.transaction;
+        let mut outpoints = HashSet::new();
+        for input in &transaction.inputs {
+            if !outpoints.insert(input.outpoint) {
+                return Err(BlockValidationErr::DuplicateInput);
+            }
+            let input_hash = input.output_hash(algorithm);
+            let output_block_index = if block_created.contains(&input_hash) {
+                block_index
+            } else if block_spent.contains(&input_hash) {
+                return Err(BlockValidationErr::InvalidInput);
+            } else {
+                self.unspent_output(&input_hash)?
+                    .ok_or(BlockValidationErr::InvalidInput)?
+            };
+
+            if let Some(relative_lock_time) = &input.relative_lock_time {
+                let output_block_timestamp = if output_block_index == block_index {
+                    block_timestamp
+                } else {
+                    self.storage
+                        .header(output_block_index)
+                        .expect("the chain has the output's block")
+                        .timestamp
+                };
+                if !relative_lock_time.is_reached(
+                    output_block_index,
+                    output_block_timestamp,
+                    block_index,
+                    block_timestamp,
+                ) {
+                    return Err(BlockValidationErr::RelativeLockTimeNotReached);
+                }
+            }
+        }
+
+        let input_value = transaction
+            .input_value()
+            .ok_or(BlockValidationErr::ValueOverflow)?;
+        let output_value = transaction
+            .output_value()
+            .ok_or(BlockValidationErr::ValueOverflow)?;
+        if output_value > input_value {
+            return Err(BlockValidationErr::InsufficientInputValue);
+        }
+
+        Ok(input_value - output_value)
+    }
+
+    /**
+     * Returns the index of the block that created the unspent output with the
+     * given hash, or None if the output isn't unspent.
+     */
+    fn unspent_output(&self, output: &Hash256) -> Result<Option<u32>, BlockValidationErr> {
+        self.storage
+            .unspent_output(output)
+            .map_err(|_| BlockValidationErr::StorageFailure)
+    }
+
+    /**
+     * Drops the bodies and the undo data of the blocks that are deeper than
+     * the pruning depth. Data that the storage fails to remove is kept, and
+     * removing it is retried the next time the chain is pruned.
+     */
+    fn prune(&mut self) {
+        let depth = match self.pruning_depth {
+            Some(depth) => depth,
+            None => return,
+        };
+        let prunable_height = self.storage.block_count().saturating_sub(depth);
+
+        while self.storage.pruned_height() < prunable_height {
+            let index = self.storage.pruned_height();
+            if self.storage.prune_block(index).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn is_genesis_block(&self, index: usize) -> bool {
+        index == 0
+    }
+}
+
+/**
+ * Verifies the unlocking script of every input of the transactions against
+ * the locking script of the output it spends, in parallel on the global
+ * thread pool (see rayon), since scripts may check signatures, which is the
+ * expensive part of validating a block. Returns the position of the first
+ * transaction (in block order) that has an invalid script and its error, so
+ * the result doesn't depend on how the work was scheduled.
+ */
+fn verify_scripts(
+    transactions: &[Transaction],
+    block_index: u32,
+    algorithm: HashAlgorithm,
+) -> Option<(usize, ScriptErr)> {
+    let contexts: Vec<ScriptContext> = transactions
+        .par_iter()
+        .map(|transaction| ScriptContext {
+            signature_hash: transaction.signature_hash(algorithm),
+            block_index,
+        })
+        .collect();
+
+    transactions
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(position, transaction)| {
+            transaction
+                .inputs
+                .iter()
+                .map(move |input| (position, input))
+        })
+        .find_map_first(|(position, input)| {
+            verify_script(
+                &input.unlocking_script,
+                &input.previous_output.locking_script,
+                &contexts[position],
+            )
+            .err()
+            .map(|error| (position, error))
+        })
+}
+
+/**
+ * Returns the ids (computed with the given algorithm) and the locations of
+ * the block's transactions.
+ */
+fn transaction_locations(
+    block: &Block,
+    algorithm: HashAlgorithm,
+) -> impl Iterator<Item = (TransactionId, TransactionLocation)> + '_ {
+    block
+        .transactions
+        .iter()
+        .enumerate()
+        .map(move |(position, transaction)| {
+            let location = TransactionLocation {
+                block_hash: block.hash,
+                block_index: block.index,
+                position,
+            };
+
+            (transaction.id_with(algorithm), location)
+        })
+}
+
+#[cfg(test)]
+mod blockchain_constructor_tests {
+    use super::{Block, Blockchain};
+    use crate::ChainStorage;
+    use crate::{Hash256, HashAlgorithm, HashAlgorithms};
+
+    fn assert_default_constructor(instance: Blockchain) {
+        assert_eq!(Vec::<Block>::new(), instance.blocks());
+        assert!(instance.storage().unspent_outputs().unwrap().is_empty());
+        assert_eq!(None, instance.expected_genesis_hash);
+        assert_eq!(HashAlgorithms::default(), instance.hash_algorithms);
+    }
+
+    #[test]
+    fn constructor_with_new() {
+        let instance = Blockchain::new();
+
+        assert_default_constructor(instance);
+    }
+
+    #[test]
+    fn constructor_with_default() {
+        let instance: Blockchain = Default::default();
+
+        assert_default_constructor(instance);
+    }
+
+    #[test]
+    fn constructor_with_genesis_hash() {
+        let instance = Blockchain::with_genesis_hash(Hash256([1; 32]));
+
+        assert_eq!(Vec::<Block>::new(), instance.blocks());
+        assert!(instance.storage().unspent_outputs().unwrap().is_empty());
+        assert_eq!(Some(Hash256([1; 32])), instance.expected_genesis_hash);
+    }
+
+    #[test]
+    fn constructor_with_hash_algorithms() {
+        let hash_algorithms = HashAlgorithms {
+            block: HashAlgorithm::Blake3,
+            transaction: HashAlgorithm::Keccak256,
+        };
+
+        let instance = Blockchain::with_hash_algorithms(hash_algorithms);
+
+        assert_eq!(Vec::<Block>::new(), instance.blocks());
+        assert!(instance.storage().unspent_outputs().unwrap().is_empty());
+        assert_eq!(None, instance.expected_genesis_hash);
+        assert_eq!(hash_algorithms, instance.hash_algorithms());
+    }
+}
+
+#[cfg(test)]
+mod blockchain_genesis_tests {
+    use crate::transaction::Output;
+    use crate::{GenesisConfig, Hash256, HashAlgorithm, HashAlgorithms, Hashable, Script};
+
+    use super::{BlockValidationErr, Blockchain};
+
+    const BITS: u32 = 0x2000_ffff;
+
+    fn create_config(message: &str) -> GenesisConfig {
+        GenesisConfig {
+            timestamp: 1_600_000_000_000,
+            outputs: vec![Output {
+                to_address: "Alice".to_owned(),
+                value: 50,
+                locking_script: Script::new(),
+            }],
+            bits: BITS,
+            message: Some(message.to_owned()),
+            hash_algorithms: HashAlgorithms::default(),
+        }
+    }
+
+    #[test]
+    fn from_genesis() {
+        let config = create_config("Genesis");
+        let genesis_block = config.build_block();
+
+        let result = Blockchain::from_genesis(&config);
+
+        assert!(result.is_ok());
+        let blockchain = result.unwrap();
+        assert_eq!(vec![genesis_block], blockchain.blocks());
+        assert_eq!(
+            Some(&blockchain.blocks()[0].hash),
+            blockchain.genesis_hash()
+        );
+    }
+
+    #[test]
+    fn add_expected_genesis_block() {
+        let config = create_config("Genesis");
+        let genesis_block = config.build_block();
+        let mut blockchain = Blockchain::with_genesis_hash(genesis_block.hash);
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(1, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn add_unexpected_genesis_block() {
+        let expected_genesis_block = create_config("Genesis").build_block();
+        let other_genesis_block = create_config("Other genesis").build_block();
+        let mut blockchain = Blockchain::with_genesis_hash(expected_genesis_block.hash);
+
+        let result = blockchain.update_with_block(other_genesis_block);
+
+        assert_eq!(Err(BlockValidationErr::MismatchedGenesisBlock), result);
+        assert!(blockchain.blocks().is_empty());
+    }
+
+    #[test]
+    fn from_genesis_with_hash_algorithms() {
+        let mut config = create_config("Genesis");
+        config.hash_algorithms = HashAlgorithms::uniform(HashAlgorithm::DoubleSha256);
+
+        let result = Blockchain::from_genesis(&config);
+
+        assert!(result.is_ok());
+        let blockchain = result.unwrap();
+        assert_eq!(config.hash_algorithms, blockchain.hash_algorithms());
+        assert_eq!(
+            blockchain.blocks()[0].hash_with(HashAlgorithm::DoubleSha256),
+            blockchain.blocks()[0].hash
+        );
+    }
+
+    #[test]
+    fn genesis_hash_of_empty_blockchain() {
+        let blockchain = Blockchain::new();
+
+        let result = blockchain.genesis_hash();
+
+        assert_eq!(None, result);
+    }
+
+    #[test]
+    fn genesis_hash_of_unpinned_blockchain() {
+        let genesis_block = create_config("Genesis").build_block();
+        let genesis_hash = genesis_block.hash;
+        let mut blockchain = Blockchain::new();
+        blockchain
+            .update_with_block(genesis_block)
+            .expect("Failed to add the genesis block.");
+
+        let result = blockchain.genesis_hash();
+
+        assert_eq!(Some(&genesis_hash), result);
+    }
+
+    #[test]
+    fn shares_genesis_with_peer_on_same_chain() {
+        let config = create_config("Genesis");
+        let blockchain = Blockchain::from_genesis(&config).unwrap();
+        let peer = Blockchain::from_genesis(&config).unwrap();
+
+        let result = blockchain.shares_genesis_with(peer.genesis_hash().unwrap());
+
+        assert!(result);
+    }
+
+    #[test]
+    fn shares_genesis_with_peer_on_other_chain() {
+        let blockchain = Blockchain::from_genesis(&create_config("Genesis")).unwrap();
+        let peer = Blockchain::from_genesis(&create_config("Other genesis")).unwrap();
+
+        let result = blockchain.shares_genesis_with(peer.genesis_hash().unwrap());
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn shares_genesis_with_peer_when_genesis_is_unknown() {
+        let blockchain = Blockchain::new();
+
+        let result = blockchain.shares_genesis_with(&Hash256::ZERO);
+
+        assert!(!result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_hash_algorithms_tests {
+    use crate::transaction::{OutPoint, Output};
+    use crate::{
+        check_difficulty, ChainStorage, Hash256, HashAlgorithm, Hashable, Script, Transaction,
+    };
+
+    use super::{Block, BlockValidationErr, Blockchain, HashAlgorithms};
+
+    const BITS: u32 = 0x1f00_ffff;
+
+    fn hash_algorithms() -> HashAlgorithms {
+        HashAlgorithms {
+            block: HashAlgorithm::Blake3,
+            transaction: HashAlgorithm::Keccak256,
+        }
+    }
+
+    fn create_genesis_block() -> Block {
+        let mut block = Block::new(
+            0,
+            1,
+            Hash256::ZERO,
+            vec![Transaction::coinbase(
+                0,
+                vec![Output {
+                    to_address: "Alice".to_owned(),
+                    value: 1,
+                    locking_script: Script::new(),
+                }],
+            )],
+            BITS,
+        );
+        block.update_transaction_root(HashAlgorithm::Keccak256);
+
+        block
+    }
+
+    #[test]
+    fn add_block_mined_with_chain_algorithm() {
+        let mut genesis_block = create_genesis_block();
+        genesis_block.mine_with(HashAlgorithm::Blake3);
+        let mut blockchain = Blockchain::with_hash_algorithms(hash_algorithms());
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn add_block_with_transaction_root_of_other_algorithm() {
+        let mut genesis_block = create_genesis_block();
+        genesis_block.update_transaction_root(HashAlgorithm::Sha256);
+        genesis_block.mine_with(HashAlgorithm::Blake3);
+        let mut blockchain = Blockchain::with_hash_algorithms(hash_algorithms());
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidTransactionRoot), result);
+    }
+
+    #[test]
+    fn add_block_mined_with_other_algorithm() {
+        let mut genesis_block = create_genesis_block();
+        genesis_block.mine_with(HashAlgorithm::Sha256);
+        assert!(!check_difficulty(
+            &genesis_block.hash_with(HashAlgorithm::Blake3),
+            BITS
+        ));
+        let mut blockchain = Blockchain::with_hash_algorithms(hash_algorithms());
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+    }
+
+    #[test]
+    fn spend_output_hashed_with_chain_algorithm() {
+        let mut genesis_block = create_genesis_block();
+        genesis_block.mine_with(HashAlgorithm::Blake3);
+        let mut block = Block::new(
+            1,
+            2,
+            genesis_block.hash,
+            vec![
+                Transaction::coinbase(1, vec![]),
+                Transaction {
+                    inputs: vec![genesis_block.transactions[0]
+                        .spend(0, HashAlgorithm::Keccak256)
+                        .unwrap()],
+                    outputs: vec![Output {
+                        to_address: "Bob".to_owned(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    }],
+                    lock_time: None,
+                },
+            ],
+            BITS,
+        );
+        block.update_transaction_root(HashAlgorithm::Keccak256);
+        block.mine_with(HashAlgorithm::Blake3);
+        let mut blockchain = Blockchain::with_hash_algorithms(hash_algorithms());
+        blockchain.update_with_block(genesis_block).unwrap();
+        let output_hash = OutPoint {
+            transaction_id: block.transactions[1].id_with(HashAlgorithm::Keccak256),
+            index: 0,
+        }
+        .output_hash(&block.transactions[1].outputs[0], HashAlgorithm::Keccak256);
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(
+            Some(1),
+            blockchain.storage().unspent_output(&output_hash).unwrap()
+        );
+    }
+}
+
+#[cfg(test)]
+mod blockchain_chain_work_tests {
+    use primitive_types::U256;
+
+    use super::{Block, Blockchain};
+    use crate::test_support::BITS;
+    use crate::Hash256;
+
+    #[test]
+    fn chain_work_of_empty_blockchain() {
+        let blockchain = Blockchain::new();
+
+        let result = blockchain.chain_work();
+
+        assert_eq!(U256::zero(), result);
+    }
+
+    #[test]
+    fn chain_work_of_two_blocks() {
+        let mut genesis_block = Block::new(0, 1, Hash256::ZERO, vec![], BITS);
+        genesis_block.mine();
+        let mut block = Block::new(1, 2, genesis_block.hash, vec![], BITS);
+        block.mine();
+        let expected_work = genesis_block.work() + block.work();
+        let mut blockchain = Blockchain::new();
+        blockchain.update_with_block(genesis_block).unwrap();
+        blockchain.update_with_block(block).unwrap();
+
+        let result = blockchain.chain_work();
+
+        assert_eq!(expected_work, result);
+        assert_eq!(U256::from(2), result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_consensus_tests {
+    use primitive_types::U256;
+
+    use super::{Block, BlockValidationErr, Blockchain};
+    use crate::test_support::signing_key;
+    use crate::transaction::Output;
+    use crate::{
+        GenesisConfig, HashAlgorithms, MemoryHardFunction, MemoryHardProofOfWork, ProofOfAuthority,
+        Script, SealErr, Transaction, VerifyingKey,
+    };
+
+    fn validators() -> Vec<VerifyingKey> {
+        (1..=2)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect()
+    }
+
+    fn create_config() -> GenesisConfig {
+        GenesisConfig {
+            timestamp: 1_600_000_000_000,
+            outputs: vec![Output {
+                to_address: "Alice".to_owned(),
+                value: 50,
+                locking_script: Script::new(),
+            }],
+            bits: 0,
+            message: None,
+            hash_algorithms: HashAlgorithms::default(),
+        }
+    }
+
+    fn create_blockchain(signing_key_byte: u8) -> Blockchain {
+        let consensus =
+            ProofOfAuthority::with_signing_key(validators(), signing_key(signing_key_byte));
+
+        Blockchain::from_genesis_with_consensus(&create_config(), Box::new(consensus)).unwrap()
+    }
+
+    fn create_next_block(blockchain: &Blockchain) -> Block {
+        let previous_block = blockchain.blocks().last().unwrap();
+
+        Block::new(
+            previous_block.index + 1,
+            previous_block.timestamp + 1,
+            previous_block.hash,
+            vec![Transaction::coinbase(
+                previous_block.index + 1,
+                vec![Output {
+                    to_address: "Bob".to_owned(),
+                    value: 1,
+                    locking_script: Script::new(),
+                }],
+            )],
+            0,
+        )
+    }
+
+    #[test]
+    fn default_consensus_is_proof_of_work() {
+        let mut block = Block::new(0, 1, Default::default(), vec![], 0x2100_ffff);
+        let blockchain = Blockchain::new();
+
+        let result = blockchain.seal_block(&mut block);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(block.work(), blockchain.consensus().work(&block));
+    }
+
+    #[test]
+    fn from_genesis_with_proof_of_authority() {
+        let config = create_config();
+
+        let result = Blockchain::from_genesis_with_consensus(
+            &config,
+            Box::new(ProofOfAuthority::new(validators())),
+        );
+
+        assert!(result.is_ok());
+        let blockchain = result.unwrap();
+        assert_eq!(1, blockchain.blocks().len());
+        assert!(blockchain.blocks()[0].seal.is_empty());
+        assert_eq!(
+            Some(&blockchain.blocks()[0].hash),
+            blockchain.genesis_hash()
+        );
+    }
+
+    #[test]
+    fn add_blocks_sealed_in_turn() {
+        let mut blockchain = create_blockchain(2);
+        let mut block = create_next_block(&blockchain);
+        blockchain.seal_block(&mut block).unwrap();
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(2, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn seal_block_out_of_turn() {
+        let blockchain = create_blockchain(1);
+        let mut block = create_next_block(&blockchain);
+
+        let result = blockchain.seal_block(&mut block);
+
+        assert_eq!(Err(SealErr::NotInTurn), result);
+    }
+
+    #[test]
+    fn add_block_sealed_by_other_chain_validator() {
+        let other_blockchain = create_blockchain(2);
+        let mut block = create_next_block(&other_blockchain);
+        other_blockchain.seal_block(&mut block).unwrap();
+        let mut blockchain = Blockchain::from_genesis_with_consensus(
+            &create_config(),
+            Box::new(ProofOfAuthority::new(vec![*signing_key(3).verifying_key()])),
+        )
+        .unwrap();
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidSeal), result);
+        assert_eq!(1, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn add_unsealed_block() {
+        let mut blockchain = create_blockchain(2);
+        let block = create_next_block(&blockchain);
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidSeal), result);
+    }
+
+    #[test]
+    fn add_block_with_memory_hard_proof_of_work() {
+        let consensus = MemoryHardProofOfWork::new(MemoryHardFunction::Scrypt {
+            log_n: 4,
+            r: 1,
+            p: 1,
+        });
+        let mut config = create_config();
+        config.bits = 0x2000_ffff;
+        let mut blockchain =
+            Blockchain::from_genesis_with_consensus(&config, Box::new(consensus)).unwrap();
+        let mut block = create_next_block(&blockchain);
+        block.bits = config.bits;
+        blockchain.seal_block(&mut block).unwrap();
+        let mut plain_block = create_next_block(&blockchain);
+        plain_block.bits = config.bits;
+        plain_block.mine();
+        while consensus
+            .function
+            .check_difficulty(&plain_block.hash, config.bits)
+        {
+            plain_block.timestamp += 1;
+            plain_block.mine();
+        }
+
+        let plain_result = blockchain.update_with_block(plain_block);
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidHash), plain_result);
+        assert_eq!(Ok(()), result);
+        assert_eq!(2, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn chain_work_counts_blocks() {
+        let mut blockchain = create_blockchain(2);
+        let mut block = create_next_block(&blockchain);
+        blockchain.seal_block(&mut block).unwrap();
+        blockchain.update_with_block(block).unwrap();
+
+        let result = blockchain.chain_work();
+
+        assert_eq!(U256::from(2), result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_script_tests {
+    use super::{Block, BlockValidationErr, Blockchain};
+    use crate::test_support::{signing_key, spend, BITS};
+    use crate::transaction::{Input, Output};
+    use crate::{
+        sign, Hash256, MultisigSpend, Script, ScriptErr, SigningKey, Transaction, VerifyingKey,
+    };
+
+    /**
+     * Returns a blockchain whose genesis block pays Alice 5 coins locked by
+     * the given script, and the input that spends them.
+     */
+    fn create_blockchain(locking_script: Script) -> (Blockchain, Input) {
+        let output = Output {
+            to_address: "Alice".to_owned(),
+            value: 5,
+            locking_script,
+        };
+        let coinbase = Transaction::coinbase(0, vec![output]);
+        let mut genesis_block = Block::new(0, 1, Hash256::ZERO, vec![coinbase.clone()], BITS);
+        genesis_block.mine();
+        let mut blockchain = Blockchain::new();
+        blockchain.update_with_block(genesis_block).unwrap();
+
+        (blockchain, spend(&coinbase, 0))
+    }
+
+    fn create_spending_transaction(input: Input) -> Transaction {
+        Transaction {
+            inputs: vec![input],
+            outputs: vec![Output {
+                to_address: "Bob".to_owned(),
+                value: 5,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        }
+    }
+
+    fn sign_with_public_key_hash(transaction: &mut Transaction, signing_key: &SigningKey) {
+        let signature_hash = transaction.signature_hash(Default::default());
+        transaction.inputs[0].unlocking_script = Script::pay_to_public_key_hash_unlocking(
+            &sign(signing_key, &signature_hash),
+            signing_key.verifying_key(),
+        );
+    }
+
+    fn add_block(
+        blockchain: &mut Blockchain,
+        transactions: Vec<Transaction>,
+    ) -> Result<(), BlockValidationErr> {
+        let previous_block = blockchain.blocks().last().unwrap();
+        let coinbase = Transaction::coinbase(previous_block.index + 1, vec![]);
+        let mut block = Block::new(
+            previous_block.index + 1,
+            previous_block.timestamp + 1,
+            previous_block.hash,
+            std::iter::once(coinbase).chain(transactions).collect(),
+            BITS,
+        );
+        block.mine();
+
+        blockchain.update_with_block(block)
+    }
+
+    #[test]
+    fn spend_pay_to_public_key_hash_output() {
+        let key = signing_key(1);
+        let (mut blockchain, input) =
+            create_blockchain(Script::pay_to_public_key_hash(key.verifying_key()));
+        let mut transaction = create_spending_transaction(input);
+        sign_with_public_key_hash(&mut transaction, &key);
+
+        let result = add_block(&mut blockchain, vec![transaction]);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(2, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn spend_pay_to_public_key_hash_output_without_signature() {
+        let key = signing_key(1);
+        let (mut blockchain, input) =
+            create_blockchain(Script::pay_to_public_key_hash(key.verifying_key()));
+        let transaction = create_spending_transaction(input);
+
+        let result = add_block(&mut blockchain, vec![transaction]);
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidScript(ScriptErr::StackUnderflow)),
+            result
+        );
+        assert_eq!(1, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn spend_pay_to_public_key_hash_output_with_other_key() {
+        let (mut blockchain, input) = create_blockchain(Script::pay_to_public_key_hash(
+            signing_key(1).verifying_key(),
+        ));
+        let mut transaction = create_spending_transaction(input);
+        sign_with_public_key_hash(&mut transaction, &signing_key(2));
+
+        let result = add_block(&mut blockchain, vec![transaction]);
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidScript(ScriptErr::VerifyFailed)),
+            result
+        );
+    }
+
+    #[test]
+    fn spend_signed_output_after_changing_transaction() {
+        let key = signing_key(1);
+        let (mut blockchain, input) =
+            create_blockchain(Script::pay_to_public_key_hash(key.verifying_key()));
+        let mut transaction = create_spending_transaction(input);
+        sign_with_public_key_hash(&mut transaction, &key);
+        transaction.outputs[0].to_address = "Mallory".to_owned();
+
+        let result = add_block(&mut blockchain, vec![transaction]);
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidScript(ScriptErr::EvalFalse)),
+            result
+        );
+    }
+
+    #[test]
+    fn spend_timelocked_output() {
+        let key = signing_key(1);
+        let (mut blockchain, input) = create_blockchain(Script::timelock(2, key.verifying_key()));
+        let mut transaction = create_spending_transaction(input);
+        sign_with_public_key_hash(&mut transaction, &key);
+
+        let early_result = add_block(&mut blockchain, vec![transaction.clone()]);
+        add_block(&mut blockchain, vec![]).unwrap();
+        let result = add_block(&mut blockchain, vec![transaction]);
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidScript(
+                ScriptErr::LockTimeNotReached
+            )),
+            early_result
+        );
+        assert_eq!(Ok(()), result);
+        assert_eq!(3, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn spend_multisig_output() {
+        let public_keys: Vec<VerifyingKey> = (1..=3)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect();
+        let treasury = Output::multisig("treasury".to_owned(), 5, 2, &public_keys).unwrap();
+        let (mut blockchain, input) = create_blockchain(treasury.locking_script);
+        let mut spend = MultisigSpend::new(
+            create_spending_transaction(input),
+            0,
+            blockchain.hash_algorithms().transaction,
+        )
+        .unwrap();
+        spend.sign(&signing_key(1)).unwrap();
+        spend.sign(&signing_key(3)).unwrap();
+        let transaction = spend.finalize().unwrap();
+
+        let result = add_block(&mut blockchain, vec![transaction]);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(2, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn spend_multisig_output_below_threshold() {
+        let public_keys: Vec<VerifyingKey> = (1..=3)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect();
+        let treasury = Output::multisig("treasury".to_owned(), 5, 2, &public_keys).unwrap();
+        let (mut blockchain, input) = create_blockchain(treasury.locking_script);
+        let mut transaction = create_spending_transaction(input);
+        let signature_hash = transaction.signature_hash(Default::default());
+        transaction.inputs[0].unlocking_script =
+            Script::multisig_unlocking(&[sign(&signing_key(2), &signature_hash)]);
+
+        let result = add_block(&mut blockchain, vec![transaction]);
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidScript(
+                ScriptErr::MultisigThresholdNotMet
+            )),
+            result
+        );
+        assert_eq!(1, blockchain.blocks().len());
+    }
+}
+
+#[cfg(test)]
+mod blockchain_parallel_validation_tests {
+    use super::{Block, BlockValidationErr, Blockchain};
+    use crate::test_support::{spend, BITS};
+    use crate::transaction::Output;
+    use crate::{sign, Hash256, Script, ScriptErr, SigningKey, Transaction};
+
+    const OUTPUT_COUNT: u64 = 32;
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&[1; 32].into()).unwrap()
+    }
+
+    fn output(value: u64) -> Output {
+        Output {
+            to_address: "Alice".to_owned(),
+            value,
+            locking_script: Script::pay_to_public_key_hash(signing_key().verifying_key()),
+        }
+    }
+
+    /**
+     * Returns the coinbase transaction of the genesis block, which pays Alice
+     * OUTPUT_COUNT outputs (worth 1 to OUTPUT_COUNT coins), which are locked
+     * to her key.
+     */
+    fn genesis_coinbase() -> Transaction {
+        Transaction::coinbase(0, (1..=OUTPUT_COUNT).map(output).collect())
+    }
+
+    fn create_blockchain() -> Blockchain {
+        let mut genesis_block = Block::new(0, 1, Hash256::ZERO, vec![genesis_coinbase()], BITS);
+        genesis_block.mine();
+        let mut blockchain = Blockchain::new();
+        blockchain.update_with_block(genesis_block).unwrap();
+
+        blockchain
+    }
+
+    /**
+     * Returns a transaction for each output of the genesis block, which
+     * spends it to Bob with a valid signature.
+     */
+    fn create_payments() -> Vec<Transaction> {
+        let genesis_coinbase = genesis_coinbase();
+        (1..=OUTPUT_COUNT)
+            .map(|value| {
+                let mut transaction = Transaction {
+                    inputs: vec![spend(&genesis_coinbase, value as usize - 1)],
+                    outputs: vec![Output {
+                        to_address: "Bob".to_owned(),
+                        value,
+                        locking_script: Script::new(),
+                    }],
+                    lock_time: None,
+                };
+                let signature_hash = transaction.signature_hash(Default::default());
+                transaction.inputs[0].unlocking_script = Script::pay_to_public_key_hash_unlocking(
+                    &sign(&signing_key(), &signature_hash),
+                    signing_key().verifying_key(),
+                );
+
+                transaction
+            })
+            .collect()
+    }
+
+    fn add_block(
+        blockchain: &mut Blockchain,
+        transactions: Vec<Transaction>,
+    ) -> Result<(), BlockValidationErr> {
+        let coinbase = Transaction::coinbase(1, vec![]);
+        let previous_block = blockchain.tip().unwrap();
+        let mut block = Block::new(
+            1,
+            previous_block.timestamp + 1,
+            previous_block.hash,
+            std::iter::once(coinbase).chain(transactions).collect(),
+            BITS,
+        );
+        block.mine();
+
+        blockchain.update_with_block(block)
+    }
+
+    fn remove_signature(transaction: &mut Transaction) {
+        transaction.inputs[0].unlocking_script = Script::new();
+    }
+
+    fn spend_unknown_output(transaction: &mut Transaction) {
+        transaction.inputs[0].previous_output.value += OUTPUT_COUNT;
+    }
+
+    fn overspend(transaction: &mut Transaction) {
+        transaction.outputs[0].value += 1;
+    }
+
+    #[test]
+    fn add_block_with_many_signed_transactions() {
+        let mut blockchain = create_blockchain();
+
+        let result = add_block(&mut blockchain, create_payments());
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(2, blockchain.block_count());
+    }
+
+    #[test]
+    fn first_invalid_script_wins() {
+        for _ in 0..10 {
+            let mut blockchain = create_blockchain();
+            let mut payments = create_payments();
+            payments[5].inputs[0].unlocking_script = Script::pay_to_public_key_hash_unlocking(
+                &sign(&signing_key(), &Hash256::ZERO),
+                signing_key().verifying_key(),
+            );
+            remove_signature(&mut payments[20]);
+
+            let result = add_block(&mut blockchain, payments);
+
+            assert_eq!(
+                Err(BlockValidationErr::InvalidScript(ScriptErr::EvalFalse)),
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_script_before_invalid_spend() {
+        let mut blockchain = create_blockchain();
+        let mut payments = create_payments();
+        remove_signature(&mut payments[3]);
+        spend_unknown_output(&mut payments[10]);
+
+        let result = add_block(&mut blockchain, payments);
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidScript(ScriptErr::StackUnderflow)),
+            result
+        );
+        assert_eq!(1, blockchain.block_count());
+    }
+
+    #[test]
+    fn invalid_spend_before_invalid_script() {
+        let mut blockchain = create_blockchain();
+        let mut payments = create_payments();
+        spend_unknown_output(&mut payments[3]);
+        remove_signature(&mut payments[10]);
+
+        let result = add_block(&mut blockchain, payments);
+
+        assert_eq!(Err(BlockValidationErr::InvalidInput), result);
+    }
+
+    #[test]
+    fn invalid_spend_and_script_of_same_transaction() {
+        let mut blockchain = create_blockchain();
+        let mut payments = create_payments();
+        overspend(&mut payments[7]);
+        remove_signature(&mut payments[7]);
+
+        let result = add_block(&mut blockchain, payments);
+
+        assert_eq!(Err(BlockValidationErr::InsufficientInputValue), result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_lock_time_tests {
+    use super::{Block, BlockValidationErr, Blockchain};
+    use crate::test_support::{coinbase, spend, BITS};
+    use crate::transaction::{Input, LockTime, Output, RelativeLockTime};
+    use crate::{Hash256, Script, Transaction};
+
+    const GENESIS_TIMESTAMP: u128 = 1_000;
+
+    /**
+     * Returns a blockchain whose genesis block pays Alice 5 coins, and the
+     * input that spends them.
+     */
+    fn create_blockchain() -> (Blockchain, Input) {
+        let coinbase = coinbase(0, "Alice", 5);
+        let mut genesis_block = Block::new(
+            0,
+            GENESIS_TIMESTAMP,
+            Hash256::ZERO,
+            vec![coinbase.clone()],
+            BITS,
+        );
+        genesis_block.mine();
+        let mut blockchain = Blockchain::new();
+        blockchain.update_with_block(genesis_block).unwrap();
+
+        (blockchain, spend(&coinbase, 0))
+    }
+
+    fn create_spending_transaction(input: Input) -> Transaction {
+        Transaction {
+            inputs: vec![input],
+            outputs: vec![Output {
+                to_address: "Bob".to_owned(),
+                value: 5,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        }
+    }
+
+    fn add_block(
+        blockchain: &mut Blockchain,
+        timestamp: u128,
+        transactions: Vec<Transaction>,
+    ) -> Result<(), BlockValidationErr> {
+        let previous_block = blockchain.blocks().last().unwrap();
+        let coinbase = Transaction::coinbase(previous_block.index + 1, vec![]);
+        let mut block = Block::new(
+            previous_block.index + 1,
+            timestamp,
+            previous_block.hash,
+            std::iter::once(coinbase).chain(transactions).collect(),
+            BITS,
+        );
+        block.mine();
+
+        blockchain.update_with_block(block)
+    }
+
+    #[test]
+    fn add_transaction_with_block_index_lock_time() {
+        let (mut blockchain, input) = create_blockchain();
+        let mut transaction = create_spending_transaction(input);
+        transaction.lock_time = Some(LockTime::BlockIndex(2));
+
+        let early_result = add_block(&mut blockchain, 2_000, vec![transaction.clone()]);
+        add_block(&mut blockchain, 2_000, vec![]).unwrap();
+        let result = add_block(&mut blockchain, 3_000, vec![transaction]);
+
+        assert_eq!(Err(BlockValidationErr::NonFinalTransaction), early_result);
+        assert_eq!(Ok(()), result);
+        assert_eq!(3, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn add_transaction_with_timestamp_lock_time() {
+        let (mut blockchain, input) = create_blockchain();
+        let mut transaction = create_spending_transaction(input);
+        transaction.lock_time = Some(LockTime::Timestamp(5_000));
+
+        let early_result = add_block(&mut blockchain, 4_999, vec![transaction.clone()]);
+        let result = add_block(&mut blockchain, 5_000, vec![transaction]);
+
+        assert_eq!(Err(BlockValidationErr::NonFinalTransaction), early_result);
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn add_coinbase_transaction_with_lock_time() {
+        let (mut blockchain, _) = create_blockchain();
+        let previous_block = blockchain.blocks().last().unwrap();
+        let mut block = Block::new(
+            1,
+            2_000,
+            previous_block.hash,
+            vec![Transaction {
+                inputs: vec![],
+                outputs: vec![],
+                lock_time: Some(LockTime::BlockIndex(2)),
+            }],
+            BITS,
+        );
+        block.mine();
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::NonFinalTransaction), result);
+    }
+
+    #[test]
+    fn add_transaction_with_relative_blocks_lock_time() {
+        let (mut blockchain, input) = create_blockchain();
+        let mut transaction = create_spending_transaction(input);
+        transaction.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(2));
+
+        let early_result = add_block(&mut blockchain, 2_000, vec![transaction.clone()]);
+        add_block(&mut blockchain, 2_000, vec![]).unwrap();
+        let result = add_block(&mut blockchain, 3_000, vec![transaction]);
+
+        assert_eq!(
+            Err(BlockValidationErr::RelativeLockTimeNotReached),
+            early_result
+        );
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn add_transaction_with_relative_milliseconds_lock_time() {
+        let (mut blockchain, input) = create_blockchain();
+        let mut transaction = create_spending_transaction(input);
+        transaction.inputs[0].relative_lock_time = Some(RelativeLockTime::Milliseconds(3_000));
+
+        let early_result = add_block(
+            &mut blockchain,
+            GENESIS_TIMESTAMP + 2_999,
+            vec![transaction.clone()],
+        );
+        let result = add_block(
+            &mut blockchain,
+            GENESIS_TIMESTAMP + 3_000,
+            vec![transaction],
+        );
+
+        assert_eq!(
+            Err(BlockValidationErr::RelativeLockTimeNotReached),
+            early_result
+        );
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn relative_lock_time_counts_from_output_block() {
+        let (mut blockchain, input) = create_blockchain();
+        let transaction = create_spending_transaction(input);
+        let change = spend(&transaction, 0);
+        add_block(&mut blockchain, 2_000, vec![transaction]).unwrap();
+        let mut refund = create_spending_transaction(change);
+        refund.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(2));
+
+        let early_result = add_block(&mut blockchain, 3_000, vec![refund.clone()]);
+        add_block(&mut blockchain, 3_000, vec![]).unwrap();
+        let result = add_block(&mut blockchain, 4_000, vec![refund]);
+
+        assert_eq!(
+            Err(BlockValidationErr::RelativeLockTimeNotReached),
+            early_result
+        );
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn relative_lock_time_of_output_of_same_block() {
+        let (mut blockchain, input) = create_blockchain();
+        let transaction = create_spending_transaction(input);
+        let mut child = Transaction {
+            inputs: vec![spend(&transaction, 0)],
+            outputs: vec![Output {
+                to_address: "Chris".to_owned(),
+                value: 5,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        };
+        child.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(1));
+        let mut unlocked_child = child.clone();
+        unlocked_child.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(0));
+
+        let locked_result = add_block(&mut blockchain, 2_000, vec![transaction.clone(), child]);
+        let result = add_block(&mut blockchain, 2_000, vec![transaction, unlocked_child]);
+
+        assert_eq!(
+            Err(BlockValidationErr::RelativeLockTimeNotReached),
+            locked_result
+        );
+        assert_eq!(Ok(()), result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_validate_transaction_tests {
+    use std::collections::HashSet;
+
+    use super::{BlockValidationErr, Blockchain};
+    use crate::test_support::{add_block, input, output, payment, signing_key, spend};
+    use crate::transaction::{LockTime, Output, RelativeLockTime};
+    use crate::{sign, HashAlgorithm, Script, ScriptErr, Transaction};
+
+    /**
+     * The genesis block pays 5 coins to Alice and 5 coins to Bob, which are
+     * locked by his public key hash.
+     */
+    fn genesis_coinbase() -> Transaction {
+        let bob_output = Output {
+            to_address: "Bob".to_owned(),
+            value: 5,
+            locking_script: Script::pay_to_public_key_hash(signing_key(1).verifying_key()),
+        };
+
+        Transaction::coinbase(0, vec![output("Alice", 5), bob_output])
+    }
+
+    fn create_blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![genesis_coinbase()]);
+
+        blockchain
+    }
+
+    #[test]
+    fn validate_transaction() {
+        let blockchain = create_blockchain();
+        let transaction = payment(spend(&genesis_coinbase(), 0), "Chris", 4);
+
+        let result = blockchain.validate_transaction(&transaction, &HashSet::new());
+
+        assert_eq!(Ok(1), result);
+    }
+
+    #[test]
+    fn reject_transaction_that_spends_unknown_output() {
+        let blockchain = create_blockchain();
+        let mut overclaiming_input = spend(&genesis_coinbase(), 0);
+        overclaiming_input.previous_output.value = 50;
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidInput),
+            blockchain
+                .validate_transaction(&payment(input("Alice", 5), "Chris", 4), &HashSet::new())
+        );
+        assert_eq!(
+            Err(BlockValidationErr::InvalidInput),
+            blockchain
+                .validate_transaction(&payment(overclaiming_input, "Chris", 40), &HashSet::new())
+        );
+    }
+
+    #[test]
+    fn reject_transaction_that_spends_output_twice() {
+        let blockchain = create_blockchain();
+        let mut transaction = payment(spend(&genesis_coinbase(), 0), "Chris", 10);
+        transaction.inputs.push(spend(&genesis_coinbase(), 0));
+
+        let result = blockchain.validate_transaction(&transaction, &HashSet::new());
+
+        assert_eq!(Err(BlockValidationErr::DuplicateInput), result);
+    }
+
+    #[test]
+    fn reject_transaction_whose_outputs_overflow() {
+        let blockchain = create_blockchain();
+        let mut transaction = payment(spend(&genesis_coinbase(), 0), "Chris", u64::MAX);
+        transaction.outputs.push(output("Dave", 1));
+
+        let result = blockchain.validate_transaction(&transaction, &HashSet::new());
+
+        assert_eq!(Err(BlockValidationErr::ValueOverflow), result);
+    }
+
+    #[test]
+    fn validate_transaction_that_spends_unconfirmed_output() {
+        let blockchain = create_blockchain();
+        let parent = payment(spend(&genesis_coinbase(), 0), "Chris", 4);
+        let child = payment(spend(&parent, 0), "Dave", 3);
+
+        let result = blockchain
+            .validate_transaction(&child, &parent.output_hashes(HashAlgorithm::default()));
+
+        assert_eq!(Ok(1), result);
+        assert_eq!(
+            Err(BlockValidationErr::InvalidInput),
+            blockchain.validate_transaction(&child, &HashSet::new())
+        );
+    }
+
+    #[test]
+    fn reject_invalid_transactions() {
+        let blockchain = create_blockchain();
+        let coinbase = Transaction::coinbase(1, vec![output("Chris", 1)]);
+        let overspending = payment(spend(&genesis_coinbase(), 0), "Chris", 6);
+        let unsigned = payment(spend(&genesis_coinbase(), 1), "Chris", 4);
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidCoinbaseTransaction),
+            blockchain.validate_transaction(&coinbase, &HashSet::new())
+        );
+        assert_eq!(
+            Err(BlockValidationErr::InsufficientInputValue),
+            blockchain.validate_transaction(&overspending, &HashSet::new())
+        );
+        assert_eq!(
+            Err(BlockValidationErr::InvalidScript(ScriptErr::StackUnderflow)),
+            blockchain.validate_transaction(&unsigned, &HashSet::new())
+        );
+    }
+
+    #[test]
+    fn lock_times_are_checked_at_next_block() {
+        let blockchain = create_blockchain();
+        let mut transaction = payment(spend(&genesis_coinbase(), 0), "Chris", 4);
+        transaction.lock_time = Some(LockTime::BlockIndex(1));
+        let mut non_final = transaction.clone();
+        non_final.lock_time = Some(LockTime::BlockIndex(2));
+        let mut relative_lock_time = transaction.clone();
+        relative_lock_time.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(2));
+
+        assert_eq!(
+            Ok(1),
+            blockchain.validate_transaction(&transaction, &HashSet::new())
+        );
+        assert_eq!(
+            Err(BlockValidationErr::NonFinalTransaction),
+            blockchain.validate_transaction(&non_final, &HashSet::new())
+        );
+        assert_eq!(
+            Err(BlockValidationErr::RelativeLockTimeNotReached),
+            blockchain.validate_transaction(&relative_lock_time, &HashSet::new())
+        );
+    }
+
+    #[test]
+    fn reject_transaction_with_stripped_relative_lock_time() {
+        let blockchain = create_blockchain();
+        let mut transaction = payment(spend(&genesis_coinbase(), 1), "Chris", 5);
+        transaction.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(10));
+        let signing_key = signing_key(1);
+        let signature = sign(
+            &signing_key,
+            &transaction.signature_hash(Default::default()),
+        );
+        transaction.inputs[0].unlocking_script =
+            Script::pay_to_public_key_hash_unlocking(&signature, signing_key.verifying_key());
+        let mut forged_transaction = transaction.clone();
+        forged_transaction.inputs[0].relative_lock_time = None;
+        forged_transaction.outputs[0].to_address = "\0\n\0\0\0Chris".to_owned();
+
+        assert_eq!(
+            Err(BlockValidationErr::RelativeLockTimeNotReached),
+            blockchain.validate_transaction(&transaction, &HashSet::new())
+        );
+        assert!(matches!(
+            blockchain.validate_transaction(&forged_transaction, &HashSet::new()),
+            Err(BlockValidationErr::InvalidScript(_))
+        ));
+    }
+
+    #[test]
+    fn unconfirmed_outputs_count_as_created_in_next_block() {
+        let blockchain = create_blockchain();
+        let parent = payment(spend(&genesis_coinbase(), 0), "Chris", 4);
+        let mut child = payment(spend(&parent, 0), "Dave", 3);
+        child.inputs[0].relative_lock_time = Some(RelativeLockTime::Blocks(1));
+
+        let result = blockchain
+            .validate_transaction(&child, &parent.output_hashes(HashAlgorithm::default()));
+
+        assert_eq!(Err(BlockValidationErr::RelativeLockTimeNotReached), result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_transaction_index_tests {
+    use super::{Blockchain, TransactionLocation};
+    use crate::test_support::{add_block, spend};
+    use crate::transaction::Output;
+    use crate::{Hash256, HashAlgorithm, HashAlgorithms, Script, Transaction};
+
+    fn create_transaction(block_index: u32, to_address: &str) -> Transaction {
+        Transaction::coinbase(
+            block_index,
+            vec![Output {
+                to_address: to_address.to_owned(),
+                value: 1,
+                locking_script: Script::new(),
+            }],
+        )
+    }
+
+    fn create_payment() -> Transaction {
+        Transaction {
+            inputs: vec![spend(&create_transaction(0, "Alice"), 0)],
+            outputs: vec![Output {
+                to_address: "Chris".to_owned(),
+                value: 1,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        }
+    }
+
+    fn create_blockchain(with_index: bool) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        if with_index {
+            blockchain.enable_transaction_index();
+        }
+        add_block(&mut blockchain, vec![create_transaction(0, "Alice")]);
+        add_block(
+            &mut blockchain,
+            vec![create_transaction(1, "Bob"), create_payment()],
+        );
+
+        blockchain
+    }
+
+    #[test]
+    fn transaction_index_is_disabled_by_default() {
+        let blockchain = Blockchain::new();
+
+        assert!(!blockchain.has_transaction_index());
+    }
+
+    #[test]
+    fn get_transaction() {
+        for with_index in [true, false].iter() {
+            let mut blockchain = create_blockchain(*with_index);
+            let transaction = create_payment();
+            let block_hash = blockchain.blocks()[1].hash;
+
+            let result = blockchain.get_transaction(&transaction.id()).unwrap();
+
+            assert_eq!(*with_index, blockchain.has_transaction_index());
+            assert_eq!(transaction, result.transaction);
+            assert_eq!(
+                TransactionLocation {
+                    block_hash,
+                    block_index: 1,
+                    position: 1,
+                },
+                result.location
+            );
+            assert_eq!(1, result.confirmations);
+
+            add_block(&mut blockchain, vec![create_transaction(2, "Dave")]);
+            add_block(&mut blockchain, vec![create_transaction(3, "Eve")]);
+
+            let result = blockchain.get_transaction(&transaction.id()).unwrap();
+
+            assert_eq!(3, result.confirmations);
+        }
+    }
+
+    #[test]
+    fn get_genesis_transaction() {
+        let blockchain = create_blockchain(true);
+
+        let result = blockchain
+            .get_transaction(&create_transaction(0, "Alice").id())
+            .unwrap();
+
+        assert_eq!(0, result.location.block_index);
+        assert_eq!(2, result.confirmations);
+    }
+
+    #[test]
+    fn get_unknown_transaction() {
+        for with_index in [true, false].iter() {
+            let blockchain = create_blockchain(*with_index);
+
+            let result = blockchain.get_transaction(&create_transaction(0, "Mallory").id());
+
+            assert_eq!(None, result);
+        }
+    }
+
+    #[test]
+    fn get_transaction_from_empty_blockchain() {
+        let blockchain = Blockchain::new();
+
+        let result = blockchain.get_transaction(&Hash256::ZERO);
+
+        assert_eq!(None, result);
+    }
+
+    #[test]
+    fn enable_transaction_index_indexes_existing_blocks() {
+        let mut blockchain = create_blockchain(false);
+
+        blockchain.enable_transaction_index();
+
+        assert!(blockchain.has_transaction_index());
+        assert_eq!(
+            blockchain.transaction_index.as_ref().unwrap()[&create_transaction(1, "Bob").id()],
+            TransactionLocation {
+                block_hash: blockchain.blocks()[1].hash,
+                block_index: 1,
+                position: 0,
+            }
+        );
+        assert_eq!(3, blockchain.transaction_index.as_ref().unwrap().len());
+    }
+
+    #[test]
+    fn get_transaction_by_chain_algorithm_id() {
+        let mut blockchain =
+            Blockchain::with_hash_algorithms(HashAlgorithms::uniform(HashAlgorithm::Blake3));
+        blockchain.enable_transaction_index();
+        let transaction = create_transaction(0, "Alice");
+        add_block(&mut blockchain, vec![transaction.clone()]);
+
+        let result = blockchain.get_transaction(&transaction.id_with(HashAlgorithm::Blake3));
+
+        assert!(result.is_some());
+        assert_eq!(None, blockchain.get_transaction(&transaction.id()));
+    }
+}
+
+#[cfg(test)]
+mod blockchain_address_index_tests {
+    use super::Blockchain;
+
+    use crate::test_support::{add_block, coinbase, create_payment};
+    use crate::AddressEventKind;
+
+    /**
+     * Alice receives 5 coins in the genesis block, pays 3 to Bob in block 1
+     * and gets 2 back, and receives the coinbase outputs of blocks 1 and 2.
+     */
+    fn create_blockchain(with_index: bool) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        if with_index {
+            blockchain.enable_address_index();
+        }
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        let payment = create_payment();
+        add_block(&mut blockchain, vec![coinbase(1, "Alice", 1), payment]);
+        add_block(&mut blockchain, vec![coinbase(2, "Alice", 7)]);
+
+        blockchain
+    }
+
+    #[test]
+    fn address_index_is_disabled_by_default() {
+        let blockchain = Blockchain::new();
+
+        assert!(!blockchain.has_address_index());
+    }
+
+    #[test]
+    fn address_history() {
+        for with_index in [true, false].iter() {
+            let blockchain = create_blockchain(*with_index);
+
+            let result = blockchain.address_history("Alice", 0, 10);
+
+            assert_eq!(5, result.total);
+            assert_eq!(
+                vec![
+                    (AddressEventKind::Received, 5, 0, 10),
+                    (AddressEventKind::Received, 1, 1, 20),
+                    (AddressEventKind::Spent, 5, 1, 20),
+                    (AddressEventKind::Received, 2, 1, 20),
+                    (AddressEventKind::Received, 7, 2, 30),
+                ],
+                result
+                    .events
+                    .iter()
+                    .map(|event| (event.kind, event.value, event.block_index, event.timestamp))
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                blockchain.blocks()[1].transactions[1].id(),
+                result.events[2].transaction_id
+            );
+        }
+    }
+
+    #[test]
+    fn address_history_pages() {
+        for with_index in [true, false].iter() {
+            let blockchain = create_blockchain(*with_index);
+            let history = blockchain.address_history("Alice", 0, usize::MAX);
+
+            let page1 = blockchain.address_history("Alice", 0, 2);
+            let page2 = blockchain.address_history("Alice", 2, 2);
+            let page3 = blockchain.address_history("Alice", 4, 2);
+            let page4 = blockchain.address_history("Alice", 6, 2);
+
+            assert_eq!(history.events[..2], page1.events[..]);
+            assert_eq!(history.events[2..4], page2.events[..]);
+            assert_eq!(history.events[4..], page3.events[..]);
+            assert!(page4.events.is_empty());
+            for page in [page1, page2, page3, page4].iter() {
+                assert_eq!(5, page.total);
+            }
+        }
+    }
+
+    #[test]
+    fn address_history_of_unknown_address() {
+        for with_index in [true, false].iter() {
+            let blockchain = create_blockchain(*with_index);
+
+            let result = blockchain.address_history("Mallory", 0, 10);
+
+            assert!(result.events.is_empty());
+            assert_eq!(0, result.total);
+        }
+    }
+
+    #[test]
+    fn enable_address_index_indexes_existing_blocks() {
+        let mut blockchain = create_blockchain(false);
+        let expected_history = blockchain.address_history("Bob", 0, 10);
+
+        blockchain.enable_address_index();
+
+        assert!(blockchain.has_address_index());
+        assert_eq!(1, expected_history.total);
+        assert_eq!(expected_history, blockchain.address_history("Bob", 0, 10));
+    }
+}
+
+#[cfg(test)]
+mod blockchain_block_filter_tests {
+    use super::Blockchain;
+
+    use crate::test_support::{add_block, coinbase, create_payment};
+    use crate::{BlockFilter, Hash256, HashAlgorithm};
+
+    /**
+     * Alice receives 5 coins in the genesis block and pays 3 to Bob in block
+     * 1, and Chris receives the coinbase outputs of blocks 1 and 2.
+     */
+    fn add_blocks(blockchain: &mut Blockchain) {
+        add_block(blockchain, vec![coinbase(0, "Alice", 5)]);
+        let payment = create_payment();
+        add_block(blockchain, vec![coinbase(1, "Chris", 1), payment]);
+        add_block(blockchain, vec![coinbase(2, "Chris", 2)]);
+    }
+
+    #[test]
+    fn block_filters_are_disabled_by_default() {
+        let mut blockchain = Blockchain::new();
+        add_blocks(&mut blockchain);
+
+        assert!(!blockchain.has_block_filters());
+        assert_eq!(None, blockchain.block_filter(0));
+        assert!(blockchain.filter_headers(0, 3).is_empty());
+    }
+
+    #[test]
+    fn build_filters_when_blocks_connect() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_block_filters();
+        add_blocks(&mut blockchain);
+
+        let filter = blockchain.block_filter(1).unwrap();
+        let filter_headers = blockchain.filter_headers(0, 10);
+
+        assert!(blockchain.has_block_filters());
+        assert_eq!(blockchain.blocks()[1].hash, filter.block_hash);
+        assert!(filter.matches_address("Bob"));
+        assert!(
+            filter.matches_outpoint(&create_payment().inputs[0].output_hash(HashAlgorithm::Sha256))
+        );
+        assert!(!blockchain.block_filter(2).unwrap().matches_address("Bob"));
+        assert_eq!(3, filter_headers.len());
+        assert_eq!(filter.header(&filter_headers[0]), filter_headers[1]);
+        assert_eq!(
+            blockchain.block_filter(0).unwrap().header(&Hash256::ZERO),
+            filter_headers[0]
+        );
+    }
+
+    #[test]
+    fn enable_block_filters_on_existing_chain() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_block_filters();
+        add_blocks(&mut blockchain);
+        let mut other_blockchain = Blockchain::new();
+        add_blocks(&mut other_blockchain);
+
+        other_blockchain.enable_block_filters();
+
+        assert_eq!(
+            blockchain.filter_headers(0, 3),
+            other_blockchain.filter_headers(0, 3)
+        );
+    }
+
+    #[test]
+    fn disconnect_tip_removes_its_filter() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_block_filters();
+        add_blocks(&mut blockchain);
+        let filter_headers = blockchain.filter_headers(0, 3);
+
+        blockchain.disconnect_tip().unwrap();
+        add_block(&mut blockchain, vec![coinbase(2, "Dave", 1)]);
+
+        assert!(blockchain.block_filter(2).unwrap().matches_address("Dave"));
+        assert_eq!(filter_headers[..2], blockchain.filter_headers(0, 3)[..2]);
+        assert_ne!(filter_headers[2], blockchain.filter_headers(0, 3)[2]);
+    }
+
+    #[test]
+    fn filters_start_after_pruned_blocks() {
+        let mut blockchain = Blockchain::new();
+        add_blocks(&mut blockchain);
+        blockchain.enable_pruning(1);
+
+        blockchain.enable_block_filters();
+
+        assert_eq!(None, blockchain.block_filter(1));
+        assert!(blockchain.filter_headers(0, 3).is_empty());
+        assert_eq!(
+            BlockFilter::new(&blockchain.block(2).unwrap(), HashAlgorithm::Sha256)
+                .header(&Hash256::ZERO),
+            blockchain.filter_headers(2, 1)[0]
+        );
+    }
+}
+
+#[cfg(test)]
+mod blockchain_update_with_block_tests {
+    use crate::transaction::Output;
+    use crate::{check_difficulty, now, Hash256, Script, Transaction};
+
+    use super::{Block, BlockHash, BlockValidationErr, Blockchain, Hashable};
+    use crate::test_support::{input, output, payment, spend, BITS};
+
+    const IMPOSSIBLE_BITS: u32 = 0x0000_0000;
+    struct BlockOutputConfig {
+        unspent_output_value: u64,
+        output_value: u64,
+        expected_difference: u64,
+    }
+
+    fn genesis_block_hash() -> BlockHash {
+        Hash256::ZERO
+    }
+
+    fn current_time() -> u128 {
+        now().expect("Failure to get the current time in milliseconds.")
+    }
+
+    fn create_coinbase_transaction(block_index: u32) -> Transaction {
+        Transaction::coinbase(block_index, vec![])
+    }
+
+    fn create_block_with_impossible_difficulty(
+        index: u32,
+        timestamp: u128,
+        previous_block_hash: BlockHash,
+        transactions: Vec<Transaction>,
+    ) -> Block {
+        let block = Block::new(
+            index,
+            timestamp,
+            previous_block_hash,
+            transactions,
+            IMPOSSIBLE_BITS,
+        );
+        assert!(!check_difficulty(&block.hash(), block.bits));
+
+        block
+    }
+
+    fn create_block_with_valid_difficulty(
+        index: u32,
+        timestamp: u128,
+        previous_block_hash: BlockHash,
+        transactions: Vec<Transaction>,
+    ) -> Block {
+        let mut block = Block::new(index, timestamp, previous_block_hash, transactions, BITS);
+        block.mine();
+        assert!(check_difficulty(&block.hash(), block.bits));
+
+        block
+    }
+
+    fn add_block_to_blockchain(blockchain: &mut Blockchain, block: Block) {
+        let original_length = blockchain.blocks().len();
+
+        let result = blockchain.update_with_block(block);
+
+        assert!(result.is_ok());
+        assert_eq!(Ok(()), result);
+        assert_eq!(original_length + 1, blockchain.blocks().len());
+    }
+
+    fn assert_add_block_with_sufficient_inputs(config: BlockOutputConfig) {
+        let timestamp = current_time();
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            timestamp,
+            genesis_block_hash(),
+            vec![Transaction::coinbase(
+                0,
+                vec![
+                    Output {
+                        to_address: "Alice".to_string(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                    Output {
+                        to_address: "Bob".to_string(),
+                        value: 2,
+                        locking_script: Script::new(),
+                    },
+                ],
+            )],
+        );
+        let mut blockchain = Blockchain::new();
+        let block = create_block_with_valid_difficulty(
+            1,
+            timestamp + 1,
+            genesis_block.hash,
+            vec![
+                Transaction::coinbase(
+                    1,
+                    vec![Output {
+                        to_address: "Chris".to_owned(),
+                        value: config.unspent_output_value,
+                        locking_script: Script::new(),
+                    }],
+                ),
+                Transaction {
+                    inputs: vec![
+                        spend(&genesis_block.transactions[0], 0),
+                        spend(&genesis_block.transactions[0], 1),
+                    ],
+                    outputs: vec![Output {
+                        to_address: "Chris".to_owned(),
+                        value: config.output_value,
+                        locking_script: Script::new(),
+                    }],
+                    lock_time: None,
+                },
+            ],
+        );
+        assert!(block.transactions[1].input_value() >= block.transactions[1].output_value());
+        assert_eq!(
+            block.transactions[1].input_value(),
+            block.transactions[1]
+                .output_value()
+                .map(|value| value + config.expected_difference)
+        );
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+        add_block_to_blockchain(&mut blockchain, block);
+    }
+
+    #[test]
+    fn add_block_with_invalid_previous_block_hash() {
+        let wrong_block_hash = Hash256([1; 32]);
+        let genesis_block =
+            create_block_with_valid_difficulty(0, current_time(), wrong_block_hash, vec![]);
+        let mut blockchain = Blockchain::new();
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InvalidGenesisBlockFormat), result);
+    }
+
+    #[test]
+    fn add_block_with_index_as_one_to_empty_blockchain() {
+        let wrong_index = 1;
+        let genesis_block = Block::new(wrong_index, 2, Hash256::ZERO, vec![], 3);
+        let mut blockchain = Blockchain::new();
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::MismatchedIndex), result);
     }
 
     #[test]
@@ -335,7 +2984,7 @@ mod blockchain_update_with_block_tests {
         let block = create_block_with_valid_difficulty(
             wrong_index,
             timestamp + 1,
-            genesis_block.hash.clone(),
+            genesis_block.hash,
             vec![],
         );
         let mut blockchain = Blockchain::new();
@@ -343,7 +2992,7 @@ mod blockchain_update_with_block_tests {
 
         let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
+        assert!(result.is_err());
         assert_eq!(Err(BlockValidationErr::MismatchedIndex), result);
     }
 
@@ -352,19 +3001,52 @@ mod blockchain_update_with_block_tests {
         let timestamp = current_time();
         let genesis_block =
             create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
-        let block = create_block_with_impossible_difficulty(
+        let block =
+            create_block_with_impossible_difficulty(1, timestamp + 1, genesis_block.hash, vec![]);
+        let mut blockchain = Blockchain::new();
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+
+        let result = blockchain.update_with_block(block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+    }
+
+    #[test]
+    fn add_block_with_mismatched_stored_hash() {
+        let timestamp = current_time();
+        let genesis_block =
+            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
+        let mut block =
+            create_block_with_valid_difficulty(1, timestamp + 1, genesis_block.hash, vec![]);
+        block.hash = Hash256::ZERO;
+        let mut blockchain = Blockchain::new();
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+
+        let result = blockchain.validate_header(&block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+        assert_eq!(result, blockchain.update_with_block(block));
+    }
+
+    #[test]
+    fn add_block_with_invalid_transaction_root() {
+        let timestamp = current_time();
+        let genesis_block =
+            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
+        let mut block = create_block_with_valid_difficulty(
             1,
             timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![],
+            genesis_block.hash,
+            vec![create_coinbase_transaction(1)],
         );
+        block.transactions.push(create_coinbase_transaction(1));
         let mut blockchain = Blockchain::new();
         add_block_to_blockchain(&mut blockchain, genesis_block);
 
         let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+        assert_eq!(Err(BlockValidationErr::InvalidTransactionRoot), result);
     }
 
     #[test]
@@ -373,18 +3055,14 @@ mod blockchain_update_with_block_tests {
         let genesis_block =
             create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
         let wrong_timestamp = timestamp - 1;
-        let block = create_block_with_valid_difficulty(
-            1,
-            wrong_timestamp,
-            genesis_block.hash.clone(),
-            vec![],
-        );
+        let block =
+            create_block_with_valid_difficulty(1, wrong_timestamp, genesis_block.hash, vec![]);
         let mut blockchain = Blockchain::new();
         add_block_to_blockchain(&mut blockchain, genesis_block);
 
         let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
+        assert!(result.is_err());
         assert_eq!(Err(BlockValidationErr::AchronologicalTimestamp), result);
     }
 
@@ -394,85 +3072,260 @@ mod blockchain_update_with_block_tests {
         let genesis_block =
             create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
         let wrong_timestamp = timestamp - 1;
+        let block =
+            create_block_with_valid_difficulty(1, wrong_timestamp, genesis_block.hash, vec![]);
+        let mut blockchain = Blockchain::new();
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+
+        let result = blockchain.update_with_block(block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::AchronologicalTimestamp), result);
+    }
+
+    #[test]
+    fn add_block_with_mismatched_previous_hash() {
+        let timestamp = current_time();
+        let genesis_block =
+            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
+        let wrong_previous_hash = Hash256([1; 32]);
+        let block =
+            create_block_with_valid_difficulty(1, timestamp + 1, wrong_previous_hash, vec![]);
+        let mut blockchain = Blockchain::new();
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+
+        let result = blockchain.update_with_block(block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::MismatchedPreviousHash), result);
+    }
+
+    #[test]
+    fn add_block_with_transaction_that_has_non_empty_inputs() {
+        let timestamp = current_time();
+        let wrong_inputs = vec![input("Alice", 1)];
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            timestamp,
+            genesis_block_hash(),
+            vec![Transaction {
+                inputs: wrong_inputs,
+                outputs: vec![],
+                lock_time: None,
+            }],
+        );
+        let mut blockchain = Blockchain::new();
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InvalidCoinbaseTransaction), result);
+    }
+
+    #[test]
+    fn add_block_with_transactions_where_first_one_has_non_empty_inputs_case1() {
+        let timestamp = current_time();
+        let wrong_inputs = vec![input("Alice", 1)];
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            timestamp,
+            genesis_block_hash(),
+            vec![
+                Transaction {
+                    inputs: wrong_inputs,
+                    outputs: vec![],
+                    lock_time: None,
+                },
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+            ],
+        );
+        let mut blockchain = Blockchain::new();
+
+        let result = blockchain.update_with_block(genesis_block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InvalidCoinbaseTransaction), result);
+    }
+
+    #[test]
+    fn add_block_with_transactions_where_first_one_has_non_empty_inputs_case2() {
+        let timestamp = current_time();
+        let wrong_inputs = vec![input("Alice", 1)];
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            timestamp,
+            genesis_block_hash(),
+            vec![create_coinbase_transaction(0)],
+        );
+        let block = create_block_with_valid_difficulty(
+            1,
+            timestamp + 1,
+            genesis_block.hash,
+            vec![
+                Transaction {
+                    inputs: wrong_inputs,
+                    outputs: vec![],
+                    lock_time: None,
+                },
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+                Transaction {
+                    inputs: vec![],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+            ],
+        );
+        let mut blockchain = Blockchain::new();
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+
+        let result = blockchain.update_with_block(block);
+
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InvalidCoinbaseTransaction), result);
+    }
+
+    #[test]
+    fn add_block_with_outputs_less_than_fee_case1() {
+        let timestamp = current_time();
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            timestamp,
+            genesis_block_hash(),
+            vec![Transaction::coinbase(
+                0,
+                vec![Output {
+                    to_address: "Alice".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                }],
+            )],
+        );
+        let mut blockchain = Blockchain::new();
         let block = create_block_with_valid_difficulty(
             1,
-            wrong_timestamp,
-            genesis_block.hash.clone(),
-            vec![],
+            timestamp + 1,
+            genesis_block.hash,
+            vec![
+                create_coinbase_transaction(1),
+                Transaction {
+                    inputs: vec![spend(&genesis_block.transactions[0], 0)],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+            ],
         );
-        let mut blockchain = Blockchain::new();
         add_block_to_blockchain(&mut blockchain, genesis_block);
 
         let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::AchronologicalTimestamp), result);
+        assert!(result.is_err());
+        assert_eq!(
+            Err(BlockValidationErr::FeeExceedsCoinbaseTransactionOutputValue),
+            result
+        );
     }
 
     #[test]
-    fn add_block_with_mismatched_previous_hash() {
+    fn add_block_with_outputs_less_than_fee_case2() {
         let timestamp = current_time();
-        let genesis_block =
-            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
-        let wrong_previous_hash = vec![1, 2, 3];
-        let block =
-            create_block_with_valid_difficulty(1, timestamp + 1, wrong_previous_hash, vec![]);
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            timestamp,
+            genesis_block_hash(),
+            vec![Transaction::coinbase(
+                0,
+                vec![Output {
+                    to_address: "Alice".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                }],
+            )],
+        );
         let mut blockchain = Blockchain::new();
+        let mut coinbase_transaction = create_coinbase_transaction(1);
+        coinbase_transaction.outputs = vec![Output {
+            to_address: "Chris".to_owned(),
+            value: 0,
+            locking_script: Script::new(),
+        }];
+        let block = create_block_with_valid_difficulty(
+            1,
+            timestamp + 1,
+            genesis_block.hash,
+            vec![
+                coinbase_transaction,
+                Transaction {
+                    inputs: vec![spend(&genesis_block.transactions[0], 0)],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+            ],
+        );
         add_block_to_blockchain(&mut blockchain, genesis_block);
 
         let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::MismatchedPreviousHash), result);
+        assert!(result.is_err());
+        assert_eq!(
+            Err(BlockValidationErr::FeeExceedsCoinbaseTransactionOutputValue),
+            result
+        );
     }
 
     #[test]
-    fn add_block_with_transaction_that_has_non_empty_inputs() {
+    fn add_block_with_second_transaction_that_has_non_empty_inputs() {
         let timestamp = current_time();
-        let wrong_inputs = vec![Output {
-            to_address: "Alice".to_string(),
-            value: 1,
-        }];
         let genesis_block = create_block_with_valid_difficulty(
             0,
             timestamp,
             genesis_block_hash(),
-            vec![Transaction {
-                inputs: wrong_inputs,
-                outputs: vec![],
-            }],
+            vec![
+                create_coinbase_transaction(0),
+                Transaction {
+                    inputs: vec![input("Alice", 1)],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+            ],
         );
         let mut blockchain = Blockchain::new();
 
         let result = blockchain.update_with_block(genesis_block);
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::InvalidCoinbaseTransaction), result);
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InvalidInput), result);
     }
 
     #[test]
-    fn add_block_with_transactions_where_first_one_has_non_empty_inputs_case1() {
+    fn add_block_with_insufficient_inputs_case1() {
         let timestamp = current_time();
-        let wrong_inputs = vec![Output {
-            to_address: "Alice".to_string(),
-            value: 1,
-        }];
         let genesis_block = create_block_with_valid_difficulty(
             0,
             timestamp,
             genesis_block_hash(),
             vec![
-                Transaction {
-                    inputs: wrong_inputs,
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![],
-                },
+                create_coinbase_transaction(0),
                 Transaction {
                     inputs: vec![],
-                    outputs: vec![],
+                    outputs: vec![Output {
+                        to_address: "Alice".to_string(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    }],
+                    lock_time: None,
                 },
             ],
         );
@@ -480,499 +3333,1068 @@ mod blockchain_update_with_block_tests {
 
         let result = blockchain.update_with_block(genesis_block);
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::InvalidCoinbaseTransaction), result);
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InsufficientInputValue), result);
     }
 
     #[test]
-    fn add_block_with_transactions_where_first_one_has_non_empty_inputs_case2() {
+    fn add_block_with_insufficient_inputs_case2() {
         let timestamp = current_time();
-        let wrong_inputs = vec![Output {
-            to_address: "Alice".to_string(),
-            value: 1,
-        }];
         let genesis_block = create_block_with_valid_difficulty(
             0,
             timestamp,
             genesis_block_hash(),
-            vec![
-                create_coinbase_transaction(),
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![],
-                },
-            ],
+            vec![Transaction::coinbase(
+                0,
+                vec![
+                    Output {
+                        to_address: "Alice".to_string(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                    Output {
+                        to_address: "Bob".to_string(),
+                        value: 2,
+                        locking_script: Script::new(),
+                    },
+                ],
+            )],
         );
+        let mut blockchain = Blockchain::new();
         let block = create_block_with_valid_difficulty(
             1,
             timestamp + 1,
-            genesis_block.hash.clone(),
+            genesis_block.hash,
             vec![
+                Transaction::coinbase(
+                    1,
+                    vec![Output {
+                        to_address: "Chris".to_owned(),
+                        value: 4,
+                        locking_script: Script::new(),
+                    }],
+                ),
                 Transaction {
-                    inputs: wrong_inputs,
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![],
+                    inputs: vec![
+                        spend(&genesis_block.transactions[0], 0),
+                        spend(&genesis_block.transactions[0], 1),
+                    ],
+                    outputs: vec![Output {
+                        to_address: "Chris".to_owned(),
+                        value: 4,
+                        locking_script: Script::new(),
+                    }],
+                    lock_time: None,
                 },
             ],
         );
-        let mut blockchain = Blockchain::new();
         add_block_to_blockchain(&mut blockchain, genesis_block);
 
         let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::InvalidCoinbaseTransaction), result);
+        assert!(result.is_err());
+        assert_eq!(Err(BlockValidationErr::InsufficientInputValue), result);
     }
 
     #[test]
-    fn add_block_with_outputs_less_than_fee_case1() {
+    fn add_block_with_exactly_sufficient_inputs() {
+        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
+            unspent_output_value: 4,
+            output_value: 3,
+            expected_difference: 0,
+        });
+    }
+
+    #[test]
+    fn add_block_with_more_than_sufficient_inputs_case1() {
+        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
+            unspent_output_value: 4,
+            output_value: 2,
+            expected_difference: 1,
+        });
+    }
+
+    #[test]
+    fn add_block_with_more_than_sufficient_inputs_case2() {
+        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
+            unspent_output_value: 4,
+            output_value: 1,
+            expected_difference: 2,
+        });
+    }
+
+    #[test]
+    fn add_block_with_more_than_sufficient_inputs_case3() {
+        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
+            unspent_output_value: 4,
+            output_value: 0,
+            expected_difference: 3,
+        });
+    }
+
+    #[test]
+    fn add_one_block_without_transactions_to_blockchain() {
+        let timestamp = current_time();
+        let genesis_block =
+            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
+        let mut blockchain = Blockchain::new();
+
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+    }
+
+    #[test]
+    fn add_two_blocks_without_transactions_to_blockchain() {
+        let timestamp = current_time();
+        let genesis_block =
+            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
+        let block =
+            create_block_with_valid_difficulty(1, timestamp + 1, genesis_block.hash, vec![]);
+        let mut blockchain = Blockchain::new();
+
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+        add_block_to_blockchain(&mut blockchain, block);
+    }
+
+    #[test]
+    fn add_three_blocks_without_transactions_to_blockchain() {
+        let timestamp = current_time();
+        let genesis_block =
+            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
+        let block1 =
+            create_block_with_valid_difficulty(1, timestamp + 1, genesis_block.hash, vec![]);
+        let block2 = create_block_with_valid_difficulty(2, timestamp + 2, block1.hash, vec![]);
+        let mut blockchain = Blockchain::new();
+
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+        add_block_to_blockchain(&mut blockchain, block1);
+        add_block_to_blockchain(&mut blockchain, block2);
+    }
+
+    #[test]
+    fn add_one_block_with_one_transaction_to_blockchain() {
         let timestamp = current_time();
         let genesis_block = create_block_with_valid_difficulty(
             0,
             timestamp,
             genesis_block_hash(),
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![Output {
+            vec![Transaction::coinbase(
+                0,
+                vec![Output {
                     to_address: "Alice".to_string(),
                     value: 1,
+                    locking_script: Script::new(),
                 }],
-            }],
+            )],
         );
         let mut blockchain = Blockchain::new();
+        let mut coinbase_transaction = create_coinbase_transaction(1);
+        coinbase_transaction.outputs = vec![Output {
+            to_address: "Chris".to_owned(),
+            value: 1,
+            locking_script: Script::new(),
+        }];
         let block = create_block_with_valid_difficulty(
             1,
             timestamp + 1,
-            genesis_block.hash.clone(),
+            genesis_block.hash,
             vec![
-                create_coinbase_transaction(),
+                coinbase_transaction,
                 Transaction {
-                    inputs: vec![Output {
-                        to_address: "Alice".to_owned(),
-                        value: 1,
-                    }],
+                    inputs: vec![spend(&genesis_block.transactions[0], 0)],
                     outputs: vec![],
+                    lock_time: None,
                 },
             ],
         );
         add_block_to_blockchain(&mut blockchain, genesis_block);
+        add_block_to_blockchain(&mut blockchain, block);
+    }
 
-        let result = blockchain.update_with_block(block);
-
-        assert_eq!(true, result.is_err());
-        assert_eq!(
-            Err(BlockValidationErr::FeeExceedsCoinbaseTransactionOutputValue),
-            result
+    #[test]
+    fn add_one_block_with_two_transactions_to_blockchain() {
+        let timestamp = current_time();
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            timestamp,
+            genesis_block_hash(),
+            vec![Transaction::coinbase(
+                0,
+                vec![
+                    Output {
+                        to_address: "Alice".to_string(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                    Output {
+                        to_address: "Bob".to_owned(),
+                        value: 2,
+                        locking_script: Script::new(),
+                    },
+                ],
+            )],
+        );
+        let mut blockchain = Blockchain::new();
+        let mut coinbase_transaction = create_coinbase_transaction(1);
+        coinbase_transaction.outputs = vec![Output {
+            to_address: "Chris".to_owned(),
+            value: 3,
+            locking_script: Script::new(),
+        }];
+        let block = create_block_with_valid_difficulty(
+            1,
+            timestamp + 1,
+            genesis_block.hash,
+            vec![
+                coinbase_transaction,
+                Transaction {
+                    inputs: vec![spend(&genesis_block.transactions[0], 0)],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+                Transaction {
+                    inputs: vec![spend(&genesis_block.transactions[0], 1)],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+            ],
         );
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+        add_block_to_blockchain(&mut blockchain, block);
     }
 
     #[test]
-    fn add_block_with_outputs_less_than_fee_case2() {
+    fn add_one_block_with_three_transactions_to_blockchain() {
         let timestamp = current_time();
         let genesis_block = create_block_with_valid_difficulty(
             0,
             timestamp,
             genesis_block_hash(),
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![Output {
-                    to_address: "Alice".to_string(),
-                    value: 1,
-                }],
-            }],
+            vec![Transaction::coinbase(
+                0,
+                vec![
+                    Output {
+                        to_address: "Alice".to_string(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                    Output {
+                        to_address: "Bob".to_owned(),
+                        value: 2,
+                        locking_script: Script::new(),
+                    },
+                    Output {
+                        to_address: "John".to_owned(),
+                        value: 3,
+                        locking_script: Script::new(),
+                    },
+                ],
+            )],
         );
         let mut blockchain = Blockchain::new();
-        let mut coinbase_transaction = create_coinbase_transaction();
+        let mut coinbase_transaction = create_coinbase_transaction(1);
         coinbase_transaction.outputs = vec![Output {
             to_address: "Chris".to_owned(),
-            value: 0,
+            value: 6,
+            locking_script: Script::new(),
         }];
         let block = create_block_with_valid_difficulty(
             1,
             timestamp + 1,
-            genesis_block.hash.clone(),
+            genesis_block.hash,
             vec![
                 coinbase_transaction,
                 Transaction {
-                    inputs: vec![Output {
-                        to_address: "Alice".to_owned(),
-                        value: 1,
-                    }],
+                    inputs: vec![spend(&genesis_block.transactions[0], 0)],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+                Transaction {
+                    inputs: vec![spend(&genesis_block.transactions[0], 1)],
+                    outputs: vec![],
+                    lock_time: None,
+                },
+                Transaction {
+                    inputs: vec![spend(&genesis_block.transactions[0], 2)],
                     outputs: vec![],
+                    lock_time: None,
                 },
             ],
         );
         add_block_to_blockchain(&mut blockchain, genesis_block);
+        add_block_to_blockchain(&mut blockchain, block);
+    }
+
+    /**
+     * Returns the coinbase transaction of the genesis block of
+     * create_blockchain, which pays Alice 1 coin and Bob 2 coins.
+     */
+    fn genesis_coinbase() -> Transaction {
+        Transaction::coinbase(0, vec![output("Alice", 1), output("Bob", 2)])
+    }
+
+    fn create_blockchain() -> Blockchain {
+        let genesis_block = create_block_with_valid_difficulty(
+            0,
+            current_time(),
+            genesis_block_hash(),
+            vec![genesis_coinbase()],
+        );
+        let mut blockchain = Blockchain::new();
+        add_block_to_blockchain(&mut blockchain, genesis_block);
+
+        blockchain
+    }
+
+    fn create_next_block(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+        let previous_block = blockchain.tip().unwrap();
+
+        create_block_with_valid_difficulty(
+            previous_block.index + 1,
+            previous_block.timestamp + 1,
+            previous_block.hash,
+            transactions,
+        )
+    }
+
+    #[test]
+    fn add_block_with_duplicate_transactions() {
+        let mut blockchain = create_blockchain();
+        let payment = payment(spend(&genesis_coinbase(), 0), "Chris", 1);
+        let block = create_next_block(
+            &blockchain,
+            vec![create_coinbase_transaction(1), payment.clone(), payment],
+        );
 
         let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(
-            Err(BlockValidationErr::FeeExceedsCoinbaseTransactionOutputValue),
-            result
+        assert_eq!(Err(BlockValidationErr::DuplicateTransaction), result);
+    }
+
+    #[test]
+    fn add_block_with_coinbase_transaction_of_other_block() {
+        let mut blockchain = create_blockchain();
+        let without_block_index = Transaction {
+            inputs: vec![],
+            outputs: vec![],
+            lock_time: None,
+        };
+
+        for coinbase in [create_coinbase_transaction(0), without_block_index].iter() {
+            let block = create_next_block(&blockchain, vec![coinbase.clone()]);
+
+            let result = blockchain.update_with_block(block);
+
+            assert_eq!(
+                Err(BlockValidationErr::MismatchedCoinbaseBlockIndex),
+                result
+            );
+        }
+        assert_eq!(1, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn add_block_that_creates_output_identical_to_unspent_output() {
+        let mut blockchain = create_blockchain();
+        let coinbase = Transaction::coinbase(1, vec![output("Alice", 1)]);
+        let block = create_next_block(&blockchain, vec![coinbase.clone()]);
+        add_block_to_blockchain(&mut blockchain, block);
+
+        let block = create_next_block(
+            &blockchain,
+            vec![
+                create_coinbase_transaction(2),
+                payment(spend(&genesis_coinbase(), 0), "Chris", 1),
+                payment(spend(&coinbase, 0), "Dave", 1),
+            ],
         );
+
+        add_block_to_blockchain(&mut blockchain, block);
     }
 
     #[test]
-    fn add_block_with_second_transaction_that_has_non_empty_inputs() {
-        let timestamp = current_time();
-        let genesis_block = create_block_with_valid_difficulty(
-            0,
-            timestamp,
-            genesis_block_hash(),
+    fn add_block_that_creates_identical_outputs() {
+        let mut blockchain = create_blockchain();
+        let coinbase = Transaction::coinbase(1, vec![output("Chris", 1)]);
+        let payment = Transaction {
+            inputs: vec![spend(&genesis_coinbase(), 1)],
+            outputs: vec![output("Chris", 1), output("Chris", 1)],
+            lock_time: None,
+        };
+        let block = create_next_block(&blockchain, vec![coinbase.clone(), payment.clone()]);
+        add_block_to_blockchain(&mut blockchain, block);
+
+        let block = create_next_block(
+            &blockchain,
             vec![
-                create_coinbase_transaction(),
+                create_coinbase_transaction(2),
                 Transaction {
-                    inputs: vec![Output {
-                        to_address: "Alice".to_string(),
-                        value: 1,
-                    }],
-                    outputs: vec![],
+                    inputs: vec![spend(&coinbase, 0), spend(&payment, 0), spend(&payment, 1)],
+                    outputs: vec![output("Dave", 3)],
+                    lock_time: None,
                 },
             ],
         );
-        let mut blockchain = Blockchain::new();
 
-        let result = blockchain.update_with_block(genesis_block);
+        add_block_to_blockchain(&mut blockchain, block);
+    }
+
+    #[test]
+    fn add_block_that_spends_output_of_earlier_transaction() {
+        let mut blockchain = create_blockchain();
+        let parent = payment(spend(&genesis_coinbase(), 0), "Dave", 1);
+        let child = payment(spend(&parent, 0), "Eve", 1);
+        let block = create_next_block(
+            &blockchain,
+            vec![create_coinbase_transaction(1), parent.clone(), child],
+        );
+        add_block_to_blockchain(&mut blockchain, block);
+
+        let spends_parent_output = payment(spend(&parent, 0), "Frank", 1);
+        let block = create_next_block(
+            &blockchain,
+            vec![create_coinbase_transaction(2), spends_parent_output],
+        );
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidInput),
+            blockchain.update_with_block(block)
+        );
+
+        blockchain.disconnect_tip().unwrap();
+        let spends_alice_output = payment(spend(&genesis_coinbase(), 0), "Frank", 1);
+        let block = create_next_block(
+            &blockchain,
+            vec![create_coinbase_transaction(1), spends_alice_output],
+        );
+
+        add_block_to_blockchain(&mut blockchain, block);
+    }
+
+    #[test]
+    fn add_block_that_spends_output_of_later_transaction() {
+        let mut blockchain = create_blockchain();
+        let parent = payment(spend(&genesis_coinbase(), 0), "Dave", 1);
+        let child = payment(spend(&parent, 0), "Eve", 1);
+        let block = create_next_block(
+            &blockchain,
+            vec![create_coinbase_transaction(1), child, parent],
+        );
+
+        let result = blockchain.update_with_block(block);
 
-        assert_eq!(true, result.is_err());
         assert_eq!(Err(BlockValidationErr::InvalidInput), result);
     }
 
     #[test]
-    fn add_block_with_insufficient_inputs_case1() {
-        let timestamp = current_time();
-        let genesis_block = create_block_with_valid_difficulty(
-            0,
-            timestamp,
-            genesis_block_hash(),
+    fn add_block_that_spends_output_twice() {
+        let mut blockchain = create_blockchain();
+        let payment_to_dave = payment(spend(&genesis_coinbase(), 0), "Dave", 1);
+        let double_spend = payment(spend(&genesis_coinbase(), 0), "Eve", 1);
+        let block = create_next_block(
+            &blockchain,
             vec![
-                create_coinbase_transaction(),
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![Output {
-                        to_address: "Alice".to_string(),
-                        value: 1,
-                    }],
-                },
+                create_coinbase_transaction(1),
+                payment_to_dave,
+                double_spend,
             ],
         );
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidInput), result);
+    }
+
+    #[test]
+    fn add_block_that_overclaims_spent_output() {
+        let mut blockchain = create_blockchain();
+        let mut input = spend(&genesis_coinbase(), 0);
+        input.previous_output.value = 2;
+        let block = create_next_block(
+            &blockchain,
+            vec![create_coinbase_transaction(1), payment(input, "Dave", 2)],
+        );
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidInput), result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_pruning_tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{BlockValidationErr, Blockchain};
+
+    use crate::test_support::{coinbase, create_next_block, payment, spend};
+    use crate::{ChainStorage, FlatFileStorage};
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "blockchain_pruning_tests-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    /**
+     * Adds 5 blocks, whose coinbase outputs pay Alice 1 to 5 coins.
+     */
+    fn add_blocks<S: ChainStorage>(blockchain: &mut Blockchain<S>) {
+        for index in 0..5 {
+            let block = create_next_block(
+                blockchain,
+                vec![coinbase(index, "Alice", u64::from(index) + 1)],
+            );
+            blockchain.update_with_block(block).unwrap();
+        }
+    }
+
+    fn body_sizes(blockchain: &Blockchain) -> Vec<usize> {
+        blockchain
+            .blocks()
+            .iter()
+            .map(|block| block.transactions.len())
+            .collect()
+    }
+
+    #[test]
+    fn pruning_is_disabled_by_default() {
         let mut blockchain = Blockchain::new();
+        add_blocks(&mut blockchain);
 
-        let result = blockchain.update_with_block(genesis_block);
+        assert_eq!(None, blockchain.pruning_depth());
+        assert_eq!(0, blockchain.pruned_height());
+        assert_eq!(vec![1, 1, 1, 1, 1], body_sizes(&blockchain));
+    }
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::InsufficientInputValue), result);
+    #[test]
+    fn prune_bodies_deeper_than_depth() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_pruning(2);
+
+        add_blocks(&mut blockchain);
+
+        assert_eq!(Some(2), blockchain.pruning_depth());
+        assert_eq!(3, blockchain.pruned_height());
+        assert_eq!(vec![0, 0, 0, 1, 1], body_sizes(&blockchain));
+        for (index, block) in blockchain.blocks().iter().enumerate().skip(1) {
+            assert_eq!(
+                blockchain.blocks()[index - 1].hash,
+                block.previous_block_hash
+            );
+        }
     }
 
     #[test]
-    fn add_block_with_insufficient_inputs_case2() {
-        let timestamp = current_time();
-        let genesis_block = create_block_with_valid_difficulty(
-            0,
-            timestamp,
-            genesis_block_hash(),
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![
-                    Output {
-                        to_address: "Alice".to_string(),
-                        value: 1,
-                    },
-                    Output {
-                        to_address: "Bob".to_string(),
-                        value: 2,
-                    },
-                ],
-            }],
+    fn enable_pruning_on_existing_chain() {
+        let mut blockchain = Blockchain::new();
+        add_blocks(&mut blockchain);
+
+        blockchain.enable_pruning(0);
+
+        assert_eq!(Some(1), blockchain.pruning_depth());
+        assert_eq!(vec![0, 0, 0, 0, 1], body_sizes(&blockchain));
+    }
+
+    #[test]
+    fn serve_headers_but_no_proofs_of_pruned_blocks() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_pruning(2);
+        add_blocks(&mut blockchain);
+
+        assert_eq!(5, blockchain.headers(0, 10).len());
+        assert_eq!(
+            None,
+            blockchain.transaction_proof(&coinbase(0, "Alice", 1).id())
         );
+        assert!(blockchain
+            .transaction_proof(&coinbase(4, "Alice", 5).id())
+            .is_some());
+    }
+
+    #[test]
+    fn validate_blocks_that_spend_pruned_outputs() {
         let mut blockchain = Blockchain::new();
-        let block = create_block_with_valid_difficulty(
-            1,
-            timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![
-                Transaction {
-                    inputs: vec![],
-                    outputs: vec![Output {
-                        to_address: "Chris".to_owned(),
-                        value: 4,
-                    }],
-                },
-                Transaction {
-                    inputs: vec![
-                        Output {
-                            to_address: "Alice".to_owned(),
-                            value: 1,
-                        },
-                        Output {
-                            to_address: "Bob".to_owned(),
-                            value: 2,
-                        },
-                    ],
-                    outputs: vec![Output {
-                        to_address: "Chris".to_owned(),
-                        value: 4,
-                    }],
-                },
-            ],
+        blockchain.enable_pruning(2);
+        add_blocks(&mut blockchain);
+        let payment = payment(spend(&coinbase(0, "Alice", 1), 0), "Bob", 1);
+        let block = create_next_block(&blockchain, vec![coinbase(5, "Alice", 6), payment.clone()]);
+
+        blockchain.update_with_block(block).unwrap();
+
+        let block = create_next_block(&blockchain, vec![coinbase(6, "Alice", 7), payment]);
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::InvalidInput), result);
+        assert_eq!(6, blockchain.blocks().len());
+        assert_eq!(4, blockchain.pruned_height());
+    }
+
+    #[test]
+    fn get_transaction_of_pruned_block() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_transaction_index();
+        add_blocks(&mut blockchain);
+        let old_id = blockchain.blocks()[0].transactions[0].id();
+        let recent_id = blockchain.blocks()[4].transactions[0].id();
+
+        blockchain.enable_pruning(2);
+
+        assert_eq!(None, blockchain.get_transaction(&old_id));
+        assert!(blockchain.get_transaction(&recent_id).is_some());
+    }
+
+    #[test]
+    fn prune_flat_file_storage() {
+        let directory = temporary_directory("prune_flat_file_storage");
+        let mut blockchain = Blockchain::new()
+            .with_storage(FlatFileStorage::create(&directory).unwrap())
+            .unwrap();
+        blockchain.enable_pruning(2);
+
+        add_blocks(&mut blockchain);
+
+        let block_files = blockchain.storage().block_files();
+        assert_eq!(5, block_files.read_headers().unwrap().len());
+        for index in 0..3 {
+            assert_eq!(None, block_files.read_body(index).unwrap());
+            assert_eq!(None, block_files.read_undo(index).unwrap());
+        }
+        for index in 3..5 {
+            assert_eq!(
+                Some(vec![coinbase(index, "Alice", index as u64 + 1)]),
+                block_files.read_body(index).unwrap()
+            );
+        }
+        let reopened = FlatFileStorage::open(&directory).unwrap();
+        assert_eq!(3, reopened.pruned_height());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn storage_failure() {
+        let directory = temporary_directory("storage_failure");
+        let mut blockchain = Blockchain::new()
+            .with_storage(FlatFileStorage::create(&directory).unwrap())
+            .unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        let block = create_next_block(&blockchain, vec![coinbase(0, "Alice", 1)]);
+
+        let result = blockchain.update_with_block(block);
+
+        assert_eq!(Err(BlockValidationErr::StorageFailure), result);
+        assert_eq!(0, blockchain.block_count());
+    }
+}
+
+#[cfg(test)]
+mod blockchain_utxo_snapshot_tests {
+    use super::Blockchain;
+    use crate::encoding::{Decodable, Encodable};
+
+    use crate::test_support::{add_block, coinbase, create_payment, payment, spend};
+    use crate::{Block, Hash256, Hashable, SnapshotErr, UtxoSnapshot};
+
+    /**
+     * Alice receives 5 coins in the genesis block and pays 3 to Bob in block
+     * 1, whose coinbase output also goes to Bob.
+     */
+    fn create_blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        let payment = create_payment();
+        add_block(&mut blockchain, vec![coinbase(1, "Bob", 1), payment]);
+
+        blockchain
+    }
+
+    fn headers(blockchain: &Blockchain) -> Vec<Block> {
+        blockchain
+            .blocks()
+            .iter()
+            .map(|block| Block::decode(&block.encode()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn snapshot_of_empty_chain() {
+        let blockchain = Blockchain::new();
+
+        assert_eq!(None, blockchain.utxo_snapshot());
+    }
+
+    #[test]
+    fn snapshot_at_tip() {
+        let blockchain = create_blockchain();
+
+        let result = blockchain.utxo_snapshot().unwrap();
+
+        assert_eq!(1, result.height());
+        assert_eq!(&blockchain.blocks()[1].hash, result.block_hash());
+        assert_eq!(3, result.len());
+        assert_eq!(
+            create_blockchain().utxo_snapshot().unwrap().hash(),
+            result.hash()
         );
-        add_block_to_blockchain(&mut blockchain, genesis_block);
+    }
 
-        let result = blockchain.update_with_block(block);
+    #[test]
+    fn load_snapshot_and_validate_forward() {
+        let source = create_blockchain();
+        let snapshot = source.utxo_snapshot().unwrap();
+        let trusted_hash = snapshot.hash();
+        let snapshot = UtxoSnapshot::decode(&snapshot.encode()).unwrap();
+        let mut blockchain = Blockchain::new();
 
-        assert_eq!(true, result.is_err());
-        assert_eq!(Err(BlockValidationErr::InsufficientInputValue), result);
+        blockchain
+            .load_utxo_snapshot(snapshot, headers(&source), &trusted_hash)
+            .unwrap();
+
+        assert_eq!(2, blockchain.blocks().len());
+        assert_eq!(2, blockchain.pruned_height());
+        assert_eq!(Some(&trusted_hash), blockchain.unconfirmed_snapshot_hash());
+        let payment = payment(spend(&create_payment(), 0), "Chris", 3);
+        add_block(&mut blockchain, vec![coinbase(2, "Chris", 1), payment]);
+        assert_eq!(4, blockchain.utxo_snapshot().unwrap().len());
+
+        blockchain.confirm_utxo_snapshot(&source).unwrap();
+
+        assert_eq!(None, blockchain.unconfirmed_snapshot_hash());
     }
 
     #[test]
-    fn add_block_with_exactly_sufficient_inputs() {
-        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
-            unspent_output_value: 4,
-            output_value: 3,
-            expected_difference: 0,
-        });
+    fn load_untrusted_snapshot() {
+        let source = create_blockchain();
+        let snapshot = source.utxo_snapshot().unwrap();
+        let mut blockchain = Blockchain::new();
+
+        let result = blockchain.load_utxo_snapshot(snapshot, headers(&source), &Hash256::ZERO);
+
+        assert_eq!(Err(SnapshotErr::UntrustedSnapshot), result);
+        assert!(blockchain.blocks().is_empty());
     }
 
     #[test]
-    fn add_block_with_more_than_sufficient_inputs_case1() {
-        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
-            unspent_output_value: 4,
-            output_value: 2,
-            expected_difference: 1,
-        });
+    fn load_snapshot_with_invalid_headers() {
+        let source = create_blockchain();
+        let snapshot = source.utxo_snapshot().unwrap();
+        let trusted_hash = snapshot.hash();
+        let mut missing_headers = headers(&source);
+        missing_headers.remove(0);
+        let mut unlinked_headers = headers(&source);
+        unlinked_headers[1].previous_block_hash = Hash256([1; 32]);
+
+        for invalid_headers in [missing_headers, unlinked_headers] {
+            let mut blockchain = Blockchain::new();
+
+            let result =
+                blockchain.load_utxo_snapshot(snapshot.clone(), invalid_headers, &trusted_hash);
+
+            assert_eq!(Err(SnapshotErr::InvalidHeaders), result);
+        }
     }
 
     #[test]
-    fn add_block_with_more_than_sufficient_inputs_case2() {
-        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
-            unspent_output_value: 4,
-            output_value: 1,
-            expected_difference: 2,
-        });
+    fn load_snapshot_of_other_genesis_block() {
+        let source = create_blockchain();
+        let snapshot = source.utxo_snapshot().unwrap();
+        let trusted_hash = snapshot.hash();
+        let mut blockchain = Blockchain::with_genesis_hash(Hash256([1; 32]));
+
+        let result = blockchain.load_utxo_snapshot(snapshot, headers(&source), &trusted_hash);
+
+        assert_eq!(Err(SnapshotErr::MismatchedGenesisBlock), result);
     }
 
     #[test]
-    fn add_block_with_more_than_sufficient_inputs_case3() {
-        assert_add_block_with_sufficient_inputs(BlockOutputConfig {
-            unspent_output_value: 4,
-            output_value: 0,
-            expected_difference: 3,
-        });
+    fn load_snapshot_into_non_empty_chain() {
+        let source = create_blockchain();
+        let snapshot = source.utxo_snapshot().unwrap();
+        let trusted_hash = snapshot.hash();
+        let mut blockchain = create_blockchain();
+
+        let result = blockchain.load_utxo_snapshot(snapshot, headers(&source), &trusted_hash);
+
+        assert_eq!(Err(SnapshotErr::ChainNotEmpty), result);
     }
 
     #[test]
-    fn add_one_block_without_transactions_to_blockchain() {
-        let timestamp = current_time();
-        let genesis_block =
-            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
+    fn confirm_snapshot_with_mismatched_replay() {
+        let source = create_blockchain();
+        let snapshot = source.utxo_snapshot().unwrap();
+        let trusted_hash = snapshot.hash();
         let mut blockchain = Blockchain::new();
+        blockchain
+            .load_utxo_snapshot(snapshot, headers(&source), &trusted_hash)
+            .unwrap();
+        let mut replayed = create_blockchain();
+        add_block(&mut replayed, vec![coinbase(2, "Alice", 1)]);
 
-        add_block_to_blockchain(&mut blockchain, genesis_block);
+        let result = blockchain.confirm_utxo_snapshot(&replayed);
+
+        assert_eq!(Err(SnapshotErr::MismatchedReplay), result);
+        assert_eq!(Some(&trusted_hash), blockchain.unconfirmed_snapshot_hash());
     }
 
     #[test]
-    fn add_two_blocks_without_transactions_to_blockchain() {
-        let timestamp = current_time();
-        let genesis_block =
-            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
-        let block = create_block_with_valid_difficulty(
-            1,
-            timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![],
+    fn confirm_without_snapshot() {
+        let mut blockchain = create_blockchain();
+
+        let result = blockchain.confirm_utxo_snapshot(&create_blockchain());
+
+        assert_eq!(Err(SnapshotErr::NoUnconfirmedSnapshot), result);
+    }
+}
+
+#[cfg(test)]
+mod blockchain_disconnect_tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{BlockValidationErr, Blockchain, DisconnectErr};
+
+    use crate::test_support::{add_block, coinbase, create_payment, output, spend};
+    use crate::{ChainStorage, FlatFileStorage, Hash256, Hashable, KvStorage, Transaction};
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "blockchain_disconnect_tests-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    fn snapshot_hash<S: ChainStorage>(blockchain: &Blockchain<S>) -> Option<Hash256> {
+        blockchain.utxo_snapshot().map(|snapshot| snapshot.hash())
+    }
+
+    #[test]
+    fn disconnect_tip() {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        let genesis_snapshot = blockchain.utxo_snapshot().unwrap();
+
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
+        );
+        let result = blockchain.disconnect_tip().unwrap();
+
+        assert_eq!(1, result.index);
+        assert_eq!(2, result.transactions.len());
+        assert_eq!(1, blockchain.blocks().len());
+        assert_eq!(Some(genesis_snapshot), blockchain.utxo_snapshot());
+
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
         );
+        assert_eq!(2, blockchain.blocks().len());
+    }
+
+    #[test]
+    fn disconnect_block_that_recreates_spent_output() {
         let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        let before = snapshot_hash(&blockchain);
+        let recreation = Transaction {
+            inputs: vec![spend(&coinbase(0, "Alice", 5), 0)],
+            outputs: vec![output("Alice", 5)],
+            lock_time: None,
+        };
 
-        add_block_to_blockchain(&mut blockchain, genesis_block);
-        add_block_to_blockchain(&mut blockchain, block);
+        add_block(&mut blockchain, vec![coinbase(1, "Bob", 1), recreation]);
+        blockchain.disconnect_tip().unwrap();
+
+        assert_eq!(before, snapshot_hash(&blockchain));
     }
 
     #[test]
-    fn add_three_blocks_without_transactions_to_blockchain() {
-        let timestamp = current_time();
-        let genesis_block =
-            create_block_with_valid_difficulty(0, timestamp, genesis_block_hash(), vec![]);
-        let block1 = create_block_with_valid_difficulty(
-            1,
-            timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![],
-        );
-        let block2 =
-            create_block_with_valid_difficulty(2, timestamp + 2, block1.hash.clone(), vec![]);
+    fn disconnect_every_block() {
         let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
+        );
 
-        add_block_to_blockchain(&mut blockchain, genesis_block);
-        add_block_to_blockchain(&mut blockchain, block1);
-        add_block_to_blockchain(&mut blockchain, block2);
+        blockchain.disconnect_tip().unwrap();
+        blockchain.disconnect_tip().unwrap();
+        let result = blockchain.disconnect_tip();
+
+        assert_eq!(Some(DisconnectErr::EmptyChain), result.err());
+        assert!(blockchain.storage().unspent_outputs().unwrap().is_empty());
     }
 
     #[test]
-    fn add_one_block_with_one_transaction_to_blockchain() {
-        let timestamp = current_time();
-        let genesis_block = create_block_with_valid_difficulty(
-            0,
-            timestamp,
-            genesis_block_hash(),
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![Output {
-                    to_address: "Alice".to_string(),
-                    value: 1,
-                }],
-            }],
+    fn disconnect_updates_indexes() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_transaction_index();
+        blockchain.enable_address_index();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
         );
+        let payment_id = create_payment().id();
+
+        blockchain.disconnect_tip().unwrap();
+
+        assert_eq!(None, blockchain.get_transaction(&payment_id));
+        assert_eq!(0, blockchain.address_history("Bob", 0, 10).total);
+        assert_eq!(1, blockchain.address_history("Alice", 0, 10).total);
+    }
+
+    #[test]
+    fn disconnect_pruned_block() {
         let mut blockchain = Blockchain::new();
-        let mut coinbase_transaction = create_coinbase_transaction();
-        coinbase_transaction.outputs = vec![Output {
-            to_address: "Chris".to_owned(),
-            value: 1,
-        }];
-        let block = create_block_with_valid_difficulty(
-            1,
-            timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![
-                coinbase_transaction,
-                Transaction {
-                    inputs: vec![Output {
-                        to_address: "Alice".to_owned(),
-                        value: 1,
-                    }],
-                    outputs: vec![],
-                },
-            ],
+        blockchain.enable_pruning(1);
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
         );
-        add_block_to_blockchain(&mut blockchain, genesis_block);
-        add_block_to_blockchain(&mut blockchain, block);
+
+        blockchain.disconnect_tip().unwrap();
+        let result = blockchain.disconnect_tip();
+
+        assert_eq!(Some(DisconnectErr::MissingUndoData), result.err());
+        assert_eq!(1, blockchain.blocks().len());
     }
 
     #[test]
-    fn add_one_block_with_two_transactions_to_blockchain() {
-        let timestamp = current_time();
-        let genesis_block = create_block_with_valid_difficulty(
-            0,
-            timestamp,
-            genesis_block_hash(),
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![
-                    Output {
-                        to_address: "Alice".to_string(),
-                        value: 1,
-                    },
-                    Output {
-                        to_address: "Bob".to_owned(),
-                        value: 2,
-                    },
-                ],
-            }],
+    fn disconnect_and_restart_flat_file_storage() {
+        let directory = temporary_directory("disconnect_and_restart_flat_file_storage");
+        let mut blockchain = Blockchain::new()
+            .with_storage(FlatFileStorage::create(&directory).unwrap())
+            .unwrap();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
         );
-        let mut blockchain = Blockchain::new();
-        let mut coinbase_transaction = create_coinbase_transaction();
-        coinbase_transaction.outputs = vec![Output {
-            to_address: "Chris".to_owned(),
-            value: 3,
-        }];
-        let block = create_block_with_valid_difficulty(
+        add_block(&mut blockchain, vec![coinbase(2, "Chris", 1)]);
+
+        blockchain.disconnect_tip().unwrap();
+
+        let block_files = blockchain.storage().block_files();
+        assert_eq!(2, block_files.read_headers().unwrap().len());
+        assert_eq!(None, block_files.read_body(2).unwrap());
+        assert_eq!(None, block_files.read_undo(2).unwrap());
+
+        let mut restarted = Blockchain::new()
+            .with_storage(FlatFileStorage::open(&directory).unwrap())
+            .unwrap();
+
+        assert_eq!(snapshot_hash(&blockchain), snapshot_hash(&restarted));
+        assert_eq!(blockchain.chain_work(), restarted.chain_work());
+        let block = restarted.disconnect_tip().unwrap();
+        assert_eq!(
+            vec![coinbase(1, "Bob", 1), create_payment()],
+            block.transactions
+        );
+        assert_eq!(
             1,
-            timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![
-                coinbase_transaction,
-                Transaction {
-                    inputs: vec![Output {
-                        to_address: "Alice".to_owned(),
-                        value: 1,
-                    }],
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![Output {
-                        to_address: "Bob".to_owned(),
-                        value: 2,
-                    }],
-                    outputs: vec![],
-                },
-            ],
+            restarted
+                .storage()
+                .block_files()
+                .read_headers()
+                .unwrap()
+                .len()
         );
-        add_block_to_blockchain(&mut blockchain, genesis_block);
-        add_block_to_blockchain(&mut blockchain, block);
+
+        fs::remove_dir_all(&directory).unwrap();
     }
 
     #[test]
-    fn add_one_block_with_three_transactions_to_blockchain() {
-        let timestamp = current_time();
-        let genesis_block = create_block_with_valid_difficulty(
-            0,
-            timestamp,
-            genesis_block_hash(),
-            vec![Transaction {
-                inputs: vec![],
-                outputs: vec![
-                    Output {
-                        to_address: "Alice".to_string(),
-                        value: 1,
-                    },
-                    Output {
-                        to_address: "Bob".to_owned(),
-                        value: 2,
-                    },
-                    Output {
-                        to_address: "John".to_owned(),
-                        value: 3,
-                    },
-                ],
-            }],
+    fn disconnect_and_restart_kv_storage() {
+        let directory = temporary_directory("disconnect_and_restart_kv_storage");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("chain.kv");
+        let mut blockchain = Blockchain::new()
+            .with_storage(KvStorage::open(&path).unwrap())
+            .unwrap();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
         );
-        let mut blockchain = Blockchain::new();
-        let mut coinbase_transaction = create_coinbase_transaction();
-        coinbase_transaction.outputs = vec![Output {
-            to_address: "Chris".to_owned(),
-            value: 6,
-        }];
-        let block = create_block_with_valid_difficulty(
-            1,
-            timestamp + 1,
-            genesis_block.hash.clone(),
-            vec![
-                coinbase_transaction,
-                Transaction {
-                    inputs: vec![Output {
-                        to_address: "Alice".to_owned(),
-                        value: 1,
-                    }],
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![Output {
-                        to_address: "Bob".to_owned(),
-                        value: 2,
-                    }],
-                    outputs: vec![],
-                },
-                Transaction {
-                    inputs: vec![Output {
-                        to_address: "John".to_owned(),
-                        value: 3,
-                    }],
-                    outputs: vec![],
-                },
-            ],
+        add_block(&mut blockchain, vec![coinbase(2, "Chris", 1)]);
+
+        blockchain.disconnect_tip().unwrap();
+
+        let mut restarted = Blockchain::new()
+            .with_storage(KvStorage::open(&path).unwrap())
+            .unwrap();
+
+        assert_eq!(2, restarted.block_count());
+        assert_eq!(snapshot_hash(&blockchain), snapshot_hash(&restarted));
+        assert_eq!(blockchain.chain_work(), restarted.chain_work());
+        restarted.disconnect_tip().unwrap();
+        add_block(&mut restarted, vec![coinbase(1, "Chris", 1)]);
+        assert_eq!(2, restarted.block_count());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn restart_kv_storage_after_interrupted_write() {
+        let directory = temporary_directory("restart_kv_storage_after_interrupted_write");
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("chain.kv");
+        let mut blockchain = Blockchain::new()
+            .with_storage(KvStorage::open(&path).unwrap())
+            .unwrap();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+        let stored_length = fs::metadata(&path).unwrap().len();
+        add_block(
+            &mut blockchain,
+            vec![coinbase(1, "Bob", 1), create_payment()],
         );
-        add_block_to_blockchain(&mut blockchain, genesis_block);
-        add_block_to_blockchain(&mut blockchain, block);
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(stored_length + 1)
+            .unwrap();
+
+        let restarted = Blockchain::new()
+            .with_storage(KvStorage::open(&path).unwrap())
+            .unwrap();
+
+        let mut expected = Blockchain::new();
+        add_block(&mut expected, vec![coinbase(0, "Alice", 5)]);
+        assert_eq!(1, restarted.block_count());
+        assert_eq!(snapshot_hash(&expected), snapshot_hash(&restarted));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn restart_storage_of_other_genesis_block() {
+        let directory = temporary_directory("restart_storage_of_other_genesis_block");
+        let mut blockchain = Blockchain::new()
+            .with_storage(FlatFileStorage::create(&directory).unwrap())
+            .unwrap();
+        add_block(&mut blockchain, vec![coinbase(0, "Alice", 5)]);
+
+        let result = Blockchain::with_genesis_hash(Hash256([1; 32]))
+            .with_storage(FlatFileStorage::open(&directory).unwrap());
+
+        assert_eq!(
+            Some(BlockValidationErr::MismatchedGenesisBlock),
+            result.err()
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
     }
 }
@@ -0,0 +1,536 @@
+use std::collections::HashMap;
+
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::ByteWriter;
+use crate::{u64_bytes, Block, BlockHash, HashAlgorithm, Mempool, Transaction, TransactionId};
+
+/**
+ * The number of bytes of a short transaction id.
+ */
+pub const SHORT_ID_LENGTH: usize = 6;
+
+/**
+ * The errors of rebuilding a block from a compact block.
+ */
+#[derive(Debug, PartialEq)]
+pub enum CompactBlockErr {
+    InvalidPrefilledTransaction,
+    MismatchedBlock,
+    MismatchedTransactionCount,
+    InvalidTransactionRoot,
+}
+
+/**
+ * A transaction that a compact block carries in full, at its position among
+ * the block's transactions.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrefilledTransaction {
+    pub position: u32,
+    pub transaction: Transaction,
+}
+
+/**
+ * A block announced to a peer that most likely already has its transactions
+ * in its mempool: the block's header, the transactions that the peer can't
+ * have (the coinbase transaction) and a short id of every other transaction,
+ * in block order.
+ *
+ * A short id is the first SHORT_ID_LENGTH bytes of the SHA-256 hash of the
+ * block's hash, the compact block's salt and the transaction's id (computed
+ * with the chain's transaction algorithm). The salt is chosen by the sender,
+ * so that nobody can craft transactions whose short ids collide in every
+ * compact block. A collision can still happen by chance, in which case the
+ * rebuilt block doesn't match its transaction root (see PartialBlock::fill)
+ * and the receiver falls back to asking for the full block.
+ */
+#[derive(Debug, PartialEq)]
+pub struct CompactBlock {
+    pub header: Block,
+    pub salt: u64,
+    pub short_ids: Vec<u64>,
+    pub prefilled_transactions: Vec<PrefilledTransaction>,
+}
+
+impl CompactBlock {
+    /**
+     * Creates the compact block of the block, whose transaction ids are
+     * computed with the given algorithm, with the given salt.
+     */
+    pub fn new(block: &Block, salt: u64, algorithm: HashAlgorithm) -> Self {
+        let mut short_ids = vec![];
+        let mut prefilled_transactions = vec![];
+        for (position, transaction) in block.transactions.iter().enumerate() {
+            if transaction.is_coinbase() {
+                prefilled_transactions.push(PrefilledTransaction {
+                    position: position as u32,
+                    transaction: transaction.clone(),
+                });
+            } else {
+                let id = transaction.id_with(algorithm);
+                short_ids.push(short_id(&block.hash, salt, &id));
+            }
+        }
+
+        CompactBlock {
+            header: block.header(),
+            salt,
+            short_ids,
+            prefilled_transactions,
+        }
+    }
+
+    /**
+     * Returns the number of transactions of the block.
+     */
+    pub fn transaction_count(&self) -> usize {
+        self.short_ids.len() + self.prefilled_transactions.len()
+    }
+
+    /**
+     * Rebuilds as much of the block as the mempool has: the prefilled
+     * transactions and the mempool's transactions whose short ids are in the
+     * compact block. A short id that several of the mempool's transactions
+     * have is treated as missing.
+     */
+    pub fn rebuild(&self, mempool: &Mempool) -> Result<PartialBlock, CompactBlockErr> {
+        let mut transactions: Vec<Option<Transaction>> = vec![None; self.transaction_count()];
+        for prefilled_transaction in &self.prefilled_transactions {
+            match transactions.get_mut(prefilled_transaction.position as usize) {
+                Some(slot @ None) => *slot = Some(prefilled_transaction.transaction.clone()),
+                _ => return Err(CompactBlockErr::InvalidPrefilledTransaction),
+            }
+        }
+
+        let mut candidates: HashMap<u64, Option<&Transaction>> = HashMap::new();
+        for (id, transaction) in mempool.transactions() {
+            candidates
+                .entry(short_id(&self.header.hash, self.salt, id))
+                .and_modify(|candidate| *candidate = None)
+                .or_insert(Some(transaction));
+        }
+
+        let empty_slots = transactions.iter_mut().filter(|slot| slot.is_none());
+        for (slot, short_id) in empty_slots.zip(&self.short_ids) {
+            if let Some(Some(transaction)) = candidates.get(short_id) {
+                *slot = Some((*transaction).clone());
+            }
+        }
+
+        Ok(PartialBlock {
+            header: self.header.header(),
+            transactions,
+        })
+    }
+}
+
+impl Encodable for CompactBlock {
+    /**
+     * Writes the header, the salt, the number of short ids followed by
+     * their SHORT_ID_LENGTH bytes, and the prefilled transactions.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.header.write_header_encoding(writer);
+        self.salt.write_encoding(writer);
+        (self.short_ids.len() as u32).write_encoding(writer);
+        for short_id in &self.short_ids {
+            writer.write(&u64_bytes(*short_id)[..SHORT_ID_LENGTH]);
+        }
+        (self.prefilled_transactions.len() as u32).write_encoding(writer);
+        for prefilled_transaction in &self.prefilled_transactions {
+            prefilled_transaction.position.write_encoding(writer);
+            prefilled_transaction.transaction.write_encoding(writer);
+        }
+    }
+}
+
+impl Decodable for CompactBlock {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        let header = Block::read_header_encoding(reader)?;
+        let salt = reader.read_u64()?;
+
+        let mut short_ids = vec![];
+        for _ in 0..reader.read_u32()? {
+            let mut bytes = [0; 8];
+            bytes[..SHORT_ID_LENGTH].copy_from_slice(reader.read(SHORT_ID_LENGTH)?);
+            short_ids.push(u64::from_le_bytes(bytes));
+        }
+
+        let mut prefilled_transactions = vec![];
+        for _ in 0..reader.read_u32()? {
+            prefilled_transactions.push(PrefilledTransaction {
+                position: reader.read_u32()?,
+                transaction: Transaction::read_encoding(reader)?,
+            });
+        }
+
+        Ok(CompactBlock {
+            header,
+            salt,
+            short_ids,
+            prefilled_transactions,
+        })
+    }
+}
+
+/**
+ * A block being rebuilt from a compact block: its header and its
+ * transactions, some of which may still be missing.
+ */
+#[derive(Debug, PartialEq)]
+pub struct PartialBlock {
+    pub header: Block,
+    pub transactions: Vec<Option<Transaction>>,
+}
+
+impl PartialBlock {
+    /**
+     * Returns the positions of the missing transactions.
+     */
+    pub fn missing_positions(&self) -> Vec<u32> {
+        self.transactions
+            .iter()
+            .enumerate()
+            .filter(|(_, transaction)| transaction.is_none())
+            .map(|(position, _)| position as u32)
+            .collect()
+    }
+
+    /**
+     * Returns a request for the missing transactions, to send to the peer
+     * that announced the block.
+     */
+    pub fn request(&self) -> BlockTransactionsRequest {
+        BlockTransactionsRequest {
+            block_hash: self.header.hash,
+            positions: self.missing_positions(),
+        }
+    }
+
+    /**
+     * Completes the block with the peer's answer to the request for the
+     * missing transactions (which is empty if none are missing), and checks
+     * that the transactions match the header's transaction root, computed
+     * with the given algorithm (the chain's transaction algorithm).
+     */
+    pub fn fill(
+        self,
+        missing_transactions: BlockTransactions,
+        algorithm: HashAlgorithm,
+    ) -> Result<Block, CompactBlockErr> {
+        if missing_transactions.block_hash != self.header.hash {
+            return Err(CompactBlockErr::MismatchedBlock);
+        }
+
+        let mut missing_transactions = missing_transactions.transactions.into_iter();
+        let mut transactions = vec![];
+        for transaction in self.transactions {
+            match transaction.or_else(|| missing_transactions.next()) {
+                Some(transaction) => transactions.push(transaction),
+                None => return Err(CompactBlockErr::MismatchedTransactionCount),
+            }
+        }
+        if missing_transactions.next().is_some() {
+            return Err(CompactBlockErr::MismatchedTransactionCount);
+        }
+
+        let mut block = self.header;
+        block.transactions = transactions;
+        if !block.has_valid_transaction_root(algorithm) {
+            return Err(CompactBlockErr::InvalidTransactionRoot);
+        }
+
+        Ok(block)
+    }
+}
+
+/**
+ * A request for the transactions at the given positions of a block.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockTransactionsRequest {
+    pub block_hash: BlockHash,
+    pub positions: Vec<u32>,
+}
+
+impl Encodable for BlockTransactionsRequest {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.block_hash.write_encoding(writer);
+        self.positions.write_encoding(writer);
+    }
+}
+
+impl Decodable for BlockTransactionsRequest {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(BlockTransactionsRequest {
+            block_hash: reader.read_hash()?,
+            positions: Vec::read_encoding(reader)?,
+        })
+    }
+}
+
+/**
+ * The answer to a BlockTransactionsRequest: the requested transactions, in
+ * the order of their positions.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockTransactions {
+    pub block_hash: BlockHash,
+    pub transactions: Vec<Transaction>,
+}
+
+impl BlockTransactions {
+    /**
+     * Answers the request with the block's transactions, or returns None if
+     * the request is for another block or for a position that the block
+     * doesn't have.
+     */
+    pub fn new(block: &Block, request: &BlockTransactionsRequest) -> Option<Self> {
+        if request.block_hash != block.hash {
+            return None;
+        }
+
+        let transactions = request
+            .positions
+            .iter()
+            .map(|position| block.transactions.get(*position as usize).cloned())
+            .collect::<Option<Vec<Transaction>>>()?;
+
+        Some(BlockTransactions {
+            block_hash: block.hash,
+            transactions,
+        })
+    }
+}
+
+impl Encodable for BlockTransactions {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.block_hash.write_encoding(writer);
+        self.transactions.write_encoding(writer);
+    }
+}
+
+impl Decodable for BlockTransactions {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(BlockTransactions {
+            block_hash: reader.read_hash()?,
+            transactions: Vec::read_encoding(reader)?,
+        })
+    }
+}
+
+/**
+ * Returns the short id of the transaction with the given id in the compact
+ * block of the block with the given hash and salt.
+ */
+fn short_id(block_hash: &BlockHash, salt: u64, id: &TransactionId) -> u64 {
+    let mut bytes = block_hash.as_bytes().to_vec();
+    bytes.extend_from_slice(&u64_bytes(salt));
+    bytes.extend_from_slice(id.as_bytes());
+    let hash = HashAlgorithm::Sha256.digest(&bytes);
+    let mut short_id = [0; 8];
+    short_id[..SHORT_ID_LENGTH].copy_from_slice(&hash.as_bytes()[..SHORT_ID_LENGTH]);
+
+    u64::from_le_bytes(short_id)
+}
+
+#[cfg(test)]
+mod compact_block_tests {
+    use super::{
+        BlockTransactions, BlockTransactionsRequest, CompactBlock, CompactBlockErr,
+        PrefilledTransaction,
+    };
+    use crate::encoding::{Decodable, Encodable};
+
+    use crate::test_support::{add_block, output, spend};
+    use crate::{Block, Blockchain, Hash256, HashAlgorithm, Mempool, Transaction};
+
+    /**
+     * The genesis block pays Alice the coins of every payment (1 to 5 coins,
+     * plus 1 coin of fee).
+     */
+    fn genesis_coinbase() -> Transaction {
+        Transaction::coinbase(0, (1..=5).map(|value| output("Alice", value + 1)).collect())
+    }
+
+    fn payment(value: u64) -> Transaction {
+        Transaction {
+            inputs: vec![spend(&genesis_coinbase(), value as usize - 1)],
+            outputs: vec![output("Bob", value)],
+            lock_time: None,
+        }
+    }
+
+    fn create_block() -> Block {
+        let coinbase = Transaction::coinbase(1, vec![output("Chris", 1)]);
+        let mut transactions = vec![coinbase];
+        transactions.extend((1..=4).map(payment));
+        let mut block = Block::new(1, 2, Hash256([1; 32]), transactions, 0x2100_ffff);
+        block.mine();
+
+        block
+    }
+
+    fn create_mempool(values: &[u64]) -> Mempool {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![genesis_coinbase()]);
+        let mut mempool = Mempool::new();
+        for value in values {
+            mempool
+                .add_transaction(payment(*value), &blockchain)
+                .unwrap();
+        }
+
+        mempool
+    }
+
+    #[test]
+    fn prefill_coinbase_transaction() {
+        let block = create_block();
+
+        let result = CompactBlock::new(&block, 7, HashAlgorithm::Sha256);
+
+        assert_eq!(block.header(), result.header);
+        assert_eq!(4, result.short_ids.len());
+        assert_eq!(
+            vec![PrefilledTransaction {
+                position: 0,
+                transaction: block.transactions[0].clone(),
+            }],
+            result.prefilled_transactions
+        );
+        assert_eq!(5, result.transaction_count());
+    }
+
+    #[test]
+    fn short_ids_depend_on_salt() {
+        let block = create_block();
+
+        let result = CompactBlock::new(&block, 7, HashAlgorithm::Sha256);
+
+        assert_ne!(
+            CompactBlock::new(&block, 8, HashAlgorithm::Sha256).short_ids,
+            result.short_ids
+        );
+        assert!(result.short_ids.iter().all(|short_id| *short_id < 1 << 48));
+    }
+
+    #[test]
+    fn rebuild_from_mempool() {
+        let block = create_block();
+        let compact_block = CompactBlock::new(&block, 7, HashAlgorithm::Sha256);
+        let mempool = create_mempool(&[4, 3, 2, 1, 5]);
+
+        let partial_block = compact_block.rebuild(&mempool).unwrap();
+
+        assert!(partial_block.missing_positions().is_empty());
+        let request = partial_block.request();
+        let result = partial_block.fill(
+            BlockTransactions {
+                block_hash: request.block_hash,
+                transactions: vec![],
+            },
+            HashAlgorithm::Sha256,
+        );
+        assert_eq!(Ok(block), result);
+    }
+
+    #[test]
+    fn rebuild_with_missing_transactions() {
+        let block = create_block();
+        let compact_block = CompactBlock::new(&block, 7, HashAlgorithm::Sha256);
+        let mempool = create_mempool(&[1, 3]);
+
+        let partial_block = compact_block.rebuild(&mempool).unwrap();
+        let request = partial_block.request();
+        let missing_transactions = BlockTransactions::new(&block, &request).unwrap();
+
+        assert_eq!(
+            BlockTransactionsRequest {
+                block_hash: block.hash,
+                positions: vec![2, 4],
+            },
+            request
+        );
+        assert_eq!(
+            vec![payment(2), payment(4)],
+            missing_transactions.transactions
+        );
+        assert_eq!(
+            Ok(block),
+            partial_block.fill(missing_transactions, HashAlgorithm::Sha256)
+        );
+    }
+
+    #[test]
+    fn reject_wrong_missing_transactions() {
+        let block = create_block();
+        let compact_block = CompactBlock::new(&block, 7, HashAlgorithm::Sha256);
+        let mempool = create_mempool(&[1, 2, 3]);
+        let fill = |transactions| {
+            compact_block.rebuild(&mempool).unwrap().fill(
+                BlockTransactions {
+                    block_hash: block.hash,
+                    transactions,
+                },
+                HashAlgorithm::Sha256,
+            )
+        };
+
+        assert_eq!(
+            Err(CompactBlockErr::MismatchedTransactionCount),
+            fill(vec![])
+        );
+        assert_eq!(
+            Err(CompactBlockErr::MismatchedTransactionCount),
+            fill(vec![payment(4), payment(5)])
+        );
+        assert_eq!(
+            Err(CompactBlockErr::InvalidTransactionRoot),
+            fill(vec![payment(5)])
+        );
+        assert_eq!(
+            Err(CompactBlockErr::MismatchedBlock),
+            compact_block.rebuild(&mempool).unwrap().fill(
+                BlockTransactions {
+                    block_hash: Hash256::ZERO,
+                    transactions: vec![payment(4)],
+                },
+                HashAlgorithm::Sha256,
+            )
+        );
+    }
+
+    #[test]
+    fn reject_invalid_prefilled_position() {
+        let block = create_block();
+        let mut compact_block = CompactBlock::new(&block, 7, HashAlgorithm::Sha256);
+        compact_block.prefilled_transactions[0].position = 5;
+
+        assert_eq!(
+            Err(CompactBlockErr::InvalidPrefilledTransaction),
+            compact_block.rebuild(&Mempool::new())
+        );
+    }
+
+    #[test]
+    fn no_transactions_for_unknown_positions() {
+        let block = create_block();
+        let request = BlockTransactionsRequest {
+            block_hash: block.hash,
+            positions: vec![5],
+        };
+
+        assert_eq!(None, BlockTransactions::new(&block, &request));
+    }
+
+    #[test]
+    fn round_trip() {
+        let block = create_block();
+        let compact_block = CompactBlock::new(&block, 7, HashAlgorithm::Sha256);
+
+        let result = CompactBlock::decode(&compact_block.encode());
+
+        assert_eq!(Ok(compact_block), result);
+    }
+}
@@ -0,0 +1,525 @@
+use k256::ecdsa::signature::{Signer, Verifier};
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use primitive_types::U256;
+
+use crate::blockchain::BlockValidationErr;
+use crate::{check_difficulty, Block, BlockHash, HashAlgorithm, Hashable, MemoryHardFunction};
+
+#[derive(Debug, PartialEq)]
+pub enum SealErr {
+    InvalidTarget,
+    InvalidParameters,
+    MissingSigningKey,
+    NotInTurn,
+}
+
+/**
+ * A consensus engine decides who may produce blocks and how a block proves
+ * that it was produced by them:
+ *
+ * - Sealing: Producing the block's hash and whatever proof the engine
+ *   requires (a nonce, a signature).
+ *
+ * - Seal verification: Checking that proof when a block is added to a chain.
+ *
+ * - Work: The weight that the block adds to the chain, which is what nodes
+ *   compare to pick the best chain.
+ *
+ * A chain is configured with one engine (proof-of-work by default), and every
+ * node of the chain must use the same one.
+ */
+pub trait ConsensusEngine {
+    /**
+     * Seals the block: sets its hash, computed with the given algorithm, and
+     * the proof that the engine requires.
+     */
+    fn seal(&self, block: &mut Block, algorithm: HashAlgorithm) -> Result<(), SealErr>;
+
+    /**
+     * Verifies the seal of the block, whose hash (computed with the chain's
+     * block hash algorithm) is the given one.
+     */
+    fn verify_seal(&self, block: &Block, block_hash: &BlockHash) -> Result<(), BlockValidationErr>;
+
+    /**
+     * Returns the work that the block adds to the chain.
+     */
+    fn work(&self, block: &Block) -> U256;
+}
+
+/**
+ * Proof-of-work: a block is sealed by mining it, that is, by finding a nonce
+ * that makes its hash meet its target (see Block::mine). The work of a block
+ * is the expected number of hashes it took to find it.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProofOfWork;
+
+impl ConsensusEngine for ProofOfWork {
+    fn seal(&self, block: &mut Block, algorithm: HashAlgorithm) -> Result<(), SealErr> {
+        if block.target().is_none() {
+            return Err(SealErr::InvalidTarget);
+        }
+
+        block.mine_with(algorithm);
+
+        Ok(())
+    }
+
+    fn verify_seal(&self, block: &Block, block_hash: &BlockHash) -> Result<(), BlockValidationErr> {
+        if check_difficulty(block_hash, block.bits) {
+            Ok(())
+        } else {
+            Err(BlockValidationErr::InvalidHash)
+        }
+    }
+
+    fn work(&self, block: &Block) -> U256 {
+        block.work()
+    }
+}
+
+/**
+ * Memory-hard proof-of-work: like proof-of-work, but the hash that must meet
+ * the target is the result of a memory-hard function (see MemoryHardFunction)
+ * applied to the block's hash, rather than the block's hash itself. The block's
+ * hash still identifies the block and links the chain.
+ *
+ * Each nonce attempt costs one evaluation of the function, and so does
+ * verifying a block's seal.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryHardProofOfWork {
+    pub function: MemoryHardFunction,
+}
+
+impl MemoryHardProofOfWork {
+    pub fn new(function: MemoryHardFunction) -> Self {
+        MemoryHardProofOfWork { function }
+    }
+}
+
+impl ConsensusEngine for MemoryHardProofOfWork {
+    fn seal(&self, block: &mut Block, algorithm: HashAlgorithm) -> Result<(), SealErr> {
+        let target = block.target().ok_or(SealErr::InvalidTarget)?;
+        let mining_state = block.mining_state(algorithm);
+
+        for nonce_attempt in 0..(u64::MAX) {
+            let hash = mining_state.hash_with_nonce(nonce_attempt);
+            let pow_hash = self
+                .function
+                .hash(&hash)
+                .ok_or(SealErr::InvalidParameters)?;
+            if target.is_met_by(&pow_hash) {
+                block.nonce = nonce_attempt;
+                block.hash = hash;
+
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn verify_seal(&self, block: &Block, block_hash: &BlockHash) -> Result<(), BlockValidationErr> {
+        if self.function.check_difficulty(block_hash, block.bits) {
+            Ok(())
+        } else {
+            Err(BlockValidationErr::InvalidHash)
+        }
+    }
+
+    fn work(&self, block: &Block) -> U256 {
+        block.work()
+    }
+}
+
+/**
+ * Proof-of-authority: blocks are signed by a configured set of validators,
+ * which take turns in round-robin order. The block at index i must be signed
+ * by validator i mod n, where n is the number of validators. Nothing is mined,
+ * so the target and the nonce of a block are ignored.
+ *
+ * The seal of a block is the validator's ECDSA (secp256k1) signature of the
+ * block's hash. The genesis block isn't signed: the chain is identified by its
+ * hash instead (see GenesisConfig).
+ *
+ * Every block adds the same work, so the best chain is the longest one.
+ *
+ * A node that only verifies blocks needs the validators' public keys; a
+ * validator also needs its signing key to seal blocks.
+ */
+pub struct ProofOfAuthority {
+    validators: Vec<VerifyingKey>,
+    signing_key: Option<SigningKey>,
+}
+
+impl ProofOfAuthority {
+    /**
+     * Creates an engine that verifies blocks signed by the given validators.
+     */
+    pub fn new(validators: Vec<VerifyingKey>) -> Self {
+        ProofOfAuthority {
+            validators,
+            signing_key: None,
+        }
+    }
+
+    /**
+     * Creates an engine that verifies blocks signed by the given validators
+     * and seals blocks with the given signing key when it's that key's turn.
+     */
+    pub fn with_signing_key(validators: Vec<VerifyingKey>, signing_key: SigningKey) -> Self {
+        ProofOfAuthority {
+            validators,
+            signing_key: Some(signing_key),
+        }
+    }
+
+    /**
+     * Returns the validators in round-robin order.
+     */
+    pub fn validators(&self) -> &[VerifyingKey] {
+        &self.validators
+    }
+
+    /**
+     * Returns the validator whose turn it is to sign the block at the given
+     * index, or None if there are no validators.
+     */
+    pub fn validator_for(&self, index: u32) -> Option<&VerifyingKey> {
+        if self.validators.is_empty() {
+            return None;
+        }
+
+        self.validators.get(index as usize % self.validators.len())
+    }
+}
+
+impl ConsensusEngine for ProofOfAuthority {
+    fn seal(&self, block: &mut Block, algorithm: HashAlgorithm) -> Result<(), SealErr> {
+        block.hash = block.hash_with(algorithm);
+        block.seal = vec![];
+        if block.index == 0 {
+            return Ok(());
+        }
+
+        let signing_key = self
+            .signing_key
+            .as_ref()
+            .ok_or(SealErr::MissingSigningKey)?;
+        if self.validator_for(block.index) != Some(signing_key.verifying_key()) {
+            return Err(SealErr::NotInTurn);
+        }
+
+        let signature: Signature = signing_key.sign(block.hash.as_bytes());
+        block.seal = signature.to_bytes().to_vec();
+
+        Ok(())
+    }
+
+    fn verify_seal(&self, block: &Block, block_hash: &BlockHash) -> Result<(), BlockValidationErr> {
+        if block.index == 0 {
+            return Ok(());
+        }
+
+        let validator = self
+            .validator_for(block.index)
+            .ok_or(BlockValidationErr::InvalidSeal)?;
+        let signature =
+            Signature::from_slice(&block.seal).map_err(|_| BlockValidationErr::InvalidSeal)?;
+
+        validator
+            .verify(block_hash.as_bytes(), &signature)
+            .map_err(|_| BlockValidationErr::InvalidSeal)
+    }
+
+    fn work(&self, _block: &Block) -> U256 {
+        U256::one()
+    }
+}
+
+#[cfg(test)]
+mod proof_of_work_tests {
+    use primitive_types::U256;
+
+    use super::{ConsensusEngine, ProofOfWork, SealErr};
+    use crate::blockchain::BlockValidationErr;
+    use crate::{Block, Hash256, HashAlgorithm, Hashable};
+
+    const BITS: u32 = 0x2000_ffff;
+
+    fn create_block(bits: u32) -> Block {
+        Block::new(1, 2, Hash256([3; 32]), vec![], bits)
+    }
+
+    #[test]
+    fn seal() {
+        let mut block = create_block(BITS);
+
+        let result = ProofOfWork.seal(&mut block, HashAlgorithm::Blake3);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(block.hash_with(HashAlgorithm::Blake3), block.hash);
+        assert!(block.seal.is_empty());
+        assert_eq!(Ok(()), ProofOfWork.verify_seal(&block, &block.hash));
+    }
+
+    #[test]
+    fn seal_with_invalid_bits() {
+        let mut block = create_block(0x2101_0000);
+
+        let result = ProofOfWork.seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Err(SealErr::InvalidTarget), result);
+        assert_eq!(Hash256::ZERO, block.hash);
+    }
+
+    #[test]
+    fn verify_seal_with_hash_above_target() {
+        let block = create_block(0x0201_0000);
+
+        let result = ProofOfWork.verify_seal(&block, &Hash256([0xff; 32]));
+
+        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+    }
+
+    #[test]
+    fn work() {
+        let block = create_block(0x1d00_ffff);
+
+        let result = ProofOfWork.work(&block);
+
+        assert_eq!(U256::from(0x0001_0001_0001_u64), result);
+    }
+}
+
+#[cfg(test)]
+mod memory_hard_proof_of_work_tests {
+    use super::{ConsensusEngine, MemoryHardProofOfWork, SealErr};
+    use crate::blockchain::BlockValidationErr;
+    use crate::{check_difficulty, Block, Hash256, HashAlgorithm, Hashable, MemoryHardFunction};
+
+    const BITS: u32 = 0x2000_ffff;
+
+    fn engine() -> MemoryHardProofOfWork {
+        MemoryHardProofOfWork::new(MemoryHardFunction::Argon2id {
+            memory_kib: 8,
+            iterations: 1,
+            parallelism: 1,
+        })
+    }
+
+    fn create_block(bits: u32) -> Block {
+        Block::new(1, 2, Hash256([3; 32]), vec![], bits)
+    }
+
+    #[test]
+    fn seal() {
+        let mut block = create_block(BITS);
+
+        let result = engine().seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(block.hash(), block.hash);
+        let pow_hash = engine().function.hash(&block.hash).unwrap();
+        assert!(check_difficulty(&pow_hash, BITS));
+        assert_eq!(Ok(()), engine().verify_seal(&block, &block.hash));
+    }
+
+    #[test]
+    fn seal_with_invalid_bits() {
+        let mut block = create_block(0x2101_0000);
+
+        let result = engine().seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Err(SealErr::InvalidTarget), result);
+    }
+
+    #[test]
+    fn seal_with_invalid_parameters() {
+        let engine = MemoryHardProofOfWork::new(MemoryHardFunction::Argon2id {
+            memory_kib: 0,
+            iterations: 1,
+            parallelism: 1,
+        });
+        let mut block = create_block(BITS);
+
+        let result = engine.seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Err(SealErr::InvalidParameters), result);
+        assert_eq!(Hash256::ZERO, block.hash);
+    }
+
+    #[test]
+    fn verify_seal_of_block_mined_with_plain_hash() {
+        let mut block = create_block(BITS);
+        block.mine();
+        while engine().function.check_difficulty(&block.hash, BITS) {
+            block.timestamp += 1;
+            block.mine();
+        }
+
+        let result = engine().verify_seal(&block, &block.hash);
+
+        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+    }
+
+    #[test]
+    fn verify_seal_with_other_parameters() {
+        let mut block = create_block(BITS);
+        engine().seal(&mut block, HashAlgorithm::Sha256).unwrap();
+        let other_engine = MemoryHardProofOfWork::new(MemoryHardFunction::Argon2id {
+            memory_kib: 16,
+            iterations: 1,
+            parallelism: 1,
+        });
+
+        let result = other_engine.verify_seal(&block, &block.hash);
+
+        // The other function's result meets the target with a probability of
+        // 1 in 256 (and deterministically doesn't for this block).
+        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+    }
+}
+
+#[cfg(test)]
+mod proof_of_authority_tests {
+    use k256::ecdsa::signature::Signer;
+    use k256::ecdsa::{Signature, VerifyingKey};
+    use primitive_types::U256;
+
+    use super::{ConsensusEngine, ProofOfAuthority, SealErr};
+    use crate::blockchain::BlockValidationErr;
+    use crate::test_support::signing_key;
+    use crate::{Block, Hash256, HashAlgorithm, Hashable};
+
+    fn validators() -> Vec<VerifyingKey> {
+        (1..=3)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect()
+    }
+
+    fn create_block(index: u32) -> Block {
+        Block::new(index, 2, Hash256([3; 32]), vec![], 0)
+    }
+
+    #[test]
+    fn validator_for() {
+        let engine = ProofOfAuthority::new(validators());
+
+        assert_eq!(Some(&validators()[0]), engine.validator_for(0));
+        assert_eq!(Some(&validators()[1]), engine.validator_for(1));
+        assert_eq!(Some(&validators()[2]), engine.validator_for(2));
+        assert_eq!(Some(&validators()[0]), engine.validator_for(3));
+    }
+
+    #[test]
+    fn validator_for_without_validators() {
+        let engine = ProofOfAuthority::new(vec![]);
+
+        assert_eq!(None, engine.validator_for(1));
+    }
+
+    #[test]
+    fn seal_in_turn() {
+        let engine = ProofOfAuthority::with_signing_key(validators(), signing_key(2));
+        let mut block = create_block(4);
+
+        let result = engine.seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(block.hash(), block.hash);
+        assert_eq!(64, block.seal.len());
+        assert_eq!(
+            Ok(()),
+            ProofOfAuthority::new(validators()).verify_seal(&block, &block.hash)
+        );
+    }
+
+    #[test]
+    fn seal_out_of_turn() {
+        let engine = ProofOfAuthority::with_signing_key(validators(), signing_key(2));
+        let mut block = create_block(3);
+
+        let result = engine.seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Err(SealErr::NotInTurn), result);
+    }
+
+    #[test]
+    fn seal_by_non_validator() {
+        let engine = ProofOfAuthority::with_signing_key(validators(), signing_key(4));
+        let mut block = create_block(1);
+
+        let result = engine.seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Err(SealErr::NotInTurn), result);
+    }
+
+    #[test]
+    fn seal_without_signing_key() {
+        let engine = ProofOfAuthority::new(validators());
+        let mut block = create_block(1);
+
+        let result = engine.seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Err(SealErr::MissingSigningKey), result);
+    }
+
+    #[test]
+    fn seal_genesis_block() {
+        let engine = ProofOfAuthority::new(validators());
+        let mut block = create_block(0);
+
+        let result = engine.seal(&mut block, HashAlgorithm::Sha256);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(block.hash(), block.hash);
+        assert!(block.seal.is_empty());
+        assert_eq!(Ok(()), engine.verify_seal(&block, &block.hash));
+    }
+
+    #[test]
+    fn verify_seal_without_signature() {
+        let engine = ProofOfAuthority::new(validators());
+        let block = create_block(1);
+
+        let result = engine.verify_seal(&block, &block.hash());
+
+        assert_eq!(Err(BlockValidationErr::InvalidSeal), result);
+    }
+
+    #[test]
+    fn verify_seal_signed_out_of_turn() {
+        let mut block = create_block(2);
+        block.hash = block.hash();
+        let signature: Signature = signing_key(2).sign(block.hash.as_bytes());
+        block.seal = signature.to_bytes().to_vec();
+
+        let result = ProofOfAuthority::new(validators()).verify_seal(&block, &block.hash);
+
+        assert_eq!(Err(BlockValidationErr::InvalidSeal), result);
+    }
+
+    #[test]
+    fn verify_seal_of_tampered_block() {
+        let engine = ProofOfAuthority::with_signing_key(validators(), signing_key(2));
+        let mut block = create_block(1);
+        engine.seal(&mut block, HashAlgorithm::Sha256).unwrap();
+        block.timestamp += 1;
+
+        let result = engine.verify_seal(&block, &block.hash());
+
+        assert_eq!(Err(BlockValidationErr::InvalidSeal), result);
+    }
+
+    #[test]
+    fn work() {
+        let engine = ProofOfAuthority::new(validators());
+
+        let result = engine.work(&create_block(1));
+
+        assert_eq!(U256::one(), result);
+    }
+}
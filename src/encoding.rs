@@ -0,0 +1,352 @@
+use primitive_types::U256;
+
+use crate::hashable::ByteWriter;
+use crate::{u128_bytes, u32_bytes, u64_bytes, Hash256};
+
+/**
+ * Errors that may occur when decoding an encoded instance.
+ */
+#[derive(Debug, PartialEq)]
+pub enum DecodeErr {
+    UnexpectedEnd,
+    InvalidTag,
+    InvalidString,
+    InvalidChecksum,
+    TrailingBytes,
+}
+
+/**
+ * Encodable instances write a binary encoding of themselves, which (unlike
+ * their hashable bytes) can be decoded back: variable-length data is
+ * prefixed with its length and optional data with a tag byte. It is the
+ * format that blocks and chain state are stored on disk in, and the one that
+ * transaction ids are the hash of. Like Hashable, the writer is a type
+ * parameter, so hashing an encoding calls the hasher directly.
+ */
+pub trait Encodable {
+    /**
+     * Writes the encoding of the encodable instance.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W);
+
+    /**
+     * Returns a vector of the encoding of the encodable instance.
+     */
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_encoding(&mut bytes);
+
+        bytes
+    }
+}
+
+/**
+ * Decodable instances read themselves from the encoding that Encodable
+ * writes.
+ */
+pub trait Decodable: Sized {
+    /**
+     * Reads a decodable instance from the reader.
+     */
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr>;
+
+    /**
+     * Decodes an instance from bytes that contain exactly its encoding.
+     */
+    fn decode(bytes: &[u8]) -> Result<Self, DecodeErr> {
+        let mut reader = ByteReader::new(bytes);
+        let decoded = Self::read_encoding(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(DecodeErr::TrailingBytes);
+        }
+
+        Ok(decoded)
+    }
+}
+
+/**
+ * A cursor over encoded bytes, which reads them in the order they were
+ * written.
+ */
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes }
+    }
+
+    /**
+     * Returns a flag that states whether every byte has been read.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /**
+     * Returns the number of bytes that haven't been read yet.
+     */
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /**
+     * Reads the given number of bytes.
+     */
+    pub fn read(&mut self, length: usize) -> Result<&'a [u8], DecodeErr> {
+        if self.bytes.len() < length {
+            return Err(DecodeErr::UnexpectedEnd);
+        }
+
+        let (read, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+
+        Ok(read)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, DecodeErr> {
+        Ok(self.read(1)?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, DecodeErr> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.read(4)?);
+
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DecodeErr> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.read(8)?);
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn read_u128(&mut self) -> Result<u128, DecodeErr> {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(self.read(16)?);
+
+        Ok(u128::from_le_bytes(bytes))
+    }
+
+    pub fn read_hash(&mut self) -> Result<Hash256, DecodeErr> {
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(self.read(32)?);
+
+        Ok(Hash256(bytes))
+    }
+
+    /**
+     * Reads bytes that are prefixed with their length (4 bytes).
+     */
+    pub fn read_length_prefixed(&mut self) -> Result<&'a [u8], DecodeErr> {
+        let length = self.read_u32()? as usize;
+
+        self.read(length)
+    }
+}
+
+impl Encodable for u32 {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u32_bytes(*self));
+    }
+}
+
+impl Decodable for u32 {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        reader.read_u32()
+    }
+}
+
+impl Encodable for u64 {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u64_bytes(*self));
+    }
+}
+
+impl Decodable for u64 {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        reader.read_u64()
+    }
+}
+
+impl Encodable for u128 {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u128_bytes(*self));
+    }
+}
+
+impl Decodable for u128 {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        reader.read_u128()
+    }
+}
+
+impl Encodable for Hash256 {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(self.as_bytes());
+    }
+}
+
+impl Decodable for Hash256 {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        reader.read_hash()
+    }
+}
+
+impl Encodable for U256 {
+    /**
+     * Writes the number's 32 bytes in little-endian order.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        let mut bytes = [0; 32];
+        self.to_little_endian(&mut bytes);
+        writer.write(&bytes);
+    }
+}
+
+impl Decodable for U256 {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(U256::from_little_endian(reader.read(32)?))
+    }
+}
+
+impl Encodable for String {
+    /**
+     * Writes the string's length (4 bytes) followed by its UTF-8 bytes.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u32_bytes(self.len() as u32));
+        writer.write(self.as_bytes());
+    }
+}
+
+impl Decodable for String {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        let bytes = reader.read_length_prefixed()?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeErr::InvalidString)
+    }
+}
+
+impl<T: Encodable> Encodable for Vec<T> {
+    /**
+     * Writes the number of elements (4 bytes) followed by each element.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u32_bytes(self.len() as u32));
+        for element in self {
+            element.write_encoding(writer);
+        }
+    }
+}
+
+impl<T: Decodable> Decodable for Vec<T> {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        let length = reader.read_u32()?;
+
+        // The length isn't trusted to preallocate, since corrupt data could
+        // claim billions of elements.
+        let mut elements = vec![];
+        for _ in 0..length {
+            elements.push(T::read_encoding(reader)?);
+        }
+
+        Ok(elements)
+    }
+}
+
+impl<T: Encodable> Encodable for Option<T> {
+    /**
+     * Writes a tag byte (0 for none, 1 for some) followed by the value, if
+     * there is one.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        match self {
+            None => writer.write(&[0]),
+            Some(value) => {
+                writer.write(&[1]);
+                value.write_encoding(writer);
+            }
+        }
+    }
+}
+
+impl<T: Decodable> Decodable for Option<T> {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        match reader.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(T::read_encoding(reader)?)),
+            _ => Err(DecodeErr::InvalidTag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::{ByteReader, Decodable, DecodeErr, Encodable};
+    use crate::Hash256;
+
+    #[test]
+    fn round_trip() {
+        let value = (
+            vec!["Alice".to_owned(), "Bob".to_owned()],
+            Some(7_u64),
+            None::<u32>,
+            Hash256([3; 32]),
+        );
+        let mut bytes = value.0.encode();
+        bytes.extend(value.1.encode());
+        bytes.extend(value.2.encode());
+        bytes.extend(value.3.encode());
+
+        let mut reader = ByteReader::new(&bytes);
+
+        assert_eq!(value.0, Vec::<String>::read_encoding(&mut reader).unwrap());
+        assert_eq!(value.1, Option::<u64>::read_encoding(&mut reader).unwrap());
+        assert_eq!(value.2, Option::<u32>::read_encoding(&mut reader).unwrap());
+        assert_eq!(value.3, Hash256::read_encoding(&mut reader).unwrap());
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn string_encoding() {
+        let result = "Bob".to_owned().encode();
+
+        assert_eq!(vec![3, 0, 0, 0, b'B', b'o', b'b'], result);
+    }
+
+    #[test]
+    fn decode_truncated() {
+        let bytes = "Alice".to_owned().encode();
+
+        let result = String::decode(&bytes[..bytes.len() - 1]);
+
+        assert_eq!(Err(DecodeErr::UnexpectedEnd), result);
+    }
+
+    #[test]
+    fn decode_with_trailing_bytes() {
+        let mut bytes = 7_u32.encode();
+        bytes.push(0);
+
+        let result = u32::decode(&bytes);
+
+        assert_eq!(Err(DecodeErr::TrailingBytes), result);
+    }
+
+    #[test]
+    fn decode_invalid_tag() {
+        let result = Option::<u32>::decode(&[2, 0, 0, 0, 0]);
+
+        assert_eq!(Err(DecodeErr::InvalidTag), result);
+    }
+
+    #[test]
+    fn decode_invalid_string() {
+        let result = String::decode(&[2, 0, 0, 0, 0xff, 0xfe]);
+
+        assert_eq!(Err(DecodeErr::InvalidString), result);
+    }
+}
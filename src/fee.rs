@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+
+use crate::Transaction;
+
+/**
+ * The fee rate below which a mempool doesn't accept transactions by default:
+ * 1 coin per 1,000 bytes.
+ */
+pub const DEFAULT_MIN_RELAY_FEE_RATE: FeeRate = FeeRate(1);
+
+/**
+ * The number of recent blocks that a fee estimator looks at by default.
+ */
+pub const DEFAULT_FEE_ESTIMATE_BLOCK_COUNT: usize = 100;
+
+/**
+ * A fee rate in coins per 1,000 bytes of a transaction's serialized size (the
+ * size of its canonical encoding, see Transaction::size).
+ *
+ * What a transaction costs the nodes that relay, validate and store it (and
+ * the block space it takes) grows with its size rather than with its value,
+ * so fees are compared per byte: mempools and miners order transactions by
+ * their fee rates, not by their fees.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FeeRate(pub u64);
+
+impl FeeRate {
+    /**
+     * Returns the fee rate of a transaction of the given size that pays the
+     * given fee, rounded down.
+     */
+    pub fn of(fee: u64, size: usize) -> Self {
+        if size == 0 {
+            return FeeRate(u64::MAX);
+        }
+
+        FeeRate((u128::from(fee) * 1_000 / size as u128).min(u128::from(u64::MAX)) as u64)
+    }
+
+    /**
+     * Returns the fee that a transaction of the given size pays at this fee
+     * rate, rounded up, so that paying it never falls short of the rate.
+     */
+    pub fn fee(&self, size: usize) -> u64 {
+        let fee = (u128::from(self.0) * size as u128).div_ceil(1_000);
+
+        fee.min(u128::from(u64::MAX)) as u64
+    }
+}
+
+/**
+ * Suggests fee rates from the transactions of recently confirmed blocks.
+ *
+ * For every block, the estimator records the lowest fee rate among the
+ * block's transactions (besides the coinbase), which is what it took to get
+ * into that block; a block without other transactions would have taken any
+ * transaction, so it records the minimum fee rate. A transaction that waits
+ * is confirmed by the first block whose lowest fee rate it meets, so the
+ * estimate for a confirmation within N blocks is the lowest fee rate that
+ * would have been confirmed within N blocks from any point of the recent
+ * blocks. The fewer blocks a transaction can wait, the higher the estimate.
+ *
+ * Estimates are never below the minimum fee rate, so transactions that pay
+ * them are accepted by mempools that use the same minimum.
+ */
+#[derive(Debug)]
+pub struct FeeEstimator {
+    min_fee_rate: FeeRate,
+    max_block_count: usize,
+    block_fee_rates: VecDeque<FeeRate>,
+}
+
+impl Default for FeeEstimator {
+    fn default() -> Self {
+        FeeEstimator::new()
+    }
+}
+
+impl FeeEstimator {
+    pub fn new() -> Self {
+        FeeEstimator {
+            min_fee_rate: DEFAULT_MIN_RELAY_FEE_RATE,
+            max_block_count: DEFAULT_FEE_ESTIMATE_BLOCK_COUNT,
+            block_fee_rates: VecDeque::new(),
+        }
+    }
+
+    /**
+     * Returns the estimator with the given minimum fee rate, which is what it
+     * suggests when there's no competition for block space.
+     */
+    pub fn with_min_fee_rate(mut self, min_fee_rate: FeeRate) -> Self {
+        self.min_fee_rate = min_fee_rate;
+
+        self
+    }
+
+    /**
+     * Returns the estimator with the given number of recent blocks to look
+     * at (at least 1).
+     */
+    pub fn with_max_block_count(mut self, max_block_count: usize) -> Self {
+        self.max_block_count = max_block_count.max(1);
+        while self.block_fee_rates.len() > self.max_block_count {
+            self.block_fee_rates.pop_front();
+        }
+
+        self
+    }
+
+    pub fn min_fee_rate(&self) -> FeeRate {
+        self.min_fee_rate
+    }
+
+    /**
+     * Returns the number of recent blocks that the estimator has recorded.
+     */
+    pub fn block_count(&self) -> usize {
+        self.block_fee_rates.len()
+    }
+
+    /**
+     * Records the lowest fee rate of the transactions of a block that was
+     * connected to the chain, and forgets the oldest block if the estimator
+     * has recorded too many. Connecting the block validated the values that
+     * its transactions' inputs claim (see OutPoint::output_hash), so their
+     * fees are the ones they really paid; transactions that no chain has
+     * connected must not be recorded.
+     */
+    pub fn add_confirmed_transactions(&mut self, transactions: &[Transaction]) {
+        let fee_rate = transactions
+            .iter()
+            .filter(|transaction| !transaction.is_coinbase())
+            .map(|transaction| transaction.fee_rate())
+            .min()
+            .unwrap_or(self.min_fee_rate);
+
+        if self.block_fee_rates.len() == self.max_block_count {
+            self.block_fee_rates.pop_front();
+        }
+        self.block_fee_rates.push_back(fee_rate);
+    }
+
+    /**
+     * Returns the fee rate that a transaction needs to pay to be confirmed
+     * within the given number of blocks (at least 1), or the minimum fee rate
+     * if the estimator hasn't recorded any block.
+     */
+    pub fn estimate_fee_rate(&self, target_block_count: usize) -> FeeRate {
+        let fee_rates: Vec<FeeRate> = self.block_fee_rates.iter().copied().collect();
+        let window = target_block_count.clamp(1, fee_rates.len().max(1));
+
+        fee_rates
+            .windows(window)
+            .filter_map(|blocks| blocks.iter().min().copied())
+            .max()
+            .unwrap_or(self.min_fee_rate)
+            .max(self.min_fee_rate)
+    }
+}
+
+#[cfg(test)]
+mod fee_rate_tests {
+    use super::FeeRate;
+
+    #[test]
+    fn of() {
+        assert_eq!(FeeRate(2_000), FeeRate::of(500, 250));
+        assert_eq!(FeeRate(3), FeeRate::of(1, 333));
+        assert_eq!(FeeRate(0), FeeRate::of(0, 250));
+        assert_eq!(FeeRate(u64::MAX), FeeRate::of(1, 0));
+    }
+
+    #[test]
+    fn fee() {
+        assert_eq!(500, FeeRate(2_000).fee(250));
+        assert_eq!(1, FeeRate(3).fee(333));
+        assert_eq!(2, FeeRate(3).fee(334));
+        assert_eq!(0, FeeRate(0).fee(250));
+        assert_eq!(u64::MAX, FeeRate(u64::MAX).fee(usize::MAX));
+    }
+
+    #[test]
+    fn fee_meets_rate() {
+        for size in 1..2_000 {
+            let fee_rate = FeeRate(7);
+
+            assert!(FeeRate::of(fee_rate.fee(size), size) >= fee_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod fee_estimator_tests {
+    use super::{FeeEstimator, FeeRate, DEFAULT_MIN_RELAY_FEE_RATE};
+
+    use crate::test_support::{outpoint, output};
+    use crate::transaction::Input;
+    use crate::Transaction;
+
+    /**
+     * Returns the transactions of a block whose payments pay the given fees.
+     */
+    fn block_transactions(fees: &[u64]) -> Vec<Transaction> {
+        let mut transactions = vec![Transaction::coinbase(1, vec![output("Miner", 50)])];
+        transactions.extend(fees.iter().enumerate().map(|(index, fee)| Transaction {
+            inputs: vec![Input::new(
+                outpoint(index as u32),
+                output("Alice", 1_000 + fee),
+            )],
+            outputs: vec![output("Bob", 1_000)],
+            lock_time: None,
+        }));
+
+        transactions
+    }
+
+    fn fee_rate(fee: u64) -> FeeRate {
+        block_transactions(&[fee])[1].fee_rate()
+    }
+
+    fn create_estimator(block_fees: &[&[u64]]) -> FeeEstimator {
+        let mut estimator = FeeEstimator::new();
+        for fees in block_fees {
+            estimator.add_confirmed_transactions(&block_transactions(fees));
+        }
+
+        estimator
+    }
+
+    #[test]
+    fn estimate_without_blocks() {
+        let estimator = FeeEstimator::new().with_min_fee_rate(FeeRate(5));
+
+        assert_eq!(0, estimator.block_count());
+        assert_eq!(FeeRate(5), estimator.estimate_fee_rate(1));
+    }
+
+    #[test]
+    fn estimate_is_lowest_fee_rate_of_block() {
+        let estimator = create_estimator(&[&[40, 10, 30]]);
+
+        assert_eq!(fee_rate(10), estimator.estimate_fee_rate(1));
+        assert_eq!(fee_rate(10), estimator.estimate_fee_rate(0));
+        assert_eq!(fee_rate(10), estimator.estimate_fee_rate(6));
+    }
+
+    #[test]
+    fn estimate_decreases_with_target() {
+        let estimator = create_estimator(&[&[20], &[50], &[10], &[60], &[30]]);
+
+        assert_eq!(fee_rate(60), estimator.estimate_fee_rate(1));
+        assert_eq!(fee_rate(30), estimator.estimate_fee_rate(2));
+        assert_eq!(fee_rate(10), estimator.estimate_fee_rate(3));
+        assert_eq!(fee_rate(10), estimator.estimate_fee_rate(5));
+    }
+
+    #[test]
+    fn blocks_without_payments_take_any_fee_rate() {
+        let estimator = create_estimator(&[&[50], &[], &[50]]);
+
+        assert_eq!(fee_rate(50), estimator.estimate_fee_rate(1));
+        assert_eq!(DEFAULT_MIN_RELAY_FEE_RATE, estimator.estimate_fee_rate(2));
+    }
+
+    #[test]
+    fn estimate_is_at_least_min_fee_rate() {
+        let mut estimator = FeeEstimator::new().with_min_fee_rate(FeeRate(1_000));
+        estimator.add_confirmed_transactions(&block_transactions(&[0]));
+
+        assert_eq!(FeeRate(1_000), estimator.estimate_fee_rate(1));
+        assert_eq!(FeeRate(1_000), estimator.min_fee_rate());
+    }
+
+    #[test]
+    fn forget_oldest_blocks() {
+        let mut estimator = create_estimator(&[&[90], &[20], &[30]]).with_max_block_count(2);
+
+        assert_eq!(2, estimator.block_count());
+        assert_eq!(fee_rate(30), estimator.estimate_fee_rate(1));
+
+        estimator.add_confirmed_transactions(&block_transactions(&[40]));
+
+        assert_eq!(2, estimator.block_count());
+        assert_eq!(fee_rate(40), estimator.estimate_fee_rate(1));
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use primitive_types::U256;
+
+use crate::block_files::sync_directory;
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::storage::{apply_changes, missing_undo_data, ChainStorage};
+use crate::{u32_bytes, Block, BlockFiles, BlockUndo, Hash256, Transaction};
+
+const CHAINSTATE_FILE_NAME: &str = "chainstate.dat";
+const CHAINSTATE_TEMPORARY_FILE_NAME: &str = "chainstate.tmp";
+
+/**
+ * A storage backend that keeps a chain's blocks in flat files (see
+ * BlockFiles) and its unspent outputs and metadata in a chain state file
+ * (chainstate.dat), which is rewritten as a whole after each change. That's
+ * simple, but slow for a large UTXO set, so it suits small nodes.
+ *
+ * The chain state file is replaced atomically (it's written to a temporary
+ * file, which is synced and then renamed), and it's what decides which
+ * blocks are in the chain: a block is written before the chain state that
+ * includes it, and removed after the chain state that excludes it. If a
+ * change is interrupted, opening the storage again drops the blocks that the
+ * chain state doesn't include.
+ */
+#[derive(Debug)]
+pub struct FlatFileStorage {
+    block_files: BlockFiles,
+    headers: Vec<Block>,
+    unspent_outputs: HashMap<Hash256, u32>,
+    chain_work: U256,
+    pruned_height: u32,
+}
+
+impl FlatFileStorage {
+    /**
+     * Creates an empty storage in the given directory, which is created if it
+     * doesn't exist. It's an error if the directory already contains block
+     * files.
+     */
+    pub fn create<P: Into<PathBuf>>(directory: P) -> io::Result<Self> {
+        let storage = FlatFileStorage {
+            block_files: BlockFiles::create(directory)?,
+            headers: vec![],
+            unspent_outputs: HashMap::new(),
+            chain_work: U256::zero(),
+            pruned_height: 0,
+        };
+        storage.write_chainstate(0, U256::zero(), 0, &[])?;
+
+        Ok(storage)
+    }
+
+    /**
+     * Opens the storage in the given directory and recovers it from an
+     * interrupted change.
+     */
+    pub fn open<P: Into<PathBuf>>(directory: P) -> io::Result<Self> {
+        let block_files = BlockFiles::open(directory)?;
+        let bytes = fs::read(chainstate_path(block_files.directory()))?;
+        let mut reader = ByteReader::new(&bytes);
+        let (block_count, chain_work, pruned_height, unspent_outputs) =
+            read_chainstate(&mut reader).map_err(invalid_data)?;
+
+        let mut headers = block_files.read_headers()?;
+        if headers.len() < block_count as usize {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "the chain state has more blocks than the block files",
+            ));
+        } else if headers.len() > block_count as usize {
+            block_files.truncate(block_count)?;
+            headers.truncate(block_count as usize);
+        }
+
+        Ok(FlatFileStorage {
+            block_files,
+            headers,
+            unspent_outputs,
+            chain_work,
+            pruned_height,
+        })
+    }
+
+    /**
+     * Returns the block files that the storage keeps the blocks in.
+     */
+    pub fn block_files(&self) -> &BlockFiles {
+        &self.block_files
+    }
+
+    /**
+     * Replaces the chain state file with the given metadata and the storage's
+     * unspent outputs with the given changes applied.
+     */
+    fn write_chainstate(
+        &self,
+        block_count: u32,
+        chain_work: U256,
+        pruned_height: u32,
+        unspent_output_changes: &[(Hash256, Option<u32>)],
+    ) -> io::Result<()> {
+        let changed_outputs: HashSet<&Hash256> = unspent_output_changes
+            .iter()
+            .map(|(output, _)| output)
+            .collect();
+        let unspent_outputs: Vec<(&Hash256, u32)> = self
+            .unspent_outputs
+            .iter()
+            .filter(|(output, _)| !changed_outputs.contains(output))
+            .map(|(output, block_index)| (output, *block_index))
+            .chain(
+                unspent_output_changes
+                    .iter()
+                    .filter_map(|(output, block_index)| Some((output, (*block_index)?))),
+            )
+            .collect();
+
+        let mut bytes = vec![];
+        block_count.write_encoding(&mut bytes);
+        chain_work.write_encoding(&mut bytes);
+        pruned_height.write_encoding(&mut bytes);
+        bytes.extend_from_slice(&u32_bytes(unspent_outputs.len() as u32));
+        for (output, block_index) in unspent_outputs {
+            output.write_encoding(&mut bytes);
+            block_index.write_encoding(&mut bytes);
+        }
+
+        let directory = self.block_files.directory();
+        let temporary_path = directory.join(CHAINSTATE_TEMPORARY_FILE_NAME);
+        {
+            let mut file = File::create(&temporary_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+        fs::rename(temporary_path, chainstate_path(directory))?;
+
+        sync_directory(directory)
+    }
+}
+
+impl ChainStorage for FlatFileStorage {
+    fn block_count(&self) -> u32 {
+        self.headers.len() as u32
+    }
+
+    fn header(&self, index: u32) -> Option<&Block> {
+        self.headers.get(index as usize)
+    }
+
+    fn read_body(&self, index: u32) -> io::Result<Option<Vec<Transaction>>> {
+        if index < self.pruned_height || index >= self.block_count() {
+            return Ok(None);
+        }
+
+        self.block_files.read_body(index)
+    }
+
+    fn read_undo(&self, index: u32) -> io::Result<Option<BlockUndo>> {
+        if index < self.pruned_height || index >= self.block_count() {
+            return Ok(None);
+        }
+
+        self.block_files.read_undo(index)
+    }
+
+    fn unspent_output(&self, output: &Hash256) -> io::Result<Option<u32>> {
+        Ok(self.unspent_outputs.get(output).copied())
+    }
+
+    fn unspent_outputs(&self) -> io::Result<Vec<(Hash256, u32)>> {
+        Ok(self
+            .unspent_outputs
+            .iter()
+            .map(|(output, block_index)| (*output, *block_index))
+            .collect())
+    }
+
+    fn chain_work(&self) -> U256 {
+        self.chain_work
+    }
+
+    fn pruned_height(&self) -> u32 {
+        self.pruned_height
+    }
+
+    fn connect_block(
+        &mut self,
+        mut block: Block,
+        undo: BlockUndo,
+        unspent_output_changes: &[(Hash256, Option<u32>)],
+        chain_work: U256,
+    ) -> io::Result<()> {
+        let block_count = self.block_count();
+        self.block_files.write_block(&block, &undo)?;
+        if let Err(error) = self.write_chainstate(
+            block_count + 1,
+            chain_work,
+            self.pruned_height,
+            unspent_output_changes,
+        ) {
+            let _ = self.block_files.truncate(block_count);
+            return Err(error);
+        }
+
+        apply_changes(&mut self.unspent_outputs, unspent_output_changes);
+        block.transactions = vec![];
+        self.headers.push(block);
+        self.chain_work = chain_work;
+
+        Ok(())
+    }
+
+    fn disconnect_tip(&mut self, chain_work: U256) -> io::Result<Block> {
+        let tip_index = self
+            .block_count()
+            .checked_sub(1)
+            .ok_or_else(missing_undo_data)?;
+        let undo = self.read_undo(tip_index)?.ok_or_else(missing_undo_data)?;
+        let transactions = self.read_body(tip_index)?.ok_or_else(missing_undo_data)?;
+
+        self.write_chainstate(
+            tip_index,
+            chain_work,
+            self.pruned_height,
+            undo.previous_entries(),
+        )?;
+        if let Err(error) = self.block_files.truncate(tip_index) {
+            let _ = self.write_chainstate(tip_index + 1, self.chain_work, self.pruned_height, &[]);
+            return Err(error);
+        }
+
+        apply_changes(&mut self.unspent_outputs, undo.previous_entries());
+        self.chain_work = chain_work;
+        let mut block = self.headers.pop().expect("the chain has a tip");
+        block.transactions = transactions;
+
+        Ok(block)
+    }
+
+    fn prune_block(&mut self, index: u32) -> io::Result<()> {
+        self.block_files.remove_body(index)?;
+        self.block_files.remove_undo(index)?;
+        self.write_chainstate(self.block_count(), self.chain_work, index + 1, &[])?;
+
+        self.pruned_height = index + 1;
+
+        Ok(())
+    }
+
+    fn load_snapshot(
+        &mut self,
+        headers: Vec<Block>,
+        unspent_outputs: &[(Hash256, u32)],
+        chain_work: U256,
+    ) -> io::Result<()> {
+        for header in &headers {
+            self.block_files.write_header(header)?;
+        }
+        let changes: Vec<(Hash256, Option<u32>)> = unspent_outputs
+            .iter()
+            .map(|(output, block_index)| (*output, Some(*block_index)))
+            .collect();
+        let block_count = headers.len() as u32;
+        self.write_chainstate(block_count, chain_work, block_count, &changes)?;
+
+        self.headers = headers;
+        self.unspent_outputs = unspent_outputs.iter().copied().collect();
+        self.chain_work = chain_work;
+        self.pruned_height = block_count;
+
+        Ok(())
+    }
+}
+
+fn chainstate_path(directory: &Path) -> PathBuf {
+    directory.join(CHAINSTATE_FILE_NAME)
+}
+
+type Chainstate = (u32, U256, u32, HashMap<Hash256, u32>);
+
+fn read_chainstate(reader: &mut ByteReader) -> Result<Chainstate, DecodeErr> {
+    let block_count = reader.read_u32()?;
+    let chain_work = U256::read_encoding(reader)?;
+    let pruned_height = reader.read_u32()?;
+    let length = reader.read_u32()?;
+
+    let mut unspent_outputs = HashMap::new();
+    for _ in 0..length {
+        unspent_outputs.insert(reader.read_hash()?, reader.read_u32()?);
+    }
+    if !reader.is_empty() {
+        return Err(DecodeErr::TrailingBytes);
+    }
+
+    Ok((block_count, chain_work, pruned_height, unspent_outputs))
+}
+
+fn invalid_data(error: DecodeErr) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("{:?}", error))
+}
+
+#[cfg(test)]
+mod flat_file_storage_tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use primitive_types::U256;
+
+    use super::FlatFileStorage;
+    use crate::storage::ChainStorage;
+
+    use crate::test_support::create_stored_block;
+    use crate::{BlockUndo, Hash256};
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "flat_file_storage_tests-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    fn connect_block(storage: &mut FlatFileStorage, index: u32) {
+        let output = Hash256([index as u8 + 100; 32]);
+        storage
+            .connect_block(
+                create_stored_block(index),
+                BlockUndo::new(vec![(output, None)]),
+                &[(output, Some(index))],
+                U256::from(index + 1),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn reopen() {
+        let directory = temporary_directory("reopen");
+        let mut storage = FlatFileStorage::create(&directory).unwrap();
+        connect_block(&mut storage, 0);
+        connect_block(&mut storage, 1);
+
+        let result = FlatFileStorage::open(&directory).unwrap();
+
+        assert_eq!(2, result.block_count());
+        assert_eq!(Hash256([1; 32]), result.header(1).unwrap().hash);
+        assert_eq!(
+            Some(create_stored_block(1).transactions),
+            result.read_body(1).unwrap()
+        );
+        assert_eq!(Some(1), result.unspent_output(&Hash256([101; 32])).unwrap());
+        assert_eq!(U256::from(2), result.chain_work());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn disconnect_tip_and_reopen() {
+        let directory = temporary_directory("disconnect_tip_and_reopen");
+        let mut storage = FlatFileStorage::create(&directory).unwrap();
+        connect_block(&mut storage, 0);
+        connect_block(&mut storage, 1);
+
+        let block = storage.disconnect_tip(U256::from(1)).unwrap();
+
+        assert_eq!(create_stored_block(1), block);
+        let result = FlatFileStorage::open(&directory).unwrap();
+        assert_eq!(1, result.block_count());
+        assert_eq!(None, result.unspent_output(&Hash256([101; 32])).unwrap());
+        assert_eq!(None, result.block_files().read_body(1).unwrap());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn prune_block_and_reopen() {
+        let directory = temporary_directory("prune_block_and_reopen");
+        let mut storage = FlatFileStorage::create(&directory).unwrap();
+        connect_block(&mut storage, 0);
+        connect_block(&mut storage, 1);
+
+        storage.prune_block(0).unwrap();
+
+        let result = FlatFileStorage::open(&directory).unwrap();
+        assert_eq!(1, result.pruned_height());
+        assert_eq!(None, result.read_body(0).unwrap());
+        assert_eq!(None, result.read_undo(0).unwrap());
+        assert_eq!(2, result.block_count());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn open_drops_block_missing_from_chain_state() {
+        let directory = temporary_directory("open_drops_block_missing_from_chain_state");
+        let mut storage = FlatFileStorage::create(&directory).unwrap();
+        connect_block(&mut storage, 0);
+        let chainstate = fs::read(directory.join("chainstate.dat")).unwrap();
+        connect_block(&mut storage, 1);
+        fs::write(directory.join("chainstate.dat"), chainstate).unwrap();
+
+        let result = FlatFileStorage::open(&directory).unwrap();
+
+        assert_eq!(1, result.block_count());
+        assert_eq!(None, result.unspent_output(&Hash256([101; 32])).unwrap());
+        assert_eq!(None, result.block_files().read_body(1).unwrap());
+        assert_eq!(1, result.block_files().read_headers().unwrap().len());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
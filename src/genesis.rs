@@ -0,0 +1,242 @@
+use crate::{
+    opcodes, transaction, Block, BlockHash, ConsensusEngine, HashAlgorithms, Script, SealErr,
+    Transaction,
+};
+
+/**
+ * Chain parameters that describe a canonical genesis block.
+ *
+ * Every node that starts from the same configuration builds (and mines) the
+ * same genesis block, so the genesis hash acts as the identity of the chain:
+ * nodes that disagree on it are on different networks.
+ *
+ * - Timestamp: Fixed, so that the block is reproducible.
+ *
+ * - Outputs: The initial coin distribution, paid by the genesis coinbase
+ *   transaction.
+ *
+ * - Bits: The compact target the genesis block is mined at.
+ *
+ * - Message: An optional text embedded in the coinbase transaction as a
+ *   zero-value, unspendable (OP_RETURN) output (Bitcoin's genesis block
+ *   famously carries a newspaper headline).
+ *
+ * - Hash algorithms: The algorithms the chain hashes blocks and transactions
+ *   with.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenesisConfig {
+    pub timestamp: u128,
+    pub outputs: Vec<transaction::Output>,
+    pub bits: u32,
+    pub message: Option<String>,
+    pub hash_algorithms: HashAlgorithms,
+}
+
+impl GenesisConfig {
+    /**
+     * Returns the genesis coinbase transaction.
+     */
+    pub fn coinbase_transaction(&self) -> Transaction {
+        let mut outputs = self.outputs.clone();
+        if let Some(message) = &self.message {
+            outputs.push(transaction::Output {
+                to_address: message.clone(),
+                value: 0,
+                locking_script: Script::new().push_opcode(opcodes::OP_RETURN),
+            });
+        }
+
+        Transaction::coinbase(0, outputs)
+    }
+
+    /**
+     * Builds and mines the genesis block.
+     */
+    pub fn build_block(&self) -> Block {
+        let mut block = self.unsealed_block();
+        block.mine_with(self.hash_algorithms.block);
+
+        block
+    }
+
+    /**
+     * Builds the genesis block and seals it with the given consensus engine.
+     */
+    pub fn build_block_with(&self, consensus: &dyn ConsensusEngine) -> Result<Block, SealErr> {
+        let mut block = self.unsealed_block();
+        consensus.seal(&mut block, self.hash_algorithms.block)?;
+
+        Ok(block)
+    }
+
+    fn unsealed_block(&self) -> Block {
+        let mut block = Block::new(
+            0,
+            self.timestamp,
+            genesis_previous_block_hash(),
+            vec![self.coinbase_transaction()],
+            self.bits,
+        );
+        block.update_transaction_root(self.hash_algorithms.transaction);
+
+        block
+    }
+}
+
+/**
+ * Returns the previous block hash that a genesis block must carry: a hash of
+ * 32 zeros.
+ */
+pub fn genesis_previous_block_hash() -> BlockHash {
+    BlockHash::ZERO
+}
+
+#[cfg(test)]
+mod genesis_config_tests {
+    use super::{genesis_previous_block_hash, GenesisConfig};
+    use crate::{
+        check_difficulty, opcodes, transaction, HashAlgorithm, HashAlgorithms, Hashable,
+        ProofOfAuthority, ProofOfWork, Script, SealErr, Transaction,
+    };
+
+    const BITS: u32 = 0x2000_ffff;
+
+    fn create_config(message: Option<String>) -> GenesisConfig {
+        GenesisConfig {
+            timestamp: 1_600_000_000_000,
+            outputs: vec![transaction::Output {
+                to_address: "Alice".to_owned(),
+                value: 50,
+                locking_script: Script::new(),
+            }],
+            bits: BITS,
+            message,
+            hash_algorithms: HashAlgorithms::default(),
+        }
+    }
+
+    #[test]
+    fn coinbase_transaction_without_message() {
+        let config = create_config(None);
+
+        let result = config.coinbase_transaction();
+
+        assert_eq!(
+            Transaction {
+                inputs: vec![],
+                outputs: vec![transaction::Output {
+                    to_address: "Alice".to_owned(),
+                    value: 50,
+                    locking_script: Script::new(),
+                }],
+                lock_time: Some(transaction::LockTime::BlockIndex(0)),
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn coinbase_transaction_with_message() {
+        let config = create_config(Some("Hello, world!".to_owned()));
+
+        let result = config.coinbase_transaction();
+
+        assert_eq!(
+            Transaction {
+                inputs: vec![],
+                outputs: vec![
+                    transaction::Output {
+                        to_address: "Alice".to_owned(),
+                        value: 50,
+                        locking_script: Script::new(),
+                    },
+                    transaction::Output {
+                        to_address: "Hello, world!".to_owned(),
+                        value: 0,
+                        locking_script: Script::new().push_opcode(opcodes::OP_RETURN),
+                    },
+                ],
+                lock_time: Some(transaction::LockTime::BlockIndex(0)),
+            },
+            result
+        );
+    }
+
+    #[test]
+    fn build_block() {
+        let config = create_config(None);
+
+        let result = config.build_block();
+
+        assert_eq!(0, result.index);
+        assert_eq!(1_600_000_000_000, result.timestamp);
+        assert_eq!(genesis_previous_block_hash(), result.previous_block_hash);
+        assert_eq!(vec![config.coinbase_transaction()], result.transactions);
+        assert_eq!(BITS, result.bits);
+        assert_eq!(result.hash(), result.hash);
+        assert!(check_difficulty(&result.hash, result.bits));
+    }
+
+    #[test]
+    fn build_block_is_deterministic() {
+        let config = create_config(Some("Hello, world!".to_owned()));
+
+        let result1 = config.build_block();
+        let result2 = config.build_block();
+
+        assert_eq!(result1, result2);
+    }
+
+    #[test]
+    fn build_block_depends_on_message() {
+        let result1 = create_config(Some("Hello".to_owned())).build_block();
+        let result2 = create_config(Some("World".to_owned())).build_block();
+
+        assert_ne!(result1.hash, result2.hash);
+    }
+
+    #[test]
+    fn build_block_with_hash_algorithms() {
+        let mut config = create_config(None);
+        config.hash_algorithms = HashAlgorithms::uniform(HashAlgorithm::Blake3);
+
+        let result = config.build_block();
+
+        assert_eq!(result.hash_with(HashAlgorithm::Blake3), result.hash);
+        assert!(result.has_valid_transaction_root(HashAlgorithm::Blake3));
+        assert!(check_difficulty(&result.hash, result.bits));
+        assert_ne!(create_config(None).build_block().hash, result.hash);
+    }
+
+    #[test]
+    fn build_block_with_proof_of_work() {
+        let config = create_config(None);
+
+        let result = config.build_block_with(&ProofOfWork);
+
+        assert_eq!(Ok(config.build_block()), result);
+    }
+
+    #[test]
+    fn build_block_with_proof_of_authority() {
+        let config = create_config(None);
+
+        let result = config.build_block_with(&ProofOfAuthority::new(vec![]));
+
+        assert!(result.is_ok());
+        let block = result.unwrap();
+        assert_eq!(block.hash(), block.hash);
+        assert!(block.seal.is_empty());
+    }
+
+    #[test]
+    fn build_block_with_invalid_bits() {
+        let mut config = create_config(None);
+        config.bits = 0x2101_0000;
+
+        let result = config.build_block_with(&ProofOfWork);
+
+        assert_eq!(Err(SealErr::InvalidTarget), result);
+    }
+}
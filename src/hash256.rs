@@ -0,0 +1,221 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+
+/**
+ * A fixed-size 256-bit hash.
+ *
+ * The bytes are stored in the order the hash function produces them. When a
+ * hash is compared numerically (against a difficulty target, for example), it
+ * is interpreted as a little-endian number, so the most significant byte is
+ * the last one.
+ *
+ * Hashes are displayed and parsed as 64 hexadecimal digits in storage order.
+ */
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Hash256(pub [u8; 32]);
+
+/**
+ * Errors that may occur when parsing a hash from a string.
+ */
+#[derive(Debug, PartialEq)]
+pub enum ParseHash256Err {
+    InvalidHexCharacter,
+    InvalidLength,
+}
+
+impl Hash256 {
+    pub const ZERO: Hash256 = Hash256([0; 32]);
+
+    /**
+     * Returns the hash's bytes.
+     */
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for Hash256 {
+    fn from(bytes: [u8; 32]) -> Self {
+        Hash256(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for Hash256 {
+    type Error = ParseHash256Err;
+
+    /**
+     * Converts a slice of exactly 32 bytes to a hash.
+     */
+    fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
+        if slice.len() != 32 {
+            return Err(ParseHash256Err::InvalidLength);
+        }
+
+        let mut bytes = [0; 32];
+        bytes.copy_from_slice(slice);
+
+        Ok(Hash256(bytes))
+    }
+}
+
+impl AsRef<[u8]> for Hash256 {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Ord for Hash256 {
+    /**
+     * Compares two hashes numerically, as little-endian numbers.
+     */
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+impl PartialOrd for Hash256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Hash256 {
+    /**
+     * Returns the hash as 64 hexadecimal digits.
+     */
+    fn fmt(&self, buffer: &mut Formatter) -> fmt::Result {
+        write!(buffer, "{}", hex::encode(self.0))
+    }
+}
+
+impl Debug for Hash256 {
+    fn fmt(&self, buffer: &mut Formatter) -> fmt::Result {
+        write!(buffer, "Hash256({})", self)
+    }
+}
+
+impl FromStr for Hash256 {
+    type Err = ParseHash256Err;
+
+    /**
+     * Parses a hash from 64 hexadecimal digits.
+     */
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.len() != 64 {
+            return Err(ParseHash256Err::InvalidLength);
+        }
+
+        let bytes = hex::decode(text).map_err(|_| ParseHash256Err::InvalidHexCharacter)?;
+
+        Hash256::try_from(bytes.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod hash256_tests {
+    use std::convert::TryFrom;
+
+    use super::{Hash256, ParseHash256Err};
+
+    fn increasing_bytes() -> [u8; 32] {
+        [
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+            24, 25, 26, 27, 28, 29, 30, 31,
+        ]
+    }
+
+    #[test]
+    fn zero() {
+        assert_eq!(Hash256([0; 32]), Hash256::ZERO);
+        assert_eq!(Hash256::ZERO, Hash256::default());
+    }
+
+    #[test]
+    fn try_from_slice_with_32_bytes() {
+        let bytes = increasing_bytes();
+
+        let result = Hash256::try_from(&bytes[..]);
+
+        assert_eq!(Ok(Hash256(increasing_bytes())), result);
+    }
+
+    #[test]
+    fn try_from_slice_with_wrong_length() {
+        let bytes = [1_u8, 2, 3];
+
+        let result = Hash256::try_from(&bytes[..]);
+
+        assert_eq!(Err(ParseHash256Err::InvalidLength), result);
+    }
+
+    #[test]
+    fn display() {
+        let hash = Hash256(increasing_bytes());
+
+        let result = format!("{}", hash);
+
+        assert_eq!(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+            result
+        );
+    }
+
+    #[test]
+    fn debug_format() {
+        let hash = Hash256::ZERO;
+
+        let result = format!("{:?}", hash);
+
+        assert_eq!(
+            "Hash256(0000000000000000000000000000000000000000000000000000000000000000)",
+            result
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        let text = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+        let result = text.parse::<Hash256>();
+
+        assert_eq!(Ok(Hash256(increasing_bytes())), result);
+    }
+
+    #[test]
+    fn from_str_with_wrong_length() {
+        let result = "0001".parse::<Hash256>();
+
+        assert_eq!(Err(ParseHash256Err::InvalidLength), result);
+    }
+
+    #[test]
+    fn from_str_with_invalid_character() {
+        let text = "z".repeat(64);
+
+        let result = text.parse::<Hash256>();
+
+        assert_eq!(Err(ParseHash256Err::InvalidHexCharacter), result);
+    }
+
+    #[test]
+    fn ordering_is_numeric() {
+        let mut lower = [0xff; 32];
+        lower[31] = 0x00;
+        let mut higher = [0x00; 32];
+        higher[31] = 0x01;
+
+        assert!(Hash256(lower) < Hash256(higher));
+        assert!(Hash256(higher) > Hash256(lower));
+        assert!(Hash256::ZERO < Hash256(lower));
+    }
+
+    #[test]
+    fn ordering_of_equal_hashes() {
+        let hash1 = Hash256(increasing_bytes());
+        let hash2 = Hash256(increasing_bytes());
+
+        assert_eq!(std::cmp::Ordering::Equal, hash1.cmp(&hash2));
+    }
+}
@@ -1,26 +1,204 @@
+use blake2::digest::consts::U32;
+use blake2::Blake2b;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use crate::Hash256;
+
+/**
+ * The digest algorithms that can hash a hashable instance. All of them produce
+ * 256-bit hashes.
+ *
+ * - SHA-256: The default.
+ *
+ * - Double SHA-256: SHA-256 applied twice, as Bitcoin does, which protects
+ *   against length-extension attacks.
+ *
+ * - BLAKE2b: BLAKE2b with a 256-bit output.
+ *
+ * - BLAKE3: Much faster than SHA-256 in software.
+ *
+ * - Keccak-256: The original Keccak submission to the SHA-3 competition, as
+ *   Ethereum uses it (it differs from the standardized SHA3-256 in padding).
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    DoubleSha256,
+    Blake2b,
+    Blake3,
+    Keccak256,
+}
+
+impl HashAlgorithm {
+    /**
+     * Returns the hash of the given bytes.
+     */
+    pub fn digest(&self, bytes: &[u8]) -> Hash256 {
+        let mut hasher = Hasher::new(*self);
+        hasher.update(bytes);
+
+        hasher.finalize()
+    }
+}
+
+/**
+ * The hash algorithms that a chain uses: one for blocks (which is the one that
+ * proof-of-work is computed with) and one for transactions and their outputs.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HashAlgorithms {
+    pub block: HashAlgorithm,
+    pub transaction: HashAlgorithm,
+}
+
+impl HashAlgorithms {
+    /**
+     * Returns the configuration that uses the same algorithm for blocks and
+     * transactions.
+     */
+    pub fn uniform(algorithm: HashAlgorithm) -> Self {
+        HashAlgorithms {
+            block: algorithm,
+            transaction: algorithm,
+        }
+    }
+}
+
+/**
+ * An incremental hasher: a running hash state of one of the hash algorithms.
+ *
+ * Bytes can be fed to a hasher piece by piece, and a hasher can be cloned to
+ * save its state. Mining uses that to hash the part of a block that doesn't
+ * change between nonce attempts only once (the "midstate").
+ */
+// The BLAKE3 state is much larger than the others, but boxing it would make
+// every midstate clone allocate, which is what mining does on each attempt.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone)]
+pub enum Hasher {
+    Sha256(Sha256),
+    DoubleSha256(Sha256),
+    Blake2b(Blake2b<U32>),
+    Blake3(blake3::Hasher),
+    Keccak256(Keccak256),
+}
+
+impl Hasher {
+    /**
+     * Returns a hasher with an empty state for the given algorithm.
+     */
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::DoubleSha256 => Hasher::DoubleSha256(Sha256::new()),
+            HashAlgorithm::Blake2b => Hasher::Blake2b(Blake2b::<U32>::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(blake3::Hasher::new()),
+            HashAlgorithm::Keccak256 => Hasher::Keccak256(Keccak256::new()),
+        }
+    }
+
+    /**
+     * Feeds the given bytes to the hasher.
+     */
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) | Hasher::DoubleSha256(hasher) => hasher.update(bytes),
+            Hasher::Blake2b(hasher) => hasher.update(bytes),
+            Hasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Hasher::Keccak256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /**
+     * Returns the hash of all the bytes fed to the hasher.
+     */
+    pub fn finalize(self) -> Hash256 {
+        let digest: [u8; 32] = match self {
+            Hasher::Sha256(hasher) => hasher.finalize().into(),
+            Hasher::DoubleSha256(hasher) => Sha256::digest(hasher.finalize()).into(),
+            Hasher::Blake2b(hasher) => hasher.finalize().into(),
+            Hasher::Blake3(hasher) => hasher.finalize().into(),
+            Hasher::Keccak256(hasher) => hasher.finalize().into(),
+        };
+
+        Hash256(digest)
+    }
+}
+
+/**
+ * A destination for the bytes of a hashable instance: either a hasher or a
+ * byte vector.
+ */
+pub trait ByteWriter {
+    fn write(&mut self, bytes: &[u8]);
+}
+
+impl ByteWriter for Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.update(bytes);
+    }
+}
+
+impl ByteWriter for Vec<u8> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+/**
+ * Hashable instances write their bytes incrementally, so they can be hashed
+ * without first being serialized into a temporary vector. The writer is a
+ * type parameter rather than a trait object, so hashing calls the hasher
+ * directly instead of through a virtual call for each piece of bytes.
+ */
 pub trait Hashable {
+    /**
+     * Writes the hashable bytes that represent the hashable instance.
+     */
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W);
+
     /**
      * Returns a vector of hashable bytes that represents the hashable instance.
      */
-    fn bytes(&self) -> Vec<u8>;
+    fn bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_bytes(&mut bytes);
+
+        bytes
+    }
+
+    /**
+     * Returns the hashable instance's hash (SHA-256).
+     */
+    fn hash(&self) -> Hash256 {
+        self.hash_with(HashAlgorithm::default())
+    }
 
     /**
-     * Returns a vector of bytes that represents the hashable instance's hash.
+     * Returns the hashable instance's hash computed with the given algorithm.
      */
-    fn hash(&self) -> Vec<u8> {
-        crypto_hash::digest(crypto_hash::Algorithm::SHA256, &self.bytes())
+    fn hash_with(&self, algorithm: HashAlgorithm) -> Hash256 {
+        let mut hasher = Hasher::new(algorithm);
+        self.write_bytes(&mut hasher);
+
+        hasher.finalize()
     }
 }
 
 #[cfg(test)]
 mod hashable_block_tests {
-    use super::Hashable;
+    use super::{ByteWriter, Hash256, HashAlgorithm, Hashable};
 
     struct DummyHashableStruct {}
 
     impl Hashable for DummyHashableStruct {
-        fn bytes(&self) -> Vec<u8> {
-            vec![1, 2, 3, 4]
+        fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+            writer.write(&[1, 2]);
+            writer.write(&[3, 4]);
         }
     }
 
@@ -31,13 +209,150 @@ mod hashable_block_tests {
 
         let result = hashable.hash();
 
-        assert_eq!(32, result.len());
         assert_eq!(
-            vec![
+            Hash256([
                 159, 100, 167, 71, 225, 185, 127, 19, 31, 171, 182, 180, 71, 41, 108, 155, 111, 2,
                 1, 231, 159, 179, 197, 53, 110, 108, 119, 232, 155, 106, 128, 106
-            ],
+            ]),
             result
         );
     }
+
+    #[test]
+    fn hash_with() {
+        let hashable = DummyHashableStruct {};
+
+        let result = hashable.hash_with(HashAlgorithm::Blake3);
+
+        assert_eq!(HashAlgorithm::Blake3.digest(&[1, 2, 3, 4]), result);
+        assert_ne!(hashable.hash(), result);
+    }
+}
+
+#[cfg(test)]
+mod hasher_tests {
+    use super::{HashAlgorithm, Hasher};
+
+    const ALGORITHMS: [HashAlgorithm; 5] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::DoubleSha256,
+        HashAlgorithm::Blake2b,
+        HashAlgorithm::Blake3,
+        HashAlgorithm::Keccak256,
+    ];
+
+    #[test]
+    fn incremental_updates() {
+        for algorithm in ALGORITHMS.iter() {
+            let mut hasher = Hasher::new(*algorithm);
+            hasher.update(b"a");
+            hasher.update(b"");
+            hasher.update(b"bc");
+
+            let result = hasher.finalize();
+
+            assert_eq!(algorithm.digest(b"abc"), result, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn cloned_state() {
+        for algorithm in ALGORITHMS.iter() {
+            let mut prefix_hasher = Hasher::new(*algorithm);
+            prefix_hasher.update(&[7; 100]);
+            let mut hasher1 = prefix_hasher.clone();
+            hasher1.update(b"1");
+            let mut hasher2 = prefix_hasher.clone();
+            hasher2.update(b"2");
+
+            let result1 = hasher1.finalize();
+            let result2 = hasher2.finalize();
+
+            let mut expected_bytes = vec![7; 100];
+            expected_bytes.push(b'1');
+            assert_eq!(
+                algorithm.digest(&expected_bytes),
+                result1,
+                "{:?}",
+                algorithm
+            );
+            expected_bytes.pop();
+            expected_bytes.push(b'2');
+            assert_eq!(
+                algorithm.digest(&expected_bytes),
+                result2,
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod hash_algorithm_tests {
+    use super::{HashAlgorithm, HashAlgorithms};
+
+    fn digest_as_hex(algorithm: HashAlgorithm) -> String {
+        algorithm.digest(b"abc").to_string()
+    }
+
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            digest_as_hex(HashAlgorithm::Sha256)
+        );
+    }
+
+    #[test]
+    fn double_sha256() {
+        assert_eq!(
+            "4f8b42c22dd3729b519ba6f68d2da7cc5b2d606d05daed5ad5128cc03e6c6358",
+            digest_as_hex(HashAlgorithm::DoubleSha256)
+        );
+    }
+
+    #[test]
+    fn blake2b() {
+        assert_eq!(
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319",
+            digest_as_hex(HashAlgorithm::Blake2b)
+        );
+    }
+
+    #[test]
+    fn blake3() {
+        assert_eq!(
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            digest_as_hex(HashAlgorithm::Blake3)
+        );
+    }
+
+    #[test]
+    fn keccak256() {
+        assert_eq!(
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45",
+            digest_as_hex(HashAlgorithm::Keccak256)
+        );
+    }
+
+    #[test]
+    fn default_algorithm() {
+        assert_eq!(HashAlgorithm::Sha256, HashAlgorithm::default());
+    }
+
+    #[test]
+    fn default_algorithms() {
+        let result = HashAlgorithms::default();
+
+        assert_eq!(HashAlgorithms::uniform(HashAlgorithm::Sha256), result);
+    }
+
+    #[test]
+    fn uniform_algorithms() {
+        let result = HashAlgorithms::uniform(HashAlgorithm::Keccak256);
+
+        assert_eq!(HashAlgorithm::Keccak256, result.block);
+        assert_eq!(HashAlgorithm::Keccak256, result.transaction);
+    }
 }
@@ -0,0 +1,579 @@
+use std::fmt::{self, Debug, Formatter};
+
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::PrimeField;
+use k256::{ProjectivePoint, Scalar};
+use sha2::Sha512;
+
+use crate::{public_key_hash, Address, SigningKey, VerifyingKey};
+
+/**
+ * The first hardened child index. Indexes from 0 up to it derive normal
+ * children, and indexes from it up derive hardened children.
+ */
+pub const HARDENED_INDEX: u32 = 0x8000_0000;
+
+/**
+ * The key of the HMAC-SHA512 that turns a seed into a master key.
+ */
+const MASTER_KEY_HMAC_KEY: &[u8] = b"Bitcoin seed";
+
+#[derive(Debug, PartialEq)]
+pub enum HdKeyErr {
+    InvalidSeedLength,
+    InvalidKey,
+    InvalidPath,
+    HardenedDerivationFromPublicKey,
+    MaximumDepthExceeded,
+}
+
+/**
+ * An extended private key of a hierarchical deterministic (HD) wallet: a
+ * signing key and a chain code, from which child keys are derived as in
+ * BIP32.
+ *
+ * Every key of the wallet is derived from one master key, which is derived
+ * from a seed (e.g., the seed of a mnemonic phrase, see Mnemonic), so backing
+ * up the seed backs up every key and every address.
+ *
+ * A key has 2^31 normal children and 2^31 hardened children (whose indexes
+ * start at HARDENED_INDEX). The public keys of normal children can also be
+ * derived from the parent's extended public key, without the private key
+ * (e.g., by a server that watches the wallet's addresses). Hardened children
+ * can only be derived from the private key, so leaking a child private key
+ * and the parent extended public key doesn't leak the parent private key.
+ *
+ * Keys are identified by paths, e.g., m/0'/1/2' is the hardened child 2 of
+ * the normal child 1 of the hardened child 0 of the master key (m).
+ */
+#[derive(Clone)]
+pub struct ExtendedPrivateKey {
+    signing_key: SigningKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    child_index: u32,
+}
+
+impl Debug for ExtendedPrivateKey {
+    /**
+     * Returns a formatted result of the key, without the private key and the
+     * chain code, so that logging a key doesn't leak it.
+     */
+    fn fmt(&self, buffer: &mut Formatter) -> fmt::Result {
+        write!(
+            buffer,
+            "ExtendedPrivateKey[depth {}, child index {}]: <redacted>",
+            &self.depth, &self.child_index
+        )
+    }
+}
+
+impl ExtendedPrivateKey {
+    /**
+     * Returns the master key of the given seed, which must be 16 to 64 bytes
+     * long.
+     */
+    pub fn from_seed(seed: &[u8]) -> Result<Self, HdKeyErr> {
+        if seed.len() < 16 || seed.len() > 64 {
+            return Err(HdKeyErr::InvalidSeedLength);
+        }
+
+        let (key, chain_code) = hmac_sha512(MASTER_KEY_HMAC_KEY, &[seed]);
+        let signing_key = SigningKey::from_bytes(&key.into()).map_err(|_| HdKeyErr::InvalidKey)?;
+
+        Ok(ExtendedPrivateKey {
+            signing_key,
+            chain_code,
+            depth: 0,
+            child_index: 0,
+        })
+    }
+
+    pub fn signing_key(&self) -> &SigningKey {
+        &self.signing_key
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    /**
+     * Returns the number of derivations between the master key and this key.
+     */
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /**
+     * Returns the index that this key was derived with (0 for the master
+     * key).
+     */
+    pub fn child_index(&self) -> u32 {
+        self.child_index
+    }
+
+    /**
+     * Returns the extended public key of this key.
+     */
+    pub fn public_key(&self) -> ExtendedPublicKey {
+        ExtendedPublicKey {
+            verifying_key: *self.signing_key.verifying_key(),
+            chain_code: self.chain_code,
+            depth: self.depth,
+            child_index: self.child_index,
+        }
+    }
+
+    /**
+     * Returns the address of this key's public key (see
+     * ExtendedPublicKey::address).
+     */
+    pub fn address(&self) -> Address {
+        self.public_key().address()
+    }
+
+    /**
+     * Returns the child key with the given index, which is hardened if the
+     * index is at least HARDENED_INDEX.
+     */
+    pub fn derive_child(&self, index: u32) -> Result<Self, HdKeyErr> {
+        let depth = self
+            .depth
+            .checked_add(1)
+            .ok_or(HdKeyErr::MaximumDepthExceeded)?;
+        let index_bytes = index.to_be_bytes();
+        let (tweak, chain_code) = if index >= HARDENED_INDEX {
+            let key_bytes = self.signing_key.to_bytes();
+            hmac_sha512(&self.chain_code, &[&[0], &key_bytes, &index_bytes])
+        } else {
+            let public_key_bytes = self.signing_key.verifying_key().to_encoded_point(true);
+            hmac_sha512(
+                &self.chain_code,
+                &[public_key_bytes.as_bytes(), &index_bytes],
+            )
+        };
+
+        let tweak =
+            Option::<Scalar>::from(Scalar::from_repr(tweak.into())).ok_or(HdKeyErr::InvalidKey)?;
+        let child_scalar = tweak + self.signing_key.as_nonzero_scalar().as_ref();
+        let signing_key =
+            SigningKey::from_bytes(&child_scalar.to_bytes()).map_err(|_| HdKeyErr::InvalidKey)?;
+
+        Ok(ExtendedPrivateKey {
+            signing_key,
+            chain_code,
+            depth,
+            child_index: index,
+        })
+    }
+
+    /**
+     * Returns the key at the given path, e.g., m/44'/0'/0'/0/1. Hardened
+     * indexes are marked with an apostrophe or an h, and the path must start
+     * at the master key (m) that this key is.
+     */
+    pub fn derive_path(&self, path: &str) -> Result<Self, HdKeyErr> {
+        parse_path(path)?
+            .into_iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(index))
+    }
+}
+
+/**
+ * An extended public key of a hierarchical deterministic (HD) wallet: a
+ * verifying key and a chain code, from which the public keys of normal (not
+ * hardened) children are derived (see ExtendedPrivateKey).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExtendedPublicKey {
+    verifying_key: VerifyingKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    child_index: u32,
+}
+
+impl ExtendedPublicKey {
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        &self.verifying_key
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn child_index(&self) -> u32 {
+        self.child_index
+    }
+
+    /**
+     * Returns the address of this key: the hexadecimal public key hash that
+     * pay-to-public-key-hash outputs to this key are locked with (see
+     * Script::pay_to_public_key_hash).
+     */
+    pub fn address(&self) -> Address {
+        public_key_hash(&self.verifying_key).to_string()
+    }
+
+    /**
+     * Returns the public key of the normal child with the given index, which
+     * must be less than HARDENED_INDEX.
+     */
+    pub fn derive_child(&self, index: u32) -> Result<Self, HdKeyErr> {
+        if index >= HARDENED_INDEX {
+            return Err(HdKeyErr::HardenedDerivationFromPublicKey);
+        }
+
+        let depth = self
+            .depth
+            .checked_add(1)
+            .ok_or(HdKeyErr::MaximumDepthExceeded)?;
+        let public_key_bytes = self.verifying_key.to_encoded_point(true);
+        let (tweak, chain_code) = hmac_sha512(
+            &self.chain_code,
+            &[public_key_bytes.as_bytes(), &index.to_be_bytes()],
+        );
+
+        let tweak =
+            Option::<Scalar>::from(Scalar::from_repr(tweak.into())).ok_or(HdKeyErr::InvalidKey)?;
+        let child_point = ProjectivePoint::GENERATOR * tweak
+            + ProjectivePoint::from(*self.verifying_key.as_affine());
+        let verifying_key =
+            VerifyingKey::from_affine(child_point.to_affine()).map_err(|_| HdKeyErr::InvalidKey)?;
+
+        Ok(ExtendedPublicKey {
+            verifying_key,
+            chain_code,
+            depth,
+            child_index: index,
+        })
+    }
+
+    /**
+     * Returns the public key at the given path of normal indexes, e.g., m/0/1,
+     * where m is this key.
+     */
+    pub fn derive_path(&self, path: &str) -> Result<Self, HdKeyErr> {
+        parse_path(path)?
+            .into_iter()
+            .try_fold(*self, |key, index| key.derive_child(index))
+    }
+}
+
+/**
+ * Returns the left and the right halves of the HMAC-SHA512 of the
+ * concatenation of the given byte slices.
+ */
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for bytes in data {
+        mac.update(bytes);
+    }
+    let output = mac.finalize().into_bytes();
+
+    let mut left = [0; 32];
+    let mut right = [0; 32];
+    left.copy_from_slice(&output[..32]);
+    right.copy_from_slice(&output[32..]);
+
+    (left, right)
+}
+
+/**
+ * Returns the child indexes of the given path, e.g., [0, 1 + HARDENED_INDEX]
+ * for m/0/1'.
+ */
+fn parse_path(path: &str) -> Result<Vec<u32>, HdKeyErr> {
+    let mut components = path.split('/');
+    if components.next() != Some("m") {
+        return Err(HdKeyErr::InvalidPath);
+    }
+
+    components
+        .map(|component| {
+            let (number, offset) = match component
+                .strip_suffix('\'')
+                .or_else(|| component.strip_suffix('h'))
+            {
+                Some(number) => (number, HARDENED_INDEX),
+                None => (component, 0),
+            };
+            if number.is_empty() || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(HdKeyErr::InvalidPath);
+            }
+
+            match number.parse::<u32>() {
+                Ok(index) if index < HARDENED_INDEX => Ok(index + offset),
+                _ => Err(HdKeyErr::InvalidPath),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod extended_private_key_tests {
+    use super::{ExtendedPrivateKey, HdKeyErr, HARDENED_INDEX};
+
+    const SEED1: &str = "000102030405060708090a0b0c0d0e0f";
+    const SEED2: &str = "fffcf9f6f3f0edeae7e4e1dedbd8d5d2cfccc9c6c3c0bdbab7b4b1aeaba8a5a29f9c999693908d8a8784817e7b7875726f6c696663605d5a5754514e4b484542";
+
+    fn master_key(seed: &str) -> ExtendedPrivateKey {
+        ExtendedPrivateKey::from_seed(&hex::decode(seed).unwrap()).unwrap()
+    }
+
+    fn assert_key(chain_code: &str, private_key: &str, public_key: &str, key: &ExtendedPrivateKey) {
+        assert_eq!(chain_code, hex::encode(key.chain_code()));
+        assert_eq!(private_key, hex::encode(key.signing_key().to_bytes()));
+        assert_eq!(
+            public_key,
+            hex::encode(
+                key.signing_key()
+                    .verifying_key()
+                    .to_encoded_point(true)
+                    .as_bytes()
+            )
+        );
+    }
+
+    /**
+     * BIP32 test vector 1.
+     */
+    #[test]
+    fn derive_test_vector1() {
+        let master = master_key(SEED1);
+
+        assert_key(
+            "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508",
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35",
+            "0339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2",
+            &master,
+        );
+        assert_key(
+            "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141",
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+            "035a784662a4a20a65bf6aab9ae98a6c068a81c52e4b032c0fb5400c706cfccc56",
+            &master.derive_path("m/0'").unwrap(),
+        );
+        assert_key(
+            "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19",
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368",
+            "03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c",
+            &master.derive_path("m/0'/1").unwrap(),
+        );
+        assert_key(
+            "04466b9cc8e161e966409ca52986c584f07e9dc81f735db683c3ff6ec7b1503f",
+            "cbce0d719ecf7431d88e6a89fa1483e02e35092af60c042b1df2ff59fa424dca",
+            "0357bfe1e341d01c69fe5654309956cbea516822fba8a601743a012a7896ee8dc2",
+            &master.derive_path("m/0'/1/2'").unwrap(),
+        );
+        assert_key(
+            "cfb71883f01676f587d023cc53a35bc7f88f724b1f8c2892ac1275ac822a3edd",
+            "0f479245fb19a38a1954c5c7c0ebab2f9bdfd96a17563ef28a6a4b1a2a764ef4",
+            "02e8445082a72f29b75ca48748a914df60622a609cacfce8ed0e35804560741d29",
+            &master.derive_path("m/0'/1/2'/2").unwrap(),
+        );
+        assert_key(
+            "c783e67b921d2beb8f6b389cc646d7263b4145701dadd2161548a8b078e65e9e",
+            "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8",
+            "022a471424da5e657499d1ff51cb43c47481a03b1e77f951fe64cec9f5a48f7011",
+            &master.derive_path("m/0'/1/2'/2/1000000000").unwrap(),
+        );
+    }
+
+    /**
+     * BIP32 test vector 2.
+     */
+    #[test]
+    fn derive_test_vector2() {
+        let master = master_key(SEED2);
+
+        assert_key(
+            "60499f801b896d83179a4374aeb7822aaeaceaa0db1f85ee3e904c4defbd9689",
+            "4b03d6fc340455b363f51020ad3ecca4f0850280cf436c70c727923f6db46c3e",
+            "03cbcaa9c98c877a26977d00825c956a238e8dddfbd322cce4f74b0b5bd6ace4a7",
+            &master,
+        );
+        assert_key(
+            "f0909affaa7ee7abe5dd4e100598d4dc53cd709d5a5c2cac40e7412f232f7c9c",
+            "abe74a98f6c7eabee0428f53798f0ab8aa1bd37873999041703c742f15ac7e1e",
+            "02fc9e5af0ac8d9b3cecfe2a888e2117ba3d089d8585886c9c826b6b22a98d12ea",
+            &master.derive_path("m/0").unwrap(),
+        );
+        assert_key(
+            "be17a268474a6bb9c61e1d720cf6215e2a88c5406c4aee7b38547f585c9a37d9",
+            "877c779ad9687164e9c2f4f0f4ff0340814392330693ce95a58fe18fd52e6e93",
+            "03c01e7425647bdefa82b12d9bad5e3e6865bee0502694b94ca58b666abc0a5c3b",
+            &master.derive_path("m/0/2147483647'").unwrap(),
+        );
+        assert_key(
+            "f366f48f1ea9f2d1d3fe958c95ca84ea18e4c4ddb9366c336c927eb246fb38cb",
+            "704addf544a06e5ee4bea37098463c23613da32020d604506da8c0518e1da4b7",
+            "03a7d1d856deb74c508e05031f9895dab54626251b3806e16b4bd12e781a7df5b9",
+            &master.derive_path("m/0/2147483647'/1").unwrap(),
+        );
+        assert_key(
+            "637807030d55d01f9a0cb3a7839515d796bd07706386a6eddf06cc29a65a0e29",
+            "f1c7c871a54a804afe328b4c83a1c33b8e5ff48f5087273f04efa83b247d6a2d",
+            "02d2b36900396c9282fa14628566582f206a5dd0bcc8d5e892611806cafb0301f0",
+            &master.derive_path("m/0/2147483647'/1/2147483646'").unwrap(),
+        );
+        assert_key(
+            "9452b549be8cea3ecb7a84bec10dcfd94afe4d129ebfd3b3cb58eedf394ed271",
+            "bb7d39bdb83ecf58f2fd82b6d918341cbef428661ef01ab97c28a4842125ac23",
+            "024d902e1a2fc7a8755ab5b694c575fce742c48d9ff192e63df5193e4c7afe1f9c",
+            &master
+                .derive_path("m/0/2147483647'/1/2147483646'/2")
+                .unwrap(),
+        );
+    }
+
+    #[test]
+    fn derive_child() {
+        let master = master_key(SEED1);
+
+        let result = master.derive_child(HARDENED_INDEX).unwrap();
+
+        assert_eq!(1, result.depth());
+        assert_eq!(HARDENED_INDEX, result.child_index());
+        assert_eq!(
+            master.derive_path("m/0h").unwrap().chain_code(),
+            result.chain_code()
+        );
+    }
+
+    #[test]
+    fn master_key_depth() {
+        let master = master_key(SEED1);
+
+        assert_eq!(0, master.depth());
+        assert_eq!(0, master.child_index());
+        assert_eq!(
+            master.chain_code(),
+            master.derive_path("m").unwrap().chain_code()
+        );
+    }
+
+    #[test]
+    fn from_seed_with_invalid_length() {
+        assert_eq!(
+            Some(HdKeyErr::InvalidSeedLength),
+            ExtendedPrivateKey::from_seed(&[1; 15]).err()
+        );
+        assert_eq!(
+            Some(HdKeyErr::InvalidSeedLength),
+            ExtendedPrivateKey::from_seed(&[1; 65]).err()
+        );
+    }
+
+    #[test]
+    fn derive_invalid_path() {
+        let master = master_key(SEED1);
+
+        for path in [
+            "",
+            "0/1",
+            "M/0",
+            "m/",
+            "m/a",
+            "m/-1",
+            "m/+1",
+            "m/1''",
+            "m/2147483648",
+            "m/4294967296",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                Some(HdKeyErr::InvalidPath),
+                master.derive_path(path).err(),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn address() {
+        let key = master_key(SEED1).derive_path("m/0'/0").unwrap();
+
+        let result = key.address();
+
+        assert_eq!(key.public_key().address(), result);
+        assert_eq!(64, result.len());
+    }
+
+    #[test]
+    fn debug_redacts_secrets() {
+        let key = master_key(SEED1).derive_path("m/0'/1").unwrap();
+
+        let result = format!("{:?}", key);
+
+        assert_eq!(
+            "ExtendedPrivateKey[depth 2, child index 1]: <redacted>",
+            result
+        );
+        assert!(!result.contains(&hex::encode(key.chain_code())));
+        assert!(!result.contains(&hex::encode(key.signing_key().to_bytes())));
+    }
+}
+
+#[cfg(test)]
+mod extended_public_key_tests {
+    use super::{ExtendedPrivateKey, HdKeyErr, HARDENED_INDEX};
+    use crate::public_key_hash;
+
+    fn master_key() -> ExtendedPrivateKey {
+        ExtendedPrivateKey::from_seed(&[7; 32]).unwrap()
+    }
+
+    #[test]
+    fn public_key_of_private_key() {
+        let key = master_key();
+
+        let result = key.public_key();
+
+        assert_eq!(key.signing_key().verifying_key(), result.verifying_key());
+        assert_eq!(key.chain_code(), result.chain_code());
+        assert_eq!(key.depth(), result.depth());
+    }
+
+    #[test]
+    fn derive_normal_child_matches_private_derivation() {
+        let account = master_key().derive_path("m/44'/0'/0'").unwrap();
+
+        let result = account.public_key().derive_path("m/0/5").unwrap();
+
+        assert_eq!(account.derive_path("m/0/5").unwrap().public_key(), result);
+        assert_eq!(5, result.depth());
+        assert_eq!(5, result.child_index());
+    }
+
+    #[test]
+    fn derive_hardened_child() {
+        let public_key = master_key().public_key();
+
+        assert_eq!(
+            Some(HdKeyErr::HardenedDerivationFromPublicKey),
+            public_key.derive_child(HARDENED_INDEX).err()
+        );
+        assert_eq!(
+            Some(HdKeyErr::HardenedDerivationFromPublicKey),
+            public_key.derive_path("m/0/1'").err()
+        );
+    }
+
+    #[test]
+    fn address_is_public_key_hash() {
+        let public_key = master_key().public_key();
+
+        let result = public_key.address();
+
+        assert_eq!(
+            public_key_hash(public_key.verifying_key()).to_string(),
+            result
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+use primitive_types::U256;
+
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::storage::{missing_undo_data, ChainStorage};
+use crate::{Block, BlockUndo, Hash256, KvStore, Transaction, WriteBatch};
+
+const BLOCK_COUNT_KEY: &[u8] = b"m:block_count";
+const CHAIN_WORK_KEY: &[u8] = b"m:chain_work";
+const PRUNED_HEIGHT_KEY: &[u8] = b"m:pruned_height";
+const HEADER_PREFIX: u8 = b'h';
+const BODY_PREFIX: u8 = b'b';
+const UNDO_PREFIX: u8 = b'u';
+const UNSPENT_OUTPUT_PREFIX: u8 = b'o';
+
+/**
+ * A storage backend that keeps a chain's state in an embedded key-value store
+ * (see KvStore): a key for each header, body and undo data of a block (a
+ * prefix byte and the block's big-endian index), a key for each unspent
+ * output (a prefix byte and the output's hash) and a key for each metadata
+ * value.
+ *
+ * Each change of the chain is written as one batch, which the store applies
+ * atomically, so the storage is consistent after an interrupted write without
+ * any recovery of its own. The headers are decoded and kept in memory; the
+ * bodies, undo data and unspent outputs are read from the store when they're
+ * needed, although the store still keeps every key (including one for each
+ * unspent output) in memory and reads its whole log when it's opened.
+ */
+#[derive(Debug)]
+pub struct KvStorage {
+    store: KvStore,
+    headers: Vec<Block>,
+    chain_work: U256,
+    pruned_height: u32,
+}
+
+impl KvStorage {
+    /**
+     * Opens the storage in the given file, which is created (as an empty
+     * chain) if it doesn't exist.
+     */
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let store = KvStore::open(path)?;
+        let block_count = read_metadata::<u32>(&store, BLOCK_COUNT_KEY)?.unwrap_or(0);
+        let chain_work = read_metadata::<U256>(&store, CHAIN_WORK_KEY)?.unwrap_or_default();
+        let pruned_height = read_metadata::<u32>(&store, PRUNED_HEIGHT_KEY)?.unwrap_or(0);
+
+        let mut headers = vec![];
+        for index in 0..block_count {
+            let bytes = store
+                .get(&block_key(HEADER_PREFIX, index))?
+                .ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, "a block of the chain has no header")
+                })?;
+            let mut reader = ByteReader::new(&bytes);
+            headers.push(Block::read_header_encoding(&mut reader).map_err(invalid_data)?);
+        }
+
+        Ok(KvStorage {
+            store,
+            headers,
+            chain_work,
+            pruned_height,
+        })
+    }
+
+    /**
+     * Returns the key-value store that the storage keeps the chain in.
+     */
+    pub fn store(&self) -> &KvStore {
+        &self.store
+    }
+
+    /**
+     * Returns the value of the key in the store, decoded.
+     */
+    fn read<T: Decodable>(&self, key: &[u8]) -> io::Result<Option<T>> {
+        read_metadata(&self.store, key)
+    }
+}
+
+impl ChainStorage for KvStorage {
+    fn block_count(&self) -> u32 {
+        self.headers.len() as u32
+    }
+
+    fn header(&self, index: u32) -> Option<&Block> {
+        self.headers.get(index as usize)
+    }
+
+    fn read_body(&self, index: u32) -> io::Result<Option<Vec<Transaction>>> {
+        self.read(&block_key(BODY_PREFIX, index))
+    }
+
+    fn read_undo(&self, index: u32) -> io::Result<Option<BlockUndo>> {
+        self.read(&block_key(UNDO_PREFIX, index))
+    }
+
+    fn unspent_output(&self, output: &Hash256) -> io::Result<Option<u32>> {
+        self.read(&unspent_output_key(output))
+    }
+
+    fn unspent_outputs(&self) -> io::Result<Vec<(Hash256, u32)>> {
+        let mut unspent_outputs = vec![];
+        for key in self.store.keys_with_prefix(&[UNSPENT_OUTPUT_PREFIX]) {
+            let output = Hash256::decode(&key[1..]).map_err(invalid_data)?;
+            let block_index = self.read(&key)?.expect("the key was listed");
+            unspent_outputs.push((output, block_index));
+        }
+
+        Ok(unspent_outputs)
+    }
+
+    fn chain_work(&self) -> U256 {
+        self.chain_work
+    }
+
+    fn pruned_height(&self) -> u32 {
+        self.pruned_height
+    }
+
+    fn connect_block(
+        &mut self,
+        mut block: Block,
+        undo: BlockUndo,
+        unspent_output_changes: &[(Hash256, Option<u32>)],
+        chain_work: U256,
+    ) -> io::Result<()> {
+        let index = self.block_count();
+        let mut header = vec![];
+        block.write_header_encoding(&mut header);
+
+        let mut batch = WriteBatch::default();
+        batch.put(block_key(HEADER_PREFIX, index), header);
+        batch.put(block_key(BODY_PREFIX, index), block.transactions.encode());
+        batch.put(block_key(UNDO_PREFIX, index), undo.encode());
+        write_unspent_output_changes(&mut batch, unspent_output_changes);
+        batch.put(BLOCK_COUNT_KEY.to_vec(), (index + 1).encode());
+        batch.put(CHAIN_WORK_KEY.to_vec(), chain_work.encode());
+        self.store.write(batch)?;
+
+        block.transactions = vec![];
+        self.headers.push(block);
+        self.chain_work = chain_work;
+
+        Ok(())
+    }
+
+    fn disconnect_tip(&mut self, chain_work: U256) -> io::Result<Block> {
+        let tip_index = self
+            .block_count()
+            .checked_sub(1)
+            .ok_or_else(missing_undo_data)?;
+        let undo = self.read_undo(tip_index)?.ok_or_else(missing_undo_data)?;
+        let transactions = self.read_body(tip_index)?.ok_or_else(missing_undo_data)?;
+
+        let mut batch = WriteBatch::default();
+        batch.delete(block_key(HEADER_PREFIX, tip_index));
+        batch.delete(block_key(BODY_PREFIX, tip_index));
+        batch.delete(block_key(UNDO_PREFIX, tip_index));
+        write_unspent_output_changes(&mut batch, undo.previous_entries());
+        batch.put(BLOCK_COUNT_KEY.to_vec(), tip_index.encode());
+        batch.put(CHAIN_WORK_KEY.to_vec(), chain_work.encode());
+        self.store.write(batch)?;
+
+        self.chain_work = chain_work;
+        let mut block = self.headers.pop().expect("the chain has a tip");
+        block.transactions = transactions;
+
+        Ok(block)
+    }
+
+    fn prune_block(&mut self, index: u32) -> io::Result<()> {
+        let mut batch = WriteBatch::default();
+        batch.delete(block_key(BODY_PREFIX, index));
+        batch.delete(block_key(UNDO_PREFIX, index));
+        batch.put(PRUNED_HEIGHT_KEY.to_vec(), (index + 1).encode());
+        self.store.write(batch)?;
+
+        self.pruned_height = index + 1;
+
+        Ok(())
+    }
+
+    fn load_snapshot(
+        &mut self,
+        headers: Vec<Block>,
+        unspent_outputs: &[(Hash256, u32)],
+        chain_work: U256,
+    ) -> io::Result<()> {
+        let block_count = headers.len() as u32;
+        let mut batch = WriteBatch::default();
+        for (index, header) in headers.iter().enumerate() {
+            let mut bytes = vec![];
+            header.write_header_encoding(&mut bytes);
+            batch.put(block_key(HEADER_PREFIX, index as u32), bytes);
+        }
+        for (output, block_index) in unspent_outputs {
+            batch.put(unspent_output_key(output), block_index.encode());
+        }
+        batch.put(BLOCK_COUNT_KEY.to_vec(), block_count.encode());
+        batch.put(CHAIN_WORK_KEY.to_vec(), chain_work.encode());
+        batch.put(PRUNED_HEIGHT_KEY.to_vec(), block_count.encode());
+        self.store.write(batch)?;
+
+        self.headers = headers;
+        self.chain_work = chain_work;
+        self.pruned_height = block_count;
+
+        Ok(())
+    }
+}
+
+/**
+ * Returns the key of a block's header, body or undo data. The index is
+ * big-endian, so the keys of a kind sort in the order of the blocks.
+ */
+fn block_key(prefix: u8, index: u32) -> Vec<u8> {
+    let mut key = vec![prefix];
+    key.extend_from_slice(&index.to_be_bytes());
+
+    key
+}
+
+fn unspent_output_key(output: &Hash256) -> Vec<u8> {
+    let mut key = vec![UNSPENT_OUTPUT_PREFIX];
+    key.extend_from_slice(output.as_bytes());
+
+    key
+}
+
+fn write_unspent_output_changes(batch: &mut WriteBatch, changes: &[(Hash256, Option<u32>)]) {
+    for (output, block_index) in changes {
+        match block_index {
+            Some(block_index) => batch.put(unspent_output_key(output), block_index.encode()),
+            None => batch.delete(unspent_output_key(output)),
+        }
+    }
+}
+
+fn read_metadata<T: Decodable>(store: &KvStore, key: &[u8]) -> io::Result<Option<T>> {
+    match store.get(key)? {
+        Some(bytes) => Ok(Some(T::decode(&bytes).map_err(invalid_data)?)),
+        None => Ok(None),
+    }
+}
+
+fn invalid_data(error: DecodeErr) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("{:?}", error))
+}
+
+#[cfg(test)]
+mod kv_storage_tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use primitive_types::U256;
+
+    use super::KvStorage;
+    use crate::storage::ChainStorage;
+
+    use crate::test_support::create_stored_block;
+    use crate::{BlockUndo, Hash256};
+
+    fn temporary_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("kv_storage_tests-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        path
+    }
+
+    fn connect_block(storage: &mut KvStorage, index: u32) -> Hash256 {
+        let output = Hash256([index as u8 + 100; 32]);
+        storage
+            .connect_block(
+                create_stored_block(index),
+                BlockUndo::new(vec![(output, None)]),
+                &[(output, Some(index))],
+                U256::from(index + 1),
+            )
+            .unwrap();
+
+        output
+    }
+
+    #[test]
+    fn reopen() {
+        let path = temporary_path("reopen");
+        let mut storage = KvStorage::open(&path).unwrap();
+        let first_output = connect_block(&mut storage, 0);
+        let second_output = connect_block(&mut storage, 1);
+
+        let result = KvStorage::open(&path).unwrap();
+
+        assert_eq!(2, result.block_count());
+        assert_eq!(Hash256([1; 32]), result.header(1).unwrap().hash);
+        assert!(result.header(1).unwrap().transactions.is_empty());
+        assert_eq!(
+            create_stored_block(1).transactions,
+            result.read_body(1).unwrap().unwrap()
+        );
+        assert_eq!(Some(0), result.unspent_output(&first_output).unwrap());
+        assert_eq!(Some(1), result.unspent_output(&second_output).unwrap());
+        assert_eq!(2, result.unspent_outputs().unwrap().len());
+        assert_eq!(U256::from(2), result.chain_work());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn disconnect_tip_and_reopen() {
+        let path = temporary_path("disconnect_tip_and_reopen");
+        let mut storage = KvStorage::open(&path).unwrap();
+        connect_block(&mut storage, 0);
+        let output = connect_block(&mut storage, 1);
+
+        let block = storage.disconnect_tip(U256::one()).unwrap();
+        let result = KvStorage::open(&path).unwrap();
+
+        assert_eq!(create_stored_block(1).transactions, block.transactions);
+        assert_eq!(1, result.block_count());
+        assert_eq!(None, result.unspent_output(&output).unwrap());
+        assert_eq!(None, result.read_body(1).unwrap());
+        assert_eq!(U256::one(), result.chain_work());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn prune_block_and_reopen() {
+        let path = temporary_path("prune_block_and_reopen");
+        let mut storage = KvStorage::open(&path).unwrap();
+        connect_block(&mut storage, 0);
+        connect_block(&mut storage, 1);
+
+        storage.prune_block(0).unwrap();
+        let result = KvStorage::open(&path).unwrap();
+
+        assert_eq!(1, result.pruned_height());
+        assert_eq!(None, result.read_body(0).unwrap());
+        assert_eq!(None, result.read_undo(0).unwrap());
+        assert!(result.read_body(1).unwrap().is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
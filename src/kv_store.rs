@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::block_files::sync_directory;
+use crate::encoding::{ByteReader, DecodeErr};
+use crate::{u32_bytes, HashAlgorithm};
+
+/**
+ * The approximate size of each record that compaction writes, so that
+ * compacting a large store doesn't have to hold all its values in memory.
+ */
+const COMPACTION_RECORD_SIZE: usize = 1 << 20;
+
+/**
+ * A batch of writes to a key-value store, which are applied atomically: a
+ * store either has all of them or none of them, even if writing the batch is
+ * interrupted.
+ */
+#[derive(Debug, Default)]
+pub struct WriteBatch {
+    operations: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+impl WriteBatch {
+    /**
+     * Sets the value of the key.
+     */
+    pub fn put(&mut self, key: Vec<u8>, value: Vec<u8>) {
+        self.operations.push((key, Some(value)));
+    }
+
+    /**
+     * Removes the key.
+     */
+    pub fn delete(&mut self, key: Vec<u8>) {
+        self.operations.push((key, None));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+/**
+ * An embedded key-value store: a log file of write batches, and an in-memory
+ * index of where each key's latest value is in the file. Reading a value
+ * reads it from the file, so the values don't have to fit in memory, but
+ * every key does, and opening the store reads the whole log to build the
+ * index.
+ *
+ * Each batch is written as a record: its length (4 bytes), a checksum (the
+ * first 4 bytes of its SHA-256 hash) and its operations, so a batch can't be
+ * longer than 4 GiB. Opening the store replays the records and truncates an
+ * incomplete last record, which is what an interrupted write leaves; an
+ * invalid record anywhere else is corruption, and an error. Deleted and
+ * overwritten values stay in the log until the store is compacted (see
+ * compact).
+ */
+#[derive(Debug)]
+pub struct KvStore {
+    path: PathBuf,
+    file: File,
+    length: u64,
+    index: HashMap<Vec<u8>, (u64, u32)>,
+}
+
+impl KvStore {
+    /**
+     * Opens the store in the given file, which is created if it doesn't
+     * exist, and recovers it from an interrupted write.
+     */
+    pub fn open<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+
+        let mut index = HashMap::new();
+        let mut length = 0;
+        let mut reader = ByteReader::new(&bytes);
+        while !reader.is_empty() {
+            match read_record(&mut reader, length as u64, &mut index) {
+                Ok(()) => length = bytes.len() - reader.remaining(),
+                Err(DecodeErr::UnexpectedEnd) | Err(DecodeErr::InvalidChecksum)
+                    if is_torn_tail(&bytes[length..]) =>
+                {
+                    break
+                }
+                Err(error) => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("{:?} at offset {}", error, length),
+                    ))
+                }
+            }
+        }
+        if length < bytes.len() {
+            file.set_len(length as u64)?;
+        }
+
+        Ok(KvStore {
+            path,
+            file,
+            length: length as u64,
+            index,
+        })
+    }
+
+    /**
+     * Returns the path of the store's file.
+     */
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /**
+     * Returns the value of the key, or None if the store doesn't have it.
+     */
+    pub fn get(&self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let (offset, length) = match self.index.get(key) {
+            Some(location) => *location,
+            None => return Ok(None),
+        };
+
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut value = vec![0; length as usize];
+        file.read_exact(&mut value)?;
+
+        Ok(Some(value))
+    }
+
+    /**
+     * Returns a flag that states whether the store has the key.
+     */
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /**
+     * Returns the keys that start with the given prefix, in no particular
+     * order.
+     */
+    pub fn keys_with_prefix(&self, prefix: &[u8]) -> Vec<Vec<u8>> {
+        self.index
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect()
+    }
+
+    /**
+     * Applies the batch of writes atomically. A batch that is longer than
+     * 4 GiB once encoded is rejected with an InvalidInput error.
+     */
+    pub fn write(&mut self, batch: WriteBatch) -> io::Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let record = encode_record(&batch)?;
+        let mut file = &self.file;
+        let written = file
+            .seek(SeekFrom::Start(self.length))
+            .and_then(|_| file.write_all(&record))
+            .and_then(|_| file.sync_data());
+        if let Err(error) = written {
+            let _ = self.file.set_len(self.length);
+            return Err(error);
+        }
+
+        let mut reader = ByteReader::new(&record);
+        read_record(&mut reader, self.length, &mut self.index)
+            .expect("an encoded record can be read");
+        self.length += record.len() as u64;
+
+        Ok(())
+    }
+
+    /**
+     * Rewrites the store's file with only the latest value of each key. The
+     * new file is written next to the old one, synced and then renamed over
+     * it, so an interrupted compaction leaves the old file as it was.
+     */
+    pub fn compact(&mut self) -> io::Result<()> {
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".compact");
+        let temporary_path = PathBuf::from(temporary_path);
+        {
+            let mut file = File::create(&temporary_path)?;
+            let mut batch = WriteBatch::default();
+            let mut batch_size = 0;
+            for key in self.index.keys() {
+                let value = self.get(key)?.expect("an indexed key has a value");
+                let size = 9 + key.len() + value.len();
+                if !batch.is_empty() && batch_size + size > COMPACTION_RECORD_SIZE {
+                    file.write_all(&encode_record(&batch)?)?;
+                    batch = WriteBatch::default();
+                    batch_size = 0;
+                }
+                batch.put(key.clone(), value);
+                batch_size += size;
+            }
+            if !batch.is_empty() {
+                file.write_all(&encode_record(&batch)?)?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&temporary_path, &self.path)?;
+        match self.path.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => sync_directory(directory)?,
+            _ => sync_directory(Path::new("."))?,
+        }
+
+        *self = KvStore::open(self.path.clone())?;
+
+        Ok(())
+    }
+}
+
+/**
+ * Returns a record of the batch: its length, its checksum and its
+ * operations, each of which is a tag (0 for a deletion, 1 for a value), the
+ * key and the value, both prefixed with their lengths. Returns an
+ * InvalidInput error if a length doesn't fit in 4 bytes.
+ */
+fn encode_record(batch: &WriteBatch) -> io::Result<Vec<u8>> {
+    let mut payload = vec![];
+    payload.extend_from_slice(&length_bytes(batch.operations.len())?);
+    for (key, value) in &batch.operations {
+        payload.push(value.is_some() as u8);
+        payload.extend_from_slice(&length_bytes(key.len())?);
+        payload.extend_from_slice(key);
+        if let Some(value) = value {
+            payload.extend_from_slice(&length_bytes(value.len())?);
+            payload.extend_from_slice(value);
+        }
+    }
+
+    let mut record = vec![];
+    record.extend_from_slice(&length_bytes(payload.len())?);
+    record.extend_from_slice(&checksum(&payload));
+    record.extend_from_slice(&payload);
+
+    Ok(record)
+}
+
+fn length_bytes(length: usize) -> io::Result<[u8; 4]> {
+    let length = u32::try_from(length).map_err(|_| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "a write batch can't be longer than 4 GiB",
+        )
+    })?;
+
+    Ok(u32_bytes(length))
+}
+
+/**
+ * Returns a flag that states whether the bytes from the start of an invalid
+ * record to the end of the file are what an interrupted write leaves: a
+ * record that is cut short, one whose last bytes weren't written (so the
+ * record reaches the end of the file), or zeros that a file system may leave
+ * when the file's length was updated but its data wasn't. Anything else is
+ * corruption in the middle of the log.
+ */
+fn is_torn_tail(tail: &[u8]) -> bool {
+    if tail.len() < 8 || tail.iter().all(|byte| *byte == 0) {
+        return true;
+    }
+
+    let payload_length = ByteReader::new(tail)
+        .read_u32()
+        .expect("the tail has a length");
+
+    8 + payload_length as usize >= tail.len()
+}
+
+/**
+ * Reads a record that starts at the given offset in the file and applies its
+ * operations to the index, unless it's invalid (e.g., its checksum doesn't
+ * match), in which case the index is left as it was.
+ */
+fn read_record(
+    reader: &mut ByteReader,
+    offset: u64,
+    index: &mut HashMap<Vec<u8>, (u64, u32)>,
+) -> Result<(), DecodeErr> {
+    let payload_length = reader.read_u32()? as usize;
+    let expected_checksum = reader.read(4)?;
+    let payload = reader.read(payload_length)?;
+    if checksum(payload) != expected_checksum {
+        return Err(DecodeErr::InvalidChecksum);
+    }
+
+    let payload_offset = offset + 8;
+    let mut payload_reader = ByteReader::new(payload);
+    let mut operations = vec![];
+    for _ in 0..payload_reader.read_u32()? {
+        let tag = payload_reader.read_u8()?;
+        let key = payload_reader.read_length_prefixed()?.to_vec();
+        let value = match tag {
+            0 => None,
+            1 => {
+                let value = payload_reader.read_length_prefixed()?;
+                let value_offset = payload.len() - payload_reader.remaining() - value.len();
+                Some((payload_offset + value_offset as u64, value.len() as u32))
+            }
+            _ => return Err(DecodeErr::InvalidTag),
+        };
+        operations.push((key, value));
+    }
+    if !payload_reader.is_empty() {
+        return Err(DecodeErr::TrailingBytes);
+    }
+
+    for (key, value) in operations {
+        match value {
+            Some(location) => index.insert(key, location),
+            None => index.remove(&key),
+        };
+    }
+
+    Ok(())
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = HashAlgorithm::Sha256.digest(payload);
+    let mut checksum = [0; 4];
+    checksum.copy_from_slice(&hash.as_bytes()[..4]);
+
+    checksum
+}
+
+#[cfg(test)]
+mod kv_store_tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use super::{length_bytes, KvStore, WriteBatch};
+
+    fn temporary_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("kv_store_tests-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        path
+    }
+
+    fn batch(operations: &[(&str, Option<&str>)]) -> WriteBatch {
+        let mut batch = WriteBatch::default();
+        for (key, value) in operations {
+            match value {
+                Some(value) => batch.put(key.as_bytes().to_vec(), value.as_bytes().to_vec()),
+                None => batch.delete(key.as_bytes().to_vec()),
+            }
+        }
+
+        batch
+    }
+
+    fn get(store: &KvStore, key: &str) -> Option<String> {
+        store
+            .get(key.as_bytes())
+            .unwrap()
+            .map(|value| String::from_utf8(value).unwrap())
+    }
+
+    #[test]
+    fn write_and_get() {
+        let path = temporary_path("write_and_get");
+        let mut store = KvStore::open(&path).unwrap();
+
+        store
+            .write(batch(&[("a", Some("1")), ("b", Some("2"))]))
+            .unwrap();
+        store
+            .write(batch(&[("a", Some("3")), ("b", None)]))
+            .unwrap();
+
+        assert_eq!(Some("3".to_owned()), get(&store, "a"));
+        assert_eq!(None, get(&store, "b"));
+        assert!(!store.contains_key(b"b"));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reopen() {
+        let path = temporary_path("reopen");
+        let mut store = KvStore::open(&path).unwrap();
+        store
+            .write(batch(&[
+                ("a:1", Some("1")),
+                ("a:2", Some("2")),
+                ("b", Some("3")),
+            ]))
+            .unwrap();
+        store.write(batch(&[("a:1", None)])).unwrap();
+
+        let result = KvStore::open(&path).unwrap();
+
+        assert_eq!(None, get(&result, "a:1"));
+        assert_eq!(Some("2".to_owned()), get(&result, "a:2"));
+        assert_eq!(vec![b"a:2".to_vec()], result.keys_with_prefix(b"a:"));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reopen_after_interrupted_write() {
+        let path = temporary_path("reopen_after_interrupted_write");
+        let mut store = KvStore::open(&path).unwrap();
+        store.write(batch(&[("a", Some("1"))])).unwrap();
+        let length = fs::metadata(&path).unwrap().len();
+        store
+            .write(batch(&[("a", Some("2")), ("b", Some("3"))]))
+            .unwrap();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(length + 12)
+            .unwrap();
+
+        let mut result = KvStore::open(&path).unwrap();
+
+        assert_eq!(Some("1".to_owned()), get(&result, "a"));
+        assert_eq!(None, get(&result, "b"));
+        assert_eq!(length, fs::metadata(&path).unwrap().len());
+        result.write(batch(&[("b", Some("4"))])).unwrap();
+        assert_eq!(
+            Some("4".to_owned()),
+            get(&KvStore::open(&path).unwrap(), "b")
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reopen_after_zeroed_tail() {
+        let path = temporary_path("reopen_after_zeroed_tail");
+        let mut store = KvStore::open(&path).unwrap();
+        store.write(batch(&[("a", Some("1"))])).unwrap();
+        let length = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(length + 64)
+            .unwrap();
+
+        let result = KvStore::open(&path).unwrap();
+
+        assert_eq!(Some("1".to_owned()), get(&result, "a"));
+        assert_eq!(length, fs::metadata(&path).unwrap().len());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reject_corrupt_record() {
+        let path = temporary_path("reject_corrupt_record");
+        let mut store = KvStore::open(&path).unwrap();
+        store.write(batch(&[("a", Some("1"))])).unwrap();
+        store.write(batch(&[("b", Some("2"))])).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let length = bytes.len();
+        bytes[length / 4] ^= 0xff;
+        fs::write(&path, &bytes).unwrap();
+
+        let result = KvStore::open(&path);
+
+        assert_eq!(ErrorKind::InvalidData, result.unwrap_err().kind());
+        assert_eq!(length as u64, fs::metadata(&path).unwrap().len());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reject_oversized_length() {
+        let result = length_bytes(u32::MAX as usize + 1);
+
+        assert_eq!(ErrorKind::InvalidInput, result.unwrap_err().kind());
+        assert_eq!([0xff; 4], length_bytes(u32::MAX as usize).unwrap());
+    }
+
+    #[test]
+    fn compact() {
+        let path = temporary_path("compact");
+        let mut store = KvStore::open(&path).unwrap();
+        for value in 0..10 {
+            store
+                .write(batch(&[("a", Some(&value.to_string())), ("b", None)]))
+                .unwrap();
+        }
+        let length = fs::metadata(&path).unwrap().len();
+
+        store.compact().unwrap();
+
+        assert!(fs::metadata(&path).unwrap().len() < length);
+        assert_eq!(Some("9".to_owned()), get(&store, "a"));
+        assert_eq!(
+            Some("9".to_owned()),
+            get(&KvStore::open(&path).unwrap(), "a")
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compact_large_store() {
+        let path = temporary_path("compact_large_store");
+        let mut store = KvStore::open(&path).unwrap();
+        let value = "x".repeat(600 * 1024);
+        for key in ["a", "b", "c"] {
+            store.write(batch(&[(key, Some(&value))])).unwrap();
+        }
+        store.write(batch(&[("b", None)])).unwrap();
+
+        store.compact().unwrap();
+
+        let result = KvStore::open(&path).unwrap();
+        assert_eq!(Some(value.clone()), get(&result, "a"));
+        assert_eq!(None, get(&result, "b"));
+        assert_eq!(Some(value), get(&result, "c"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
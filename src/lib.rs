@@ -1,17 +1,90 @@
 use std::time::{SystemTime, SystemTimeError, UNIX_EPOCH};
 
+mod address_index;
 mod block;
+mod block_files;
+mod block_filter;
+mod block_template;
+mod block_undo;
 mod blockchain;
+mod compact_block;
+mod consensus;
+mod encoding;
+mod fee;
+mod flat_file_storage;
+mod genesis;
+mod hash256;
 mod hashable;
+mod hd_key;
+mod kv_storage;
+mod kv_store;
+mod light_client;
+mod memory_hard;
+mod mempool;
+mod merkle;
+mod mnemonic;
+mod relay;
+mod script;
+mod storage;
+mod target;
+#[cfg(test)]
+mod test_support;
 pub mod transaction;
+mod utxo_snapshot;
+mod wallet;
 
+pub use crate::address_index::{AddressEvent, AddressEventKind, AddressHistory, AddressIndex};
 pub use crate::block::check_difficulty;
-pub use crate::block::Block;
-pub use crate::blockchain::Blockchain;
-pub use crate::hashable::Hashable;
+pub use crate::block::{Block, MiningState};
+pub use crate::block_files::BlockFiles;
+pub use crate::block_filter::BlockFilter;
+pub use crate::block_template::{BlockTemplate, DEFAULT_MAX_BLOCK_TEMPLATE_SIZE};
+pub use crate::block_undo::BlockUndo;
+pub use crate::blockchain::{
+    BlockValidationErr, Blockchain, ConfirmedTransaction, DisconnectErr, TransactionLocation,
+};
+pub use crate::compact_block::{
+    BlockTransactions, BlockTransactionsRequest, CompactBlock, CompactBlockErr, PartialBlock,
+    PrefilledTransaction, SHORT_ID_LENGTH,
+};
+pub use crate::consensus::{
+    ConsensusEngine, MemoryHardProofOfWork, ProofOfAuthority, ProofOfWork, SealErr,
+};
+pub use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+pub use crate::fee::{
+    FeeEstimator, FeeRate, DEFAULT_FEE_ESTIMATE_BLOCK_COUNT, DEFAULT_MIN_RELAY_FEE_RATE,
+};
+pub use crate::flat_file_storage::FlatFileStorage;
+pub use crate::genesis::GenesisConfig;
+pub use crate::hash256::{Hash256, ParseHash256Err};
+pub use crate::hashable::{ByteWriter, HashAlgorithm, HashAlgorithms, Hashable, Hasher};
+pub use crate::hd_key::{ExtendedPrivateKey, ExtendedPublicKey, HdKeyErr, HARDENED_INDEX};
+pub use crate::kv_storage::KvStorage;
+pub use crate::kv_store::{KvStore, WriteBatch};
+pub use crate::light_client::{FullNode, LightClient, SpvErr, TransactionProof, HEADER_BATCH_SIZE};
+pub use crate::memory_hard::MemoryHardFunction;
+pub use crate::mempool::{
+    Mempool, MempoolErr, DEFAULT_MAX_ANCESTOR_COUNT, DEFAULT_MAX_ANCESTOR_SIZE,
+    DEFAULT_MAX_REPLACED_TRANSACTIONS,
+};
+pub use crate::merkle::{transaction_root, MerkleProof};
+pub use crate::mnemonic::{Mnemonic, MnemonicErr};
+pub use crate::relay::{
+    Connection, Message, RelayErr, RelayNode, MAX_MESSAGE_LENGTH, MAX_PARTIAL_BLOCKS,
+};
+pub use crate::script::{
+    opcodes, public_key_hash, sign, verify_script, Script, ScriptContext, ScriptErr,
+};
+pub use crate::storage::{ChainStorage, MemoryStorage};
+pub use crate::target::Target;
 pub use crate::transaction::Transaction;
+pub use crate::utxo_snapshot::{SnapshotErr, UtxoSnapshot};
+pub use crate::wallet::{MultisigSpend, PaymentBuilder, WalletErr};
+pub use k256::ecdsa::{SigningKey, VerifyingKey};
+pub use primitive_types::U256;
 
-type BlockHash = Vec<u8>;
+type BlockHash = Hash256;
+type TransactionId = Hash256;
 type Address = String;
 
 /**
@@ -75,33 +148,6 @@ pub fn u128_bytes(data: u128) -> [u8; 16] {
     ]
 }
 
-/**
- * The function assumes that the byte vector has 32 bytes.
- *
- * Performs ORing the most significant 16 bytes as a u128 result that represents
- * the difficulty:
- *
- * v[31] | v[30] | v[29] | ... | v[18] | v[17] | v[16]
- */
-pub fn difficulty_bytes_as_u128(v: &[u8]) -> u128 {
-    u128::from(v[31]) << 120
-        | u128::from(v[30]) << 112
-        | u128::from(v[29]) << 104
-        | u128::from(v[28]) << 96
-        | u128::from(v[27]) << 88
-        | u128::from(v[26]) << 80
-        | u128::from(v[25]) << 72
-        | u128::from(v[24]) << 64
-        | u128::from(v[23]) << 56
-        | u128::from(v[22]) << 48
-        | u128::from(v[21]) << 40
-        | u128::from(v[20]) << 32
-        | u128::from(v[19]) << 24
-        | u128::from(v[18]) << 16
-        | u128::from(v[17]) << 8
-        | u128::from(v[16])
-}
-
 #[cfg(test)]
 mod now_tests {
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -268,259 +314,3 @@ mod u128_bytes_tests {
         );
     }
 }
-
-#[cfg(test)]
-mod difficulty_bytes_as_u128_tests {
-    use super::difficulty_bytes_as_u128;
-
-    #[test]
-    fn with_zero_bytes() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(0_u128, result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_16() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(0), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_17() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(8), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_18() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(16), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_19() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(24), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_20() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(32), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_21() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(40), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_22() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(48), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_23() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(56), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_24() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(64), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_25() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(72), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_26() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(80), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_27() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 1_u8,
-            0_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(88), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_28() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            1_u8, 0_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(96), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_29() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 1_u8, 0_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(104), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_30() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 1_u8, 0_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(112), result);
-    }
-
-    #[test]
-    fn with_one_byte_at_index_31() {
-        let data = vec![
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8, 0_u8,
-            0_u8, 0_u8, 0_u8, 1_u8,
-        ];
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(2_u128.pow(120), result);
-    }
-
-    #[test]
-    fn with_increasing_bytes() {
-        let data = vec![
-            0_u8, 1_u8, 2_u8, 3_u8, 4_u8, 5_u8, 6_u8, 7_u8, 8_u8, 9_u8, 10_u8, 11_u8, 12_u8, 13_u8,
-            14_u8, 15_u8, 16_u8, 17_u8, 18_u8, 19_u8, 20_u8, 21_u8, 22_u8, 23_u8, 24_u8, 25_u8,
-            26_u8, 27_u8, 28_u8, 29_u8, 30_u8, 31_u8,
-        ];
-        let expected_result: u128 = u128::from(16_u8)
-            | u128::from(17_u8) << 8
-            | u128::from(18_u8) << 16
-            | u128::from(19_u8) << 24
-            | u128::from(20_u8) << 32
-            | u128::from(21_u8) << 40
-            | u128::from(22_u8) << 48
-            | u128::from(23_u8) << 56
-            | u128::from(24_u8) << 64
-            | u128::from(25_u8) << 72
-            | u128::from(26_u8) << 80
-            | u128::from(27_u8) << 88
-            | u128::from(28_u8) << 96
-            | u128::from(29_u8) << 104
-            | u128::from(30_u8) << 112
-            | u128::from(31_u8) << 120;
-        assert_eq!(41362427191743139026751447860679676176, expected_result);
-
-        let result = difficulty_bytes_as_u128(&data);
-
-        assert_eq!(expected_result, result);
-    }
-}
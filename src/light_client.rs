@@ -0,0 +1,897 @@
+use std::collections::HashMap;
+
+use primitive_types::U256;
+
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::genesis::genesis_previous_block_hash;
+use crate::{
+    Block, BlockFilter, BlockHash, BlockValidationErr, Blockchain, ByteWriter, ChainStorage,
+    ConfirmedTransaction, ConsensusEngine, Hash256, HashAlgorithms, Hashable, MerkleProof,
+    ProofOfWork, Target, Transaction, TransactionId, TransactionLocation,
+};
+
+/**
+ * The maximum number of headers that a light client asks a full node for at
+ * once.
+ */
+pub const HEADER_BATCH_SIZE: u32 = 2000;
+
+/**
+ * The errors of verifying that a transaction is in the chain.
+ */
+#[derive(Debug, PartialEq)]
+pub enum SpvErr {
+    UnknownTransaction,
+    MismatchedTransaction,
+    UnknownBlock,
+    InvalidProof,
+    UnknownFilter,
+    InvalidFilter,
+}
+
+/**
+ * A full node's answer to a light client that asks whether a transaction is
+ * in the chain: the transaction, the hash and the index of the block that
+ * contains it, and a proof that the block's transaction root commits to it.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionProof {
+    pub transaction: Transaction,
+    pub block_hash: BlockHash,
+    pub block_index: u32,
+    pub proof: MerkleProof,
+}
+
+impl Encodable for TransactionProof {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.transaction.write_encoding(writer);
+        self.block_hash.write_encoding(writer);
+        self.block_index.write_encoding(writer);
+        self.proof.write_encoding(writer);
+    }
+}
+
+impl Decodable for TransactionProof {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(TransactionProof {
+            transaction: Transaction::read_encoding(reader)?,
+            block_hash: reader.read_hash()?,
+            block_index: reader.read_u32()?,
+            proof: MerkleProof::read_encoding(reader)?,
+        })
+    }
+}
+
+/**
+ * What a light client needs from a full node. A Blockchain answers in
+ * process; a client of a node's p2p or RPC interface implements it by
+ * sending the request and decoding the answer (headers and proofs are
+ * Encodable), and returns nothing if the node can't be reached.
+ */
+pub trait FullNode {
+    /**
+     * Returns up to count headers (blocks without their transactions) of
+     * the node's chain, starting at the given index.
+     */
+    fn headers(&self, start: u32, count: u32) -> Vec<Block>;
+
+    /**
+     * Returns a proof that the transaction with the given id is in the
+     * node's chain, or None if the node can't prove it.
+     */
+    fn transaction_proof(&self, id: &TransactionId) -> Option<TransactionProof>;
+
+    /**
+     * Returns up to count filter headers of the node's chain, starting at the
+     * one of the block with the given index. A node that doesn't serve block
+     * filters returns none.
+     */
+    fn filter_headers(&self, _start: u32, _count: u32) -> Vec<Hash256> {
+        vec![]
+    }
+
+    /**
+     * Returns the filter of the block with the given index, or None if the
+     * node doesn't serve it.
+     */
+    fn block_filter(&self, _index: u32) -> Option<BlockFilter> {
+        None
+    }
+}
+
+impl<S: ChainStorage> FullNode for Blockchain<S> {
+    fn headers(&self, start: u32, count: u32) -> Vec<Block> {
+        Blockchain::headers(self, start, count)
+    }
+
+    fn transaction_proof(&self, id: &TransactionId) -> Option<TransactionProof> {
+        Blockchain::transaction_proof(self, id)
+    }
+
+    fn filter_headers(&self, start: u32, count: u32) -> Vec<Hash256> {
+        Blockchain::filter_headers(self, start, count)
+    }
+
+    fn block_filter(&self, index: u32) -> Option<BlockFilter> {
+        Blockchain::block_filter(self, index).cloned()
+    }
+}
+
+/**
+ * A light (SPV) client: it keeps only the chain's headers, not its
+ * transactions, so it can't validate spends, but it can check that a
+ * transaction was confirmed.
+ *
+ * Headers are downloaded from a full node (see sync_headers) and validated
+ * like a Blockchain validates blocks, except for their transactions: the
+ * index, the hash and the seal (e.g., the proof-of-work), the timestamp and
+ * the link to the previous header (or the genesis block pin).
+ *
+ * To check that a transaction was confirmed, the client asks the node for a
+ * Merkle proof (see verify_transaction), which shows that the transaction is
+ * committed to by the transaction root of a header that the client has. A
+ * node can hide a transaction, but it can't make up one without producing
+ * headers with valid seals.
+ *
+ * A seal is only as hard to produce as the target in its own header, so a
+ * client that only checks seals can be fed a made-up chain sealed at the
+ * easiest target, which takes a couple of attempts per header. A client
+ * should be pinned to the chain's genesis block and configured with the
+ * chain's maximum target (see with_max_target), which rejects headers that
+ * prove less work than a block of the chain, and with checkpoints (see
+ * with_checkpoint) of blocks known to be in the chain, which rejects a chain
+ * that forks before them.
+ *
+ * To find the blocks that concern it without telling the node its addresses,
+ * the client downloads the chain of filter headers (see sync_filter_headers)
+ * and then the blocks' compact filters, which it checks against the filter
+ * headers and tests locally (see matching_blocks). Comparing the filter
+ * headers of several nodes exposes a node that serves wrong filters.
+ *
+ * The client follows the chain of the node it syncs from and doesn't handle
+ * reorganizations.
+ */
+pub struct LightClient {
+    headers: Vec<Block>,
+    filter_headers: Vec<Hash256>,
+    chain_work: U256,
+    expected_genesis_hash: Option<BlockHash>,
+    min_block_work: U256,
+    checkpoints: HashMap<u32, BlockHash>,
+    hash_algorithms: HashAlgorithms,
+    consensus: Box<dyn ConsensusEngine>,
+}
+
+impl Default for LightClient {
+    fn default() -> Self {
+        LightClient::new()
+    }
+}
+
+impl LightClient {
+    pub fn new() -> Self {
+        LightClient {
+            headers: vec![],
+            filter_headers: vec![],
+            chain_work: U256::zero(),
+            expected_genesis_hash: None,
+            min_block_work: U256::zero(),
+            checkpoints: HashMap::new(),
+            hash_algorithms: HashAlgorithms::default(),
+            consensus: Box::new(ProofOfWork),
+        }
+    }
+
+    /**
+     * Creates a client whose headers are verified by the given consensus
+     * engine, which must be the chain's.
+     */
+    pub fn with_consensus(consensus: Box<dyn ConsensusEngine>) -> Self {
+        LightClient {
+            consensus,
+            ..LightClient::new()
+        }
+    }
+
+    /**
+     * Creates a client that hashes blocks and transactions with the given
+     * algorithms, which must be the chain's.
+     */
+    pub fn with_hash_algorithms(hash_algorithms: HashAlgorithms) -> Self {
+        LightClient {
+            hash_algorithms,
+            ..LightClient::new()
+        }
+    }
+
+    /**
+     * Creates a client that only accepts a genesis block with the given
+     * hash.
+     */
+    pub fn with_genesis_hash(genesis_hash: BlockHash) -> Self {
+        LightClient {
+            expected_genesis_hash: Some(genesis_hash),
+            ..LightClient::new()
+        }
+    }
+
+    /**
+     * Returns the client with the given maximum target (the easiest target
+     * of the chain's blocks): headers whose seal proves less work than a
+     * block at that target are rejected.
+     */
+    pub fn with_max_target(mut self, max_target: Target) -> Self {
+        self.min_block_work = max_target.work();
+
+        self
+    }
+
+    /**
+     * Returns the client with a checkpoint: the header with the given index
+     * must have the given hash.
+     */
+    pub fn with_checkpoint(mut self, index: u32, block_hash: BlockHash) -> Self {
+        self.checkpoints.insert(index, block_hash);
+
+        self
+    }
+
+    /**
+     * Returns the headers of the chain.
+     */
+    pub fn headers(&self) -> &[Block] {
+        &self.headers
+    }
+
+    /**
+     * Returns the filter headers of the chain (see BlockFilter::header).
+     */
+    pub fn filter_headers(&self) -> &[Hash256] {
+        &self.filter_headers
+    }
+
+    /**
+     * Returns the header of the chain's tip, if any.
+     */
+    pub fn tip(&self) -> Option<&Block> {
+        self.headers.last()
+    }
+
+    /**
+     * Returns the total work of the chain's headers.
+     */
+    pub fn chain_work(&self) -> U256 {
+        self.chain_work
+    }
+
+    /**
+     * Validates the header and appends it to the chain. The header's
+     * transactions, if any, are dropped without being checked.
+     */
+    pub fn add_header(&mut self, header: Block) -> Result<(), BlockValidationErr> {
+        let index = self.headers.len() as u32;
+        let block_hash = header.hash_with(self.hash_algorithms.block);
+
+        if header.index != index {
+            return Err(BlockValidationErr::MismatchedIndex);
+        } else if header.hash != block_hash {
+            return Err(BlockValidationErr::InvalidHash);
+        }
+
+        self.consensus.verify_seal(&header, &block_hash)?;
+
+        let work = self.consensus.work(&header);
+        if work < self.min_block_work {
+            return Err(BlockValidationErr::InsufficientWork);
+        } else if self
+            .checkpoints
+            .get(&index)
+            .is_some_and(|checkpoint_hash| checkpoint_hash != &block_hash)
+        {
+            return Err(BlockValidationErr::MismatchedCheckpoint);
+        }
+
+        match self.headers.last() {
+            None => {
+                if header.previous_block_hash != genesis_previous_block_hash() {
+                    return Err(BlockValidationErr::InvalidGenesisBlockFormat);
+                } else if let Some(expected_genesis_hash) = &self.expected_genesis_hash {
+                    if &block_hash != expected_genesis_hash {
+                        return Err(BlockValidationErr::MismatchedGenesisBlock);
+                    }
+                }
+            }
+            Some(previous_header) => {
+                if header.timestamp <= previous_header.timestamp {
+                    return Err(BlockValidationErr::AchronologicalTimestamp);
+                } else if header.previous_block_hash != previous_header.hash {
+                    return Err(BlockValidationErr::MismatchedPreviousHash);
+                }
+            }
+        }
+
+        self.chain_work = self.chain_work.saturating_add(work);
+        self.headers.push(header.header());
+
+        Ok(())
+    }
+
+    /**
+     * Downloads the headers that the client doesn't have yet from the node,
+     * in batches of HEADER_BATCH_SIZE, and returns how many were added. The
+     * headers before an invalid one are kept.
+     */
+    pub fn sync_headers(&mut self, node: &impl FullNode) -> Result<u32, BlockValidationErr> {
+        let mut added = 0;
+        loop {
+            let headers = node.headers(self.headers.len() as u32, HEADER_BATCH_SIZE);
+            let count = headers.len() as u32;
+            for header in headers {
+                self.add_header(header)?;
+                added += 1;
+            }
+
+            if count < HEADER_BATCH_SIZE {
+                return Ok(added);
+            }
+        }
+    }
+
+    /**
+     * Downloads the filter headers that the client doesn't have yet from the
+     * node, up to the tip of the client's headers, and returns how many were
+     * added.
+     */
+    pub fn sync_filter_headers(&mut self, node: &impl FullNode) -> u32 {
+        let mut added = 0;
+        while self.filter_headers.len() < self.headers.len() {
+            let start = self.filter_headers.len() as u32;
+            let count = (self.headers.len() as u32 - start).min(HEADER_BATCH_SIZE);
+            let filter_headers = node.filter_headers(start, count);
+            if filter_headers.is_empty() {
+                break;
+            }
+
+            let new_headers = filter_headers.into_iter().take(count as usize);
+            let length = self.filter_headers.len();
+            self.filter_headers.extend(new_headers);
+            added += (self.filter_headers.len() - length) as u32;
+        }
+
+        added
+    }
+
+    /**
+     * Downloads the filter of the block with the given index from the node
+     * and checks it against the block's header and filter header.
+     */
+    pub fn block_filter(&self, node: &impl FullNode, index: u32) -> Result<BlockFilter, SpvErr> {
+        let filter_header = self
+            .filter_headers
+            .get(index as usize)
+            .ok_or(SpvErr::UnknownFilter)?;
+        let filter = node.block_filter(index).ok_or(SpvErr::UnknownFilter)?;
+        if filter.block_hash != self.headers[index as usize].hash {
+            return Err(SpvErr::UnknownBlock);
+        }
+
+        let previous_filter_header = match index.checked_sub(1) {
+            Some(previous_index) => self.filter_headers[previous_index as usize],
+            None => Hash256::ZERO,
+        };
+        if &filter.header(&previous_filter_header) != filter_header {
+            return Err(SpvErr::InvalidFilter);
+        }
+
+        Ok(filter)
+    }
+
+    /**
+     * Returns the indexes of the blocks, from the given one up to the last
+     * one with a filter header, whose filters match any of the addresses or
+     * of the outputs with the given hashes (see BlockFilter::matches_any).
+     * The matching blocks may concern the client, and the others don't.
+     */
+    pub fn matching_blocks(
+        &self,
+        node: &impl FullNode,
+        start: u32,
+        addresses: &[&str],
+        output_hashes: &[Hash256],
+    ) -> Result<Vec<u32>, SpvErr> {
+        let mut matching_blocks = vec![];
+        for index in start..self.filter_headers.len() as u32 {
+            let filter = self.block_filter(node, index)?;
+            if filter.matches_any(addresses, output_hashes) {
+                matching_blocks.push(index);
+            }
+        }
+
+        Ok(matching_blocks)
+    }
+
+    /**
+     * Asks the node for a proof that the transaction with the given id (see
+     * Transaction::id_with and the chain's transaction algorithm) is in the
+     * chain, and verifies it against the client's headers. Returns the
+     * transaction, its location and its number of confirmations.
+     *
+     * The block must be among the client's headers, so the client should
+     * sync its headers first.
+     */
+    pub fn verify_transaction(
+        &self,
+        node: &impl FullNode,
+        id: &TransactionId,
+    ) -> Result<ConfirmedTransaction, SpvErr> {
+        let proof = node
+            .transaction_proof(id)
+            .ok_or(SpvErr::UnknownTransaction)?;
+        if &proof.transaction.id_with(self.hash_algorithms.transaction) != id {
+            return Err(SpvErr::MismatchedTransaction);
+        }
+
+        let header = self
+            .headers
+            .get(proof.block_index as usize)
+            .filter(|header| header.hash == proof.block_hash)
+            .ok_or(SpvErr::UnknownBlock)?;
+        if !proof.proof.verify(
+            &proof.transaction,
+            &header.transaction_root,
+            self.hash_algorithms.transaction,
+        ) {
+            return Err(SpvErr::InvalidProof);
+        }
+
+        Ok(ConfirmedTransaction {
+            transaction: proof.transaction,
+            location: TransactionLocation {
+                block_hash: proof.block_hash,
+                block_index: proof.block_index,
+                position: proof.proof.position as usize,
+            },
+            confirmations: self.headers.len() as u32 - proof.block_index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod light_client_tests {
+    use std::cell::Cell;
+
+    use super::{FullNode, LightClient, SpvErr, TransactionProof};
+    use crate::encoding::{Decodable, Encodable};
+    use crate::test_support::{add_block, create_next_block, spend, BITS};
+    use crate::transaction::Output;
+    use crate::{
+        Block, BlockFilter, BlockValidationErr, Blockchain, Hash256, HashAlgorithm, HashAlgorithms,
+        Script, Target, Transaction, TransactionId, HEADER_BATCH_SIZE,
+    };
+
+    fn create_transaction(block_index: u32, to_address: &str) -> Transaction {
+        Transaction::coinbase(
+            block_index,
+            vec![Output {
+                to_address: to_address.to_owned(),
+                value: u64::from(block_index) + 1,
+                locking_script: Script::new(),
+            }],
+        )
+    }
+
+    fn create_payment() -> Transaction {
+        Transaction {
+            inputs: vec![spend(&create_transaction(0, "Alice"), 0)],
+            outputs: vec![Output {
+                to_address: "Chris".to_owned(),
+                value: 1,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        }
+    }
+
+    fn create_blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![create_transaction(0, "Alice")]);
+        add_block(
+            &mut blockchain,
+            vec![create_transaction(1, "Bob"), create_payment()],
+        );
+        add_block(&mut blockchain, vec![create_transaction(2, "Chris")]);
+
+        blockchain
+    }
+
+    /**
+     * A node that serves headers in small batches and counts the requests.
+     */
+    struct CountingNode<'a> {
+        blockchain: &'a Blockchain,
+        requests: Cell<u32>,
+    }
+
+    impl FullNode for CountingNode<'_> {
+        fn headers(&self, start: u32, count: u32) -> Vec<Block> {
+            self.requests.set(self.requests.get() + 1);
+
+            self.blockchain.headers(start, count)
+        }
+
+        fn transaction_proof(&self, id: &TransactionId) -> Option<TransactionProof> {
+            self.blockchain.transaction_proof(id)
+        }
+    }
+
+    #[test]
+    fn sync_headers() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+
+        let result = light_client.sync_headers(&blockchain);
+
+        assert_eq!(Ok(3), result);
+        assert_eq!(3, light_client.headers().len());
+        assert!(light_client
+            .headers()
+            .iter()
+            .all(|header| header.transactions.is_empty()));
+        assert_eq!(
+            blockchain.tip().unwrap().hash,
+            light_client.tip().unwrap().hash
+        );
+        assert_eq!(blockchain.chain_work(), light_client.chain_work());
+        assert_eq!(Ok(0), light_client.sync_headers(&blockchain));
+    }
+
+    #[test]
+    fn sync_headers_in_batches() {
+        let mut blockchain = Blockchain::new();
+        for _ in 0..HEADER_BATCH_SIZE + 1 {
+            add_block(&mut blockchain, vec![]);
+        }
+        let node = CountingNode {
+            blockchain: &blockchain,
+            requests: Cell::new(0),
+        };
+        let mut light_client = LightClient::new();
+
+        let result = light_client.sync_headers(&node);
+
+        assert_eq!(Ok(HEADER_BATCH_SIZE + 1), result);
+        assert_eq!(2, node.requests.get());
+    }
+
+    #[test]
+    fn reject_header_with_invalid_proof_of_work() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+        let mut header = blockchain.header(0).unwrap().header();
+        header.bits = 0x0100_0001;
+
+        let result = light_client.add_header(header);
+
+        assert_eq!(Err(BlockValidationErr::InvalidHash), result);
+        assert!(light_client.headers().is_empty());
+    }
+
+    #[test]
+    fn reject_header_with_mismatched_hash() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+        let mut header = blockchain.header(0).unwrap().header();
+        header.hash = Hash256::ZERO;
+
+        assert_eq!(
+            Err(BlockValidationErr::InvalidHash),
+            light_client.add_header(header)
+        );
+    }
+
+    #[test]
+    fn reject_unlinked_header() {
+        let blockchain = create_blockchain();
+        let mut other_blockchain = Blockchain::new();
+        add_block(&mut other_blockchain, vec![create_transaction(0, "Dave")]);
+        let unlinked_header = create_next_block(&other_blockchain, vec![]);
+        let mut light_client = LightClient::new();
+        light_client
+            .add_header(blockchain.header(0).unwrap().header())
+            .unwrap();
+
+        let result = light_client.add_header(unlinked_header);
+
+        assert_eq!(Err(BlockValidationErr::MismatchedPreviousHash), result);
+        assert_eq!(1, light_client.headers().len());
+    }
+
+    #[test]
+    fn reject_mismatched_genesis_block() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::with_genesis_hash(Hash256::ZERO);
+
+        let result = light_client.sync_headers(&blockchain);
+
+        assert_eq!(Err(BlockValidationErr::MismatchedGenesisBlock), result);
+    }
+
+    #[test]
+    fn reject_header_above_max_target() {
+        let blockchain = create_blockchain();
+        let mut light_client =
+            LightClient::new().with_max_target(Target::from_compact(0x2000_ffff).unwrap());
+
+        let result = light_client.sync_headers(&blockchain);
+
+        assert_eq!(Err(BlockValidationErr::InsufficientWork), result);
+        assert!(light_client.headers().is_empty());
+        assert_eq!(
+            Ok(3),
+            LightClient::new()
+                .with_max_target(Target::from_compact(BITS).unwrap())
+                .sync_headers(&blockchain)
+        );
+    }
+
+    #[test]
+    fn reject_mismatched_checkpoint() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new().with_checkpoint(1, Hash256::ZERO);
+
+        let result = light_client.sync_headers(&blockchain);
+
+        assert_eq!(Err(BlockValidationErr::MismatchedCheckpoint), result);
+        assert_eq!(1, light_client.headers().len());
+        assert_eq!(
+            Ok(3),
+            LightClient::new()
+                .with_checkpoint(1, blockchain.header(1).unwrap().hash)
+                .sync_headers(&blockchain)
+        );
+    }
+
+    #[test]
+    fn verify_transaction() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+        light_client.sync_headers(&blockchain).unwrap();
+        let payment = create_payment();
+
+        let result = light_client
+            .verify_transaction(&blockchain, &payment.id())
+            .unwrap();
+
+        assert_eq!(blockchain.get_transaction(&payment.id()).unwrap(), result);
+        assert_eq!(2, result.confirmations);
+    }
+
+    #[test]
+    fn verify_transaction_with_chain_algorithm() {
+        let hash_algorithms = HashAlgorithms {
+            block: HashAlgorithm::Sha256,
+            transaction: HashAlgorithm::Keccak256,
+        };
+        let mut blockchain = Blockchain::with_hash_algorithms(hash_algorithms);
+        add_block(&mut blockchain, vec![create_transaction(0, "Alice")]);
+        add_block(&mut blockchain, vec![create_transaction(1, "Bob")]);
+        let mut light_client = LightClient::with_hash_algorithms(hash_algorithms);
+        light_client.sync_headers(&blockchain).unwrap();
+        let id = create_transaction(1, "Bob").id_with(HashAlgorithm::Keccak256);
+
+        let result = light_client.verify_transaction(&blockchain, &id).unwrap();
+
+        assert_eq!(1, result.location.block_index);
+        assert_eq!(1, result.confirmations);
+    }
+
+    #[test]
+    fn verify_unknown_transaction() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+        light_client.sync_headers(&blockchain).unwrap();
+
+        let result = light_client.verify_transaction(&blockchain, &Hash256::ZERO);
+
+        assert_eq!(Err(SpvErr::UnknownTransaction), result);
+    }
+
+    #[test]
+    fn verify_transaction_in_unknown_block() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+        light_client
+            .add_header(blockchain.header(0).unwrap().header())
+            .unwrap();
+
+        let result = light_client.verify_transaction(&blockchain, &create_payment().id());
+
+        assert_eq!(Err(SpvErr::UnknownBlock), result);
+    }
+
+    /**
+     * A node that alters the proofs that it serves.
+     */
+    struct DishonestNode<'a, F: Fn(&mut TransactionProof)> {
+        blockchain: &'a Blockchain,
+        alter: F,
+    }
+
+    impl<F: Fn(&mut TransactionProof)> FullNode for DishonestNode<'_, F> {
+        fn headers(&self, start: u32, count: u32) -> Vec<Block> {
+            self.blockchain.headers(start, count)
+        }
+
+        fn transaction_proof(&self, id: &TransactionId) -> Option<TransactionProof> {
+            let mut proof = self.blockchain.transaction_proof(id)?;
+            (self.alter)(&mut proof);
+
+            Some(proof)
+        }
+    }
+
+    #[test]
+    fn reject_tampered_proof() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+        light_client.sync_headers(&blockchain).unwrap();
+        let id = create_payment().id();
+
+        let other_transaction = DishonestNode {
+            blockchain: &blockchain,
+            alter: |proof: &mut TransactionProof| proof.transaction = create_transaction(0, "Eve"),
+        };
+        let other_sibling = DishonestNode {
+            blockchain: &blockchain,
+            alter: |proof: &mut TransactionProof| proof.proof.siblings[0] = Hash256::ZERO,
+        };
+        let other_block = DishonestNode {
+            blockchain: &blockchain,
+            alter: |proof: &mut TransactionProof| proof.block_index = 2,
+        };
+
+        assert_eq!(
+            Err(SpvErr::MismatchedTransaction),
+            light_client.verify_transaction(&other_transaction, &id)
+        );
+        assert_eq!(
+            Err(SpvErr::InvalidProof),
+            light_client.verify_transaction(&other_sibling, &id)
+        );
+        assert_eq!(
+            Err(SpvErr::UnknownBlock),
+            light_client.verify_transaction(&other_block, &id)
+        );
+    }
+
+    /**
+     * A node that serves a filter of another block.
+     */
+    struct WrongFilterNode<'a> {
+        blockchain: &'a Blockchain,
+    }
+
+    impl FullNode for WrongFilterNode<'_> {
+        fn headers(&self, start: u32, count: u32) -> Vec<Block> {
+            self.blockchain.headers(start, count)
+        }
+
+        fn transaction_proof(&self, id: &TransactionId) -> Option<TransactionProof> {
+            self.blockchain.transaction_proof(id)
+        }
+
+        fn filter_headers(&self, start: u32, count: u32) -> Vec<Hash256> {
+            self.blockchain.filter_headers(start, count)
+        }
+
+        fn block_filter(&self, index: u32) -> Option<BlockFilter> {
+            let mut filter = self.blockchain.block_filter(index + 1)?.clone();
+            filter.block_hash = self.blockchain.header(index)?.hash;
+
+            Some(filter)
+        }
+    }
+
+    fn create_blockchain_with_filters() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_block_filters();
+        for transactions in create_blockchain()
+            .blocks()
+            .iter()
+            .map(|block| block.transactions.clone())
+        {
+            add_block(&mut blockchain, transactions);
+        }
+
+        blockchain
+    }
+
+    #[test]
+    fn sync_filter_headers() {
+        let blockchain = create_blockchain_with_filters();
+        let mut light_client = LightClient::new();
+        light_client.sync_headers(&blockchain).unwrap();
+
+        let result = light_client.sync_filter_headers(&blockchain);
+
+        assert_eq!(3, result);
+        assert_eq!(
+            blockchain.filter_headers(0, 3),
+            light_client.filter_headers()
+        );
+        assert_eq!(0, light_client.sync_filter_headers(&blockchain));
+    }
+
+    #[test]
+    fn sync_filter_headers_up_to_headers() {
+        let blockchain = create_blockchain_with_filters();
+        let mut light_client = LightClient::new();
+        light_client
+            .add_header(blockchain.header(0).unwrap().header())
+            .unwrap();
+
+        assert_eq!(1, light_client.sync_filter_headers(&blockchain));
+    }
+
+    #[test]
+    fn sync_filter_headers_from_node_without_filters() {
+        let blockchain = create_blockchain();
+        let mut light_client = LightClient::new();
+        light_client.sync_headers(&blockchain).unwrap();
+
+        assert_eq!(0, light_client.sync_filter_headers(&blockchain));
+    }
+
+    #[test]
+    fn find_matching_blocks() {
+        let blockchain = create_blockchain_with_filters();
+        let mut light_client = LightClient::new();
+        light_client.sync_headers(&blockchain).unwrap();
+        light_client.sync_filter_headers(&blockchain);
+        let alice_output_hash =
+            spend(&create_transaction(0, "Alice"), 0).output_hash(HashAlgorithm::default());
+
+        assert_eq!(
+            Ok(vec![1, 2]),
+            light_client.matching_blocks(&blockchain, 0, &["Chris"], &[])
+        );
+        assert_eq!(
+            Ok(vec![1]),
+            light_client.matching_blocks(&blockchain, 0, &[], &[alice_output_hash])
+        );
+        assert_eq!(
+            Ok(vec![]),
+            light_client.matching_blocks(&blockchain, 0, &["Dave"], &[])
+        );
+    }
+
+    #[test]
+    fn reject_filter_that_mismatches_filter_header() {
+        let blockchain = create_blockchain_with_filters();
+        let mut light_client = LightClient::new();
+        light_client.sync_headers(&blockchain).unwrap();
+        light_client.sync_filter_headers(&blockchain);
+        let node = WrongFilterNode {
+            blockchain: &blockchain,
+        };
+
+        assert_eq!(
+            Err(SpvErr::InvalidFilter),
+            light_client.block_filter(&node, 0)
+        );
+        assert_eq!(
+            Err(SpvErr::UnknownFilter),
+            light_client.block_filter(&blockchain, 3)
+        );
+    }
+
+    #[test]
+    fn transaction_proof_round_trip() {
+        let blockchain = create_blockchain();
+        let proof = blockchain
+            .transaction_proof(&create_payment().id())
+            .unwrap();
+
+        assert_eq!(Ok(proof.clone()), TransactionProof::decode(&proof.encode()));
+    }
+}
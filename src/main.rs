@@ -1,4 +1,30 @@
-use blockchainlib::{now, transaction, Block, Blockchain, Hashable, Transaction};
+use blockchainlib::{
+    now, sign, transaction, Block, Blockchain, ExtendedPrivateKey, GenesisConfig, HashAlgorithms,
+    Hashable, Mnemonic, Script, SigningKey, Target, Transaction,
+};
+
+/**
+ * The mnemonic phrase of the example wallet. Real phrases must encode random
+ * entropy (see Mnemonic::from_entropy).
+ */
+const PHRASE: &str = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+/**
+ * Returns the signing key at the given path of the example wallet.
+ */
+fn signing_key(path: &str) -> SigningKey {
+    let mnemonic = PHRASE
+        .parse::<Mnemonic>()
+        .expect("Failed to parse the mnemonic phrase.");
+    let master_key = ExtendedPrivateKey::from_seed(&mnemonic.to_seed(""))
+        .expect("Failed to create the master key.");
+
+    master_key
+        .derive_path(path)
+        .expect("Failed to derive a key.")
+        .signing_key()
+        .clone()
+}
 
 #[allow(unused_assignments)]
 /**
@@ -6,54 +32,63 @@ use blockchainlib::{now, transaction, Block, Blockchain, Hashable, Transaction};
  *
  * We need to:
  *
- * 1. Create a genesis block with transactions.
+ * 1. Create a genesis block with transactions from the chain parameters.
  *
  * 2. Mine it.
  *
  * 3. Add it to the blockchain.
  *
  * 4. Create another block with more transactions (particularly some that use
- *    transactions from the first block).
+ *    transactions from the first block, which their owners sign).
  *
  * 5. Mine that one.
  *
  * 6. Add it to the blockchain.
  */
 fn main() {
-    // FF = 11111111 11111111 (2 bytes = 16 bits)
-    // 16 bytes * 8 bits = 128 bits
+    // The target is the 256-bit value that a block's hash must not exceed. A
+    // difficulty of 256 means that the most significant byte of the hash must
+    // be zero, so about 1 in 256 attempts succeeds.
     //
-    // If the difficulty were 0x0000_0000_0000_0000_0000_0000_0000_0000, then
-    // mining will never succeed because it's impossible to satisfy
-    // difficulty = 0 > some value of the block's hash.
+    // If the target were 0, then mining would (practically) never succeed
+    // because it's impossible to find a hash that is less than or equal to 0.
     // So we have to use a reasonable difficulty value for illustration
     // purposes.
-    let difficulty: u128 = 0x00ff_ffff_ffff_ffff_ffff_ffff_ffff_ffff;
+    let bits = Target::from_difficulty(256.0).to_compact();
 
-    let mut genesis_block = Block::new(
-        0,
-        now().expect("Failure to get the current time in milliseconds."),
-        vec![0; 32],
-        vec![Transaction {
-            inputs: vec![],
-            outputs: vec![
-                transaction::Output {
-                    to_address: "Alice".to_owned(),
-                    value: 1,
-                },
-                transaction::Output {
-                    to_address: "Bob".to_owned(),
-                    value: 2,
-                },
-            ],
-        }],
-        difficulty,
-    );
-    println!("Genesis block: {:?}", &genesis_block);
+    // Alice's and Bob's coins are locked to their public keys, so only they
+    // can spend them. Both keys come from one wallet, which can regenerate
+    // them from its mnemonic phrase.
+    let alice_key = signing_key("m/0'/0");
+    let bob_key = signing_key("m/0'/1");
+    let alice_output = transaction::Output {
+        to_address: "Alice".to_owned(),
+        value: 1,
+        locking_script: Script::pay_to_public_key_hash(alice_key.verifying_key()),
+    };
+    let bob_output = transaction::Output {
+        to_address: "Bob".to_owned(),
+        value: 2,
+        locking_script: Script::pay_to_public_key_hash(bob_key.verifying_key()),
+    };
 
-    println!("Genesis block before mining: {:?}", &genesis_block);
+    let genesis_config = GenesisConfig {
+        timestamp: now().expect("Failure to get the current time in milliseconds."),
+        outputs: vec![alice_output, bob_output],
+        bits,
+        message: Some("Blockchain in Rust".to_owned()),
+        hash_algorithms: HashAlgorithms::default(),
+    };
 
-    genesis_block.mine();
+    let genesis_block = genesis_config.build_block();
+    let genesis_coinbase = &genesis_block.transactions[0];
+    let transaction_algorithm = genesis_config.hash_algorithms.transaction;
+    let alice_coin = genesis_coinbase
+        .spend(0, transaction_algorithm)
+        .expect("The genesis block pays Alice.");
+    let bob_coin = genesis_coinbase
+        .spend(1, transaction_algorithm)
+        .expect("The genesis block pays Bob.");
 
     println!("Genesis block after mining: {:?}", &genesis_block);
 
@@ -61,51 +96,64 @@ fn main() {
 
     println!("Building a blockchain");
 
-    let mut last_hash = genesis_block.hash().clone();
-    let mut blockchain = Blockchain::new();
+    let mut last_hash = genesis_block.hash();
+    let mut blockchain = Blockchain::with_genesis_hash(last_hash);
+    blockchain.enable_transaction_index();
     blockchain
         .update_with_block(genesis_block)
         .expect("Failed to add the genesis block.");
 
+    let mut payment = Transaction {
+        inputs: vec![alice_coin, bob_coin],
+        outputs: vec![transaction::Output {
+            to_address: "Chris".to_owned(),
+            value: 3,
+            locking_script: Script::new(),
+        }],
+        lock_time: None,
+    };
+    let signature_hash = payment.signature_hash(blockchain.hash_algorithms().transaction);
+    for (input, key) in payment.inputs.iter_mut().zip([alice_key, bob_key].iter()) {
+        input.unlocking_script = Script::pay_to_public_key_hash_unlocking(
+            &sign(key, &signature_hash),
+            key.verifying_key(),
+        );
+    }
+
+    let payment_id = payment.id_with(blockchain.hash_algorithms().transaction);
+
     let mut block = Block::new(
         1,
         now().expect("Failure to get the current time in milliseconds."),
         last_hash,
         vec![
-            Transaction {
-                inputs: vec![],
-                outputs: vec![transaction::Output {
+            Transaction::coinbase(
+                1,
+                vec![transaction::Output {
                     to_address: "Chris".to_owned(),
                     value: 4,
+                    locking_script: Script::new(),
                 }],
-            },
-            Transaction {
-                inputs: vec![
-                    transaction::Output {
-                        to_address: "Alice".to_owned(),
-                        value: 1,
-                    },
-                    transaction::Output {
-                        to_address: "Bob".to_owned(),
-                        value: 2,
-                    },
-                ],
-                outputs: vec![transaction::Output {
-                    to_address: "Chris".to_owned(),
-                    value: 3,
-                }],
-            },
+            ),
+            payment,
         ],
-        difficulty,
+        bits,
     );
 
     block.mine();
 
     println!("Mined block {:?}", &block);
 
-    last_hash = block.hash.clone();
+    last_hash = block.hash;
 
     blockchain
         .update_with_block(block)
         .expect("Failed to add a block.");
+
+    if let Some(confirmed_payment) = blockchain.get_transaction(&payment_id) {
+        println!(
+            "Payment {} has {} confirmation(s)",
+            payment_id, confirmed_payment.confirmations
+        );
+    }
 }
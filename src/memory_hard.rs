@@ -0,0 +1,171 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+
+use crate::Hash256;
+
+/**
+ * The salt of memory-hard proof-of-work hashes. Both functions require one,
+ * but the block hash they're applied to is already unique.
+ */
+const SALT: &[u8] = b"blockchain-in-rust proof-of-work";
+
+/**
+ * A memory-hard function for proof-of-work. Computing it requires a tunable
+ * amount of memory, which makes specialized mining hardware (which is fast
+ * but has little memory per core) much less of an advantage than it is with
+ * SHA-256.
+ *
+ * - Scrypt: N = 2^log_n, r (block size) and p (parallelization). It uses
+ *   128 * r * N bytes of memory, e.g., 1 MiB for log_n = 10 and r = 8.
+ *
+ * - Argon2id: memory_kib (memory in KiB), iterations (passes over the
+ *   memory) and parallelism (lanes), e.g., 4 MiB for memory_kib = 4096.
+ *
+ * Verifying a block costs one evaluation of the function, which with the
+ * example parameters above takes about 2 milliseconds (see the
+ * memory_hard_verification benchmark), so verifying long chains remains
+ * practical. Mining costs one evaluation per nonce attempt.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryHardFunction {
+    Scrypt {
+        log_n: u8,
+        r: u32,
+        p: u32,
+    },
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+        parallelism: u32,
+    },
+}
+
+impl MemoryHardFunction {
+    /**
+     * Returns the result of the function for the given input, or None if
+     * the function's parameters are invalid.
+     */
+    pub fn hash(&self, input: &Hash256) -> Option<Hash256> {
+        let mut output = [0; 32];
+        match *self {
+            MemoryHardFunction::Scrypt { log_n, r, p } => {
+                let params = scrypt::Params::new(log_n, r, p, output.len()).ok()?;
+                scrypt::scrypt(input.as_bytes(), SALT, &params, &mut output).ok()?;
+            }
+            MemoryHardFunction::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                let params =
+                    Params::new(memory_kib, iterations, parallelism, Some(output.len())).ok()?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(input.as_bytes(), SALT, &mut output)
+                    .ok()?;
+            }
+        }
+
+        Some(Hash256(output))
+    }
+
+    /**
+     * Checks whether the function's result for the given block hash is less
+     * than or equal to the target that the given compact bits encode. A block
+     * hash never meets an invalid encoding or invalid function parameters.
+     */
+    pub fn check_difficulty(&self, block_hash: &Hash256, bits: u32) -> bool {
+        self.hash(block_hash)
+            .is_some_and(|hash| crate::check_difficulty(&hash, bits))
+    }
+}
+
+#[cfg(test)]
+mod memory_hard_function_tests {
+    use super::MemoryHardFunction;
+    use crate::{check_difficulty, Hash256};
+
+    const SCRYPT: MemoryHardFunction = MemoryHardFunction::Scrypt {
+        log_n: 4,
+        r: 1,
+        p: 1,
+    };
+    const ARGON2ID: MemoryHardFunction = MemoryHardFunction::Argon2id {
+        memory_kib: 8,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn hash_is_deterministic() {
+        for function in [SCRYPT, ARGON2ID].iter() {
+            let result1 = function.hash(&Hash256([1; 32]));
+            let result2 = function.hash(&Hash256([1; 32]));
+
+            assert!(result1.is_some(), "{:?}", function);
+            assert_eq!(result1, result2, "{:?}", function);
+            assert_ne!(result1, function.hash(&Hash256([2; 32])), "{:?}", function);
+        }
+    }
+
+    #[test]
+    fn hash_depends_on_function() {
+        let result1 = SCRYPT.hash(&Hash256([1; 32]));
+        let result2 = ARGON2ID.hash(&Hash256([1; 32]));
+
+        assert_ne!(result1, result2);
+    }
+
+    #[test]
+    fn hash_depends_on_parameters() {
+        let function = MemoryHardFunction::Scrypt {
+            log_n: 5,
+            r: 1,
+            p: 1,
+        };
+
+        let result = function.hash(&Hash256([1; 32]));
+
+        assert_ne!(SCRYPT.hash(&Hash256([1; 32])), result);
+    }
+
+    #[test]
+    fn hash_with_invalid_parameters() {
+        let scrypt = MemoryHardFunction::Scrypt {
+            log_n: 4,
+            r: 0,
+            p: 1,
+        };
+        let argon2id = MemoryHardFunction::Argon2id {
+            memory_kib: 0,
+            iterations: 1,
+            parallelism: 1,
+        };
+
+        assert_eq!(None, scrypt.hash(&Hash256([1; 32])));
+        assert_eq!(None, argon2id.hash(&Hash256([1; 32])));
+    }
+
+    #[test]
+    fn check_difficulty_uses_function_result() {
+        let block_hash = Hash256([1; 32]);
+        let hash = SCRYPT.hash(&block_hash).unwrap();
+
+        for bits in [0x2100_ffff, 0x2000_ffff, 0x1f00_ffff, 0x0201_0000].iter() {
+            let result = SCRYPT.check_difficulty(&block_hash, *bits);
+
+            assert_eq!(check_difficulty(&hash, *bits), result, "{:#x}", bits);
+        }
+    }
+
+    #[test]
+    fn check_difficulty_with_invalid_parameters() {
+        let function = MemoryHardFunction::Scrypt {
+            log_n: 4,
+            r: 0,
+            p: 1,
+        };
+
+        let result = function.check_difficulty(&Hash256([1; 32]), 0x2100_ffff);
+
+        assert!(!result);
+    }
+}
@@ -0,0 +1,952 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    BlockValidationErr, Blockchain, ChainStorage, FeeRate, Hash256, HashAlgorithm, Transaction,
+    TransactionId, DEFAULT_MIN_RELAY_FEE_RATE,
+};
+
+/**
+ * The number of transactions that a replacement may evict from a mempool by
+ * default, counting the transactions it conflicts with and their
+ * descendants.
+ */
+pub const DEFAULT_MAX_REPLACED_TRANSACTIONS: usize = 100;
+
+/**
+ * The number of unconfirmed ancestors that a transaction in a mempool may
+ * have by default, counting itself.
+ */
+pub const DEFAULT_MAX_ANCESTOR_COUNT: usize = 25;
+
+/**
+ * The combined serialized size of the unconfirmed ancestors that a
+ * transaction in a mempool may have by default, counting itself.
+ */
+pub const DEFAULT_MAX_ANCESTOR_SIZE: usize = 101_000;
+
+/**
+ * The errors of adding a transaction to a mempool.
+ */
+#[derive(Debug, PartialEq)]
+pub enum MempoolErr {
+    CoinbaseTransaction,
+    DuplicateTransaction,
+    ConflictingTransaction,
+    InvalidTransaction(BlockValidationErr),
+    InsufficientFee,
+    InsufficientReplacementFee,
+    TooManyReplacedTransactions,
+    TooManyAncestors,
+    AncestorSizeExceeded,
+}
+
+/**
+ * A pool of unconfirmed transactions, which a node relays to its peers and
+ * takes the transactions of the blocks it builds from. Peers that share their
+ * transactions end up with mostly the same pool, which is what lets them
+ * relay blocks compactly (see CompactBlock).
+ *
+ * Transactions are identified by their ids computed with the pool's
+ * algorithm (the chain's transaction algorithm). A transaction that spends
+ * an output that another transaction in the pool spends conflicts with it,
+ * and is only accepted as its replacement (see below). Every transaction is
+ * validated against the chain before it's accepted, as if it were in the
+ * next block after its parents in the pool (see
+ * Blockchain::validate_transaction), so the pool only holds transactions that
+ * can be mined, and the fees it compares are the ones that they really pay.
+ *
+ * Policy
+ * ------
+ *
+ * Besides the rules that every block must follow (consensus), a pool has
+ * rules of its own about what it's willing to relay (policy), which protect
+ * it from being flooded with transactions that nobody would mine. A block may
+ * contain a transaction that pays no fee, but the pool only accepts
+ * transactions that pay at least its minimum fee rate (see FeeRate) for
+ * their serialized size.
+ *
+ * A transaction may spend the outputs of other transactions in the pool, its
+ * parents, which must be confirmed before (or along with) it. A transaction's
+ * unconfirmed ancestors (its parents, their parents and so on) are limited in
+ * number and in combined size, counting the transaction itself, so that
+ * blocks can be assembled from packages of transactions with their ancestors
+ * (see BlockTemplate) without unbounded work.
+ *
+ * Replacement
+ * -----------
+ *
+ * A payment that pays too low a fee to be mined can be replaced by one that
+ * spends the same outputs and pays more (see PaymentBuilder::bump_fee). The
+ * replacement evicts the transactions it conflicts with and their
+ * descendants (the transactions that spend their outputs, and so on), which
+ * can't be confirmed alongside it. It's accepted if:
+ *
+ * 1. Its fee rate is higher than the fee rates of the transactions it
+ *    conflicts with, so miners prefer it.
+ * 2. Its fee covers the fees of every evicted transaction plus the minimum
+ *    fee for its own size, so the bandwidth of relaying it is paid for and
+ *    replacing a transaction over and over gets more expensive every time.
+ * 3. It evicts at most the pool's maximum number of replaced transactions,
+ *    so a replacement can't make the pool do an unbounded amount of work.
+ * 4. It doesn't spend the outputs of the transactions it evicts.
+ *
+ * A replacement is validated against the chain like any other transaction
+ * before anything is evicted, so a transaction that can't be mined (e.g., one
+ * whose unlocking scripts don't satisfy the outputs it spends) can't evict the
+ * ones that can.
+ */
+#[derive(Debug)]
+pub struct Mempool {
+    algorithm: HashAlgorithm,
+    min_fee_rate: FeeRate,
+    max_replaced_transactions: usize,
+    max_ancestor_count: usize,
+    max_ancestor_size: usize,
+    transactions: HashMap<TransactionId, Transaction>,
+    fees: HashMap<TransactionId, u64>,
+    spent_outputs: HashMap<Hash256, TransactionId>,
+    created_outputs: HashMap<Hash256, TransactionId>,
+}
+
+impl Default for Mempool {
+    fn default() -> Self {
+        Mempool::new()
+    }
+}
+
+impl Mempool {
+    pub fn new() -> Self {
+        Mempool::with_hash_algorithm(HashAlgorithm::Sha256)
+    }
+
+    /**
+     * Creates an empty pool that identifies transactions and the outputs
+     * they spend with the given algorithm.
+     */
+    pub fn with_hash_algorithm(algorithm: HashAlgorithm) -> Self {
+        Mempool {
+            algorithm,
+            min_fee_rate: DEFAULT_MIN_RELAY_FEE_RATE,
+            max_replaced_transactions: DEFAULT_MAX_REPLACED_TRANSACTIONS,
+            max_ancestor_count: DEFAULT_MAX_ANCESTOR_COUNT,
+            max_ancestor_size: DEFAULT_MAX_ANCESTOR_SIZE,
+            transactions: HashMap::new(),
+            fees: HashMap::new(),
+            spent_outputs: HashMap::new(),
+            created_outputs: HashMap::new(),
+        }
+    }
+
+    /**
+     * Returns the pool with the given minimum fee rate, below which it
+     * rejects transactions.
+     */
+    pub fn with_min_fee_rate(mut self, min_fee_rate: FeeRate) -> Self {
+        self.min_fee_rate = min_fee_rate;
+
+        self
+    }
+
+    /**
+     * Returns the pool with the given maximum number of transactions that a
+     * replacement may evict.
+     */
+    pub fn with_max_replaced_transactions(mut self, max_replaced_transactions: usize) -> Self {
+        self.max_replaced_transactions = max_replaced_transactions;
+
+        self
+    }
+
+    /**
+     * Returns the pool with the given maximum number of unconfirmed
+     * ancestors of a transaction, counting itself.
+     */
+    pub fn with_max_ancestor_count(mut self, max_ancestor_count: usize) -> Self {
+        self.max_ancestor_count = max_ancestor_count;
+
+        self
+    }
+
+    /**
+     * Returns the pool with the given maximum combined size of the
+     * unconfirmed ancestors of a transaction, counting itself.
+     */
+    pub fn with_max_ancestor_size(mut self, max_ancestor_size: usize) -> Self {
+        self.max_ancestor_size = max_ancestor_size;
+
+        self
+    }
+
+    /**
+     * Returns the algorithm that the pool's transaction ids are computed
+     * with.
+     */
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn min_fee_rate(&self) -> FeeRate {
+        self.min_fee_rate
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /**
+     * Returns a flag that states whether the pool has the transaction with
+     * the given id.
+     */
+    pub fn contains(&self, id: &TransactionId) -> bool {
+        self.transactions.contains_key(id)
+    }
+
+    /**
+     * Returns the transaction with the given id, if the pool has it.
+     */
+    pub fn transaction(&self, id: &TransactionId) -> Option<&Transaction> {
+        self.transactions.get(id)
+    }
+
+    /**
+     * Returns the pool's transactions and their ids, in no particular order.
+     */
+    pub fn transactions(&self) -> impl Iterator<Item = (&TransactionId, &Transaction)> {
+        self.transactions.iter()
+    }
+
+    /**
+     * Returns the fee of the transaction with the given id, which the chain
+     * computed from the outputs it spends when the transaction was added.
+     */
+    pub fn fee(&self, id: &TransactionId) -> Option<u64> {
+        self.fees.get(id).copied()
+    }
+
+    fn fee_rate(&self, id: &TransactionId) -> FeeRate {
+        FeeRate::of(self.fees[id], self.transactions[id].size())
+    }
+
+    /**
+     * Returns the ids of the unconfirmed ancestors of the transaction with
+     * the given id: the transactions in the pool whose outputs it spends,
+     * the transactions whose outputs they spend, and so on.
+     */
+    pub fn ancestors(&self, id: &TransactionId) -> HashSet<TransactionId> {
+        match self.transactions.get(id) {
+            Some(transaction) => self.find_ancestors(transaction),
+            None => HashSet::new(),
+        }
+    }
+
+    /**
+     * Returns the ids of the transactions in the pool that the given
+     * transaction descends from.
+     */
+    fn find_ancestors(&self, transaction: &Transaction) -> HashSet<TransactionId> {
+        let mut ancestor_ids = HashSet::new();
+        let mut unvisited = vec![transaction];
+        while let Some(transaction) = unvisited.pop() {
+            for output in transaction.input_hashes(self.algorithm) {
+                if let Some(parent_id) = self.created_outputs.get(&output) {
+                    if ancestor_ids.insert(*parent_id) {
+                        unvisited.push(&self.transactions[parent_id]);
+                    }
+                }
+            }
+        }
+
+        ancestor_ids
+    }
+
+    /**
+     * Returns the hashes of the outputs that the transaction spends that were
+     * created by transactions in the pool, which the chain doesn't have yet.
+     */
+    pub(crate) fn unconfirmed_outputs(&self, transaction: &Transaction) -> HashSet<Hash256> {
+        transaction
+            .input_hashes(self.algorithm)
+            .into_iter()
+            .filter(|output| self.created_outputs.contains_key(output))
+            .collect()
+    }
+
+    /**
+     * Adds the transaction to the pool and returns its id, replacing the
+     * transactions it conflicts with (and their descendants). Coinbase
+     * transactions (which only belong in blocks), transactions that the chain
+     * (whose transaction algorithm must be the pool's) wouldn't accept in its
+     * next block, transactions that pay less than the minimum fee rate,
+     * transactions with too many unconfirmed ancestors and conflicting
+     * transactions that don't meet the rules of replacement are rejected.
+     */
+    pub fn add_transaction<S: ChainStorage>(
+        &mut self,
+        transaction: Transaction,
+        blockchain: &Blockchain<S>,
+    ) -> Result<TransactionId, MempoolErr> {
+        let id = transaction.id_with(self.algorithm);
+        if transaction.is_coinbase() {
+            return Err(MempoolErr::CoinbaseTransaction);
+        } else if self.transactions.contains_key(&id) {
+            return Err(MempoolErr::DuplicateTransaction);
+        }
+
+        let fee = blockchain
+            .validate_transaction(&transaction, &self.unconfirmed_outputs(&transaction))
+            .map_err(MempoolErr::InvalidTransaction)?;
+        if fee < self.min_fee_rate.fee(transaction.size()) {
+            return Err(MempoolErr::InsufficientFee);
+        }
+
+        let ancestor_ids = self.find_ancestors(&transaction);
+        let ancestor_size: usize = ancestor_ids
+            .iter()
+            .map(|id| self.transactions[id].size())
+            .sum();
+        if ancestor_ids.len() + 1 > self.max_ancestor_count {
+            return Err(MempoolErr::TooManyAncestors);
+        } else if ancestor_size + transaction.size() > self.max_ancestor_size {
+            return Err(MempoolErr::AncestorSizeExceeded);
+        }
+
+        let spent_outputs = transaction.input_hashes(self.algorithm);
+        let conflicting_ids: HashSet<TransactionId> = spent_outputs
+            .iter()
+            .filter_map(|output| self.spent_outputs.get(output))
+            .copied()
+            .collect();
+        let replaced_ids = if conflicting_ids.is_empty() {
+            vec![]
+        } else {
+            self.find_replaced_transactions(&transaction, fee, &spent_outputs, conflicting_ids)?
+        };
+
+        let created_outputs = transaction.output_hashes(self.algorithm);
+        for replaced_id in &replaced_ids {
+            self.remove_transaction(replaced_id);
+        }
+        self.spent_outputs
+            .extend(spent_outputs.into_iter().map(|output| (output, id)));
+        self.created_outputs
+            .extend(created_outputs.into_iter().map(|output| (output, id)));
+        self.transactions.insert(id, transaction);
+        self.fees.insert(id, fee);
+
+        Ok(id)
+    }
+
+    /**
+     * Returns the ids of the transactions that the given transaction, which
+     * pays the given fee and spends the given outputs, replaces: the
+     * transactions it conflicts with and their descendants. Returns an error
+     * if it doesn't meet the rules of replacement.
+     */
+    fn find_replaced_transactions(
+        &self,
+        transaction: &Transaction,
+        fee: u64,
+        spent_outputs: &HashSet<Hash256>,
+        conflicting_ids: HashSet<TransactionId>,
+    ) -> Result<Vec<TransactionId>, MempoolErr> {
+        let fee_rate = FeeRate::of(fee, transaction.size());
+        if conflicting_ids
+            .iter()
+            .any(|id| self.fee_rate(id) >= fee_rate)
+        {
+            return Err(MempoolErr::InsufficientReplacementFee);
+        }
+
+        let replaced_ids = self
+            .find_descendants(conflicting_ids, self.max_replaced_transactions)
+            .ok_or(MempoolErr::TooManyReplacedTransactions)?;
+
+        let created_outputs: HashSet<Hash256> = replaced_ids
+            .iter()
+            .flat_map(|id| self.transactions[id].output_hashes(self.algorithm))
+            .collect();
+        if spent_outputs
+            .iter()
+            .any(|output| created_outputs.contains(output))
+        {
+            return Err(MempoolErr::ConflictingTransaction);
+        }
+
+        let replaced_fee = replaced_ids
+            .iter()
+            .map(|id| self.fees[id])
+            .fold(0u64, u64::saturating_add);
+        let min_fee = replaced_fee.saturating_add(self.min_fee_rate.fee(transaction.size()));
+        if fee < min_fee {
+            return Err(MempoolErr::InsufficientReplacementFee);
+        }
+
+        Ok(replaced_ids)
+    }
+
+    /**
+     * Returns the ids of the given transactions and of their descendants in
+     * the pool, or None as soon as there are more than the given number of
+     * them.
+     */
+    fn find_descendants(
+        &self,
+        ids: HashSet<TransactionId>,
+        max_count: usize,
+    ) -> Option<Vec<TransactionId>> {
+        let mut descendant_ids: Vec<TransactionId> = ids.iter().copied().collect();
+        let mut visited_ids = ids;
+        let mut next = 0;
+        while next < descendant_ids.len() {
+            if descendant_ids.len() > max_count {
+                return None;
+            }
+
+            for output in self.transactions[&descendant_ids[next]].output_hashes(self.algorithm) {
+                if let Some(child_id) = self.spent_outputs.get(&output) {
+                    if visited_ids.insert(*child_id) {
+                        descendant_ids.push(*child_id);
+                    }
+                }
+            }
+            next += 1;
+        }
+        if descendant_ids.len() > max_count {
+            return None;
+        }
+
+        Some(descendant_ids)
+    }
+
+    /**
+     * Removes the transaction with the given id from the pool and returns it.
+     */
+    pub fn remove_transaction(&mut self, id: &TransactionId) -> Option<Transaction> {
+        let transaction = self.transactions.remove(id)?;
+        self.fees.remove(id);
+        for output in transaction.input_hashes(self.algorithm) {
+            self.spent_outputs.remove(&output);
+        }
+        for output in transaction.output_hashes(self.algorithm) {
+            self.created_outputs.remove(&output);
+        }
+
+        Some(transaction)
+    }
+
+    /**
+     * Removes the given transactions, which were confirmed by a block that
+     * was connected to the chain, and the transactions that spend the same
+     * outputs as them, along with their descendants, which can't be confirmed
+     * anymore.
+     */
+    pub fn remove_confirmed_transactions(&mut self, transactions: &[Transaction]) {
+        for transaction in transactions {
+            self.remove_transaction(&transaction.id_with(self.algorithm));
+
+            let conflicting_ids = transaction
+                .input_hashes(self.algorithm)
+                .iter()
+                .filter_map(|output| self.spent_outputs.get(output).copied())
+                .collect();
+            let evicted_ids = self
+                .find_descendants(conflicting_ids, self.transactions.len())
+                .expect("the descendants are in the pool");
+            for evicted_id in &evicted_ids {
+                self.remove_transaction(evicted_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod mempool_tests {
+    use std::collections::HashSet;
+
+    use super::{Mempool, MempoolErr};
+
+    use crate::test_support::{add_block, input, output, payment, signing_key, spend};
+    use crate::transaction::{Input, LockTime, Output};
+    use crate::{
+        sign, BlockValidationErr, Blockchain, FeeRate, HashAlgorithm, HashAlgorithms, Script,
+        ScriptErr, Transaction, DEFAULT_MIN_RELAY_FEE_RATE,
+    };
+
+    /**
+     * The coins that the genesis block pays, by address and value.
+     */
+    const COINS: [(&str, u64); 8] = [
+        ("Alice", 5),
+        ("Alice", 50),
+        ("Alice", 100),
+        ("Bob", 1),
+        ("Chris", 3),
+        ("Chris", 50),
+        ("Eve", 2),
+        ("Eve", 50),
+    ];
+
+    /**
+     * Returns the coinbase transaction of the genesis block, which pays the
+     * coins and 50 coins to Frank, locked by his public key hash.
+     */
+    fn genesis_coinbase() -> Transaction {
+        let frank_output = Output {
+            to_address: "Frank".to_owned(),
+            value: 50,
+            locking_script: Script::pay_to_public_key_hash(signing_key(1).verifying_key()),
+        };
+        let outputs = COINS
+            .iter()
+            .map(|(to_address, value)| output(to_address, *value))
+            .chain(std::iter::once(frank_output))
+            .collect();
+
+        Transaction::coinbase(0, outputs)
+    }
+
+    fn create_blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![genesis_coinbase()]);
+
+        blockchain
+    }
+
+    /**
+     * Returns an input that spends the coin that pays the given value to the
+     * given address.
+     */
+    fn coin(to_address: &str, value: u64) -> Input {
+        let position = COINS
+            .iter()
+            .position(|coin| *coin == (to_address, value))
+            .unwrap();
+
+        spend(&genesis_coinbase(), position)
+    }
+
+    /**
+     * Returns Frank's payment of the given value to Bob, signed if requested.
+     */
+    fn frank_payment(value: u64, signed: bool) -> Transaction {
+        let mut transaction = payment(spend(&genesis_coinbase(), COINS.len()), "Bob", value);
+        if signed {
+            let signing_key = signing_key(1);
+            let signature = sign(
+                &signing_key,
+                &transaction.signature_hash(Default::default()),
+            );
+            transaction.inputs[0].unlocking_script =
+                Script::pay_to_public_key_hash_unlocking(&signature, signing_key.verifying_key());
+        }
+
+        transaction
+    }
+
+    #[test]
+    fn add_transaction() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let transaction = payment(coin("Alice", 5), "Bob", 4);
+
+        let result = mempool.add_transaction(transaction.clone(), &blockchain);
+
+        assert_eq!(Ok(transaction.id()), result);
+        assert_eq!(1, mempool.len());
+        assert!(mempool.contains(&transaction.id()));
+        assert_eq!(Some(&transaction), mempool.transaction(&transaction.id()));
+        assert_eq!(Some(1), mempool.fee(&transaction.id()));
+    }
+
+    #[test]
+    fn ids_use_given_algorithm() {
+        let mut blockchain =
+            Blockchain::with_hash_algorithms(HashAlgorithms::uniform(HashAlgorithm::Blake3));
+        add_block(&mut blockchain, vec![genesis_coinbase()]);
+        let mut mempool = Mempool::with_hash_algorithm(HashAlgorithm::Blake3);
+        let input = genesis_coinbase().spend(0, HashAlgorithm::Blake3).unwrap();
+        let transaction = payment(input, "Bob", 4);
+
+        let result = mempool.add_transaction(transaction.clone(), &blockchain);
+
+        assert_eq!(Ok(transaction.id_with(HashAlgorithm::Blake3)), result);
+    }
+
+    #[test]
+    fn reject_invalid_transactions() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let transaction = payment(coin("Alice", 5), "Bob", 4);
+        mempool
+            .add_transaction(transaction.clone(), &blockchain)
+            .unwrap();
+        let coinbase = Transaction {
+            inputs: vec![],
+            outputs: vec![output("Alice", 1)],
+            lock_time: None,
+        };
+
+        assert_eq!(
+            Err(MempoolErr::DuplicateTransaction),
+            mempool.add_transaction(transaction, &blockchain)
+        );
+        assert_eq!(
+            Err(MempoolErr::InsufficientReplacementFee),
+            mempool.add_transaction(payment(coin("Alice", 5), "Chris", 4), &blockchain)
+        );
+        assert_eq!(
+            Err(MempoolErr::InvalidTransaction(
+                BlockValidationErr::InsufficientInputValue
+            )),
+            mempool.add_transaction(payment(coin("Bob", 1), "Chris", 2), &blockchain)
+        );
+        assert_eq!(
+            Err(MempoolErr::CoinbaseTransaction),
+            mempool.add_transaction(coinbase, &blockchain)
+        );
+        assert_eq!(1, mempool.len());
+    }
+
+    #[test]
+    fn reject_transactions_that_chain_rejects() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let mut non_final = payment(coin("Alice", 5), "Bob", 4);
+        non_final.lock_time = Some(LockTime::BlockIndex(2));
+
+        assert_eq!(
+            Err(MempoolErr::InvalidTransaction(
+                BlockValidationErr::InvalidInput
+            )),
+            mempool.add_transaction(payment(input("Alice", 5), "Bob", 4), &blockchain)
+        );
+        assert_eq!(
+            Err(MempoolErr::InvalidTransaction(
+                BlockValidationErr::NonFinalTransaction
+            )),
+            mempool.add_transaction(non_final, &blockchain)
+        );
+        assert_eq!(
+            Err(MempoolErr::InvalidTransaction(
+                BlockValidationErr::InvalidScript(ScriptErr::StackUnderflow)
+            )),
+            mempool.add_transaction(frank_payment(40, false), &blockchain)
+        );
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn reject_transaction_below_min_fee_rate() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let free = payment(coin("Alice", 5), "Bob", 5);
+
+        let result = mempool.add_transaction(free.clone(), &blockchain);
+
+        assert_eq!(DEFAULT_MIN_RELAY_FEE_RATE, mempool.min_fee_rate());
+        assert_eq!(Err(MempoolErr::InsufficientFee), result);
+        assert!(Mempool::new()
+            .with_min_fee_rate(FeeRate(0))
+            .add_transaction(free, &blockchain)
+            .is_ok());
+    }
+
+    #[test]
+    fn min_fee_rate_depends_on_size() {
+        let blockchain = create_blockchain();
+        let transaction = payment(coin("Alice", 100), "Bob", 90);
+        let fee_rate = FeeRate::of(10, transaction.size());
+        let mut larger = transaction.clone();
+        larger.outputs[0].to_address = "Bob".repeat(100);
+
+        assert!(Mempool::new()
+            .with_min_fee_rate(fee_rate)
+            .add_transaction(transaction, &blockchain)
+            .is_ok());
+        assert_eq!(
+            Err(MempoolErr::InsufficientFee),
+            Mempool::new()
+                .with_min_fee_rate(fee_rate)
+                .add_transaction(larger, &blockchain)
+        );
+    }
+
+    #[test]
+    fn ancestors() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let parent = payment(coin("Alice", 50), "Bob", 49);
+        let child = payment(spend(&parent, 0), "Chris", 48);
+        let grandchild = payment(spend(&child, 0), "Dave", 47);
+        let unrelated = payment(coin("Eve", 50), "Dave", 49);
+        for transaction in [&parent, &child, &grandchild, &unrelated].iter() {
+            mempool
+                .add_transaction((*transaction).clone(), &blockchain)
+                .unwrap();
+        }
+
+        assert_eq!(
+            vec![parent.id(), child.id()]
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            mempool.ancestors(&grandchild.id())
+        );
+        assert_eq!(
+            vec![parent.id()].into_iter().collect::<HashSet<_>>(),
+            mempool.ancestors(&child.id())
+        );
+        assert!(mempool.ancestors(&parent.id()).is_empty());
+        assert!(mempool.ancestors(&unrelated.id()).is_empty());
+
+        mempool.remove_confirmed_transactions(&[parent]);
+
+        assert!(mempool.ancestors(&child.id()).is_empty());
+    }
+
+    #[test]
+    fn reject_transaction_with_too_many_ancestors() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new().with_max_ancestor_count(3);
+        let parent = payment(coin("Alice", 50), "Bob", 49);
+        let child = payment(spend(&parent, 0), "Chris", 48);
+        let grandchild = payment(spend(&child, 0), "Dave", 47);
+        for transaction in [&parent, &child, &grandchild].iter() {
+            mempool
+                .add_transaction((*transaction).clone(), &blockchain)
+                .unwrap();
+        }
+
+        let result =
+            mempool.add_transaction(payment(spend(&grandchild, 0), "Eve", 46), &blockchain);
+
+        assert_eq!(Err(MempoolErr::TooManyAncestors), result);
+        assert_eq!(3, mempool.len());
+    }
+
+    #[test]
+    fn reject_transaction_with_too_large_ancestors() {
+        let blockchain = create_blockchain();
+        let parent = payment(coin("Alice", 50), "Bob", 49);
+        let child = payment(spend(&parent, 0), "Chris", 48);
+        let mut mempool = Mempool::new().with_max_ancestor_size(parent.size() + child.size() - 1);
+        mempool.add_transaction(parent, &blockchain).unwrap();
+
+        let result = mempool.add_transaction(child, &blockchain);
+
+        assert_eq!(Err(MempoolErr::AncestorSizeExceeded), result);
+        assert_eq!(1, mempool.len());
+    }
+
+    #[test]
+    fn replace_conflicting_transaction() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let original = payment(coin("Alice", 50), "Bob", 49);
+        let unrelated = payment(coin("Chris", 50), "Dave", 49);
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+        mempool
+            .add_transaction(unrelated.clone(), &blockchain)
+            .unwrap();
+        let replacement = payment(coin("Alice", 50), "Bob", 40);
+
+        let result = mempool.add_transaction(replacement.clone(), &blockchain);
+
+        assert_eq!(Ok(replacement.id()), result);
+        assert_eq!(2, mempool.len());
+        assert!(!mempool.contains(&original.id()));
+        assert!(mempool.contains(&unrelated.id()));
+    }
+
+    #[test]
+    fn replacement_evicts_descendants() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let parent = payment(coin("Alice", 50), "Bob", 49);
+        let child = payment(spend(&parent, 0), "Chris", 48);
+        let grandchild = payment(spend(&child, 0), "Dave", 47);
+        for transaction in [&parent, &child, &grandchild].iter() {
+            mempool
+                .add_transaction((*transaction).clone(), &blockchain)
+                .unwrap();
+        }
+        let replacement = payment(coin("Alice", 50), "Eve", 40);
+
+        let result = mempool.add_transaction(replacement.clone(), &blockchain);
+
+        assert_eq!(Ok(replacement.id()), result);
+        assert_eq!(1, mempool.len());
+        assert_eq!(
+            Err(MempoolErr::InvalidTransaction(
+                BlockValidationErr::InvalidInput
+            )),
+            mempool.add_transaction(payment(spend(&parent, 0), "Chris", 47), &blockchain)
+        );
+    }
+
+    #[test]
+    fn reject_replacement_with_lower_fee_rate() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        mempool
+            .add_transaction(payment(coin("Alice", 50), "Bob", 40), &blockchain)
+            .unwrap();
+        let mut replacement = payment(coin("Alice", 50), "Bob", 39);
+        replacement.outputs[0].to_address = "Bob".repeat(100);
+
+        let result = mempool.add_transaction(replacement.clone(), &blockchain);
+
+        assert!(replacement.fee() > 10);
+        assert_eq!(Err(MempoolErr::InsufficientReplacementFee), result);
+    }
+
+    #[test]
+    fn reject_replacement_that_does_not_pay_for_evicted_transactions() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let parent = payment(coin("Alice", 50), "Bob", 40);
+        let child = payment(spend(&parent, 0), "Chris", 20);
+        mempool.add_transaction(parent, &blockchain).unwrap();
+        mempool.add_transaction(child, &blockchain).unwrap();
+
+        let result = mempool.add_transaction(payment(coin("Alice", 50), "Bob", 25), &blockchain);
+
+        assert_eq!(Err(MempoolErr::InsufficientReplacementFee), result);
+        assert_eq!(2, mempool.len());
+        assert!(mempool
+            .add_transaction(payment(coin("Alice", 50), "Bob", 19), &blockchain)
+            .is_ok());
+    }
+
+    #[test]
+    fn reject_replacement_that_evicts_too_many_transactions() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new().with_max_replaced_transactions(2);
+        let parent = payment(coin("Alice", 50), "Bob", 49);
+        let child = payment(spend(&parent, 0), "Chris", 48);
+        let grandchild = payment(spend(&child, 0), "Dave", 47);
+        for transaction in [&parent, &child, &grandchild].iter() {
+            mempool
+                .add_transaction((*transaction).clone(), &blockchain)
+                .unwrap();
+        }
+
+        let result = mempool.add_transaction(payment(coin("Alice", 50), "Eve", 10), &blockchain);
+
+        assert_eq!(Err(MempoolErr::TooManyReplacedTransactions), result);
+        assert_eq!(3, mempool.len());
+        assert!(mempool
+            .add_transaction(payment(spend(&parent, 0), "Eve", 10), &blockchain)
+            .is_ok());
+    }
+
+    #[test]
+    fn reject_invalid_replacement() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let original = frank_payment(49, true);
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+
+        let result = mempool.add_transaction(frank_payment(10, false), &blockchain);
+
+        assert_eq!(
+            Err(MempoolErr::InvalidTransaction(
+                BlockValidationErr::InvalidScript(ScriptErr::StackUnderflow)
+            )),
+            result
+        );
+        assert!(mempool.contains(&original.id()));
+        assert!(mempool
+            .add_transaction(frank_payment(10, true), &blockchain)
+            .is_ok());
+    }
+
+    #[test]
+    fn reject_replacement_that_spends_replaced_outputs() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let original = payment(coin("Alice", 50), "Bob", 49);
+        mempool
+            .add_transaction(original.clone(), &blockchain)
+            .unwrap();
+        let replacement = Transaction {
+            inputs: vec![coin("Alice", 50), spend(&original, 0)],
+            outputs: vec![output("Chris", 10)],
+            lock_time: None,
+        };
+
+        let result = mempool.add_transaction(replacement, &blockchain);
+
+        assert_eq!(Err(MempoolErr::ConflictingTransaction), result);
+        assert_eq!(1, mempool.len());
+    }
+
+    #[test]
+    fn remove_transaction_frees_its_spent_outputs() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let transaction = payment(coin("Alice", 5), "Bob", 4);
+        mempool
+            .add_transaction(transaction.clone(), &blockchain)
+            .unwrap();
+
+        assert_eq!(
+            Some(transaction.clone()),
+            mempool.remove_transaction(&payment(coin("Alice", 5), "Bob", 4).id())
+        );
+        assert!(mempool.is_empty());
+        assert_eq!(None, mempool.fee(&transaction.id()));
+        assert!(mempool
+            .add_transaction(payment(coin("Alice", 5), "Chris", 4), &blockchain)
+            .is_ok());
+    }
+
+    #[test]
+    fn remove_confirmed_transactions_and_conflicts() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let confirmed = payment(coin("Alice", 5), "Bob", 4);
+        let conflicting = payment(coin("Chris", 3), "Dave", 2);
+        let unrelated = payment(coin("Eve", 2), "Dave", 1);
+        for transaction in [&confirmed, &conflicting, &unrelated].iter() {
+            mempool
+                .add_transaction((*transaction).clone(), &blockchain)
+                .unwrap();
+        }
+        let double_spend = payment(coin("Chris", 3), "Chris", 3);
+
+        mempool.remove_confirmed_transactions(&[confirmed, double_spend]);
+
+        assert_eq!(1, mempool.len());
+        assert!(mempool.contains(&unrelated.id()));
+    }
+
+    #[test]
+    fn remove_descendants_of_conflicts_with_confirmed_transactions() {
+        let blockchain = create_blockchain();
+        let mut mempool = Mempool::new();
+        let conflicting = payment(coin("Chris", 50), "Dave", 49);
+        let child = payment(spend(&conflicting, 0), "Eve", 48);
+        let grandchild = payment(spend(&child, 0), "Frank", 47);
+        let unrelated = payment(coin("Eve", 2), "Dave", 1);
+        for transaction in [&conflicting, &child, &grandchild, &unrelated].iter() {
+            mempool
+                .add_transaction((*transaction).clone(), &blockchain)
+                .unwrap();
+        }
+        let double_spend = payment(coin("Chris", 50), "Chris", 50);
+
+        mempool.remove_confirmed_transactions(&[double_spend]);
+
+        assert_eq!(1, mempool.len());
+        assert!(mempool.contains(&unrelated.id()));
+        assert!(mempool.unconfirmed_outputs(&grandchild).is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::ByteWriter;
+use crate::{Hash256, HashAlgorithm, Transaction, TransactionId};
+
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+
+/**
+ * Returns the root of the Merkle tree of the transactions, which a block's
+ * header commits to, or a hash of 32 zeros if there are none.
+ *
+ * The leaves are the transactions' ids and each node hashes its two children
+ * (see node_hash). A node without a sibling (the last one of a level with an
+ * odd number of nodes) is moved up to the next level as is, rather than
+ * hashed with a copy of itself, so two different lists of transactions can't
+ * have the same root. The ids and the tree are hashed with the given
+ * algorithm (the chain's transaction algorithm), so the root commits to the
+ * ids that the chain indexes (see Transaction::id_with).
+ */
+pub fn transaction_root(transactions: &[Transaction], algorithm: HashAlgorithm) -> Hash256 {
+    let mut level: Vec<Hash256> = transactions
+        .iter()
+        .map(|transaction| leaf_hash(&transaction.id_with(algorithm), algorithm))
+        .collect();
+    if level.is_empty() {
+        return Hash256::ZERO;
+    }
+
+    while level.len() > 1 {
+        level = next_level(&level, algorithm);
+    }
+
+    level[0]
+}
+
+/**
+ * A proof that a transaction is in a block: the transaction's position among
+ * the block's transactions, their number and the sibling of each node on the
+ * path from the transaction's leaf to the root (see transaction_root).
+ * Someone who only has the block's header, like a light client, checks it by
+ * recomputing the root from the transaction (see root) and comparing it with
+ * the header's transaction root.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct MerkleProof {
+    pub position: u32,
+    pub transaction_count: u32,
+    pub siblings: Vec<Hash256>,
+}
+
+impl MerkleProof {
+    /**
+     * Creates a proof that the transaction at the given position is among
+     * the given transactions, with a tree hashed with the given algorithm, or
+     * returns None if there is no such position.
+     */
+    pub fn new(
+        transactions: &[Transaction],
+        position: usize,
+        algorithm: HashAlgorithm,
+    ) -> Option<Self> {
+        if position >= transactions.len() {
+            return None;
+        }
+
+        let mut level: Vec<Hash256> = transactions
+            .iter()
+            .map(|transaction| leaf_hash(&transaction.id_with(algorithm), algorithm))
+            .collect();
+        let mut level_position = position;
+        let mut siblings = vec![];
+        while level.len() > 1 {
+            let sibling_position = level_position ^ 1;
+            if let Some(sibling) = level.get(sibling_position) {
+                siblings.push(*sibling);
+            }
+
+            level = next_level(&level, algorithm);
+            level_position /= 2;
+        }
+
+        Some(MerkleProof {
+            position: position as u32,
+            transaction_count: transactions.len() as u32,
+            siblings,
+        })
+    }
+
+    /**
+     * Returns the root that the proof leads to from the transaction with the
+     * given id (see Transaction::id_with), with a tree hashed with the given
+     * algorithm, or None if the proof doesn't have the shape of a tree of
+     * transaction_count transactions.
+     */
+    pub fn root(
+        &self,
+        transaction_id: &TransactionId,
+        algorithm: HashAlgorithm,
+    ) -> Option<Hash256> {
+        if self.position >= self.transaction_count {
+            return None;
+        }
+
+        let mut hash = leaf_hash(transaction_id, algorithm);
+        let mut position = self.position;
+        let mut count = self.transaction_count;
+        let mut siblings = self.siblings.iter();
+        while count > 1 {
+            let is_right_child = position % 2 == 1;
+            if is_right_child || position + 1 < count {
+                let sibling = siblings.next()?;
+                hash = if is_right_child {
+                    node_hash(sibling, &hash, algorithm)
+                } else {
+                    node_hash(&hash, sibling, algorithm)
+                };
+            }
+
+            position /= 2;
+            count = count.div_ceil(2);
+        }
+
+        if siblings.next().is_some() {
+            return None;
+        }
+
+        Some(hash)
+    }
+
+    /**
+     * Returns a flag that states whether the proof shows that the
+     * transaction is in a block with the given transaction root, which was
+     * computed with the given algorithm (see transaction_root).
+     */
+    pub fn verify(
+        &self,
+        transaction: &Transaction,
+        transaction_root: &Hash256,
+        algorithm: HashAlgorithm,
+    ) -> bool {
+        self.root(&transaction.id_with(algorithm), algorithm)
+            .as_ref()
+            == Some(transaction_root)
+    }
+}
+
+impl Encodable for MerkleProof {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.position.write_encoding(writer);
+        self.transaction_count.write_encoding(writer);
+        self.siblings.write_encoding(writer);
+    }
+}
+
+impl Decodable for MerkleProof {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(MerkleProof {
+            position: reader.read_u32()?,
+            transaction_count: reader.read_u32()?,
+            siblings: Vec::read_encoding(reader)?,
+        })
+    }
+}
+
+/**
+ * Returns the level of the tree above the given one: the hash of each pair of
+ * nodes, and the last node as is if it has no sibling.
+ */
+fn next_level(level: &[Hash256], algorithm: HashAlgorithm) -> Vec<Hash256> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right, algorithm),
+            [single] => *single,
+            _ => unreachable!("chunks have one or two nodes"),
+        })
+        .collect()
+}
+
+/**
+ * Returns the hash of a leaf, which is prefixed with a tag byte that differs
+ * from a node's, so that a leaf can't pass for a node (or vice versa).
+ */
+fn leaf_hash(transaction_id: &TransactionId, algorithm: HashAlgorithm) -> Hash256 {
+    let mut bytes = vec![LEAF_TAG];
+    bytes.extend_from_slice(transaction_id.as_bytes());
+
+    algorithm.digest(&bytes)
+}
+
+fn node_hash(left: &Hash256, right: &Hash256, algorithm: HashAlgorithm) -> Hash256 {
+    let mut bytes = vec![NODE_TAG];
+    bytes.extend_from_slice(left.as_bytes());
+    bytes.extend_from_slice(right.as_bytes());
+
+    algorithm.digest(&bytes)
+}
+
+#[cfg(test)]
+mod merkle_tests {
+    use super::{transaction_root, MerkleProof};
+    use crate::encoding::{Decodable, Encodable};
+    use crate::transaction::Output;
+    use crate::{Hash256, HashAlgorithm, Script, Transaction};
+
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+
+    fn create_transactions(count: u64) -> Vec<Transaction> {
+        (0..count)
+            .map(|value| Transaction {
+                inputs: vec![],
+                outputs: vec![Output {
+                    to_address: "Alice".to_owned(),
+                    value,
+                    locking_script: Script::new(),
+                }],
+                lock_time: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn root_of_no_transactions() {
+        assert_eq!(Hash256::ZERO, transaction_root(&[], ALGORITHM));
+    }
+
+    #[test]
+    fn root_depends_on_order() {
+        let mut transactions = create_transactions(3);
+        let root = transaction_root(&transactions, ALGORITHM);
+
+        transactions.swap(0, 2);
+
+        assert_ne!(root, transaction_root(&transactions, ALGORITHM));
+    }
+
+    #[test]
+    fn root_of_odd_count_differs_from_duplicated_last() {
+        let mut transactions = create_transactions(3);
+        let root = transaction_root(&transactions, ALGORITHM);
+
+        transactions.push(transactions[2].clone());
+
+        assert_ne!(root, transaction_root(&transactions, ALGORITHM));
+    }
+
+    #[test]
+    fn root_commits_to_ids_of_given_algorithm() {
+        let transactions = create_transactions(3);
+        let root = transaction_root(&transactions, HashAlgorithm::Blake3);
+        let proof = MerkleProof::new(&transactions, 1, HashAlgorithm::Blake3).unwrap();
+
+        let result = proof.root(
+            &transactions[1].id_with(HashAlgorithm::Blake3),
+            HashAlgorithm::Blake3,
+        );
+
+        assert_eq!(Some(root), result);
+        assert_ne!(root, transaction_root(&transactions, ALGORITHM));
+        assert!(proof.verify(&transactions[1], &root, HashAlgorithm::Blake3));
+        assert!(!proof.verify(&transactions[1], &root, ALGORITHM));
+    }
+
+    #[test]
+    fn prove_every_position() {
+        for count in 1..=9 {
+            let transactions = create_transactions(count);
+            let root = transaction_root(&transactions, ALGORITHM);
+
+            for (position, transaction) in transactions.iter().enumerate() {
+                let proof = MerkleProof::new(&transactions, position, ALGORITHM).unwrap();
+
+                assert!(proof.verify(transaction, &root, ALGORITHM));
+                assert_eq!(Ok(proof.clone()), MerkleProof::decode(&proof.encode()));
+            }
+        }
+    }
+
+    #[test]
+    fn reject_proof_of_other_transaction() {
+        let transactions = create_transactions(5);
+        let root = transaction_root(&transactions, ALGORITHM);
+
+        let proof = MerkleProof::new(&transactions, 1, ALGORITHM).unwrap();
+
+        assert!(!proof.verify(&transactions[2], &root, ALGORITHM));
+    }
+
+    #[test]
+    fn reject_proof_with_other_position() {
+        let transactions = create_transactions(5);
+        let root = transaction_root(&transactions, ALGORITHM);
+        let mut proof = MerkleProof::new(&transactions, 1, ALGORITHM).unwrap();
+
+        proof.position = 0;
+
+        assert!(!proof.verify(&transactions[1], &root, ALGORITHM));
+    }
+
+    #[test]
+    fn reject_malformed_proof() {
+        let transactions = create_transactions(5);
+        let proof = MerkleProof::new(&transactions, 4, ALGORITHM).unwrap();
+
+        let mut extra_sibling = proof.clone();
+        extra_sibling.siblings.push(Hash256::ZERO);
+        let mut outside_position = proof;
+        outside_position.position = 5;
+
+        assert_eq!(None, extra_sibling.root(&transactions[4].id(), ALGORITHM));
+        assert_eq!(
+            None,
+            outside_position.root(&transactions[4].id(), ALGORITHM)
+        );
+    }
+
+    #[test]
+    fn proof_is_none_for_missing_position() {
+        assert_eq!(
+            None,
+            MerkleProof::new(&create_transactions(2), 2, ALGORITHM)
+        );
+    }
+}
@@ -0,0 +1,377 @@
+use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
+
+use sha2::{Digest, Sha256, Sha512};
+
+/**
+ * The BIP39 English wordlist: 2048 words in alphabetical order, so a word's
+ * position encodes 11 bits.
+ */
+const WORDLIST: &str = include_str!("mnemonic/english.txt");
+
+/**
+ * The number of PBKDF2 rounds that turn a phrase into a seed.
+ */
+const SEED_ROUNDS: u32 = 2048;
+
+/**
+ * Errors that may occur when creating a mnemonic from entropy or parsing one
+ * from a phrase.
+ */
+#[derive(Debug, PartialEq)]
+pub enum MnemonicErr {
+    InvalidEntropyLength,
+    InvalidWordCount,
+    UnknownWord,
+    InvalidChecksum,
+}
+
+/**
+ * A mnemonic phrase that encodes random entropy as words (BIP39), so that a
+ * user can write down the seed of their wallet and type it back in.
+ *
+ * The entropy (128 to 256 bits, in steps of 32) is followed by a checksum
+ * (the first bits of its SHA-256 hash, one per 32 bits of entropy), and every
+ * 11 bits of the result select one of 2048 words, e.g., 128 bits make 12
+ * words. The checksum catches most typos when a phrase is typed back in.
+ *
+ * A phrase and an optional passphrase make a 512-bit seed, which is the seed
+ * of a wallet's master key (see ExtendedPrivateKey). A different passphrase
+ * makes a different seed, so the passphrase is a second factor that the
+ * written down phrase alone doesn't reveal. Passphrases are used as given:
+ * BIP39 normalizes them (NFKD) first, which only matters for non-ASCII ones.
+ */
+#[derive(Clone, PartialEq, Eq)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+}
+
+impl Debug for Mnemonic {
+    /**
+     * Returns a formatted result of the mnemonic, without its entropy, so
+     * that logging a mnemonic doesn't leak it (its phrase is only written by
+     * Display).
+     */
+    fn fmt(&self, buffer: &mut Formatter) -> fmt::Result {
+        write!(
+            buffer,
+            "Mnemonic[{} words]: <redacted>",
+            self.entropy.len() * 3 / 4
+        )
+    }
+}
+
+impl Mnemonic {
+    /**
+     * Returns the mnemonic of the given entropy, which must be 16, 20, 24, 28
+     * or 32 bytes long. The entropy must come from a cryptographically secure
+     * random number generator.
+     */
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, MnemonicErr> {
+        if entropy.len() < 16 || entropy.len() > 32 || !entropy.len().is_multiple_of(4) {
+            return Err(MnemonicErr::InvalidEntropyLength);
+        }
+
+        Ok(Mnemonic {
+            entropy: entropy.to_vec(),
+        })
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    /**
+     * Returns the words of the phrase.
+     */
+    pub fn words(&self) -> Vec<&'static str> {
+        let wordlist = wordlist();
+        let checksum = Sha256::digest(&self.entropy);
+        let bit_count = self.entropy.len() * 8 + self.entropy.len() / 4;
+        let bit = |position: usize| {
+            let byte = match self.entropy.get(position / 8) {
+                Some(byte) => *byte,
+                None => checksum[position / 8 - self.entropy.len()],
+            };
+
+            (byte >> (7 - position % 8)) & 1
+        };
+
+        (0..bit_count / 11)
+            .map(|word_index| {
+                let position = (0..11).fold(0, |position, offset| {
+                    position << 1 | usize::from(bit(word_index * 11 + offset))
+                });
+
+                wordlist[position]
+            })
+            .collect()
+    }
+
+    /**
+     * Returns the phrase: the words separated by spaces.
+     */
+    pub fn phrase(&self) -> String {
+        self.words().join(" ")
+    }
+
+    /**
+     * Returns the 512-bit seed of the phrase and the given passphrase (which
+     * may be empty): PBKDF2 with HMAC-SHA512 over the phrase, salted with
+     * "mnemonic" and the passphrase.
+     */
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let salt = format!("mnemonic{}", passphrase);
+        let mut seed = [0; 64];
+        pbkdf2::pbkdf2_hmac::<Sha512>(
+            self.phrase().as_bytes(),
+            salt.as_bytes(),
+            SEED_ROUNDS,
+            &mut seed,
+        );
+
+        seed
+    }
+}
+
+impl Display for Mnemonic {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.phrase())
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = MnemonicErr;
+
+    /**
+     * Parses a phrase of 12, 15, 18, 21 or 24 words separated by whitespace
+     * and checks its checksum.
+     */
+    fn from_str(phrase: &str) -> Result<Self, Self::Err> {
+        let wordlist = wordlist();
+        let positions = phrase
+            .split_whitespace()
+            .map(|word| {
+                wordlist
+                    .binary_search(&word)
+                    .map_err(|_| MnemonicErr::UnknownWord)
+            })
+            .collect::<Result<Vec<usize>, MnemonicErr>>()?;
+        if positions.len() < 12 || positions.len() > 24 || !positions.len().is_multiple_of(3) {
+            return Err(MnemonicErr::InvalidWordCount);
+        }
+
+        let bit_count = positions.len() * 11;
+        let checksum_bit_count = bit_count / 33;
+        let mut bytes = vec![0; bit_count.div_ceil(8)];
+        for (word_index, position) in positions.iter().enumerate() {
+            for offset in 0..11 {
+                if position >> (10 - offset) & 1 == 1 {
+                    let bit = word_index * 11 + offset;
+                    bytes[bit / 8] |= 0x80 >> (bit % 8);
+                }
+            }
+        }
+
+        let entropy_length = (bit_count - checksum_bit_count) / 8;
+        let mnemonic = Mnemonic::from_entropy(&bytes[..entropy_length])?;
+        let checksum_mask = (0xff00_u16 >> checksum_bit_count) as u8;
+        let expected_checksum = Sha256::digest(&mnemonic.entropy)[0] & checksum_mask;
+        if bytes[entropy_length] & checksum_mask != expected_checksum {
+            return Err(MnemonicErr::InvalidChecksum);
+        }
+
+        Ok(mnemonic)
+    }
+}
+
+fn wordlist() -> Vec<&'static str> {
+    WORDLIST.lines().collect()
+}
+
+#[cfg(test)]
+mod mnemonic_tests {
+    use super::{wordlist, Mnemonic, MnemonicErr};
+    use crate::ExtendedPrivateKey;
+
+    /**
+     * Entropies and phrases of the BIP39 test vectors.
+     */
+    const VECTORS: [(&str, &str); 12] = [
+        (
+            "00000000000000000000000000000000",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        ),
+        (
+            "7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        ),
+        (
+            "80808080808080808080808080808080",
+            "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+        ),
+        (
+            "ffffffffffffffffffffffffffffffff",
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+        ),
+        (
+            "000000000000000000000000000000000000000000000000",
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+        ),
+        (
+            "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+            "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+        ),
+        (
+            "9e885d952ad362caeb4efe34a8e91bd2",
+            "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+        ),
+        (
+            "6610b25967cdcca9d59875f5cb50b0ea75433311869e930b",
+            "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+        ),
+        (
+            "68a79eaca2324873eacc50cb9c6eca8cc68ea5d936f98787c60c7ebc74e6ce7c",
+            "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+        ),
+        (
+            "c0ba5a8e914111210f2bd131f3d5e08d",
+            "scheme spot photo card baby mountain device kick cradle pact join borrow",
+        ),
+        (
+            "7ac45cfe7722ee6c7ba84fbc2d5bd61b45cb2fe5eb65aa78",
+            "kiss carry display unusual confirm curtain upgrade antique rotate hello void custom frequent obey nut hole price segment",
+        ),
+        (
+            "15da872c95a13dd738fbf50e427583ad61f18fd99f628c417a61cf8343c90419",
+            "beyond stage sleep clip because twist token leaf atom beauty genius food business side grid unable middle armed observe pair crouch tonight away coconut",
+        ),
+    ];
+
+    #[test]
+    fn wordlist_is_sorted() {
+        let words = wordlist();
+
+        assert_eq!(2048, words.len());
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn phrase_of_test_vectors() {
+        for (entropy, phrase) in VECTORS.iter() {
+            let mnemonic = Mnemonic::from_entropy(&hex::decode(entropy).unwrap()).unwrap();
+
+            let result = mnemonic.phrase();
+
+            assert_eq!(*phrase, result);
+            assert_eq!(*phrase, mnemonic.to_string());
+        }
+    }
+
+    #[test]
+    fn parse_test_vectors() {
+        for (entropy, phrase) in VECTORS.iter() {
+            let result = phrase.parse::<Mnemonic>().unwrap();
+
+            assert_eq!(*entropy, hex::encode(result.entropy()));
+        }
+    }
+
+    #[test]
+    fn seed_of_test_vectors() {
+        let mnemonic = VECTORS[0].1.parse::<Mnemonic>().unwrap();
+        let other_mnemonic = VECTORS[5].1.parse::<Mnemonic>().unwrap();
+
+        assert_eq!(
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04",
+            hex::encode(&mnemonic.to_seed("TREZOR")[..])
+        );
+        assert_eq!(
+            "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc19a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4",
+            hex::encode(&mnemonic.to_seed("")[..])
+        );
+        assert_eq!(
+            "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad",
+            hex::encode(&other_mnemonic.to_seed("TREZOR")[..])
+        );
+    }
+
+    #[test]
+    fn parse_with_extra_whitespace() {
+        let phrase = format!("  {}\n", VECTORS[1].1.replace(' ', "\t "));
+
+        let result = phrase.parse::<Mnemonic>();
+
+        assert_eq!(VECTORS[1].1.parse::<Mnemonic>(), result);
+    }
+
+    #[test]
+    fn parse_with_unknown_word() {
+        let phrase = VECTORS[1].1.replace("winner", "winer");
+
+        let result = phrase.parse::<Mnemonic>();
+
+        assert_eq!(Err(MnemonicErr::UnknownWord), result);
+    }
+
+    #[test]
+    fn parse_with_invalid_word_count() {
+        for word_count in [0, 3, 11, 13, 27].iter() {
+            let phrase = vec!["abandon"; *word_count].join(" ");
+
+            let result = phrase.parse::<Mnemonic>();
+
+            assert_eq!(Err(MnemonicErr::InvalidWordCount), result, "{}", word_count);
+        }
+    }
+
+    #[test]
+    fn parse_with_invalid_checksum() {
+        let phrase = ["abandon"; 12].join(" ");
+
+        let result = phrase.parse::<Mnemonic>();
+
+        assert_eq!(Err(MnemonicErr::InvalidChecksum), result);
+    }
+
+    #[test]
+    fn from_entropy_with_invalid_length() {
+        for length in [0, 12, 15, 17, 36].iter() {
+            let result = Mnemonic::from_entropy(&vec![0; *length]);
+
+            assert_eq!(Err(MnemonicErr::InvalidEntropyLength), result, "{}", length);
+        }
+    }
+
+    /**
+     * The master key of the first test vector without a passphrase, i.e.,
+     * xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSGfubmSFDxo1kuHnLisriDvSnRRuL2Qrg5ggqHKNVpxR86QEC8w35uxmGoggxtQTPvfUu.
+     */
+    #[test]
+    fn master_key_of_phrase() {
+        let mnemonic = VECTORS[0].1.parse::<Mnemonic>().unwrap();
+
+        let result = ExtendedPrivateKey::from_seed(&mnemonic.to_seed("")).unwrap();
+
+        assert_eq!(
+            "7923408dadd3c7b56eed15567707ae5e5dca089de972e07f3b860450e2a3b70e",
+            hex::encode(result.chain_code())
+        );
+        assert_eq!(
+            "1837c1be8e2995ec11cda2b066151be2cfb48adf9e47b151d46adab3a21cdf67",
+            hex::encode(result.signing_key().to_bytes())
+        );
+    }
+
+    #[test]
+    fn debug_redacts_entropy() {
+        let entropy = "c0ba5a8e914111210f2bd131f3d5e08d";
+        let mnemonic = Mnemonic::from_entropy(&hex::decode(entropy).unwrap()).unwrap();
+
+        let result = format!("{:?}", mnemonic);
+
+        assert_eq!("Mnemonic[12 words]: <redacted>", result);
+        assert!(!result.contains(entropy));
+        assert!(!result.contains("scheme"));
+    }
+}
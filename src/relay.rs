@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::ByteWriter;
+use crate::{
+    u32_bytes, Block, BlockHash, BlockTransactions, BlockTransactionsRequest, BlockValidationErr,
+    Blockchain, ChainStorage, CompactBlock, CompactBlockErr, FeeEstimator, MemoryStorage, Mempool,
+    MempoolErr, PartialBlock, Transaction, TransactionId, DEFAULT_FEE_ESTIMATE_BLOCK_COUNT,
+};
+
+/**
+ * The maximum length of a message's encoding that a connection accepts.
+ */
+pub const MAX_MESSAGE_LENGTH: u32 = 32 * 1024 * 1024;
+
+/**
+ * The number of blocks that a node rebuilds from compact blocks at once. A
+ * node that's already waiting for the missing transactions of that many
+ * blocks asks for the full block instead.
+ */
+pub const MAX_PARTIAL_BLOCKS: usize = 8;
+
+/**
+ * The messages that nodes relay blocks with.
+ */
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Block(Block),
+    GetBlock(BlockHash),
+    CompactBlock(CompactBlock),
+    GetBlockTransactions(BlockTransactionsRequest),
+    BlockTransactions(BlockTransactions),
+}
+
+impl Encodable for Message {
+    /**
+     * Writes a tag byte (0 to 4, in the order of the variants) followed by
+     * the message's content.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        match self {
+            Message::Block(block) => {
+                writer.write(&[0]);
+                block.write_encoding(writer);
+            }
+            Message::GetBlock(block_hash) => {
+                writer.write(&[1]);
+                block_hash.write_encoding(writer);
+            }
+            Message::CompactBlock(compact_block) => {
+                writer.write(&[2]);
+                compact_block.write_encoding(writer);
+            }
+            Message::GetBlockTransactions(request) => {
+                writer.write(&[3]);
+                request.write_encoding(writer);
+            }
+            Message::BlockTransactions(transactions) => {
+                writer.write(&[4]);
+                transactions.write_encoding(writer);
+            }
+        }
+    }
+}
+
+impl Decodable for Message {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        match reader.read_u8()? {
+            0 => Ok(Message::Block(Block::read_encoding(reader)?)),
+            1 => Ok(Message::GetBlock(reader.read_hash()?)),
+            2 => Ok(Message::CompactBlock(CompactBlock::read_encoding(reader)?)),
+            3 => Ok(Message::GetBlockTransactions(
+                BlockTransactionsRequest::read_encoding(reader)?,
+            )),
+            4 => Ok(Message::BlockTransactions(
+                BlockTransactions::read_encoding(reader)?,
+            )),
+            _ => Err(DecodeErr::InvalidTag),
+        }
+    }
+}
+
+/**
+ * A connection to a peer over a byte stream (e.g., a TCP stream), which
+ * sends and receives messages prefixed with their length (4 bytes), and
+ * counts the bytes sent and received.
+ */
+pub struct Connection<S: Read + Write> {
+    stream: S,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl<S: Read + Write> Connection<S> {
+    pub fn new(stream: S) -> Self {
+        Connection {
+            stream,
+            bytes_sent: 0,
+            bytes_received: 0,
+        }
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let encoding = message.encode();
+        self.stream.write_all(&u32_bytes(encoding.len() as u32))?;
+        self.stream.write_all(&encoding)?;
+        self.stream.flush()?;
+        self.bytes_sent += 4 + encoding.len() as u64;
+
+        Ok(())
+    }
+
+    /**
+     * Waits for the next message. A stream that the peer closed returns an
+     * error of the UnexpectedEof kind.
+     */
+    pub fn receive(&mut self) -> io::Result<Message> {
+        let mut length = [0; 4];
+        self.stream.read_exact(&mut length)?;
+        let length = u32::from_le_bytes(length);
+        if length > MAX_MESSAGE_LENGTH {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "the message is too long",
+            ));
+        }
+
+        let mut encoding = vec![0; length as usize];
+        self.stream.read_exact(&mut encoding)?;
+        self.bytes_received += 4 + length as u64;
+
+        Message::decode(&encoding)
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, format!("{:?}", error)))
+    }
+}
+
+/**
+ * The errors of handling a message.
+ */
+#[derive(Debug, PartialEq)]
+pub enum RelayErr {
+    InvalidBlock(BlockValidationErr),
+    InvalidCompactBlock(CompactBlockErr),
+    UnknownBlock,
+}
+
+/**
+ * A node's side of block relay: its chain, its mempool and the blocks that
+ * it's rebuilding from compact blocks. It handles the messages it receives
+ * from its peers, whichever transport they come over (see Connection), and
+ * returns the replies to send back.
+ *
+ * A block is announced with a compact block (see announce_block). The
+ * receiver rebuilds it from its mempool and, if some transactions are
+ * missing, asks the announcer for them; if the rebuilt block doesn't match
+ * its transaction root (because of a short id collision), it asks for the
+ * full block instead. A compact block's header is validated as the header of
+ * the chain's next block (see Blockchain::validate_header) before the node
+ * keeps any state for it, so a peer can't make the node hold blocks that it
+ * could never connect, and the partial blocks are dropped once a block is
+ * connected, since they can't follow the new tip. Connecting a block removes
+ * its transactions from the mempool and records their fee rates (see
+ * FeeEstimator), so that the node can suggest fee rates to its wallet.
+ *
+ * The node keeps the index of each of its chain's blocks by hash, so that
+ * looking up a block that a peer asks for doesn't scan the chain.
+ */
+pub struct RelayNode<S: ChainStorage = MemoryStorage> {
+    blockchain: Blockchain<S>,
+    mempool: Mempool,
+    fee_estimator: FeeEstimator,
+    partial_blocks: HashMap<BlockHash, PartialBlock>,
+    block_indexes: HashMap<BlockHash, u32>,
+}
+
+impl<S: ChainStorage> RelayNode<S> {
+    /**
+     * Creates a node with the given chain and an empty mempool that uses the
+     * chain's transaction algorithm. The fee estimator starts with the
+     * chain's recent blocks (those that haven't been pruned).
+     */
+    pub fn new(blockchain: Blockchain<S>) -> Self {
+        let mempool = Mempool::with_hash_algorithm(blockchain.hash_algorithms().transaction);
+        let mut fee_estimator = FeeEstimator::new().with_min_fee_rate(mempool.min_fee_rate());
+        let block_count = blockchain.block_count();
+        let block_indexes = (0..block_count)
+            .filter_map(|index| blockchain.header(index).map(|header| (header.hash, index)))
+            .collect();
+        let first_index = block_count.saturating_sub(DEFAULT_FEE_ESTIMATE_BLOCK_COUNT as u32);
+        for index in first_index..block_count {
+            if let Some(block) = blockchain.block(index) {
+                fee_estimator.add_confirmed_transactions(&block.transactions);
+            }
+        }
+
+        RelayNode {
+            blockchain,
+            mempool,
+            fee_estimator,
+            partial_blocks: HashMap::new(),
+            block_indexes,
+        }
+    }
+
+    pub fn blockchain(&self) -> &Blockchain<S> {
+        &self.blockchain
+    }
+
+    pub fn mempool(&self) -> &Mempool {
+        &self.mempool
+    }
+
+    pub fn mempool_mut(&mut self) -> &mut Mempool {
+        &mut self.mempool
+    }
+
+    pub fn fee_estimator(&self) -> &FeeEstimator {
+        &self.fee_estimator
+    }
+
+    /**
+     * Returns a flag that states whether the node's chain has the block with
+     * the given hash.
+     */
+    pub fn has_block(&self, block_hash: &BlockHash) -> bool {
+        self.find_block_index(block_hash).is_some()
+    }
+
+    /**
+     * Adds the transaction to the node's mempool, validated against the
+     * node's chain (see Mempool::add_transaction), and returns its id.
+     */
+    pub fn add_transaction(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<TransactionId, MempoolErr> {
+        self.mempool.add_transaction(transaction, &self.blockchain)
+    }
+
+    /**
+     * Adds the block to the node's chain, removes its transactions from the
+     * mempool, records their fee rates and drops the partial blocks.
+     */
+    pub fn connect_block(&mut self, block: Block) -> Result<(), BlockValidationErr> {
+        let transactions = block.transactions.clone();
+        self.blockchain.update_with_block(block)?;
+        if let Some(tip) = self.blockchain.tip() {
+            self.block_indexes.insert(tip.hash, tip.index);
+        }
+        self.partial_blocks.clear();
+        self.mempool.remove_confirmed_transactions(&transactions);
+        self.fee_estimator.add_confirmed_transactions(&transactions);
+
+        Ok(())
+    }
+
+    /**
+     * Returns the compact block of the chain's block with the given index,
+     * with the given salt, to announce it to a peer, or None if the chain
+     * doesn't have the block's body.
+     */
+    pub fn announce_block(&self, index: u32, salt: u64) -> Option<Message> {
+        let block = self.blockchain.block(index)?;
+        let algorithm = self.blockchain.hash_algorithms().transaction;
+
+        Some(Message::CompactBlock(CompactBlock::new(
+            &block, salt, algorithm,
+        )))
+    }
+
+    /**
+     * Handles a message from a peer and returns the reply to send back, if
+     * any.
+     */
+    pub fn handle_message(&mut self, message: Message) -> Result<Option<Message>, RelayErr> {
+        match message {
+            Message::Block(block) => {
+                if self.has_block(&block.hash) {
+                    return Ok(None);
+                }
+                self.partial_blocks.remove(&block.hash);
+                self.connect_block(block).map_err(RelayErr::InvalidBlock)?;
+
+                Ok(None)
+            }
+            Message::GetBlock(block_hash) => {
+                let block = self.find_block(&block_hash).ok_or(RelayErr::UnknownBlock)?;
+
+                Ok(Some(Message::Block(block)))
+            }
+            Message::CompactBlock(compact_block) => {
+                if self.has_block(&compact_block.header.hash) {
+                    return Ok(None);
+                }
+                self.blockchain
+                    .validate_header(&compact_block.header)
+                    .map_err(RelayErr::InvalidBlock)?;
+                let partial_block = compact_block
+                    .rebuild(&self.mempool)
+                    .map_err(RelayErr::InvalidCompactBlock)?;
+                let request = partial_block.request();
+                if request.positions.is_empty() {
+                    let no_transactions = BlockTransactions {
+                        block_hash: request.block_hash,
+                        transactions: vec![],
+                    };
+
+                    return self.complete_block(partial_block, no_transactions);
+                } else if self.partial_blocks.len() >= MAX_PARTIAL_BLOCKS {
+                    return Ok(Some(Message::GetBlock(request.block_hash)));
+                }
+
+                self.partial_blocks
+                    .insert(request.block_hash, partial_block);
+
+                Ok(Some(Message::GetBlockTransactions(request)))
+            }
+            Message::GetBlockTransactions(request) => {
+                let transactions = self
+                    .find_block(&request.block_hash)
+                    .and_then(|block| BlockTransactions::new(&block, &request))
+                    .ok_or(RelayErr::UnknownBlock)?;
+
+                Ok(Some(Message::BlockTransactions(transactions)))
+            }
+            Message::BlockTransactions(transactions) => {
+                let partial_block = self
+                    .partial_blocks
+                    .remove(&transactions.block_hash)
+                    .ok_or(RelayErr::UnknownBlock)?;
+
+                self.complete_block(partial_block, transactions)
+            }
+        }
+    }
+
+    /**
+     * Completes the partial block with the missing transactions and connects
+     * it, or asks for the full block if the rebuilt one doesn't match its
+     * transaction root.
+     */
+    fn complete_block(
+        &mut self,
+        partial_block: PartialBlock,
+        missing_transactions: BlockTransactions,
+    ) -> Result<Option<Message>, RelayErr> {
+        let block_hash = partial_block.header.hash;
+        let algorithm = self.blockchain.hash_algorithms().transaction;
+        match partial_block.fill(missing_transactions, algorithm) {
+            Ok(block) => {
+                self.connect_block(block).map_err(RelayErr::InvalidBlock)?;
+
+                Ok(None)
+            }
+            Err(CompactBlockErr::InvalidTransactionRoot) => Ok(Some(Message::GetBlock(block_hash))),
+            Err(error) => Err(RelayErr::InvalidCompactBlock(error)),
+        }
+    }
+
+    fn find_block_index(&self, block_hash: &BlockHash) -> Option<u32> {
+        self.block_indexes.get(block_hash).copied()
+    }
+
+    fn find_block(&self, block_hash: &BlockHash) -> Option<Block> {
+        self.blockchain.block(self.find_block_index(block_hash)?)
+    }
+}
+
+#[cfg(test)]
+mod relay_tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use super::{Connection, Message, RelayErr, RelayNode, MAX_PARTIAL_BLOCKS};
+    use crate::encoding::{Decodable, Encodable};
+
+    use crate::test_support::{output, spend, BITS};
+    use crate::{
+        Block, BlockHash, BlockTransactions, BlockTransactionsRequest, BlockValidationErr,
+        Blockchain, CompactBlock, GenesisConfig, Hash256, HashAlgorithms, Transaction,
+    };
+
+    const PAYMENT_COUNT: u64 = 50;
+
+    /**
+     * The genesis block pays 100 coins to each of PAYMENT_COUNT addresses.
+     */
+    fn create_genesis_config() -> GenesisConfig {
+        GenesisConfig {
+            timestamp: 1,
+            outputs: (0..PAYMENT_COUNT)
+                .map(|n| output(&format!("Payer {}", n), 100))
+                .collect(),
+            bits: BITS,
+            message: None,
+            hash_algorithms: HashAlgorithms::default(),
+        }
+    }
+
+    fn payment(n: u64) -> Transaction {
+        Transaction {
+            inputs: vec![spend(
+                &create_genesis_config().coinbase_transaction(),
+                n as usize,
+            )],
+            outputs: vec![
+                output(&format!("Payee {}", n), 60),
+                output(&format!("Payer {}", n), 39),
+            ],
+            lock_time: None,
+        }
+    }
+
+    /**
+     * Returns a node whose mempool has every payment but the given ones.
+     */
+    fn create_node(missing_payments: &[u64]) -> RelayNode {
+        let blockchain = Blockchain::from_genesis(&create_genesis_config()).unwrap();
+        let mut node = RelayNode::new(blockchain);
+        for n in (0..PAYMENT_COUNT).filter(|n| !missing_payments.contains(n)) {
+            node.add_transaction(payment(n)).unwrap();
+        }
+
+        node
+    }
+
+    /**
+     * Returns the sealed block that follows the node's tip with every payment,
+     * whose coinbase pays the given miner.
+     */
+    fn create_block(node: &RelayNode, miner: &str) -> Block {
+        let tip = node.blockchain().tip().unwrap();
+        let coinbase = Transaction::coinbase(tip.index + 1, vec![output(miner, 50)]);
+        let mut transactions = vec![coinbase];
+        transactions.extend((0..PAYMENT_COUNT).map(payment));
+        let mut block = Block::new(
+            tip.index + 1,
+            tip.timestamp + 1,
+            tip.hash,
+            transactions,
+            BITS,
+        );
+        node.blockchain().seal_block(&mut block).unwrap();
+
+        block
+    }
+
+    /**
+     * Mines a block with every payment of the node's mempool, connects it and
+     * returns its hash.
+     */
+    fn mine_block(node: &mut RelayNode) -> BlockHash {
+        let block = create_block(node, "Miner");
+        let block_hash = block.hash;
+        node.connect_block(block).unwrap();
+
+        block_hash
+    }
+
+    /**
+     * Handles the peer's messages until the node has the block, then closes
+     * the connection.
+     */
+    fn receive_block(node: &mut RelayNode, stream: TcpStream, block_hash: &BlockHash) {
+        let mut connection = Connection::new(stream);
+        while !node.has_block(block_hash) {
+            let message = connection.receive().unwrap();
+            if let Some(reply) = node.handle_message(message).unwrap() {
+                connection.send(&reply).unwrap();
+            }
+        }
+    }
+
+    /**
+     * Announces the tip to the peer and answers its requests until it closes
+     * the connection, and returns the number of bytes exchanged.
+     */
+    fn send_tip(node: &mut RelayNode, stream: TcpStream) -> u64 {
+        let mut connection = Connection::new(stream);
+        let announcement = node
+            .announce_block(node.blockchain().block_count() - 1, 7)
+            .unwrap();
+        connection.send(&announcement).unwrap();
+        while let Ok(message) = connection.receive() {
+            if let Some(reply) = node.handle_message(message).unwrap() {
+                connection.send(&reply).unwrap();
+            }
+        }
+
+        connection.bytes_sent() + connection.bytes_received()
+    }
+
+    #[test]
+    fn relay_block_with_known_transactions() {
+        let mut sender = create_node(&[]);
+        let block_hash = mine_block(&mut sender);
+        let mut receiver = create_node(&[]);
+        let announcement = sender.announce_block(1, 7).unwrap();
+
+        let result = receiver.handle_message(announcement);
+
+        assert_eq!(Ok(None), result);
+        assert!(receiver.has_block(&block_hash));
+        assert!(receiver.mempool().is_empty());
+    }
+
+    #[test]
+    fn relay_block_with_missing_transactions() {
+        let mut sender = create_node(&[]);
+        let block_hash = mine_block(&mut sender);
+        let mut receiver = create_node(&[3, 4]);
+        let announcement = sender.announce_block(1, 7).unwrap();
+
+        let request = receiver.handle_message(announcement).unwrap().unwrap();
+        let missing_transactions = sender.handle_message(request).unwrap().unwrap();
+
+        match &missing_transactions {
+            Message::BlockTransactions(missing_transactions) => assert_eq!(
+                vec![payment(3), payment(4)],
+                missing_transactions.transactions
+            ),
+            message => panic!("unexpected reply: {:?}", message),
+        }
+        assert_eq!(Ok(None), receiver.handle_message(missing_transactions));
+        assert!(receiver.has_block(&block_hash));
+    }
+
+    #[test]
+    fn ask_for_full_block_when_rebuilt_block_mismatches() {
+        let mut sender = create_node(&[]);
+        let block_hash = mine_block(&mut sender);
+        let mut receiver = create_node(&[3]);
+        let announcement = sender.announce_block(1, 7).unwrap();
+        receiver.handle_message(announcement).unwrap();
+        let wrong_transactions = Message::BlockTransactions(BlockTransactions {
+            block_hash,
+            transactions: vec![payment(4)],
+        });
+
+        let request = receiver
+            .handle_message(wrong_transactions)
+            .unwrap()
+            .unwrap();
+        let block = sender.handle_message(request).unwrap().unwrap();
+
+        assert!(matches!(block, Message::Block(_)));
+        assert_eq!(Ok(None), receiver.handle_message(block));
+        assert!(receiver.has_block(&block_hash));
+    }
+
+    #[test]
+    fn reject_compact_block_with_invalid_header() {
+        let mut sender = create_node(&[]);
+        let block_hash = mine_block(&mut sender);
+        let mut receiver = create_node(&[3]);
+        let algorithm = receiver.blockchain().hash_algorithms().transaction;
+        let mut unlinked_block = create_block(&receiver, "Miner");
+        unlinked_block.previous_block_hash = Hash256::ZERO;
+        receiver
+            .blockchain()
+            .seal_block(&mut unlinked_block)
+            .unwrap();
+        let unlinked_announcement =
+            Message::CompactBlock(CompactBlock::new(&unlinked_block, 7, algorithm));
+        let mut forged_announcement = sender.announce_block(1, 7).unwrap();
+        if let Message::CompactBlock(compact_block) = &mut forged_announcement {
+            compact_block.header.nonce += 1;
+        }
+        let missing_transactions = Message::BlockTransactions(BlockTransactions {
+            block_hash,
+            transactions: vec![payment(3)],
+        });
+
+        assert_eq!(
+            Err(RelayErr::InvalidBlock(
+                BlockValidationErr::MismatchedPreviousHash
+            )),
+            receiver.handle_message(unlinked_announcement)
+        );
+        assert_eq!(
+            Err(RelayErr::InvalidBlock(BlockValidationErr::InvalidHash)),
+            receiver.handle_message(forged_announcement)
+        );
+        assert_eq!(
+            Err(RelayErr::UnknownBlock),
+            receiver.handle_message(missing_transactions)
+        );
+    }
+
+    #[test]
+    fn limit_partial_blocks() {
+        let mut receiver = create_node(&[3]);
+        let algorithm = receiver.blockchain().hash_algorithms().transaction;
+        let blocks: Vec<Block> = (0..=MAX_PARTIAL_BLOCKS)
+            .map(|n| create_block(&receiver, &format!("Miner {}", n)))
+            .collect();
+
+        let replies: Vec<Option<Message>> = blocks
+            .iter()
+            .map(|block| {
+                let compact_block = CompactBlock::new(block, 7, algorithm);
+                receiver
+                    .handle_message(Message::CompactBlock(compact_block))
+                    .unwrap()
+            })
+            .collect();
+        let missing_transactions = |block: &Block| {
+            Message::BlockTransactions(BlockTransactions {
+                block_hash: block.hash,
+                transactions: vec![payment(3)],
+            })
+        };
+
+        assert!(replies[..MAX_PARTIAL_BLOCKS]
+            .iter()
+            .all(|reply| matches!(reply, Some(Message::GetBlockTransactions(_)))));
+        assert_eq!(
+            Some(Message::GetBlock(blocks[MAX_PARTIAL_BLOCKS].hash)),
+            replies[MAX_PARTIAL_BLOCKS]
+        );
+        assert_eq!(
+            Ok(None),
+            receiver.handle_message(missing_transactions(&blocks[0]))
+        );
+        assert_eq!(
+            Err(RelayErr::UnknownBlock),
+            receiver.handle_message(missing_transactions(&blocks[1]))
+        );
+    }
+
+    #[test]
+    fn estimate_fee_rate_from_connected_blocks() {
+        let mut node = create_node(&[]);
+        let genesis_block_count = node.fee_estimator().block_count();
+
+        mine_block(&mut node);
+
+        assert_eq!(genesis_block_count + 1, node.fee_estimator().block_count());
+        assert_eq!(
+            (0..PAYMENT_COUNT)
+                .map(|n| payment(n).fee_rate())
+                .min()
+                .unwrap(),
+            node.fee_estimator().estimate_fee_rate(1)
+        );
+    }
+
+    #[test]
+    fn serve_blocks_by_hash() {
+        let mut node = create_node(&[]);
+        let genesis_hash = node.blockchain().header(0).unwrap().hash;
+        let block_hash = mine_block(&mut node);
+        let genesis_block = node.blockchain().block(0).unwrap();
+        let block = node.blockchain().block(1).unwrap();
+
+        assert_eq!(
+            Ok(Some(Message::Block(genesis_block))),
+            node.handle_message(Message::GetBlock(genesis_hash))
+        );
+        assert_eq!(
+            Ok(Some(Message::Block(block))),
+            node.handle_message(Message::GetBlock(block_hash))
+        );
+    }
+
+    #[test]
+    fn reject_messages_about_unknown_blocks() {
+        let mut node = create_node(&[]);
+        let missing_transactions = Message::BlockTransactions(BlockTransactions {
+            block_hash: Hash256::ZERO,
+            transactions: vec![payment(1)],
+        });
+        let request = Message::GetBlockTransactions(BlockTransactionsRequest {
+            block_hash: Hash256::ZERO,
+            positions: vec![0],
+        });
+
+        assert_eq!(
+            Err(RelayErr::UnknownBlock),
+            node.handle_message(missing_transactions)
+        );
+        assert_eq!(Err(RelayErr::UnknownBlock), node.handle_message(request));
+        assert_eq!(
+            Err(RelayErr::UnknownBlock),
+            node.handle_message(Message::GetBlock(Hash256::ZERO))
+        );
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let mut sender = create_node(&[]);
+        let block_hash = mine_block(&mut sender);
+        let messages = vec![
+            sender.announce_block(1, 7).unwrap(),
+            Message::Block(sender.blockchain().block(1).unwrap()),
+            Message::GetBlock(block_hash),
+            Message::GetBlockTransactions(BlockTransactionsRequest {
+                block_hash,
+                positions: vec![1, 2],
+            }),
+            Message::BlockTransactions(BlockTransactions {
+                block_hash,
+                transactions: vec![payment(1)],
+            }),
+        ];
+
+        for message in messages {
+            assert_eq!(Ok(&message), Message::decode(&message.encode()).as_ref());
+        }
+    }
+
+    /**
+     * Node A mines a block and relays it to node B, which relays it to node
+     * C, over localhost TCP connections. B's mempool has every transaction of
+     * the block and C's misses a few, which C asks B for. Relaying the block
+     * in full would cost the size of a block message on each connection.
+     */
+    #[test]
+    fn relay_block_between_nodes_over_localhost() {
+        let b_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let c_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b_address = b_listener.local_addr().unwrap();
+        let c_address = c_listener.local_addr().unwrap();
+
+        let mut a = create_node(&[]);
+        let block_hash = mine_block(&mut a);
+        let full_block_bytes = 4 + Message::Block(a.blockchain().block(1).unwrap())
+            .encode()
+            .len() as u64;
+
+        let c = thread::spawn(move || {
+            let mut c = create_node(&[10, 20, 30]);
+            let (stream, _) = c_listener.accept().unwrap();
+            receive_block(&mut c, stream, &block_hash);
+
+            c.blockchain().tip().unwrap().hash
+        });
+        let b = thread::spawn(move || {
+            let mut b = create_node(&[]);
+            let (stream, _) = b_listener.accept().unwrap();
+            receive_block(&mut b, stream, &block_hash);
+
+            send_tip(&mut b, TcpStream::connect(c_address).unwrap())
+        });
+        let a_to_b_bytes = send_tip(&mut a, TcpStream::connect(b_address).unwrap());
+        let b_to_c_bytes = b.join().unwrap();
+
+        assert_eq!(block_hash, c.join().unwrap());
+        assert!(a_to_b_bytes < b_to_c_bytes);
+        assert!(b_to_c_bytes * 4 < full_block_bytes);
+        println!(
+            "full block: {} bytes, compact block: {} bytes (A to B), {} bytes with 3 missing \
+             transactions (B to C)",
+            full_block_bytes, a_to_b_bytes, b_to_c_bytes
+        );
+    }
+}
@@ -0,0 +1,1211 @@
+use k256::ecdsa::signature::{Signer, Verifier};
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::ByteWriter;
+use crate::{u32_bytes, Hash256, Hashable};
+
+/**
+ * The opcodes of the script language. Their values are Bitcoin's, but only a
+ * small subset of Bitcoin's opcodes is supported.
+ */
+pub mod opcodes {
+    /** Pushes an empty element (false, or the number 0). */
+    pub const OP_0: u8 = 0x00;
+    /** The next byte is the length of the element to push. */
+    pub const OP_PUSHDATA1: u8 = 0x4c;
+    /** The next 2 bytes (little-endian) are the length of the element to push. */
+    pub const OP_PUSHDATA2: u8 = 0x4d;
+    /** Pushes the number -1. */
+    pub const OP_1NEGATE: u8 = 0x4f;
+    /** Pushes the number 1 (OP_2 to OP_16 follow it and push 2 to 16). */
+    pub const OP_1: u8 = 0x51;
+    /** Pushes the number 16. */
+    pub const OP_16: u8 = 0x60;
+    /** Fails unless the top element is true, which it removes. */
+    pub const OP_VERIFY: u8 = 0x69;
+    /** Fails. Marks an output as unspendable. */
+    pub const OP_RETURN: u8 = 0x6a;
+    /** Removes the top element. */
+    pub const OP_DROP: u8 = 0x75;
+    /** Duplicates the top element. */
+    pub const OP_DUP: u8 = 0x76;
+    /** Replaces the top two elements with true if they're equal, else false. */
+    pub const OP_EQUAL: u8 = 0x87;
+    /** OP_EQUAL followed by OP_VERIFY. */
+    pub const OP_EQUALVERIFY: u8 = 0x88;
+    /** Replaces the top element with its SHA-256 hash. */
+    pub const OP_SHA256: u8 = 0xa8;
+    /**
+     * Replaces a signature and a public key with true if the signature of the
+     * transaction is valid for the key, else false.
+     */
+    pub const OP_CHECKSIG: u8 = 0xac;
+    /** OP_CHECKSIG followed by OP_VERIFY. */
+    pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+    /**
+     * Replaces m signatures, m, n public keys and n with true if each of the
+     * signatures is valid for a different key, in the order of the keys.
+     * Fails if fewer than m valid signatures are provided (the threshold
+     * isn't met).
+     */
+    pub const OP_CHECKMULTISIG: u8 = 0xae;
+    /** OP_CHECKMULTISIG followed by OP_VERIFY. */
+    pub const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+    /**
+     * Fails unless the top element, a block index, is less than or equal to
+     * the index of the block that includes the spending transaction. Leaves
+     * the element on the stack.
+     */
+    pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+}
+
+use self::opcodes::*;
+
+/** The maximum size of a script in bytes. */
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/** The maximum size of a stack element in bytes. */
+pub const MAX_ELEMENT_SIZE: usize = 520;
+/**
+ * The maximum number of non-push opcodes that a script executes (the public
+ * keys of a multisig count too). The count starts over for the locking
+ * script, although an unlocking script, which may only push, never counts.
+ */
+pub const MAX_OPERATIONS: usize = 201;
+/** The maximum number of elements on the stack. */
+pub const MAX_STACK_SIZE: usize = 1_000;
+/** The maximum number of public keys of a multisig. */
+pub const MAX_MULTISIG_KEYS: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptErr {
+    ScriptTooLarge,
+    ElementTooLarge,
+    TooManyOperations,
+    StackOverflow,
+    StackUnderflow,
+    MalformedPush,
+    InvalidOpcode(u8),
+    InvalidNumber,
+    UnlockingScriptNotPushOnly,
+    VerifyFailed,
+    OpReturn,
+    InvalidPublicKeyCount,
+    InvalidSignatureCount,
+    MultisigThresholdNotMet,
+    NegativeLockTime,
+    LockTimeNotReached,
+    EvalFalse,
+}
+
+/**
+ * A script: a program for a small stack-based virtual machine, in the spirit
+ * of Bitcoin's Script.
+ *
+ * Each output carries a locking script, which states the conditions for
+ * spending it, and each input carries an unlocking script, which provides the
+ * data (signatures, public keys, preimages) that meets them. To validate an
+ * input, the unlocking script is executed first, and the locking script is
+ * then executed on the stack that it left. The input is valid if no opcode
+ * fails and the top element of the stack is true.
+ *
+ * Instructions
+ * ------------
+ *
+ * A script is a sequence of bytes. Each instruction is either an opcode (see
+ * opcodes) or the push of an element: a byte between 0x01 and 0x4b pushes
+ * that many of the bytes that follow it (OP_PUSHDATA1 and OP_PUSHDATA2 push
+ * larger elements).
+ *
+ * Elements are byte strings. An element is false if all of its bytes are zero
+ * (or if it's a "negative zero"), and true otherwise. Numbers are encoded in
+ * little-endian order with a sign bit in the most significant byte.
+ *
+ * Sandbox
+ * -------
+ *
+ * Scripts can't loop, access anything beyond the stack and the spending
+ * transaction's signature hash and block index, and they're limited in size,
+ * element size, stack size and number of operations (see the MAX_ constants),
+ * so executing one is always cheap.
+ *
+ * Templates
+ * ---------
+ *
+ * - Pay-to-public-key-hash: spendable with a signature from the key whose
+ *   SHA-256 hash the output is locked to.
+ *
+ * - Multisig: spendable with m signatures from n keys.
+ *
+ * - Hashlock: spendable with the preimage of a SHA-256 hash.
+ *
+ * - Timelock: pay-to-public-key-hash that can't be spent before a block index.
+ *
+ * An empty locking script places no conditions on spending the output (which
+ * is how outputs behaved before scripts existed).
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Script(pub Vec<u8>);
+
+impl Script {
+    pub fn new() -> Self {
+        Script(vec![])
+    }
+
+    /**
+     * Returns the script with the given opcode appended.
+     */
+    pub fn push_opcode(mut self, opcode: u8) -> Self {
+        self.0.push(opcode);
+
+        self
+    }
+
+    /**
+     * Returns the script with the push of the given element appended, using
+     * the shortest push instruction.
+     */
+    pub fn push_data(mut self, data: &[u8]) -> Self {
+        let length = data.len();
+        if length == 0 {
+            self.0.push(OP_0);
+        } else if length < OP_PUSHDATA1 as usize {
+            self.0.push(length as u8);
+        } else if length <= u8::MAX as usize {
+            self.0.push(OP_PUSHDATA1);
+            self.0.push(length as u8);
+        } else {
+            self.0.push(OP_PUSHDATA2);
+            self.0.extend_from_slice(&(length as u16).to_le_bytes());
+        }
+        self.0.extend_from_slice(data);
+
+        self
+    }
+
+    /**
+     * Returns the script with the push of the given number appended, using
+     * OP_1NEGATE or OP_0 to OP_16 when possible.
+     */
+    pub fn push_number(self, number: i64) -> Self {
+        match number {
+            -1 => self.push_opcode(OP_1NEGATE),
+            0 => self.push_opcode(OP_0),
+            1..=16 => self.push_opcode(OP_1 + number as u8 - 1),
+            _ => self.push_data(&encode_number(number)),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /**
+     * Returns a flag that states whether the script only pushes elements.
+     * Unlocking scripts must be push-only, so that they can only provide data
+     * to the locking script, not change its logic.
+     */
+    pub fn is_push_only(&self) -> bool {
+        let mut reader = InstructionReader::new(&self.0);
+        loop {
+            match reader.next() {
+                Some(Ok(Instruction::Push(_))) => continue,
+                Some(Ok(Instruction::Opcode(opcode))) => {
+                    if !(opcode == OP_1NEGATE || (OP_1..=OP_16).contains(&opcode)) {
+                        return false;
+                    }
+                }
+                Some(Err(_)) => return false,
+                None => return true,
+            }
+        }
+    }
+
+    /**
+     * Returns a pay-to-public-key-hash locking script:
+     *
+     *   OP_DUP OP_SHA256 <public key hash> OP_EQUALVERIFY OP_CHECKSIG
+     */
+    pub fn pay_to_public_key_hash(public_key: &VerifyingKey) -> Self {
+        Script::new()
+            .push_opcode(OP_DUP)
+            .push_opcode(OP_SHA256)
+            .push_data(public_key_hash(public_key).as_bytes())
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_CHECKSIG)
+    }
+
+    /**
+     * Returns the unlocking script of a pay-to-public-key-hash output:
+     *
+     *   <signature> <public key>
+     */
+    pub fn pay_to_public_key_hash_unlocking(signature: &[u8], public_key: &VerifyingKey) -> Self {
+        Script::new()
+            .push_data(signature)
+            .push_data(&public_key_bytes(public_key))
+    }
+
+    /**
+     * Returns a multisig locking script that requires signatures from
+     * threshold (m) of the given (n) public keys:
+     *
+     *   m <public key 1> ... <public key n> n OP_CHECKMULTISIG
+     */
+    pub fn multisig(threshold: usize, public_keys: &[VerifyingKey]) -> Self {
+        let script = public_keys.iter().fold(
+            Script::new().push_number(threshold as i64),
+            |script, key| script.push_data(&public_key_bytes(key)),
+        );
+
+        script
+            .push_number(public_keys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+    }
+
+    /**
+     * Returns the threshold and the public keys of a multisig locking script,
+     * or None if the script isn't one.
+     */
+    pub fn parse_multisig(&self) -> Option<(usize, Vec<VerifyingKey>)> {
+        let instructions = InstructionReader::new(&self.0)
+            .collect::<Result<Vec<Instruction>, ScriptErr>>()
+            .ok()?;
+        let (last, rest) = instructions.split_last()?;
+        let (key_count, rest) = rest.split_last()?;
+        let (threshold, keys) = rest.split_first()?;
+        if !matches!(last, Instruction::Opcode(OP_CHECKMULTISIG)) {
+            return None;
+        }
+
+        let threshold = instruction_number(threshold)?;
+        let key_count = instruction_number(key_count)?;
+        let public_keys = keys
+            .iter()
+            .map(|instruction| match instruction {
+                Instruction::Push(bytes) => VerifyingKey::from_sec1_bytes(bytes).ok(),
+                Instruction::Opcode(_) => None,
+            })
+            .collect::<Option<Vec<VerifyingKey>>>()?;
+        if key_count != public_keys.len() as i64 || threshold < 1 || threshold > key_count {
+            return None;
+        }
+
+        Some((threshold as usize, public_keys))
+    }
+
+    /**
+     * Returns the unlocking script of a multisig output. The signatures must
+     * be in the order of the public keys they belong to:
+     *
+     *   <signature 1> ... <signature m>
+     */
+    pub fn multisig_unlocking(signatures: &[Vec<u8>]) -> Self {
+        signatures.iter().fold(Script::new(), |script, signature| {
+            script.push_data(signature)
+        })
+    }
+
+    /**
+     * Returns a hashlock locking script, spendable by whoever reveals the
+     * preimage of the given SHA-256 hash:
+     *
+     *   OP_SHA256 <hash> OP_EQUAL
+     */
+    pub fn hashlock(hash: &Hash256) -> Self {
+        Script::new()
+            .push_opcode(OP_SHA256)
+            .push_data(hash.as_bytes())
+            .push_opcode(OP_EQUAL)
+    }
+
+    /**
+     * Returns the unlocking script of a hashlock output:
+     *
+     *   <preimage>
+     */
+    pub fn hashlock_unlocking(preimage: &[u8]) -> Self {
+        Script::new().push_data(preimage)
+    }
+
+    /**
+     * Returns a timelock locking script: a pay-to-public-key-hash that can't
+     * be spent in a block whose index is lower than the given one. It is
+     * unlocked like a pay-to-public-key-hash output.
+     *
+     *   <block index> OP_CHECKLOCKTIMEVERIFY OP_DROP
+     *   OP_DUP OP_SHA256 <public key hash> OP_EQUALVERIFY OP_CHECKSIG
+     */
+    pub fn timelock(block_index: u32, public_key: &VerifyingKey) -> Self {
+        let mut script = Script::new()
+            .push_number(i64::from(block_index))
+            .push_opcode(OP_CHECKLOCKTIMEVERIFY)
+            .push_opcode(OP_DROP);
+        script
+            .0
+            .extend_from_slice(Script::pay_to_public_key_hash(public_key).as_bytes());
+
+        script
+    }
+}
+
+impl Hashable for Script {
+    /**
+     * Writes the hashable bytes that represent the script: its length (4
+     * bytes) followed by its bytes.
+     */
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u32_bytes(self.0.len() as u32));
+        writer.write(&self.0);
+    }
+}
+
+impl Encodable for Script {
+    /**
+     * Writes the encoding of the script, which is the same as its hashable
+     * bytes.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.write_bytes(writer);
+    }
+}
+
+impl Decodable for Script {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(Script(reader.read_length_prefixed()?.to_vec()))
+    }
+}
+
+/**
+ * The data that a script can access besides its stack.
+ *
+ * - Signature hash: What signatures sign (see Transaction::signature_hash).
+ *
+ * - Block index: The index of the block that includes the spending
+ *   transaction.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScriptContext {
+    pub signature_hash: Hash256,
+    pub block_index: u32,
+}
+
+/**
+ * Executes the unlocking script, then the locking script on the resulting
+ * stack, and returns whether the locking script's conditions are met.
+ */
+pub fn verify_script(
+    unlocking_script: &Script,
+    locking_script: &Script,
+    context: &ScriptContext,
+) -> Result<(), ScriptErr> {
+    if locking_script.is_empty() {
+        return Ok(());
+    } else if !unlocking_script.is_push_only() {
+        return Err(ScriptErr::UnlockingScriptNotPushOnly);
+    }
+
+    let mut interpreter = Interpreter::new(context);
+    interpreter.execute(unlocking_script)?;
+    interpreter.operation_count = 0;
+    interpreter.execute(locking_script)?;
+
+    match interpreter.stack.last() {
+        Some(element) if is_true(element) => Ok(()),
+        _ => Err(ScriptErr::EvalFalse),
+    }
+}
+
+/**
+ * Returns the hash that a pay-to-public-key-hash output is locked to: the
+ * SHA-256 hash of the public key's compressed encoding.
+ */
+pub fn public_key_hash(public_key: &VerifyingKey) -> Hash256 {
+    Hash256(Sha256::digest(public_key_bytes(public_key)).into())
+}
+
+/**
+ * Returns the signature of the given signature hash: 64 bytes (r and s) of an
+ * ECDSA (secp256k1) signature.
+ */
+pub fn sign(signing_key: &SigningKey, signature_hash: &Hash256) -> Vec<u8> {
+    let signature: Signature = signing_key.sign(signature_hash.as_bytes());
+
+    signature.to_bytes().to_vec()
+}
+
+fn public_key_bytes(public_key: &VerifyingKey) -> Vec<u8> {
+    public_key.to_encoded_point(true).as_bytes().to_vec()
+}
+
+fn check_signature(signature: &[u8], public_key: &[u8], signature_hash: &Hash256) -> bool {
+    match (
+        Signature::from_slice(signature),
+        VerifyingKey::from_sec1_bytes(public_key),
+    ) {
+        (Ok(signature), Ok(public_key)) => public_key
+            .verify(signature_hash.as_bytes(), &signature)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+fn is_true(element: &[u8]) -> bool {
+    match element.split_last() {
+        None => false,
+        Some((last, rest)) => rest.iter().any(|byte| *byte != 0) || (*last & 0x7f) != 0,
+    }
+}
+
+fn boolean(value: bool) -> Vec<u8> {
+    if value {
+        vec![1]
+    } else {
+        vec![]
+    }
+}
+
+/**
+ * Encodes a number in little-endian order with a sign bit in the most
+ * significant byte (0 is encoded as an empty element).
+ */
+fn encode_number(number: i64) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut magnitude = number.unsigned_abs();
+    while magnitude > 0 {
+        bytes.push(magnitude as u8);
+        magnitude >>= 8;
+    }
+
+    if let Some(last) = bytes.last_mut() {
+        if *last & 0x80 != 0 {
+            bytes.push(if number < 0 { 0x80 } else { 0 });
+        } else if number < 0 {
+            *last |= 0x80;
+        }
+    }
+
+    bytes
+}
+
+/**
+ * Decodes a number of at most the given number of bytes.
+ */
+fn decode_number(bytes: &[u8], max_size: usize) -> Result<i64, ScriptErr> {
+    if bytes.len() > max_size {
+        return Err(ScriptErr::InvalidNumber);
+    }
+
+    let (last, _) = match bytes.split_last() {
+        Some(split) => split,
+        None => return Ok(0),
+    };
+    let magnitude = bytes
+        .iter()
+        .enumerate()
+        .fold(0_i64, |result, (index, byte)| {
+            let byte = if index == bytes.len() - 1 {
+                byte & 0x7f
+            } else {
+                *byte
+            };
+
+            result | i64::from(byte) << (8 * index)
+        });
+
+    if last & 0x80 != 0 {
+        Ok(-magnitude)
+    } else {
+        Ok(magnitude)
+    }
+}
+
+enum Instruction<'a> {
+    Push(&'a [u8]),
+    Opcode(u8),
+}
+
+/**
+ * Returns the number that the instruction pushes, if it pushes one.
+ */
+fn instruction_number(instruction: &Instruction) -> Option<i64> {
+    match instruction {
+        Instruction::Push(bytes) => decode_number(bytes, 4).ok(),
+        Instruction::Opcode(OP_1NEGATE) => Some(-1),
+        Instruction::Opcode(opcode @ OP_1..=OP_16) => Some(i64::from(opcode - OP_1 + 1)),
+        Instruction::Opcode(_) => None,
+    }
+}
+
+struct InstructionReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> InstructionReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        InstructionReader { bytes, position: 0 }
+    }
+
+    fn read(&mut self, length: usize) -> Result<&'a [u8], ScriptErr> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(ScriptErr::MalformedPush)?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(bytes)
+    }
+}
+
+impl<'a> Iterator for InstructionReader<'a> {
+    type Item = Result<Instruction<'a>, ScriptErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let opcode = *self.bytes.get(self.position)?;
+        self.position += 1;
+
+        let length = match opcode {
+            OP_0 => return Some(Ok(Instruction::Push(&[]))),
+            0x01..=0x4b => Ok(opcode as usize),
+            OP_PUSHDATA1 => self.read(1).map(|bytes| bytes[0] as usize),
+            OP_PUSHDATA2 => self
+                .read(2)
+                .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize),
+            _ => return Some(Ok(Instruction::Opcode(opcode))),
+        };
+
+        Some(
+            length
+                .and_then(|length| self.read(length))
+                .map(Instruction::Push),
+        )
+    }
+}
+
+struct Interpreter<'a> {
+    stack: Vec<Vec<u8>>,
+    context: &'a ScriptContext,
+    operation_count: usize,
+}
+
+impl<'a> Interpreter<'a> {
+    fn new(context: &'a ScriptContext) -> Self {
+        Interpreter {
+            stack: vec![],
+            context,
+            operation_count: 0,
+        }
+    }
+
+    fn execute(&mut self, script: &Script) -> Result<(), ScriptErr> {
+        if script.len() > MAX_SCRIPT_SIZE {
+            return Err(ScriptErr::ScriptTooLarge);
+        }
+
+        for instruction in InstructionReader::new(script.as_bytes()) {
+            match instruction? {
+                Instruction::Push(element) => self.push(element.to_vec())?,
+                Instruction::Opcode(opcode) => self.execute_opcode(opcode)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_opcode(&mut self, opcode: u8) -> Result<(), ScriptErr> {
+        match opcode {
+            OP_1NEGATE => return self.push(encode_number(-1)),
+            OP_1..=OP_16 => return self.push(encode_number(i64::from(opcode - OP_1 + 1))),
+            _ => {}
+        }
+
+        self.count_operations(1)?;
+        match opcode {
+            OP_VERIFY => self.verify(),
+            OP_RETURN => Err(ScriptErr::OpReturn),
+            OP_DROP => self.pop().map(|_| ()),
+            OP_DUP => {
+                let element = self.peek()?.to_vec();
+                self.push(element)
+            }
+            OP_EQUAL | OP_EQUALVERIFY => {
+                let element1 = self.pop()?;
+                let element2 = self.pop()?;
+                self.push(boolean(element1 == element2))?;
+                self.verify_if(opcode == OP_EQUALVERIFY)
+            }
+            OP_SHA256 => {
+                let element = self.pop()?;
+                self.push(Sha256::digest(element).to_vec())
+            }
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                let public_key = self.pop()?;
+                let signature = self.pop()?;
+                let is_valid =
+                    check_signature(&signature, &public_key, &self.context.signature_hash);
+                self.push(boolean(is_valid))?;
+                self.verify_if(opcode == OP_CHECKSIGVERIFY)
+            }
+            OP_CHECKMULTISIG => {
+                self.check_multisig()?;
+                self.push(boolean(true))
+            }
+            OP_CHECKMULTISIGVERIFY => self.check_multisig(),
+            OP_CHECKLOCKTIMEVERIFY => {
+                let lock_time = decode_number(self.peek()?, 5)?;
+                if lock_time < 0 {
+                    Err(ScriptErr::NegativeLockTime)
+                } else if lock_time > i64::from(self.context.block_index) {
+                    Err(ScriptErr::LockTimeNotReached)
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Err(ScriptErr::InvalidOpcode(opcode)),
+        }
+    }
+
+    fn check_multisig(&mut self) -> Result<(), ScriptErr> {
+        let key_count = decode_number(&self.pop()?, 4)?;
+        if key_count < 0 || key_count as usize > MAX_MULTISIG_KEYS {
+            return Err(ScriptErr::InvalidPublicKeyCount);
+        }
+        self.count_operations(key_count as usize)?;
+        let public_keys = self.pop_many(key_count as usize)?;
+
+        let signature_count = decode_number(&self.pop()?, 4)?;
+        if signature_count < 0 || signature_count > key_count {
+            return Err(ScriptErr::InvalidSignatureCount);
+        }
+        if (signature_count as usize) > self.stack.len() {
+            return Err(ScriptErr::MultisigThresholdNotMet);
+        }
+        let signatures = self.pop_many(signature_count as usize)?;
+
+        // Each signature must match a key after the one the previous signature
+        // matched.
+        let mut remaining_keys = public_keys.iter();
+        let is_valid = signatures.iter().all(|signature| {
+            remaining_keys.any(|public_key| {
+                check_signature(signature, public_key, &self.context.signature_hash)
+            })
+        });
+
+        if is_valid {
+            Ok(())
+        } else {
+            Err(ScriptErr::MultisigThresholdNotMet)
+        }
+    }
+
+    fn count_operations(&mut self, count: usize) -> Result<(), ScriptErr> {
+        self.operation_count += count;
+        if self.operation_count > MAX_OPERATIONS {
+            return Err(ScriptErr::TooManyOperations);
+        }
+
+        Ok(())
+    }
+
+    fn verify(&mut self) -> Result<(), ScriptErr> {
+        if is_true(&self.pop()?) {
+            Ok(())
+        } else {
+            Err(ScriptErr::VerifyFailed)
+        }
+    }
+
+    fn verify_if(&mut self, condition: bool) -> Result<(), ScriptErr> {
+        if condition {
+            self.verify()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn push(&mut self, element: Vec<u8>) -> Result<(), ScriptErr> {
+        if element.len() > MAX_ELEMENT_SIZE {
+            return Err(ScriptErr::ElementTooLarge);
+        } else if self.stack.len() >= MAX_STACK_SIZE {
+            return Err(ScriptErr::StackOverflow);
+        }
+        self.stack.push(element);
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, ScriptErr> {
+        self.stack.pop().ok_or(ScriptErr::StackUnderflow)
+    }
+
+    /**
+     * Pops the given number of elements, and returns them in the order they
+     * were pushed in.
+     */
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Vec<u8>>, ScriptErr> {
+        if count > self.stack.len() {
+            return Err(ScriptErr::StackUnderflow);
+        }
+
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    fn peek(&self) -> Result<&[u8], ScriptErr> {
+        self.stack
+            .last()
+            .map(|element| element.as_slice())
+            .ok_or(ScriptErr::StackUnderflow)
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use k256::ecdsa::{SigningKey, VerifyingKey};
+
+    use super::opcodes::*;
+    use super::{decode_number, encode_number, Hashable, Script};
+
+    #[test]
+    fn push_data_with_empty_element() {
+        let result = Script::new().push_data(&[]);
+
+        assert_eq!(vec![OP_0], result.0);
+    }
+
+    #[test]
+    fn push_data_with_small_element() {
+        let result = Script::new().push_data(&[7; 75]);
+
+        assert_eq!(75, result.0[0]);
+        assert_eq!(76, result.len());
+    }
+
+    #[test]
+    fn push_data_with_pushdata1() {
+        let result = Script::new().push_data(&[7; 76]);
+
+        assert_eq!(vec![OP_PUSHDATA1, 76], result.0[..2].to_vec());
+        assert_eq!(78, result.len());
+    }
+
+    #[test]
+    fn push_data_with_pushdata2() {
+        let result = Script::new().push_data(&[7; 256]);
+
+        assert_eq!(vec![OP_PUSHDATA2, 0, 1], result.0[..3].to_vec());
+        assert_eq!(259, result.len());
+    }
+
+    #[test]
+    fn push_number() {
+        assert_eq!(vec![OP_1NEGATE], Script::new().push_number(-1).0);
+        assert_eq!(vec![OP_0], Script::new().push_number(0).0);
+        assert_eq!(vec![OP_1], Script::new().push_number(1).0);
+        assert_eq!(vec![OP_16], Script::new().push_number(16).0);
+        assert_eq!(vec![1, 17], Script::new().push_number(17).0);
+        assert_eq!(vec![2, 0xe8, 0x03], Script::new().push_number(1_000).0);
+    }
+
+    #[test]
+    fn is_push_only() {
+        assert!(Script::new().is_push_only());
+        assert!(Script::new()
+            .push_data(&[1, 2])
+            .push_number(3)
+            .push_number(-1)
+            .is_push_only());
+        assert!(!Script::new().push_opcode(OP_DUP).is_push_only());
+        assert!(!Script(vec![2, 1]).is_push_only());
+    }
+
+    #[test]
+    fn parse_multisig() {
+        let public_keys: Vec<VerifyingKey> = (1..=3)
+            .map(|byte| {
+                *SigningKey::from_bytes(&[byte; 32].into())
+                    .unwrap()
+                    .verifying_key()
+            })
+            .collect();
+        let script = Script::multisig(2, &public_keys);
+
+        let result = script.parse_multisig();
+
+        assert_eq!(Some((2, public_keys)), result);
+    }
+
+    #[test]
+    fn parse_multisig_of_other_scripts() {
+        let public_key = *SigningKey::from_bytes(&[1; 32].into())
+            .unwrap()
+            .verifying_key();
+
+        assert_eq!(None, Script::new().parse_multisig());
+        assert_eq!(
+            None,
+            Script::pay_to_public_key_hash(&public_key).parse_multisig()
+        );
+        assert_eq!(None, Script::multisig(2, &[public_key]).parse_multisig());
+        assert_eq!(None, Script::multisig(0, &[public_key]).parse_multisig());
+        assert_eq!(
+            None,
+            Script::new()
+                .push_number(1)
+                .push_data(&[1; 33])
+                .push_number(1)
+                .push_opcode(OP_CHECKMULTISIG)
+                .parse_multisig()
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        let script = Script::new().push_opcode(OP_DUP).push_data(&[9]);
+
+        let result = script.bytes();
+
+        assert_eq!(vec![3, 0, 0, 0, OP_DUP, 1, 9], result);
+    }
+
+    #[test]
+    fn encode_and_decode_numbers() {
+        let cases: [(i64, Vec<u8>); 7] = [
+            (0, vec![]),
+            (1, vec![0x01]),
+            (-1, vec![0x81]),
+            (127, vec![0x7f]),
+            (128, vec![0x80, 0x00]),
+            (-128, vec![0x80, 0x80]),
+            (0x1234_5678, vec![0x78, 0x56, 0x34, 0x12]),
+        ];
+
+        for (number, bytes) in cases.iter() {
+            assert_eq!(*bytes, encode_number(*number), "{}", number);
+            assert_eq!(Ok(*number), decode_number(bytes, 4), "{}", number);
+        }
+    }
+
+    #[test]
+    fn decode_number_that_is_too_large() {
+        let result = decode_number(&[1, 2, 3, 4, 5], 4);
+
+        assert!(result.is_err());
+    }
+}
+
+#[cfg(test)]
+mod verify_script_tests {
+
+    use super::opcodes::*;
+    use super::{
+        sign, verify_script, Script, ScriptContext, ScriptErr, MAX_ELEMENT_SIZE, MAX_OPERATIONS,
+        MAX_SCRIPT_SIZE, MAX_STACK_SIZE,
+    };
+    use crate::test_support::signing_key;
+    use crate::Hash256;
+
+    fn context() -> ScriptContext {
+        ScriptContext {
+            signature_hash: Hash256([42; 32]),
+            block_index: 10,
+        }
+    }
+
+    fn signature(byte: u8) -> Vec<u8> {
+        sign(&signing_key(byte), &context().signature_hash)
+    }
+
+    fn verify(unlocking_script: &Script, locking_script: &Script) -> Result<(), ScriptErr> {
+        verify_script(unlocking_script, locking_script, &context())
+    }
+
+    #[test]
+    fn empty_locking_script() {
+        let result = verify(&Script::new(), &Script::new());
+
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn pay_to_public_key_hash() {
+        let key = signing_key(1);
+        let locking_script = Script::pay_to_public_key_hash(key.verifying_key());
+        let unlocking_script =
+            Script::pay_to_public_key_hash_unlocking(&signature(1), key.verifying_key());
+
+        let result = verify(&unlocking_script, &locking_script);
+
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn pay_to_public_key_hash_with_other_public_key() {
+        let locking_script = Script::pay_to_public_key_hash(signing_key(1).verifying_key());
+        let unlocking_script =
+            Script::pay_to_public_key_hash_unlocking(&signature(2), signing_key(2).verifying_key());
+
+        let result = verify(&unlocking_script, &locking_script);
+
+        assert_eq!(Err(ScriptErr::VerifyFailed), result);
+    }
+
+    #[test]
+    fn pay_to_public_key_hash_with_signature_by_other_key() {
+        let key = signing_key(1);
+        let locking_script = Script::pay_to_public_key_hash(key.verifying_key());
+        let unlocking_script =
+            Script::pay_to_public_key_hash_unlocking(&signature(2), key.verifying_key());
+
+        let result = verify(&unlocking_script, &locking_script);
+
+        assert_eq!(Err(ScriptErr::EvalFalse), result);
+    }
+
+    #[test]
+    fn pay_to_public_key_hash_with_signature_of_other_transaction() {
+        let key = signing_key(1);
+        let locking_script = Script::pay_to_public_key_hash(key.verifying_key());
+        let unlocking_script = Script::pay_to_public_key_hash_unlocking(
+            &sign(&key, &Hash256([43; 32])),
+            key.verifying_key(),
+        );
+
+        let result = verify(&unlocking_script, &locking_script);
+
+        assert_eq!(Err(ScriptErr::EvalFalse), result);
+    }
+
+    #[test]
+    fn unlocking_script_that_is_not_push_only() {
+        let key = signing_key(1);
+        let locking_script = Script::pay_to_public_key_hash(key.verifying_key());
+        let unlocking_script = Script::new().push_opcode(OP_1).push_opcode(OP_RETURN);
+
+        let result = verify(&unlocking_script, &locking_script);
+
+        assert_eq!(Err(ScriptErr::UnlockingScriptNotPushOnly), result);
+    }
+
+    #[test]
+    fn multisig() {
+        let keys = [signing_key(1), signing_key(2), signing_key(3)];
+        let public_keys: Vec<_> = keys.iter().map(|key| *key.verifying_key()).collect();
+        let locking_script = Script::multisig(2, &public_keys);
+
+        let result1 = verify(
+            &Script::multisig_unlocking(&[signature(1), signature(3)]),
+            &locking_script,
+        );
+        let result2 = verify(
+            &Script::multisig_unlocking(&[signature(2), signature(3)]),
+            &locking_script,
+        );
+
+        assert_eq!(Ok(()), result1);
+        assert_eq!(Ok(()), result2);
+    }
+
+    #[test]
+    fn multisig_with_signatures_out_of_order() {
+        let public_keys: Vec<_> = (1..=3)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect();
+        let locking_script = Script::multisig(2, &public_keys);
+
+        let result = verify(
+            &Script::multisig_unlocking(&[signature(3), signature(1)]),
+            &locking_script,
+        );
+
+        assert_eq!(Err(ScriptErr::MultisigThresholdNotMet), result);
+    }
+
+    #[test]
+    fn multisig_with_same_signature_twice() {
+        let public_keys: Vec<_> = (1..=3)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect();
+        let locking_script = Script::multisig(2, &public_keys);
+
+        let result = verify(
+            &Script::multisig_unlocking(&[signature(1), signature(1)]),
+            &locking_script,
+        );
+
+        assert_eq!(Err(ScriptErr::MultisigThresholdNotMet), result);
+    }
+
+    #[test]
+    fn multisig_with_too_few_signatures() {
+        let public_keys: Vec<_> = (1..=3)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect();
+        let locking_script = Script::multisig(2, &public_keys);
+
+        let result = verify(
+            &Script::multisig_unlocking(&[signature(1)]),
+            &locking_script,
+        );
+
+        assert_eq!(Err(ScriptErr::MultisigThresholdNotMet), result);
+    }
+
+    #[test]
+    fn multisig_with_too_many_public_keys() {
+        let locking_script = Script::new()
+            .push_number(0)
+            .push_number(21)
+            .push_opcode(OP_CHECKMULTISIG);
+
+        let result = verify(&Script::new(), &locking_script);
+
+        assert_eq!(Err(ScriptErr::InvalidPublicKeyCount), result);
+    }
+
+    #[test]
+    fn hashlock() {
+        let preimage = b"secret";
+        let hash = Hash256([
+            0x2b, 0xb8, 0x0d, 0x53, 0x7b, 0x1d, 0xa3, 0xe3, 0x8b, 0xd3, 0x03, 0x61, 0xaa, 0x85,
+            0x56, 0x86, 0xbd, 0xe0, 0xea, 0xcd, 0x71, 0x62, 0xfe, 0xf6, 0xa2, 0x5f, 0xe9, 0x7b,
+            0xf5, 0x27, 0xa2, 0x5b,
+        ]);
+        let locking_script = Script::hashlock(&hash);
+
+        let result = verify(&Script::hashlock_unlocking(preimage), &locking_script);
+        let wrong_result = verify(&Script::hashlock_unlocking(b"guess"), &locking_script);
+
+        assert_eq!(Ok(()), result);
+        assert_eq!(Err(ScriptErr::EvalFalse), wrong_result);
+    }
+
+    #[test]
+    fn timelock() {
+        let key = signing_key(1);
+        let unlocking_script =
+            Script::pay_to_public_key_hash_unlocking(&signature(1), key.verifying_key());
+
+        let result_at_lock = verify(
+            &unlocking_script,
+            &Script::timelock(10, key.verifying_key()),
+        );
+        let result_before_lock = verify(
+            &unlocking_script,
+            &Script::timelock(11, key.verifying_key()),
+        );
+
+        assert_eq!(Ok(()), result_at_lock);
+        assert_eq!(Err(ScriptErr::LockTimeNotReached), result_before_lock);
+    }
+
+    #[test]
+    fn negative_lock_time() {
+        let locking_script = Script::new()
+            .push_number(-1)
+            .push_opcode(OP_CHECKLOCKTIMEVERIFY);
+
+        let result = verify(&Script::new(), &locking_script);
+
+        assert_eq!(Err(ScriptErr::NegativeLockTime), result);
+    }
+
+    #[test]
+    fn op_return() {
+        let locking_script = Script::new().push_opcode(OP_RETURN);
+
+        let result = verify(&Script::new().push_number(1), &locking_script);
+
+        assert_eq!(Err(ScriptErr::OpReturn), result);
+    }
+
+    #[test]
+    fn invalid_opcode() {
+        let locking_script = Script::new().push_opcode(0xff);
+
+        let result = verify(&Script::new(), &locking_script);
+
+        assert_eq!(Err(ScriptErr::InvalidOpcode(0xff)), result);
+    }
+
+    #[test]
+    fn malformed_push() {
+        let locking_script = Script(vec![OP_1, 5, 1, 2]);
+
+        let result = verify(&Script::new(), &locking_script);
+
+        assert_eq!(Err(ScriptErr::MalformedPush), result);
+    }
+
+    #[test]
+    fn stack_underflow() {
+        let locking_script = Script::new().push_opcode(OP_DROP);
+
+        let result = verify(&Script::new(), &locking_script);
+
+        assert_eq!(Err(ScriptErr::StackUnderflow), result);
+    }
+
+    #[test]
+    fn false_result() {
+        let locking_script = Script::new().push_opcode(OP_EQUAL);
+
+        let result = verify(
+            &Script::new().push_number(1).push_number(2),
+            &locking_script,
+        );
+
+        assert_eq!(Err(ScriptErr::EvalFalse), result);
+    }
+
+    #[test]
+    fn too_many_operations() {
+        let locking_script = (0..=MAX_OPERATIONS)
+            .fold(Script::new().push_number(1), |script, _| {
+                script.push_opcode(OP_DUP).push_opcode(OP_DROP)
+            });
+
+        let result = verify(&Script::new(), &locking_script);
+
+        assert_eq!(Err(ScriptErr::TooManyOperations), result);
+    }
+
+    #[test]
+    fn element_too_large() {
+        let unlocking_script = Script::new().push_data(&[1; MAX_ELEMENT_SIZE + 1]);
+
+        let result = verify(&unlocking_script, &Script::new().push_opcode(OP_DROP));
+
+        assert_eq!(Err(ScriptErr::ElementTooLarge), result);
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let unlocking_script =
+            (0..=MAX_STACK_SIZE).fold(Script::new(), |script, _| script.push_number(1));
+
+        let result = verify(&unlocking_script, &Script::new().push_opcode(OP_DROP));
+
+        assert_eq!(Err(ScriptErr::StackOverflow), result);
+    }
+
+    #[test]
+    fn script_too_large() {
+        let locking_script = Script(vec![OP_1; MAX_SCRIPT_SIZE + 1]);
+
+        let result = verify(&Script::new(), &locking_script);
+
+        assert_eq!(Err(ScriptErr::ScriptTooLarge), result);
+    }
+}
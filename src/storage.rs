@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::io;
+
+use primitive_types::U256;
+
+use crate::{Block, BlockUndo, Hash256, Transaction};
+
+/**
+ * The storage of a chain's state: its blocks (headers and bodies), the undo
+ * data of its blocks, its unspent outputs (the UTXO set) and its metadata
+ * (the number of blocks, whose last one is the best tip, the chain work and
+ * the pruned height).
+ *
+ * A blockchain validates blocks and a storage backend keeps what it decided
+ * (see Blockchain). There are three backends:
+ *
+ * - MemoryStorage: Keeps everything in memory, which suits tests and chains
+ *   that don't have to survive a restart.
+ *
+ * - FlatFileStorage: Keeps blocks in flat files and rewrites the UTXO set
+ *   after each block, which suits small nodes.
+ *
+ * - KvStorage: Keeps everything in an embedded key-value store, whose writes
+ *   of a block's changes are atomic, which suits big nodes.
+ *
+ * Every backend keeps the block headers in memory: they're small, and
+ * validating a block reads them. The changes of a block (connecting it,
+ * disconnecting it or pruning it) are written as a whole, so a backend that
+ * persists them can recover a consistent state after an interrupted write.
+ */
+pub trait ChainStorage {
+    /**
+     * Returns the number of blocks in the chain (the index of its tip plus
+     * one).
+     */
+    fn block_count(&self) -> u32;
+
+    /**
+     * Returns the header of the block with the given index, which is a block
+     * whose transactions may have been left out.
+     */
+    fn header(&self, index: u32) -> Option<&Block>;
+
+    /**
+     * Returns the transactions of the block with the given index, or None if
+     * they aren't stored (e.g., because they were pruned).
+     */
+    fn read_body(&self, index: u32) -> io::Result<Option<Vec<Transaction>>>;
+
+    /**
+     * Returns the undo data of the block with the given index, or None if it
+     * isn't stored.
+     */
+    fn read_undo(&self, index: u32) -> io::Result<Option<BlockUndo>>;
+
+    /**
+     * Returns the index of the block that created the unspent output with the
+     * given hash, or None if the output isn't unspent.
+     */
+    fn unspent_output(&self, output: &Hash256) -> io::Result<Option<u32>>;
+
+    /**
+     * Returns every unspent output, in no particular order.
+     */
+    fn unspent_outputs(&self) -> io::Result<Vec<(Hash256, u32)>>;
+
+    /**
+     * Returns the total work of the chain.
+     */
+    fn chain_work(&self) -> U256;
+
+    /**
+     * Returns the number of blocks, from the genesis block on, whose bodies
+     * and undo data have been pruned.
+     */
+    fn pruned_height(&self) -> u32;
+
+    /**
+     * Adds the block as the new tip, with its undo data, and applies its
+     * changes to the unspent outputs: each output is set to its new entry,
+     * the index of the block that created it, or removed if it has none.
+     */
+    fn connect_block(
+        &mut self,
+        block: Block,
+        undo: BlockUndo,
+        unspent_output_changes: &[(Hash256, Option<u32>)],
+        chain_work: U256,
+    ) -> io::Result<()>;
+
+    /**
+     * Removes the tip, whose undo data must be stored, restores the unspent
+     * outputs with its undo data and returns it (with its transactions).
+     */
+    fn disconnect_tip(&mut self, chain_work: U256) -> io::Result<Block>;
+
+    /**
+     * Removes the body and the undo data of the block with the given index,
+     * which must be the pruned height, and increments the pruned height.
+     */
+    fn prune_block(&mut self, index: u32) -> io::Result<()>;
+
+    /**
+     * Replaces the empty chain with the given headers and unspent outputs
+     * (see Blockchain::load_utxo_snapshot). The blocks are pruned up to the
+     * last one.
+     */
+    fn load_snapshot(
+        &mut self,
+        headers: Vec<Block>,
+        unspent_outputs: &[(Hash256, u32)],
+        chain_work: U256,
+    ) -> io::Result<()>;
+}
+
+/**
+ * A storage backend that keeps a chain's state in memory: a block vector and
+ * a map of the unspent outputs.
+ */
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    blocks: Vec<Block>,
+    undos: Vec<Option<BlockUndo>>,
+    unspent_outputs: HashMap<Hash256, u32>,
+    chain_work: U256,
+    pruned_height: u32,
+}
+
+impl MemoryStorage {
+    /**
+     * Returns the blocks, whose bodies are empty if they were pruned.
+     */
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+}
+
+impl ChainStorage for MemoryStorage {
+    fn block_count(&self) -> u32 {
+        self.blocks.len() as u32
+    }
+
+    fn header(&self, index: u32) -> Option<&Block> {
+        self.blocks.get(index as usize)
+    }
+
+    fn read_body(&self, index: u32) -> io::Result<Option<Vec<Transaction>>> {
+        if index < self.pruned_height {
+            return Ok(None);
+        }
+
+        Ok(self
+            .blocks
+            .get(index as usize)
+            .map(|block| block.transactions.clone()))
+    }
+
+    fn read_undo(&self, index: u32) -> io::Result<Option<BlockUndo>> {
+        Ok(self.undos.get(index as usize).cloned().flatten())
+    }
+
+    fn unspent_output(&self, output: &Hash256) -> io::Result<Option<u32>> {
+        Ok(self.unspent_outputs.get(output).copied())
+    }
+
+    fn unspent_outputs(&self) -> io::Result<Vec<(Hash256, u32)>> {
+        Ok(self
+            .unspent_outputs
+            .iter()
+            .map(|(output, block_index)| (*output, *block_index))
+            .collect())
+    }
+
+    fn chain_work(&self) -> U256 {
+        self.chain_work
+    }
+
+    fn pruned_height(&self) -> u32 {
+        self.pruned_height
+    }
+
+    fn connect_block(
+        &mut self,
+        block: Block,
+        undo: BlockUndo,
+        unspent_output_changes: &[(Hash256, Option<u32>)],
+        chain_work: U256,
+    ) -> io::Result<()> {
+        apply_changes(&mut self.unspent_outputs, unspent_output_changes);
+        self.blocks.push(block);
+        self.undos.push(Some(undo));
+        self.chain_work = chain_work;
+
+        Ok(())
+    }
+
+    fn disconnect_tip(&mut self, chain_work: U256) -> io::Result<Block> {
+        let undo = match self.undos.last() {
+            Some(Some(undo)) => undo,
+            _ => return Err(missing_undo_data()),
+        };
+
+        apply_changes(&mut self.unspent_outputs, undo.previous_entries());
+        self.undos.pop();
+        self.chain_work = chain_work;
+
+        Ok(self.blocks.pop().expect("a block has undo data"))
+    }
+
+    fn prune_block(&mut self, index: u32) -> io::Result<()> {
+        self.blocks[index as usize].transactions = vec![];
+        self.undos[index as usize] = None;
+        self.pruned_height = index + 1;
+
+        Ok(())
+    }
+
+    fn load_snapshot(
+        &mut self,
+        headers: Vec<Block>,
+        unspent_outputs: &[(Hash256, u32)],
+        chain_work: U256,
+    ) -> io::Result<()> {
+        self.pruned_height = headers.len() as u32;
+        self.undos = headers.iter().map(|_| None).collect();
+        self.blocks = headers;
+        self.unspent_outputs = unspent_outputs.iter().copied().collect();
+        self.chain_work = chain_work;
+
+        Ok(())
+    }
+}
+
+/**
+ * Sets each unspent output to its entry, or removes it if it has none.
+ */
+pub(crate) fn apply_changes(
+    unspent_outputs: &mut HashMap<Hash256, u32>,
+    changes: &[(Hash256, Option<u32>)],
+) {
+    for (output, block_index) in changes {
+        match block_index {
+            Some(block_index) => unspent_outputs.insert(*output, *block_index),
+            None => unspent_outputs.remove(output),
+        };
+    }
+}
+
+pub(crate) fn missing_undo_data() -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, "the tip has no undo data")
+}
+
+#[cfg(test)]
+mod memory_storage_tests {
+    use primitive_types::U256;
+
+    use super::{ChainStorage, MemoryStorage};
+    use crate::{Block, BlockUndo, Hash256};
+
+    fn create_block(index: u32) -> Block {
+        Block::new(index, index as u128, Hash256::ZERO, vec![], 0)
+    }
+
+    #[test]
+    fn connect_and_disconnect_blocks() {
+        let mut storage = MemoryStorage::default();
+        let output = Hash256([1; 32]);
+
+        storage
+            .connect_block(
+                create_block(0),
+                BlockUndo::new(vec![(output, None)]),
+                &[(output, Some(0))],
+                U256::one(),
+            )
+            .unwrap();
+
+        assert_eq!(1, storage.block_count());
+        assert_eq!(Some(0), storage.unspent_output(&output).unwrap());
+        assert_eq!(U256::one(), storage.chain_work());
+
+        let result = storage.disconnect_tip(U256::zero()).unwrap();
+
+        assert_eq!(0, result.index);
+        assert_eq!(0, storage.block_count());
+        assert_eq!(None, storage.unspent_output(&output).unwrap());
+        assert_eq!(U256::zero(), storage.chain_work());
+    }
+
+    #[test]
+    fn prune_block() {
+        let mut storage = MemoryStorage::default();
+        storage
+            .connect_block(create_block(0), BlockUndo::default(), &[], U256::one())
+            .unwrap();
+
+        storage.prune_block(0).unwrap();
+
+        assert_eq!(1, storage.pruned_height());
+        assert_eq!(None, storage.read_body(0).unwrap());
+        assert_eq!(None, storage.read_undo(0).unwrap());
+        assert!(storage.header(0).is_some());
+        assert!(storage.disconnect_tip(U256::zero()).is_err());
+    }
+}
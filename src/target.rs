@@ -0,0 +1,445 @@
+use std::fmt::{self, Debug, Formatter};
+
+use primitive_types::U256;
+
+use crate::Hash256;
+
+/**
+ * A proof-of-work target: a full 256-bit number that a block's hash must not
+ * exceed for the block to be considered "valid". The lower the target, the
+ * harder it is to find a hash below it.
+ *
+ * Compact Encoding
+ * ----------------
+ *
+ * Like Bitcoin's "nBits", a block stores its target in 32 bits, as a base-256
+ * floating point number:
+ *
+ *   bits = eeeeeeee smmmmmmm mmmmmmmm mmmmmmmm
+ *
+ *   target = mantissa * 256^(exponent - 3)
+ *
+ * where the exponent is the number of significant bytes of the target and the
+ * 23-bit mantissa holds its most significant bytes. The sign bit (s) must be
+ * zero, since targets are never negative. Only the 3 most significant bytes
+ * of the target survive the encoding, which is plenty of precision.
+ *
+ * Example: 0x1d00ffff is 0x00ffff * 256^(0x1d - 3), Bitcoin's original target.
+ *
+ * Difficulty
+ * ----------
+ *
+ * The human-readable difficulty is the ratio between the maximum target
+ * (2^256 - 1, which any hash meets) and the target. A difficulty of 2^n means
+ * that roughly the n most significant bits of a valid hash are zero.
+ *
+ * Work
+ * ----
+ *
+ * The work of a block is the expected number of hashes it takes to find a hash
+ * at or below its target: 2^256 / (target + 1). Summing the work of all blocks
+ * gives the total work of a chain.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Target(U256);
+
+impl Target {
+    pub const MAX: Target = Target(U256::MAX);
+
+    /**
+     * Returns the target that corresponds to the given number.
+     */
+    pub fn from_u256(value: U256) -> Self {
+        Target(value)
+    }
+
+    /**
+     * Returns the target as a number.
+     */
+    pub fn as_u256(&self) -> U256 {
+        self.0
+    }
+
+    /**
+     * Decodes a target from its compact encoding. Returns None if the encoding
+     * is negative or overflows 256 bits.
+     */
+    pub fn from_compact(bits: u32) -> Option<Self> {
+        let exponent = bits >> 24;
+        let mantissa = bits & 0x007f_ffff;
+        let is_negative = bits & 0x0080_0000 != 0;
+
+        if mantissa == 0 {
+            return Some(Target(U256::zero()));
+        } else if is_negative {
+            return None;
+        }
+
+        let target = if exponent <= 3 {
+            U256::from(mantissa >> (8 * (3 - exponent)))
+        } else {
+            let shift = 8 * (exponent - 3);
+            let mantissa = U256::from(mantissa);
+            if shift >= 256 || mantissa.bits() + shift as usize > 256 {
+                return None;
+            }
+
+            mantissa << shift
+        };
+
+        Some(Target(target))
+    }
+
+    /**
+     * Encodes the target in its compact form. The least significant bytes of
+     * the target beyond the 3 most significant ones are dropped.
+     */
+    pub fn to_compact(&self) -> u32 {
+        let mut size = self.0.bits().div_ceil(8);
+        let mut compact = if size <= 3 {
+            self.0.low_u32() << (8 * (3 - size))
+        } else {
+            (self.0 >> (8 * (size - 3))).low_u32()
+        };
+
+        // The mantissa must not have the sign bit set.
+        if compact & 0x0080_0000 != 0 {
+            compact >>= 8;
+            size += 1;
+        }
+
+        compact | (size as u32) << 24
+    }
+
+    /**
+     * Returns the target that corresponds to the given human-readable
+     * difficulty. A difficulty of 1 (or less) yields the maximum target.
+     */
+    pub fn from_difficulty(difficulty: f64) -> Self {
+        if difficulty.is_nan() || difficulty <= 1.0 {
+            return Target::MAX;
+        }
+
+        Target(f64_to_u256(u256_to_f64(U256::MAX) / difficulty))
+    }
+
+    /**
+     * Returns the human-readable difficulty of the target.
+     */
+    pub fn difficulty(&self) -> f64 {
+        u256_to_f64(U256::MAX) / u256_to_f64(self.0)
+    }
+
+    /**
+     * Returns the expected number of hashes it takes to find a hash at or
+     * below the target: 2^256 / (target + 1), which is computed as
+     * (~target / (target + 1)) + 1 so that it fits 256 bits.
+     */
+    pub fn work(&self) -> U256 {
+        let (denominator, overflow) = self.0.overflowing_add(U256::one());
+        if overflow {
+            return U256::one();
+        }
+
+        (!self.0 / denominator).saturating_add(U256::one())
+    }
+
+    /**
+     * Checks whether the hash, interpreted as a little-endian number, is less
+     * than or equal to the target.
+     */
+    pub fn is_met_by(&self, hash: &Hash256) -> bool {
+        U256::from_little_endian(hash.as_bytes()) <= self.0
+    }
+}
+
+impl Debug for Target {
+    fn fmt(&self, buffer: &mut Formatter) -> fmt::Result {
+        write!(buffer, "Target({:#066x})", self.0)
+    }
+}
+
+fn u256_to_f64(value: U256) -> f64 {
+    value.0.iter().rev().fold(0.0, |result, limb| {
+        result * 18_446_744_073_709_551_616.0 + *limb as f64
+    })
+}
+
+fn f64_to_u256(value: f64) -> U256 {
+    if value < 1.0 {
+        return U256::zero();
+    } else if value < 18_446_744_073_709_551_616.0 {
+        return U256::from(value as u64);
+    }
+
+    let exponent = value.log2().floor() as usize;
+    if exponent >= 256 {
+        return U256::MAX;
+    }
+
+    let shift = exponent - 63;
+    let mantissa = (value / 2_f64.powi(shift as i32)) as u64;
+
+    U256::from(mantissa) << shift
+}
+
+#[cfg(test)]
+mod target_compact_tests {
+    use primitive_types::U256;
+
+    use super::Target;
+
+    #[test]
+    fn from_compact_with_bitcoin_genesis_bits() {
+        let result = Target::from_compact(0x1d00_ffff);
+
+        assert_eq!(Some(Target::from_u256(U256::from(0xffff) << 208)), result);
+    }
+
+    #[test]
+    fn from_compact_with_small_exponents() {
+        assert_eq!(
+            Some(Target::from_u256(U256::from(0x12))),
+            Target::from_compact(0x0112_0000)
+        );
+        assert_eq!(
+            Some(Target::from_u256(U256::from(0x1234))),
+            Target::from_compact(0x0212_3400)
+        );
+        assert_eq!(
+            Some(Target::from_u256(U256::from(0x12_3456))),
+            Target::from_compact(0x0312_3456)
+        );
+    }
+
+    #[test]
+    fn from_compact_with_zero_mantissa() {
+        let result = Target::from_compact(0x2000_0000);
+
+        assert_eq!(Some(Target::from_u256(U256::zero())), result);
+    }
+
+    #[test]
+    fn from_compact_with_negative_encoding() {
+        let result = Target::from_compact(0x0492_3456);
+
+        assert_eq!(None, result);
+    }
+
+    #[test]
+    fn from_compact_with_overflow() {
+        assert_eq!(None, Target::from_compact(0x2101_0000));
+        assert_eq!(None, Target::from_compact(0xff12_3456));
+    }
+
+    #[test]
+    fn from_compact_with_largest_encoding() {
+        let result = Target::from_compact(0x2100_ffff);
+
+        assert_eq!(Some(Target::from_u256(U256::from(0xffff) << 240)), result);
+    }
+
+    #[test]
+    fn to_compact_with_bitcoin_genesis_target() {
+        let target = Target::from_u256(U256::from(0xffff) << 208);
+
+        let result = target.to_compact();
+
+        assert_eq!(0x1d00_ffff, result);
+    }
+
+    #[test]
+    fn to_compact_with_zero() {
+        let target = Target::from_u256(U256::zero());
+
+        let result = target.to_compact();
+
+        assert_eq!(0, result);
+    }
+
+    #[test]
+    fn to_compact_with_small_target() {
+        let target = Target::from_u256(U256::from(0x1234));
+
+        let result = target.to_compact();
+
+        assert_eq!(0x0212_3400, result);
+    }
+
+    #[test]
+    fn to_compact_avoids_sign_bit() {
+        let target = Target::from_u256(U256::from(0x80));
+
+        let result = target.to_compact();
+
+        assert_eq!(0x0200_8000, result);
+        assert_eq!(Some(target), Target::from_compact(result));
+    }
+
+    #[test]
+    fn to_compact_drops_least_significant_bytes() {
+        let target = Target::from_u256(U256::from(0x1234_5678));
+
+        let result = target.to_compact();
+
+        assert_eq!(0x0412_3456, result);
+        assert_eq!(
+            Some(Target::from_u256(U256::from(0x1234_5600))),
+            Target::from_compact(result)
+        );
+    }
+
+    #[test]
+    fn to_compact_with_max_target() {
+        let result = Target::MAX.to_compact();
+
+        assert_eq!(0x2100_ffff, result);
+    }
+}
+
+#[cfg(test)]
+mod target_difficulty_tests {
+    use primitive_types::U256;
+
+    use super::Target;
+
+    #[test]
+    fn difficulty_of_max_target() {
+        let result = Target::MAX.difficulty();
+
+        assert_eq!(1.0, result);
+    }
+
+    #[test]
+    fn difficulty_of_target_with_16_leading_zero_bits() {
+        let target = Target::from_u256(U256::MAX >> 16);
+
+        let result = target.difficulty();
+
+        assert!((result - 65_536.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn from_difficulty_of_one() {
+        let result = Target::from_difficulty(1.0);
+
+        assert_eq!(Target::MAX, result);
+    }
+
+    #[test]
+    fn from_difficulty_below_one() {
+        let result = Target::from_difficulty(0.5);
+
+        assert_eq!(Target::MAX, result);
+    }
+
+    #[test]
+    fn from_difficulty_of_256() {
+        let result = Target::from_difficulty(256.0);
+
+        assert_eq!(0x2001_0000, result.to_compact());
+        assert!((result.difficulty() - 256.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn from_difficulty_round_trip() {
+        let difficulty = 123_456_789.0;
+
+        let result = Target::from_difficulty(difficulty).difficulty();
+
+        assert!((result - difficulty).abs() / difficulty < 1e-9);
+    }
+}
+
+#[cfg(test)]
+mod target_work_tests {
+    use primitive_types::U256;
+
+    use super::Target;
+
+    #[test]
+    fn work_of_max_target() {
+        let result = Target::MAX.work();
+
+        assert_eq!(U256::one(), result);
+    }
+
+    #[test]
+    fn work_of_zero_target() {
+        let result = Target::from_u256(U256::zero()).work();
+
+        assert_eq!(U256::MAX, result);
+    }
+
+    #[test]
+    fn work_of_target_with_16_leading_zero_bits() {
+        let target = Target::from_u256(U256::MAX >> 16);
+
+        let result = target.work();
+
+        assert_eq!(U256::from(65_536), result);
+    }
+
+    #[test]
+    fn work_of_bitcoin_genesis_target() {
+        let target = Target::from_compact(0x1d00_ffff).unwrap();
+
+        let result = target.work();
+
+        assert_eq!(U256::from(0x0001_0001_0001_u64), result);
+    }
+}
+
+#[cfg(test)]
+mod target_is_met_by_tests {
+    use primitive_types::U256;
+
+    use super::Target;
+    use crate::Hash256;
+
+    fn hash_with_most_significant_byte(byte: u8) -> Hash256 {
+        let mut bytes = [0xff; 32];
+        bytes[31] = byte;
+
+        Hash256(bytes)
+    }
+
+    #[test]
+    fn hash_below_target() {
+        let target = Target::from_u256(U256::from(0x01) << 248);
+
+        let result = target.is_met_by(&hash_with_most_significant_byte(0x00));
+
+        assert!(result);
+    }
+
+    #[test]
+    fn hash_equal_to_target() {
+        let target = Target::from_u256(U256::MAX);
+
+        let result = target.is_met_by(&Hash256([0xff; 32]));
+
+        assert!(result);
+    }
+
+    #[test]
+    fn hash_above_target() {
+        let target = Target::from_u256(U256::from(0x01) << 248);
+
+        let result = target.is_met_by(&hash_with_most_significant_byte(0x01));
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn lower_bytes_of_hash_matter() {
+        let target = Target::from_u256(U256::from(0x10));
+        let mut below = [0; 32];
+        below[0] = 0x0f;
+        let mut above = [0; 32];
+        above[0] = 0x11;
+
+        assert!(target.is_met_by(&Hash256(below)));
+        assert!(!target.is_met_by(&Hash256(above)));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::transaction::{Input, OutPoint, Output};
+use crate::{
+    Block, Blockchain, ChainStorage, Hash256, HashAlgorithm, Script, SigningKey, Transaction,
+};
+
+/**
+ * The bits of the blocks that the fixtures create: the easiest target, so
+ * that sealing a block takes a couple of attempts.
+ */
+pub const BITS: u32 = 0x2100_ffff;
+
+pub fn output(to_address: &str, value: u64) -> Output {
+    Output {
+        to_address: to_address.to_owned(),
+        value,
+        locking_script: Script::new(),
+    }
+}
+
+pub fn signing_key(byte: u8) -> SigningKey {
+    SigningKey::from_bytes(&[byte; 32].into()).unwrap()
+}
+
+pub fn coinbase(block_index: u32, to_address: &str, value: u64) -> Transaction {
+    Transaction::coinbase(block_index, vec![output(to_address, value)])
+}
+
+/**
+ * Returns an input that spends the output at the given position of the given
+ * transaction, whose id is computed with the default algorithm.
+ */
+pub fn spend(transaction: &Transaction, index: usize) -> Input {
+    transaction.spend(index, HashAlgorithm::default()).unwrap()
+}
+
+/**
+ * Returns the outpoint at the given position of a transaction that no chain
+ * has, for transactions that are only checked by themselves.
+ */
+pub fn outpoint(index: u32) -> OutPoint {
+    OutPoint {
+        transaction_id: Hash256([0xff; 32]),
+        index,
+    }
+}
+
+/**
+ * Returns an input that spends an output that pays the given value to the
+ * given address, at an outpoint of a transaction that no chain has.
+ */
+pub fn input(to_address: &str, value: u64) -> Input {
+    Input::new(outpoint(0), output(to_address, value))
+}
+
+/**
+ * Returns a transaction that spends the given input and pays the given value
+ * to the given address.
+ */
+pub fn payment(input: Input, to_address: &str, value: u64) -> Transaction {
+    Transaction {
+        inputs: vec![input],
+        outputs: vec![output(to_address, value)],
+        lock_time: None,
+    }
+}
+
+/**
+ * Returns the payment in which Alice spends the 5 coins that the genesis
+ * block (whose only transaction is coinbase(0, "Alice", 5)) pays her: 3 go
+ * to Bob, and 2 come back to her.
+ */
+pub fn create_payment() -> Transaction {
+    Transaction {
+        inputs: vec![spend(&coinbase(0, "Alice", 5), 0)],
+        outputs: vec![output("Bob", 3), output("Alice", 2)],
+        lock_time: None,
+    }
+}
+
+/**
+ * Returns the sealed block with the given transactions that follows the tip
+ * of the chain (or its genesis block, if the chain is empty), 10 milliseconds
+ * after it.
+ */
+pub fn create_next_block<S: ChainStorage>(
+    blockchain: &Blockchain<S>,
+    transactions: Vec<Transaction>,
+) -> Block {
+    let (index, timestamp, previous_block_hash) = match blockchain.tip() {
+        Some(block) => (block.index + 1, block.timestamp + 10, block.hash),
+        None => (0, 10, Hash256::ZERO),
+    };
+    let mut block = Block::new(index, timestamp, previous_block_hash, transactions, BITS);
+    block.update_transaction_root(blockchain.hash_algorithms().transaction);
+    blockchain.seal_block(&mut block).unwrap();
+
+    block
+}
+
+/**
+ * Adds the block with the given transactions to the tip of the chain.
+ */
+pub fn add_block<S: ChainStorage>(blockchain: &mut Blockchain<S>, transactions: Vec<Transaction>) {
+    let block = create_next_block(blockchain, transactions);
+    blockchain.update_with_block(block).unwrap();
+}
+
+/**
+ * Returns the block with the given index and a coinbase transaction that
+ * pays Alice 1 coin, for storage tests: it isn't sealed, and its hash is its
+ * index repeated.
+ */
+pub fn create_stored_block(index: u32) -> Block {
+    let coinbase = Transaction {
+        inputs: vec![],
+        outputs: vec![output("Alice", 1)],
+        lock_time: None,
+    };
+    let mut block = Block::new(index, index as u128, Hash256::ZERO, vec![coinbase], 0);
+    block.hash = Hash256([index as u8; 32]);
+
+    block
+}
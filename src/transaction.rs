@@ -1,29 +1,342 @@
 use std::collections::HashSet;
 
-use crate::{u64_bytes, Address, BlockHash, Hashable};
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::{ByteWriter, Hasher};
+use crate::script::MAX_MULTISIG_KEYS;
+use crate::{
+    u128_bytes, u32_bytes, u64_bytes, Address, BlockHash, FeeRate, Hash256, HashAlgorithm,
+    Hashable, Script, TransactionId, VerifyingKey,
+};
 
 /**
- * Represents a transaction output that has the recipient's address and the
- * value to transfer to the recipient.
+ * The earliest point at which a transaction can be included in a block:
+ * either a block index or a block timestamp (in milliseconds).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockTime {
+    BlockIndex(u32),
+    Timestamp(u128),
+}
+
+impl LockTime {
+    /**
+     * Returns a flag that states whether a block with the given index and
+     * timestamp is at or past the lock time.
+     */
+    pub fn is_reached(&self, block_index: u32, block_timestamp: u128) -> bool {
+        match *self {
+            LockTime::BlockIndex(index) => block_index >= index,
+            LockTime::Timestamp(timestamp) => block_timestamp >= timestamp,
+        }
+    }
+}
+
+impl Hashable for LockTime {
+    /**
+     * Writes the hashable bytes that represent the lock time: a tag byte
+     * followed by the block index or the timestamp.
+     */
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        match self {
+            LockTime::BlockIndex(index) => {
+                writer.write(&[0]);
+                writer.write(&u32_bytes(*index));
+            }
+            LockTime::Timestamp(timestamp) => {
+                writer.write(&[1]);
+                writer.write(&u128_bytes(*timestamp));
+            }
+        }
+    }
+}
+
+impl Encodable for LockTime {
+    /**
+     * Writes the encoding of the lock time, which is the same as its hashable
+     * bytes.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.write_bytes(writer);
+    }
+}
+
+impl Decodable for LockTime {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        match reader.read_u8()? {
+            0 => Ok(LockTime::BlockIndex(reader.read_u32()?)),
+            1 => Ok(LockTime::Timestamp(reader.read_u128()?)),
+            _ => Err(DecodeErr::InvalidTag),
+        }
+    }
+}
+
+/**
+ * How long after the output that an input spends was created the input can
+ * be included in a block: either a number of blocks or a duration (in
+ * milliseconds), both counted from the block that contains the output.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelativeLockTime {
+    Blocks(u32),
+    Milliseconds(u128),
+}
+
+impl RelativeLockTime {
+    /**
+     * Returns a flag that states whether a block with the given index and
+     * timestamp is at or past the relative lock time of an output created in
+     * the block with the given index and timestamp.
+     */
+    pub fn is_reached(
+        &self,
+        output_block_index: u32,
+        output_block_timestamp: u128,
+        block_index: u32,
+        block_timestamp: u128,
+    ) -> bool {
+        match *self {
+            RelativeLockTime::Blocks(blocks) => {
+                u64::from(block_index) >= u64::from(output_block_index) + u64::from(blocks)
+            }
+            RelativeLockTime::Milliseconds(milliseconds) => {
+                block_timestamp >= output_block_timestamp.saturating_add(milliseconds)
+            }
+        }
+    }
+}
+
+impl Hashable for RelativeLockTime {
+    /**
+     * Writes the hashable bytes that represent the relative lock time: a tag
+     * byte followed by the number of blocks or the duration.
+     */
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        match self {
+            RelativeLockTime::Blocks(blocks) => {
+                writer.write(&[0]);
+                writer.write(&u32_bytes(*blocks));
+            }
+            RelativeLockTime::Milliseconds(milliseconds) => {
+                writer.write(&[1]);
+                writer.write(&u128_bytes(*milliseconds));
+            }
+        }
+    }
+}
+
+impl Encodable for RelativeLockTime {
+    /**
+     * Writes the encoding of the relative lock time, which is the same as its
+     * hashable bytes.
+     */
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.write_bytes(writer);
+    }
+}
+
+impl Decodable for RelativeLockTime {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        match reader.read_u8()? {
+            0 => Ok(RelativeLockTime::Blocks(reader.read_u32()?)),
+            1 => Ok(RelativeLockTime::Milliseconds(reader.read_u128()?)),
+            _ => Err(DecodeErr::InvalidTag),
+        }
+    }
+}
+
+/**
+ * Represents a transaction output that has the recipient's address, the
+ * value to transfer to the recipient and the locking script that states the
+ * conditions for spending the output (see Script).
  */
 #[derive(Clone, Debug, PartialEq)]
 pub struct Output {
     pub to_address: Address,
     pub value: u64,
+    pub locking_script: Script,
+}
+
+impl Output {
+    /**
+     * Returns an output that can only be spent with valid signatures from at
+     * least threshold of the given public keys (M of N), or None if the
+     * threshold is zero or greater than the number of keys, or if there are
+     * more keys than a multisig script allows.
+     */
+    pub fn multisig(
+        to_address: Address,
+        value: u64,
+        threshold: usize,
+        public_keys: &[VerifyingKey],
+    ) -> Option<Self> {
+        if threshold == 0 || threshold > public_keys.len() || public_keys.len() > MAX_MULTISIG_KEYS
+        {
+            return None;
+        }
+
+        Some(Output {
+            to_address,
+            value,
+            locking_script: Script::multisig(threshold, public_keys),
+        })
+    }
 }
 
 impl Hashable for Output {
     /**
-     * Returns a vector of hashable bytes that represents the transaction
-     * output.
+     * Writes the hashable bytes that represent the transaction output.
      */
-    fn bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![];
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(self.to_address.as_bytes());
+        writer.write(&u64_bytes(self.value));
+        self.locking_script.write_bytes(writer);
+    }
+}
 
-        bytes.extend(self.to_address.as_bytes());
-        bytes.extend(&u64_bytes(self.value));
+impl Encodable for Output {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.to_address.write_encoding(writer);
+        self.value.write_encoding(writer);
+        self.locking_script.write_encoding(writer);
+    }
+}
 
-        bytes
+impl Decodable for Output {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(Output {
+            to_address: Address::read_encoding(reader)?,
+            value: reader.read_u64()?,
+            locking_script: Script::read_encoding(reader)?,
+        })
+    }
+}
+
+/**
+ * Identifies a transaction output: the id of the transaction that created it
+ * and the output's position in that transaction. Identical outputs (the same
+ * address, value and locking script) of different transactions, or of the
+ * same transaction, have different outpoints.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OutPoint {
+    pub transaction_id: TransactionId,
+    pub index: u32,
+}
+
+impl OutPoint {
+    /**
+     * Returns the hash (computed with the given algorithm) that identifies
+     * the given output at this outpoint in the unspent outputs: the hash of
+     * the outpoint followed by the output. Since it covers the output, an
+     * input that claims another value or locking script than the output it
+     * spends doesn't match an unspent output.
+     */
+    pub fn output_hash(&self, output: &Output, algorithm: HashAlgorithm) -> Hash256 {
+        let mut hasher = Hasher::new(algorithm);
+        self.write_bytes(&mut hasher);
+        output.write_bytes(&mut hasher);
+
+        hasher.finalize()
+    }
+}
+
+impl Hashable for OutPoint {
+    /**
+     * Writes the hashable bytes that represent the outpoint: the transaction
+     * id followed by the output's index.
+     */
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(self.transaction_id.as_bytes());
+        writer.write(&u32_bytes(self.index));
+    }
+}
+
+impl Encodable for OutPoint {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.transaction_id.write_encoding(writer);
+        self.index.write_encoding(writer);
+    }
+}
+
+impl Decodable for OutPoint {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(OutPoint {
+            transaction_id: reader.read_hash()?,
+            index: reader.read_u32()?,
+        })
+    }
+}
+
+/**
+ * Represents a transaction input: the outpoint of the output it spends, a
+ * copy of that output (so that the input's value and locking script are
+ * known without looking the output up), the unlocking script that meets the
+ * conditions of that output's locking script and an optional relative lock
+ * time, which keeps the input out of blocks until the output is old enough.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Input {
+    pub outpoint: OutPoint,
+    pub previous_output: Output,
+    pub unlocking_script: Script,
+    pub relative_lock_time: Option<RelativeLockTime>,
+}
+
+impl Input {
+    /**
+     * Returns an input that spends the given output at the given outpoint
+     * with an empty unlocking script and no relative lock time.
+     */
+    pub fn new(outpoint: OutPoint, previous_output: Output) -> Self {
+        Input {
+            outpoint,
+            previous_output,
+            unlocking_script: Script::new(),
+            relative_lock_time: None,
+        }
+    }
+
+    /**
+     * Returns the hash (computed with the given algorithm) of the output
+     * that the input spends (see OutPoint::output_hash).
+     */
+    pub fn output_hash(&self, algorithm: HashAlgorithm) -> Hash256 {
+        self.outpoint.output_hash(&self.previous_output, algorithm)
+    }
+}
+
+impl Hashable for Input {
+    /**
+     * Writes the hashable bytes that represent the transaction input. The
+     * relative lock time is only written if the input has one.
+     */
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.outpoint.write_bytes(writer);
+        self.previous_output.write_bytes(writer);
+        self.unlocking_script.write_bytes(writer);
+        if let Some(relative_lock_time) = &self.relative_lock_time {
+            relative_lock_time.write_bytes(writer);
+        }
+    }
+}
+
+impl Encodable for Input {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.outpoint.write_encoding(writer);
+        self.previous_output.write_encoding(writer);
+        self.unlocking_script.write_encoding(writer);
+        self.relative_lock_time.write_encoding(writer);
+    }
+}
+
+impl Decodable for Input {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(Input {
+            outpoint: OutPoint::read_encoding(reader)?,
+            previous_output: Output::read_encoding(reader)?,
+            unlocking_script: Script::read_encoding(reader)?,
+            relative_lock_time: Option::read_encoding(reader)?,
+        })
     }
 }
 
@@ -87,6 +400,9 @@ impl Hashable for Output {
  * - Allow the miner to collect all the transaction fees in that block and
  *   that block's block reward (coin genesis).
  *
+ * - Commit to the index of their block (see Transaction::coinbase), so that
+ *   no two blocks have the same coinbase transaction.
+ *
  * Transaction Verification Requirements
  * -------------------------------------
  *
@@ -124,53 +440,167 @@ impl Hashable for Output {
  *   We can't assume that whoever sent us the transaction over the network is
  *   also the person who created the transaction.
  *
- *   For now, we'll kind of ignore solving this problem. We might come back to
- *   it when we go over smart contracts.
+ *   Each output is locked by a script, and each input must provide an
+ *   unlocking script that satisfies it, e.g., a signature of the transaction
+ *   from the owner of the output's public key (see Script). The signatures
+ *   sign the transaction's signature hash, which covers everything but the
+ *   unlocking scripts themselves.
  *
  * (In Bitcoin, there are more transaction verification requirements but for
  * our project, we're going to cover these three.)
+ *
+ * Lock Times
+ * ----------
+ *
+ * A transaction may state when it becomes valid: a lock time (a block index
+ * or a timestamp) that the block that includes it must have reached, and, for
+ * each input, a relative lock time (a number of blocks or a duration) that
+ * must have passed since the block that created the spent output. Until then
+ * the transaction isn't final and can't be included in a block. E.g., vesting
+ * payouts are signed in advance with increasing lock times, and an escrow
+ * refund is signed with a relative lock time so that it's only valid if the
+ * escrow isn't released first.
  */
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Transaction {
-    pub inputs: Vec<Output>,
+    pub inputs: Vec<Input>,
     pub outputs: Vec<Output>,
+    pub lock_time: Option<LockTime>,
 }
 
 impl Transaction {
     /**
-     * Returns the sum of the transaction's inputs.
+     * Returns the sum of the transaction's inputs, or None if it doesn't fit
+     * in a u64.
      */
-    pub fn input_value(&self) -> u64 {
-        self.inputs.iter().map(|input| input.value).sum()
+    pub fn input_value(&self) -> Option<u64> {
+        self.inputs.iter().try_fold(0u64, |sum, input| {
+            sum.checked_add(input.previous_output.value)
+        })
     }
 
     /**
-     * Returns the sum of the transaction's outputs.
+     * Returns the sum of the transaction's outputs, or None if it doesn't fit
+     * in a u64.
      */
-    pub fn output_value(&self) -> u64 {
-        self.outputs.iter().map(|output| output.value).sum()
+    pub fn output_value(&self) -> Option<u64> {
+        self.outputs
+            .iter()
+            .try_fold(0u64, |sum, output| sum.checked_add(output.value))
     }
 
     /**
-     * Returns a set of hashes of the transaction's inputs.
+     * Returns the transaction's fee: the value of its inputs that its outputs
+     * don't spend, or zero if its outputs spend more than its inputs, if
+     * either sum doesn't fit in a u64 (or if it's a coinbase transaction). The inputs' values are the ones they
+     * claim, which are only known to be real once the transaction has been
+     * validated against a chain (see Blockchain::validate_transaction).
      */
-    pub fn input_hashes(&self) -> HashSet<BlockHash> {
+    pub fn fee(&self) -> u64 {
+        match (self.input_value(), self.output_value()) {
+            (Some(input_value), Some(output_value)) => input_value.saturating_sub(output_value),
+            _ => 0,
+        }
+    }
+
+    /**
+     * Returns the transaction's serialized size in bytes: the size of its
+     * canonical encoding, which is what fee rates are measured against.
+     */
+    pub fn size(&self) -> usize {
+        self.encode().len()
+    }
+
+    /**
+     * Returns the transaction's fee rate: its fee per 1,000 bytes of its
+     * serialized size.
+     */
+    pub fn fee_rate(&self) -> FeeRate {
+        FeeRate::of(self.fee(), self.size())
+    }
+
+    /**
+     * Returns a set of hashes of the outputs that the transaction's inputs
+     * spend, computed with the given algorithm (see OutPoint::output_hash).
+     */
+    pub fn input_hashes(&self, algorithm: HashAlgorithm) -> HashSet<BlockHash> {
         self.inputs
             .iter()
-            .map(Hashable::hash)
+            .map(|input| input.output_hash(algorithm))
             .collect::<HashSet<BlockHash>>()
     }
 
     /**
-     * Returns a set of hashes of the transaction's outputs.
+     * Returns a set of hashes of the transaction's outputs, computed with the
+     * given algorithm (see OutPoint::output_hash).
      */
-    pub fn output_hashes(&self) -> HashSet<BlockHash> {
+    pub fn output_hashes(&self, algorithm: HashAlgorithm) -> HashSet<BlockHash> {
+        let id = self.id_with(algorithm);
         self.outputs
             .iter()
-            .map(Hashable::hash)
+            .enumerate()
+            .map(|(index, output)| {
+                OutPoint {
+                    transaction_id: id,
+                    index: index as u32,
+                }
+                .output_hash(output, algorithm)
+            })
             .collect::<HashSet<BlockHash>>()
     }
 
+    /**
+     * Returns an input that spends the transaction's output at the given
+     * position, whose outpoint has the transaction's id computed with the
+     * given algorithm (the chain's transaction algorithm), or None if the
+     * transaction has no such output.
+     */
+    pub fn spend(&self, index: usize, algorithm: HashAlgorithm) -> Option<Input> {
+        let output = self.outputs.get(index)?;
+        let outpoint = OutPoint {
+            transaction_id: self.id_with(algorithm),
+            index: index as u32,
+        };
+
+        Some(Input::new(outpoint, output.clone()))
+    }
+
+    /**
+     * Returns the transaction's id (SHA-256), which identifies it in blocks
+     * and in transaction lookups: the hash of its canonical encoding (see
+     * Encodable). Unlike its hashable bytes, which concatenate its fields, the
+     * encoding prefixes every list and every variable-length field with its
+     * length, so two different transactions can't share an id.
+     */
+    pub fn id(&self) -> TransactionId {
+        self.id_with(HashAlgorithm::default())
+    }
+
+    /**
+     * Returns the transaction's id computed with the given algorithm (the
+     * chain's transaction algorithm).
+     */
+    pub fn id_with(&self, algorithm: HashAlgorithm) -> TransactionId {
+        let mut hasher = Hasher::new(algorithm);
+        self.write_encoding(&mut hasher);
+
+        hasher.finalize()
+    }
+
+    /**
+     * Returns the coinbase transaction of the block with the given index,
+     * which pays the given outputs. The coinbase commits to the block's index
+     * through its lock time, so the coinbases of different blocks have
+     * different ids even if they pay the same outputs.
+     */
+    pub fn coinbase(block_index: u32, outputs: Vec<Output>) -> Self {
+        Transaction {
+            inputs: vec![],
+            outputs,
+            lock_time: Some(LockTime::BlockIndex(block_index)),
+        }
+    }
+
     /**
      * Returns a flag that states whether this transaction is a coinbase one.
      * A coinbase transaction has empty inputs.
@@ -178,56 +608,168 @@ impl Transaction {
     pub fn is_coinbase(&self) -> bool {
         self.inputs.is_empty()
     }
+
+    /**
+     * Returns the index of the block that the coinbase transaction commits
+     * to, or None if the transaction isn't a coinbase one or doesn't commit to
+     * a block index.
+     */
+    pub fn coinbase_block_index(&self) -> Option<u32> {
+        match self.lock_time {
+            Some(LockTime::BlockIndex(block_index)) if self.is_coinbase() => Some(block_index),
+            _ => None,
+        }
+    }
+
+    /**
+     * Returns a flag that states whether the transaction's lock time allows
+     * it to be included in a block with the given index and timestamp. A
+     * transaction without a lock time is always final. Relative lock times
+     * depend on the blocks of the spent outputs, so they're checked by the
+     * blockchain.
+     */
+    pub fn is_final(&self, block_index: u32, block_timestamp: u128) -> bool {
+        self.lock_time
+            .is_none_or(|lock_time| lock_time.is_reached(block_index, block_timestamp))
+    }
+
+    /**
+     * Returns the hash that the signatures in the unlocking scripts sign,
+     * computed with the given algorithm: the hash of the transaction's
+     * encoding (the one that its id is the hash of) with empty unlocking
+     * scripts, since they contain the signatures. The encoding prefixes every
+     * length and tags every optional field, so no two transactions that differ
+     * in anything but their unlocking scripts have the same signature hash.
+     */
+    pub fn signature_hash(&self, algorithm: HashAlgorithm) -> Hash256 {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.write(&u32_bytes(self.inputs.len() as u32));
+        for input in &self.inputs {
+            input.outpoint.write_encoding(&mut hasher);
+            input.previous_output.write_encoding(&mut hasher);
+            Script::new().write_encoding(&mut hasher);
+            input.relative_lock_time.write_encoding(&mut hasher);
+        }
+        self.outputs.write_encoding(&mut hasher);
+        self.lock_time.write_encoding(&mut hasher);
+
+        hasher.finalize()
+    }
 }
 
 impl Hashable for Transaction {
     /**
-     * Returns a vector of hashable bytes that represents the transaction.
+     * Writes the hashable bytes that represent the transaction. The lock time
+     * is only written if the transaction has one, so transactions without
+     * lock times keep the bytes they had before lock times existed.
      */
-    fn bytes(&self) -> Vec<u8> {
-        let mut bytes = vec![];
-
-        bytes.extend(
-            self.inputs
-                .iter()
-                .flat_map(Hashable::bytes)
-                .collect::<Vec<u8>>(),
-        );
-        bytes.extend(
-            self.outputs
-                .iter()
-                .flat_map(Hashable::bytes)
-                .collect::<Vec<u8>>(),
-        );
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        for input in &self.inputs {
+            input.write_bytes(writer);
+        }
+        for output in &self.outputs {
+            output.write_bytes(writer);
+        }
+        if let Some(lock_time) = &self.lock_time {
+            lock_time.write_bytes(writer);
+        }
+    }
+}
+
+impl Encodable for Transaction {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.inputs.write_encoding(writer);
+        self.outputs.write_encoding(writer);
+        self.lock_time.write_encoding(writer);
+    }
+}
 
-        bytes
+impl Decodable for Transaction {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        Ok(Transaction {
+            inputs: Vec::read_encoding(reader)?,
+            outputs: Vec::read_encoding(reader)?,
+            lock_time: Option::read_encoding(reader)?,
+        })
     }
 }
 
 #[cfg(test)]
 mod output_constructor_tests {
-    use super::Output;
+    use super::{Output, Script, VerifyingKey};
+    use crate::SigningKey;
+
+    fn public_keys(count: u8) -> Vec<VerifyingKey> {
+        (1..=count)
+            .map(|byte| {
+                *SigningKey::from_bytes(&[byte; 32].into())
+                    .unwrap()
+                    .verifying_key()
+            })
+            .collect()
+    }
 
     #[test]
     fn constructor() {
         let instance = Output {
             to_address: "test-recipient-address".to_string(),
             value: 1,
+            locking_script: Script::new(),
         };
 
         assert_eq!(1, instance.value);
     }
+
+    #[test]
+    fn multisig() {
+        let public_keys = public_keys(3);
+
+        let result = Output::multisig("treasury".to_owned(), 7, 2, &public_keys);
+
+        assert_eq!(
+            Some(Output {
+                to_address: "treasury".to_owned(),
+                value: 7,
+                locking_script: Script::multisig(2, &public_keys),
+            }),
+            result
+        );
+    }
+
+    #[test]
+    fn multisig_with_invalid_threshold() {
+        let public_keys = public_keys(3);
+
+        assert_eq!(
+            None,
+            Output::multisig("treasury".to_owned(), 7, 0, &public_keys)
+        );
+        assert_eq!(
+            None,
+            Output::multisig("treasury".to_owned(), 7, 4, &public_keys)
+        );
+    }
+
+    #[test]
+    fn multisig_with_too_many_keys() {
+        let public_keys = public_keys(21);
+
+        let result = Output::multisig("treasury".to_owned(), 7, 1, &public_keys);
+
+        assert_eq!(None, result);
+    }
 }
 
 #[cfg(test)]
 mod hashable_output_tests {
-    use super::{Hashable, Output};
+    use super::{Hashable, Output, Script};
 
     #[test]
     fn bytes() {
         let output = Output {
             to_address: "test-recipient-address".to_string(),
             value: 1,
+            locking_script: Script::new(),
         };
 
         let result = output.bytes();
@@ -235,7 +777,7 @@ mod hashable_output_tests {
         assert_eq!(
             vec![
                 116, 101, 115, 116, 45, 114, 101, 99, 105, 112, 105, 101, 110, 116, 45, 97, 100,
-                100, 114, 101, 115, 115, 1, 0, 0, 0, 0, 0, 0, 0
+                100, 114, 101, 115, 115, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
             ],
             result
         );
@@ -244,25 +786,38 @@ mod hashable_output_tests {
 
 #[cfg(test)]
 mod transaction_constructor_tests {
-    use super::{Output, Transaction};
+    use super::{Input, Output, Script, Transaction};
+    use crate::test_support::outpoint;
 
     #[test]
     fn constructor() {
         let instance = Transaction {
-            inputs: vec![Output {
-                to_address: "test-recipient-address1".to_string(),
-                value: 1,
-            }],
+            inputs: vec![Input::new(
+                outpoint(0),
+                Output {
+                    to_address: "test-recipient-address1".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                },
+            )],
             outputs: vec![Output {
                 to_address: "test-recipient-address2".to_string(),
                 value: 2,
+                locking_script: Script::new(),
             }],
+            lock_time: None,
         };
 
         assert_eq!(
-            vec![Output {
-                to_address: "test-recipient-address1".to_string(),
-                value: 1,
+            vec![Input {
+                outpoint: outpoint(0),
+                previous_output: Output {
+                    to_address: "test-recipient-address1".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                },
+                unlocking_script: Script::new(),
+                relative_lock_time: None,
             }],
             instance.inputs
         );
@@ -270,6 +825,7 @@ mod transaction_constructor_tests {
             vec![Output {
                 to_address: "test-recipient-address2".to_string(),
                 value: 2,
+                locking_script: Script::new(),
             }],
             instance.outputs
         );
@@ -280,43 +836,64 @@ mod transaction_constructor_tests {
 mod transaction_tests {
     use std::collections::HashSet;
 
-    use super::{BlockHash, Hashable, Output, Transaction};
+    use sha2::{Digest, Sha256};
+
+    use super::{
+        BlockHash, FeeRate, Hash256, HashAlgorithm, Hashable, Input, LockTime, OutPoint, Output,
+        RelativeLockTime, Script, Transaction,
+    };
+    use crate::encoding::Encodable;
+    use crate::test_support::outpoint;
 
     #[test]
     fn input_value_with_zero_elements() {
         let transaction = Transaction {
             inputs: vec![],
             outputs: vec![],
+            lock_time: None,
         };
 
         let result = transaction.input_value();
 
-        assert_eq!(0, result);
+        assert_eq!(Some(0), result);
     }
 
     #[test]
     fn input_value_with_three_elements() {
         let transaction = Transaction {
             inputs: vec![
-                Output {
-                    to_address: "test-recipient-address1".to_string(),
-                    value: 1,
-                },
-                Output {
-                    to_address: "test-recipient-address2".to_string(),
-                    value: 2,
-                },
-                Output {
-                    to_address: "test-recipient-address3".to_string(),
-                    value: 3,
-                },
+                Input::new(
+                    outpoint(0),
+                    Output {
+                        to_address: "test-recipient-address1".to_string(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                ),
+                Input::new(
+                    outpoint(1),
+                    Output {
+                        to_address: "test-recipient-address2".to_string(),
+                        value: 2,
+                        locking_script: Script::new(),
+                    },
+                ),
+                Input::new(
+                    outpoint(2),
+                    Output {
+                        to_address: "test-recipient-address3".to_string(),
+                        value: 3,
+                        locking_script: Script::new(),
+                    },
+                ),
             ],
             outputs: vec![],
+            lock_time: None,
         };
 
         let result = transaction.input_value();
 
-        assert_eq!(6, result);
+        assert_eq!(Some(6), result);
     }
 
     #[test]
@@ -324,11 +901,12 @@ mod transaction_tests {
         let transaction = Transaction {
             inputs: vec![],
             outputs: vec![],
+            lock_time: None,
         };
 
         let result = transaction.output_value();
 
-        assert_eq!(0, result);
+        assert_eq!(Some(0), result);
     }
 
     #[test]
@@ -339,21 +917,92 @@ mod transaction_tests {
                 Output {
                     to_address: "test-recipient-address1".to_string(),
                     value: 1,
+                    locking_script: Script::new(),
                 },
                 Output {
                     to_address: "test-recipient-address2".to_string(),
                     value: 2,
+                    locking_script: Script::new(),
                 },
                 Output {
                     to_address: "test-recipient-address3".to_string(),
                     value: 3,
+                    locking_script: Script::new(),
                 },
             ],
+            lock_time: None,
         };
 
         let result = transaction.output_value();
 
-        assert_eq!(6, result);
+        assert_eq!(Some(6), result);
+    }
+
+    #[test]
+    fn values_that_overflow() {
+        let output = |value| Output {
+            to_address: "Alice".to_owned(),
+            value,
+            locking_script: Script::new(),
+        };
+        let transaction = Transaction {
+            inputs: vec![
+                Input::new(outpoint(0), output(u64::MAX)),
+                Input::new(outpoint(1), output(1)),
+            ],
+            outputs: vec![output(u64::MAX), output(1)],
+            lock_time: None,
+        };
+
+        assert_eq!(None, transaction.input_value());
+        assert_eq!(None, transaction.output_value());
+        assert_eq!(0, transaction.fee());
+    }
+
+    #[test]
+    fn fee() {
+        let output = |value| Output {
+            to_address: "Alice".to_owned(),
+            value,
+            locking_script: Script::new(),
+        };
+        let payment = Transaction {
+            inputs: vec![Input::new(outpoint(0), output(5))],
+            outputs: vec![output(3)],
+            lock_time: None,
+        };
+        let overspending = Transaction {
+            inputs: vec![Input::new(outpoint(1), output(3))],
+            outputs: vec![output(5)],
+            lock_time: None,
+        };
+        let coinbase = Transaction::coinbase(1, vec![output(50)]);
+
+        assert_eq!(2, payment.fee());
+        assert_eq!(0, overspending.fee());
+        assert_eq!(0, coinbase.fee());
+    }
+
+    #[test]
+    fn size_and_fee_rate() {
+        let transaction = Transaction {
+            inputs: vec![Input::new(
+                outpoint(0),
+                Output {
+                    to_address: "Alice".to_owned(),
+                    value: 1_000,
+                    locking_script: Script::new(),
+                },
+            )],
+            outputs: vec![],
+            lock_time: None,
+        };
+
+        assert_eq!(transaction.encode().len(), transaction.size());
+        assert_eq!(
+            FeeRate(1_000_000 / transaction.size() as u64),
+            transaction.fee_rate()
+        );
     }
 
     #[test]
@@ -361,9 +1010,10 @@ mod transaction_tests {
         let transaction = Transaction {
             inputs: vec![],
             outputs: vec![],
+            lock_time: None,
         };
 
-        let result = transaction.input_hashes();
+        let result = transaction.input_hashes(HashAlgorithm::Sha256);
 
         assert_eq!(HashSet::<BlockHash>::new(), result);
     }
@@ -372,27 +1022,40 @@ mod transaction_tests {
     fn input_hashes_with_three_elements() {
         let transaction = Transaction {
             inputs: vec![
-                Output {
-                    to_address: "test-recipient-address1".to_string(),
-                    value: 1,
-                },
-                Output {
-                    to_address: "test-recipient-address2".to_string(),
-                    value: 2,
-                },
-                Output {
-                    to_address: "test-recipient-address3".to_string(),
-                    value: 3,
-                },
+                Input::new(
+                    outpoint(0),
+                    Output {
+                        to_address: "test-recipient-address1".to_string(),
+                        value: 1,
+                        locking_script: Script::new(),
+                    },
+                ),
+                Input::new(
+                    outpoint(1),
+                    Output {
+                        to_address: "test-recipient-address2".to_string(),
+                        value: 2,
+                        locking_script: Script::new(),
+                    },
+                ),
+                Input::new(
+                    outpoint(2),
+                    Output {
+                        to_address: "test-recipient-address3".to_string(),
+                        value: 3,
+                        locking_script: Script::new(),
+                    },
+                ),
             ],
             outputs: vec![],
+            lock_time: None,
         };
         let mut expected_set = HashSet::<BlockHash>::new();
         for input in &transaction.inputs {
-            expected_set.insert(input.hash());
+            expected_set.insert(input.output_hash(HashAlgorithm::Sha256));
         }
 
-        let result = transaction.input_hashes();
+        let result = transaction.input_hashes(HashAlgorithm::Sha256);
 
         assert_eq!(expected_set, result);
     }
@@ -402,9 +1065,10 @@ mod transaction_tests {
         let transaction = Transaction {
             inputs: vec![],
             outputs: vec![],
+            lock_time: None,
         };
 
-        let result = transaction.output_hashes();
+        let result = transaction.output_hashes(HashAlgorithm::Sha256);
 
         assert_eq!(HashSet::<BlockHash>::new(), result);
     }
@@ -417,82 +1081,547 @@ mod transaction_tests {
                 Output {
                     to_address: "test-recipient-address1".to_string(),
                     value: 1,
+                    locking_script: Script::new(),
                 },
                 Output {
                     to_address: "test-recipient-address2".to_string(),
                     value: 2,
+                    locking_script: Script::new(),
                 },
                 Output {
                     to_address: "test-recipient-address3".to_string(),
                     value: 3,
+                    locking_script: Script::new(),
                 },
             ],
+            lock_time: None,
         };
         let mut expected_set = HashSet::<BlockHash>::new();
-        for output in &transaction.outputs {
-            expected_set.insert(output.hash());
+        for (index, output) in transaction.outputs.iter().enumerate() {
+            let outpoint = OutPoint {
+                transaction_id: transaction.id(),
+                index: index as u32,
+            };
+            expected_set.insert(outpoint.output_hash(output, HashAlgorithm::Sha256));
         }
 
-        let result = transaction.output_hashes();
+        let result = transaction.output_hashes(HashAlgorithm::Sha256);
 
         assert_eq!(expected_set, result);
     }
 
+    #[test]
+    fn output_hashes_of_identical_outputs() {
+        let output = Output {
+            to_address: "Alice".to_owned(),
+            value: 1,
+            locking_script: Script::new(),
+        };
+        let transaction = Transaction::coinbase(1, vec![output.clone(), output.clone()]);
+        let other_transaction = Transaction::coinbase(2, vec![output]);
+
+        let result = transaction.output_hashes(HashAlgorithm::Sha256);
+
+        assert_eq!(2, result.len());
+        assert!(result.is_disjoint(&other_transaction.output_hashes(HashAlgorithm::Sha256)));
+    }
+
+    #[test]
+    fn spend() {
+        let transaction = Transaction::coinbase(
+            1,
+            vec![Output {
+                to_address: "Alice".to_owned(),
+                value: 1,
+                locking_script: Script::new(),
+            }],
+        );
+
+        let result = transaction.spend(0, HashAlgorithm::Blake3).unwrap();
+
+        assert_eq!(
+            OutPoint {
+                transaction_id: transaction.id_with(HashAlgorithm::Blake3),
+                index: 0,
+            },
+            result.outpoint
+        );
+        assert_eq!(transaction.outputs[0], result.previous_output);
+        assert!(transaction
+            .output_hashes(HashAlgorithm::Blake3)
+            .contains(&result.output_hash(HashAlgorithm::Blake3)));
+        assert_eq!(None, transaction.spend(1, HashAlgorithm::Blake3));
+    }
+
+    #[test]
+    fn output_hash_covers_output() {
+        let output = Output {
+            to_address: "Alice".to_owned(),
+            value: 1,
+            locking_script: Script::new(),
+        };
+        let input = Input::new(outpoint(0), output.clone());
+        let mut claimed_output = output;
+        claimed_output.value = 100;
+        let overclaiming_input = Input::new(outpoint(0), claimed_output);
+
+        assert_ne!(
+            input.output_hash(HashAlgorithm::Sha256),
+            overclaiming_input.output_hash(HashAlgorithm::Sha256)
+        );
+    }
+
     #[test]
     fn is_coinbase_with_zero_elements() {
         let transaction = Transaction {
             inputs: vec![],
             outputs: vec![],
+            lock_time: None,
         };
 
         let result = transaction.is_coinbase();
 
-        assert_eq!(true, result);
+        assert!(result);
     }
 
     #[test]
     fn is_coinbase_with_one_element() {
         let transaction = Transaction {
-            inputs: vec![Output {
+            inputs: vec![Input::new(
+                outpoint(0),
+                Output {
+                    to_address: "test-recipient-address".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                },
+            )],
+            outputs: vec![],
+            lock_time: None,
+        };
+
+        let result = transaction.is_coinbase();
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn coinbase_commits_to_block_index() {
+        let outputs = vec![Output {
+            to_address: "Miner".to_owned(),
+            value: 50,
+            locking_script: Script::new(),
+        }];
+
+        let coinbase = Transaction::coinbase(7, outputs.clone());
+
+        assert!(coinbase.is_coinbase());
+        assert!(coinbase.is_final(7, 0));
+        assert_eq!(Some(7), coinbase.coinbase_block_index());
+        assert_ne!(Transaction::coinbase(8, outputs).id(), coinbase.id());
+    }
+
+    #[test]
+    fn coinbase_block_index_without_commitment() {
+        let output = Output {
+            to_address: "Miner".to_owned(),
+            value: 50,
+            locking_script: Script::new(),
+        };
+        let without_lock_time = Transaction {
+            inputs: vec![],
+            outputs: vec![output.clone()],
+            lock_time: None,
+        };
+        let with_timestamp = Transaction {
+            lock_time: Some(LockTime::Timestamp(7)),
+            ..without_lock_time.clone()
+        };
+        let payment = Transaction {
+            inputs: vec![Input::new(outpoint(0), output)],
+            lock_time: Some(LockTime::BlockIndex(7)),
+            ..without_lock_time.clone()
+        };
+
+        assert_eq!(None, without_lock_time.coinbase_block_index());
+        assert_eq!(None, with_timestamp.coinbase_block_index());
+        assert_eq!(None, payment.coinbase_block_index());
+    }
+
+    #[test]
+    fn id() {
+        let transaction = Transaction {
+            inputs: vec![],
+            outputs: vec![Output {
                 to_address: "test-recipient-address".to_string(),
                 value: 1,
+                locking_script: Script::new(),
             }],
+            lock_time: None,
+        };
+
+        let result = transaction.id();
+
+        assert_eq!(Hash256(Sha256::digest(transaction.encode()).into()), result);
+        assert_eq!(transaction.id_with(HashAlgorithm::Sha256), result);
+        assert_ne!(transaction.id_with(HashAlgorithm::Blake3), result);
+    }
+
+    #[test]
+    fn id_of_transactions_with_same_hashable_bytes() {
+        let output = |to_address: String, value| Output {
+            to_address,
+            value,
+            locking_script: Script::new(),
+        };
+        let first_output = output("Alice".to_owned(), 1);
+        let mut merged_address = first_output.bytes();
+        merged_address.extend_from_slice(b"Bob");
+        let transaction = Transaction {
+            inputs: vec![],
+            outputs: vec![first_output, output("Bob".to_owned(), 2)],
+            lock_time: None,
+        };
+        let merged = Transaction {
+            inputs: vec![],
+            outputs: vec![output(String::from_utf8(merged_address).unwrap(), 2)],
+            lock_time: None,
+        };
+
+        assert_eq!(transaction.hash(), merged.hash());
+        assert_ne!(transaction.id(), merged.id());
+    }
+
+    #[test]
+    fn is_final_without_lock_time() {
+        let transaction = Transaction {
+            inputs: vec![],
             outputs: vec![],
+            lock_time: None,
         };
 
-        let result = transaction.is_coinbase();
+        let result = transaction.is_final(0, 0);
+
+        assert!(result);
+    }
+
+    #[test]
+    fn is_final_with_block_index_lock_time() {
+        let transaction = Transaction {
+            inputs: vec![],
+            outputs: vec![],
+            lock_time: Some(LockTime::BlockIndex(5)),
+        };
+
+        assert!(!transaction.is_final(4, u128::MAX));
+        assert!(transaction.is_final(5, 0));
+        assert!(transaction.is_final(6, 0));
+    }
+
+    #[test]
+    fn is_final_with_timestamp_lock_time() {
+        let transaction = Transaction {
+            inputs: vec![],
+            outputs: vec![],
+            lock_time: Some(LockTime::Timestamp(1_000)),
+        };
 
-        assert_eq!(false, result);
+        assert!(!transaction.is_final(u32::MAX, 999));
+        assert!(transaction.is_final(0, 1_000));
+        assert!(transaction.is_final(0, 1_001));
+    }
+
+    #[test]
+    fn signature_hash_covers_lock_times() {
+        let transaction = Transaction {
+            inputs: vec![Input::new(
+                outpoint(0),
+                Output {
+                    to_address: "test-recipient-address".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                },
+            )],
+            outputs: vec![],
+            lock_time: None,
+        };
+        let mut locked_transaction = transaction.clone();
+        locked_transaction.lock_time = Some(LockTime::BlockIndex(5));
+        let mut relatively_locked_transaction = transaction.clone();
+        relatively_locked_transaction.inputs[0].relative_lock_time =
+            Some(RelativeLockTime::Blocks(5));
+
+        let result = transaction.signature_hash(HashAlgorithm::Sha256);
+
+        assert_ne!(
+            locked_transaction.signature_hash(HashAlgorithm::Sha256),
+            result
+        );
+        assert_ne!(
+            relatively_locked_transaction.signature_hash(HashAlgorithm::Sha256),
+            result
+        );
+    }
+
+    #[test]
+    fn signature_hash_is_hash_of_encoding_without_unlocking_scripts() {
+        let mut input = Input::new(
+            outpoint(0),
+            Output {
+                to_address: "test-recipient-address".to_string(),
+                value: 1,
+                locking_script: Script::new(),
+            },
+        );
+        input.relative_lock_time = Some(RelativeLockTime::Blocks(5));
+        let transaction = Transaction {
+            inputs: vec![input],
+            outputs: vec![],
+            lock_time: Some(LockTime::BlockIndex(3)),
+        };
+        let mut signed_transaction = transaction.clone();
+        signed_transaction.inputs[0].unlocking_script = Script(vec![1, 2, 3]);
+
+        let result = signed_transaction.signature_hash(HashAlgorithm::Blake3);
+
+        assert_eq!(transaction.id_with(HashAlgorithm::Blake3), result);
+    }
+
+    #[test]
+    fn signature_hash_covers_field_boundaries() {
+        let mut input = Input::new(
+            outpoint(0),
+            Output {
+                to_address: "Alice".to_string(),
+                value: 50,
+                locking_script: Script::new(),
+            },
+        );
+        input.relative_lock_time = Some(RelativeLockTime::Blocks(10));
+        let transaction = Transaction {
+            inputs: vec![input],
+            outputs: vec![Output {
+                to_address: "Bob".to_string(),
+                value: 50,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        };
+        let mut forged_transaction = transaction.clone();
+        forged_transaction.inputs[0].relative_lock_time = None;
+        forged_transaction.outputs[0].to_address = "\0\n\0\0\0Bob".to_string();
+
+        let result = forged_transaction.signature_hash(HashAlgorithm::Sha256);
+
+        assert_eq!(transaction.bytes(), forged_transaction.bytes());
+        assert_ne!(transaction.signature_hash(HashAlgorithm::Sha256), result);
+    }
+}
+
+#[cfg(test)]
+mod lock_time_tests {
+    use super::{Hashable, LockTime, RelativeLockTime};
+
+    #[test]
+    fn block_index_lock_time_is_reached() {
+        let lock_time = LockTime::BlockIndex(5);
+
+        assert!(!lock_time.is_reached(4, u128::MAX));
+        assert!(lock_time.is_reached(5, 0));
+    }
+
+    #[test]
+    fn timestamp_lock_time_is_reached() {
+        let lock_time = LockTime::Timestamp(1_000);
+
+        assert!(!lock_time.is_reached(u32::MAX, 999));
+        assert!(lock_time.is_reached(0, 1_000));
+    }
+
+    #[test]
+    fn lock_time_bytes() {
+        assert_eq!(vec![0, 5, 0, 0, 0], LockTime::BlockIndex(5).bytes());
+        assert_eq!(
+            vec![1, 0xe8, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            LockTime::Timestamp(1_000).bytes()
+        );
+    }
+
+    #[test]
+    fn relative_blocks_lock_time_is_reached() {
+        let lock_time = RelativeLockTime::Blocks(3);
+
+        assert!(!lock_time.is_reached(10, 100, 12, u128::MAX));
+        assert!(lock_time.is_reached(10, 100, 13, 0));
+        assert!(!RelativeLockTime::Blocks(u32::MAX).is_reached(1, 0, u32::MAX, 0));
+    }
+
+    #[test]
+    fn relative_milliseconds_lock_time_is_reached() {
+        let lock_time = RelativeLockTime::Milliseconds(500);
+
+        assert!(!lock_time.is_reached(10, 100, u32::MAX, 599));
+        assert!(lock_time.is_reached(10, 100, 10, 600));
+        assert!(!RelativeLockTime::Milliseconds(u128::MAX).is_reached(0, 1, 0, u128::MAX - 1));
+    }
+
+    #[test]
+    fn relative_lock_time_bytes() {
+        assert_eq!(vec![0, 3, 0, 0, 0], RelativeLockTime::Blocks(3).bytes());
+        assert_eq!(
+            vec![1, 0xf4, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            RelativeLockTime::Milliseconds(500).bytes()
+        );
     }
 }
 
 #[cfg(test)]
 mod hashable_transaction_tests {
-    use super::{Hashable, Output, Transaction};
+    use super::{Hashable, Input, LockTime, Output, RelativeLockTime, Script, Transaction};
+    use crate::test_support::outpoint;
 
     #[test]
     fn bytes() {
         let transaction = Transaction {
-            inputs: vec![Output {
-                to_address: "test-recipient-address1".to_string(),
-                value: 1,
-            }],
+            inputs: vec![Input::new(
+                outpoint(0),
+                Output {
+                    to_address: "test-recipient-address1".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                },
+            )],
             outputs: vec![Output {
                 to_address: "test-recipient-address2".to_string(),
                 value: 2,
+                locking_script: Script::new(),
             }],
+            lock_time: None,
         };
 
         let result = transaction.bytes();
 
         assert_eq!(
             vec![
-                116, 101, 115, 116, 45, 114, 101, 99, 105, 112, 105, 101, 110, 116, 45, 97, 100,
-                100, 114, 101, 115, 115, 49, 1, 0, 0, 0, 0, 0, 0, 0, 116, 101, 115, 116, 45, 114,
-                101, 99, 105, 112, 105, 101, 110, 116, 45, 97, 100, 100, 114, 101, 115, 115, 50, 2,
-                0, 0, 0, 0, 0, 0, 0
+                255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
+                255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 0,
+                0, 0, 0, 116, 101, 115, 116, 45, 114, 101, 99, 105, 112, 105, 101, 110, 116, 45,
+                97, 100, 100, 114, 101, 115, 115, 49, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 116, 101, 115, 116, 45, 114, 101, 99, 105, 112, 105, 101, 110, 116, 45, 97, 100,
+                100, 114, 101, 115, 115, 50, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn bytes_with_lock_times() {
+        let transaction = Transaction {
+            inputs: vec![Input {
+                outpoint: outpoint(0),
+                previous_output: Output {
+                    to_address: "a".to_string(),
+                    value: 1,
+                    locking_script: Script::new(),
+                },
+                unlocking_script: Script::new(),
+                relative_lock_time: Some(RelativeLockTime::Blocks(3)),
+            }],
+            outputs: vec![],
+            lock_time: Some(LockTime::BlockIndex(5)),
+        };
+
+        let result = transaction.bytes();
+
+        assert_eq!(
+            vec![
+                255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
+                255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 0,
+                0, 0, 0, 97, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 5,
+                0, 0, 0
+            ],
+            result
+        );
+    }
+}
+
+#[cfg(test)]
+mod encodable_transaction_tests {
+    use super::{Input, LockTime, Output, RelativeLockTime, Script, Transaction};
+    use crate::encoding::{Decodable, DecodeErr, Encodable};
+    use crate::test_support::outpoint;
+
+    fn create_transaction() -> Transaction {
+        Transaction {
+            inputs: vec![Input {
+                outpoint: outpoint(1),
+                previous_output: Output {
+                    to_address: "Alice".to_string(),
+                    value: 5,
+                    locking_script: Script(vec![1, 2, 3]),
+                },
+                unlocking_script: Script(vec![4]),
+                relative_lock_time: Some(RelativeLockTime::Milliseconds(1_000)),
+            }],
+            outputs: vec![
+                Output {
+                    to_address: "Bob".to_string(),
+                    value: 3,
+                    locking_script: Script::new(),
+                },
+                Output {
+                    to_address: "Alice".to_string(),
+                    value: 2,
+                    locking_script: Script::new(),
+                },
             ],
+            lock_time: Some(LockTime::Timestamp(42)),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let transaction = create_transaction();
+
+        let result = Transaction::decode(&transaction.encode());
+
+        assert_eq!(Ok(transaction), result);
+    }
+
+    #[test]
+    fn round_trip_without_lock_times() {
+        let mut transaction = create_transaction();
+        transaction.lock_time = None;
+        transaction.inputs[0].relative_lock_time = None;
+
+        let result = Transaction::decode(&transaction.encode());
+
+        assert_eq!(Ok(transaction), result);
+    }
+
+    #[test]
+    fn output_encoding() {
+        let output = Output {
+            to_address: "a".to_string(),
+            value: 1,
+            locking_script: Script(vec![7]),
+        };
+
+        let result = output.encode();
+
+        assert_eq!(
+            vec![1, 0, 0, 0, 97, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 7],
             result
         );
     }
+
+    #[test]
+    fn decode_invalid_lock_time_tag() {
+        let mut bytes = create_transaction().encode();
+        let tag_position = bytes.len() - 17;
+        bytes[tag_position] = 2;
+
+        let result = Transaction::decode(&bytes);
+
+        assert_eq!(Err(DecodeErr::InvalidTag), result);
+    }
 }
@@ -0,0 +1,190 @@
+use crate::encoding::{ByteReader, Decodable, DecodeErr, Encodable};
+use crate::hashable::ByteWriter;
+use crate::{u32_bytes, BlockHash, Hash256, Hashable};
+
+/**
+ * The errors of loading a UTXO snapshot into a chain and of confirming it.
+ */
+#[derive(Debug, PartialEq)]
+pub enum SnapshotErr {
+    ChainNotEmpty,
+    UntrustedSnapshot,
+    InvalidHeaders,
+    MismatchedGenesisBlock,
+    NoUnconfirmedSnapshot,
+    MismatchedReplay,
+    StorageFailure,
+}
+
+/**
+ * A snapshot of a chain's unspent outputs (the UTXO set) at a height: the
+ * hash of every unspent output, with the index of the block that created it,
+ * after the block at that height was added.
+ *
+ * The outputs are kept sorted by hash, so the snapshot's hash (SHA-256 of its
+ * hashable bytes) is a deterministic commitment to the set: every node that
+ * has the same unspent outputs at the same block computes the same hash. A
+ * node can start from a snapshot whose hash it trusts instead of replaying the
+ * whole chain (see Blockchain::load_utxo_snapshot).
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct UtxoSnapshot {
+    height: u32,
+    block_hash: BlockHash,
+    unspent_outputs: Vec<(Hash256, u32)>,
+}
+
+impl UtxoSnapshot {
+    /**
+     * Returns the snapshot of the given unspent outputs (output hashes and
+     * the indexes of the blocks that created them) at the block with the
+     * given height and hash.
+     */
+    pub fn new<I>(height: u32, block_hash: BlockHash, unspent_outputs: I) -> Self
+    where
+        I: IntoIterator<Item = (Hash256, u32)>,
+    {
+        let mut unspent_outputs: Vec<(Hash256, u32)> = unspent_outputs.into_iter().collect();
+        unspent_outputs.sort_unstable();
+        unspent_outputs.dedup();
+
+        UtxoSnapshot {
+            height,
+            block_hash,
+            unspent_outputs,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn block_hash(&self) -> &BlockHash {
+        &self.block_hash
+    }
+
+    /**
+     * Returns the unspent outputs, sorted by hash.
+     */
+    pub fn unspent_outputs(&self) -> &[(Hash256, u32)] {
+        &self.unspent_outputs
+    }
+
+    pub fn len(&self) -> usize {
+        self.unspent_outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unspent_outputs.is_empty()
+    }
+}
+
+impl Hashable for UtxoSnapshot {
+    /**
+     * Writes the hashable bytes that represent the snapshot: the height, the
+     * block hash and every unspent output's hash and block index, in order.
+     */
+    fn write_bytes<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        writer.write(&u32_bytes(self.height));
+        writer.write(self.block_hash.as_bytes());
+        for (output_hash, block_index) in &self.unspent_outputs {
+            writer.write(output_hash.as_bytes());
+            writer.write(&u32_bytes(*block_index));
+        }
+    }
+}
+
+impl Encodable for UtxoSnapshot {
+    fn write_encoding<W: ByteWriter + ?Sized>(&self, writer: &mut W) {
+        self.height.write_encoding(writer);
+        self.block_hash.write_encoding(writer);
+        writer.write(&u32_bytes(self.unspent_outputs.len() as u32));
+        for (output_hash, block_index) in &self.unspent_outputs {
+            output_hash.write_encoding(writer);
+            block_index.write_encoding(writer);
+        }
+    }
+}
+
+impl Decodable for UtxoSnapshot {
+    fn read_encoding(reader: &mut ByteReader) -> Result<Self, DecodeErr> {
+        let height = reader.read_u32()?;
+        let block_hash = reader.read_hash()?;
+        let length = reader.read_u32()?;
+
+        let mut unspent_outputs = vec![];
+        for _ in 0..length {
+            unspent_outputs.push((reader.read_hash()?, reader.read_u32()?));
+        }
+
+        Ok(UtxoSnapshot::new(height, block_hash, unspent_outputs))
+    }
+}
+
+#[cfg(test)]
+mod utxo_snapshot_tests {
+    use super::UtxoSnapshot;
+    use crate::encoding::{Decodable, Encodable};
+    use crate::{Hash256, Hashable};
+
+    fn unspent_outputs() -> Vec<(Hash256, u32)> {
+        vec![
+            (Hash256([3; 32]), 1),
+            (Hash256([1; 32]), 0),
+            (Hash256([2; 32]), 1),
+        ]
+    }
+
+    #[test]
+    fn sorted_by_output_hash() {
+        let result = UtxoSnapshot::new(1, Hash256([9; 32]), unspent_outputs());
+
+        assert_eq!(
+            vec![
+                (Hash256([1; 32]), 0),
+                (Hash256([2; 32]), 1),
+                (Hash256([3; 32]), 1),
+            ],
+            result.unspent_outputs()
+        );
+        assert_eq!(3, result.len());
+    }
+
+    #[test]
+    fn hash_is_independent_of_order() {
+        let mut reversed = unspent_outputs();
+        reversed.reverse();
+
+        let snapshot1 = UtxoSnapshot::new(1, Hash256([9; 32]), unspent_outputs());
+        let snapshot2 = UtxoSnapshot::new(1, Hash256([9; 32]), reversed);
+
+        assert_eq!(snapshot1.hash(), snapshot2.hash());
+    }
+
+    #[test]
+    fn hash_commits_to_height_block_and_outputs() {
+        let snapshot = UtxoSnapshot::new(1, Hash256([9; 32]), unspent_outputs());
+        let mut other_outputs = unspent_outputs();
+        other_outputs[0].1 = 0;
+
+        let others = vec![
+            UtxoSnapshot::new(2, Hash256([9; 32]), unspent_outputs()),
+            UtxoSnapshot::new(1, Hash256([8; 32]), unspent_outputs()),
+            UtxoSnapshot::new(1, Hash256([9; 32]), other_outputs),
+            UtxoSnapshot::new(1, Hash256([9; 32]), vec![]),
+        ];
+
+        for other in &others {
+            assert_ne!(snapshot.hash(), other.hash());
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let snapshot = UtxoSnapshot::new(1, Hash256([9; 32]), unspent_outputs());
+
+        let result = UtxoSnapshot::decode(&snapshot.encode());
+
+        assert_eq!(Ok(snapshot), result);
+    }
+}
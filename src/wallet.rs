@@ -0,0 +1,862 @@
+use k256::ecdsa::signature::Verifier;
+use k256::ecdsa::{Signature, SigningKey, VerifyingKey};
+
+use crate::transaction::{Input, OutPoint, Output};
+use crate::{
+    sign, Address, FeeRate, Hash256, HashAlgorithm, Script, Transaction, DEFAULT_MIN_RELAY_FEE_RATE,
+};
+
+/**
+ * The errors of building payments and of collecting the signatures of a
+ * multisig spend.
+ */
+#[derive(Debug, PartialEq)]
+pub enum WalletErr {
+    InvalidInputIndex,
+    NotMultisig,
+    UnknownPublicKey,
+    InvalidSignature,
+    ThresholdNotMet,
+    MismatchedSigningKey,
+    InsufficientFunds,
+    UnknownCoin,
+    InsufficientFeeRate,
+    MismatchedOutputs,
+}
+
+/**
+ * Builds signed payments from pay-to-public-key-hash coins: unspent outputs
+ * locked to the public keys of signing keys that the wallet holds.
+ *
+ * The builder spends the coins in the order they were added, until they
+ * cover the payment's outputs and its fee: the fee rate (e.g., the estimate
+ * of a FeeEstimator) times the serialized size of the signed transaction.
+ * Signatures always take 64 bytes, so that size is known before the
+ * transaction is signed. What's left over goes back to the change address in
+ * an output after the builder's outputs, unless it doesn't cover the fee of
+ * the change output itself, in which case it's left to the miner.
+ *
+ * A payment whose fee rate turned out too low to be mined can be replaced
+ * with one that pays a higher fee rate (see bump_fee).
+ */
+pub struct PaymentBuilder {
+    algorithm: HashAlgorithm,
+    fee_rate: FeeRate,
+    change_address: Address,
+    change_script: Script,
+    coins: Vec<(Input, SigningKey)>,
+    outputs: Vec<Output>,
+}
+
+impl PaymentBuilder {
+    /**
+     * Returns a builder of payments that pay the given fee rate and send
+     * their change to the given address, locked to the given public key.
+     */
+    pub fn new(fee_rate: FeeRate, change_address: Address, change_key: &VerifyingKey) -> Self {
+        PaymentBuilder {
+            algorithm: HashAlgorithm::default(),
+            fee_rate,
+            change_address,
+            change_script: Script::pay_to_public_key_hash(change_key),
+            coins: vec![],
+            outputs: vec![],
+        }
+    }
+
+    /**
+     * Returns the builder with the given algorithm (the chain's transaction
+     * algorithm), which the signature hashes are computed with.
+     */
+    pub fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+
+        self
+    }
+
+    /**
+     * Adds a coin that the payment may spend: the output at the given
+     * outpoint, which must be locked to the public key of the given signing
+     * key.
+     */
+    pub fn add_coin(
+        &mut self,
+        outpoint: OutPoint,
+        output: Output,
+        signing_key: SigningKey,
+    ) -> Result<(), WalletErr> {
+        if output.locking_script != Script::pay_to_public_key_hash(signing_key.verifying_key()) {
+            return Err(WalletErr::MismatchedSigningKey);
+        }
+
+        self.coins.push((Input::new(outpoint, output), signing_key));
+
+        Ok(())
+    }
+
+    /**
+     * Adds an output that the payment pays.
+     */
+    pub fn add_output(&mut self, output: Output) {
+        self.outputs.push(output);
+    }
+
+    /**
+     * Returns the signed payment, or an error if the coins don't cover its
+     * outputs and its fee.
+     */
+    pub fn build(&self) -> Result<Transaction, WalletErr> {
+        let coins: Vec<&(Input, SigningKey)> = self.coins.iter().collect();
+
+        self.select_coins(&coins, 1, &self.outputs, |size| self.fee_rate.fee(size))
+    }
+
+    /**
+     * Returns a signed replacement of the given payment, which was built
+     * from the builder's coins and outputs, that pays the builder's fee rate
+     * (which must be higher than the payment's fee rate) instead. It's an
+     * error if the payment's outputs aren't the builder's outputs, followed
+     * by its change (if any).
+     *
+     * The replacement spends the payment's coins and pays the builder's
+     * outputs, so mempools replace the payment with it (see Mempool). The
+     * payment's change is told apart by its position rather than its address,
+     * so an output that pays the change address is kept. The higher fee comes out of
+     * the change, and if that's not enough, more of the builder's coins are
+     * spent. A mempool only accepts the replacement if it pays the payment's
+     * fee plus the minimum fee for its own size, so the replacement pays at
+     * least that (at the default minimum fee rate). Transactions in the
+     * mempool that spend the payment's change are evicted along with it.
+     */
+    pub fn bump_fee(&self, payment: &Transaction) -> Result<Transaction, WalletErr> {
+        if self.fee_rate <= payment.fee_rate() {
+            return Err(WalletErr::InsufficientFeeRate);
+        }
+
+        let mut coins = Vec::new();
+        for input in &payment.inputs {
+            let coin = self
+                .coins
+                .iter()
+                .find(|(coin, _)| coin.outpoint == input.outpoint)
+                .ok_or(WalletErr::UnknownCoin)?;
+            coins.push(coin);
+        }
+        let payment_coin_count = coins.len();
+        coins.extend(self.coins.iter().filter(|(coin, _)| {
+            !payment
+                .inputs
+                .iter()
+                .any(|input| input.outpoint == coin.outpoint)
+        }));
+        let change_index = self.outputs.len();
+        if !payment.outputs.starts_with(&self.outputs)
+            || payment.outputs.len() > change_index + 1
+            || payment.outputs[change_index..].iter().any(|change| {
+                change.to_address != self.change_address
+                    || change.locking_script != self.change_script
+            })
+        {
+            return Err(WalletErr::MismatchedOutputs);
+        }
+
+        self.select_coins(&coins, payment_coin_count.max(1), &self.outputs, |size| {
+            let replacement_fee = payment
+                .fee()
+                .saturating_add(DEFAULT_MIN_RELAY_FEE_RATE.fee(size));
+
+            self.fee_rate.fee(size).max(replacement_fee)
+        })
+    }
+
+    /**
+     * Returns the signed transaction that spends at least the given number of
+     * the given coins (in order) and pays the given outputs and the fee that
+     * the given function returns for its size, plus change if it's worth it.
+     */
+    fn select_coins(
+        &self,
+        coins: &[&(Input, SigningKey)],
+        min_coin_count: usize,
+        outputs: &[Output],
+        fee: impl Fn(usize) -> u64,
+    ) -> Result<Transaction, WalletErr> {
+        let payment_value: u64 = outputs.iter().map(|output| output.value).sum();
+        if coins.len() < min_coin_count {
+            return Err(WalletErr::InsufficientFunds);
+        }
+        let mut input_value: u64 = coins[..min_coin_count - 1]
+            .iter()
+            .map(|(coin, _)| coin.previous_output.value)
+            .sum();
+
+        for count in min_coin_count..=coins.len() {
+            let coins = &coins[..count];
+            input_value += coins[count - 1].0.previous_output.value;
+            let available_value = match input_value.checked_sub(payment_value) {
+                Some(available_value) => available_value,
+                None => continue,
+            };
+
+            let with_change = self.sign(coins, outputs, Some(0));
+            let with_change_fee = fee(with_change.size());
+            if available_value > with_change_fee {
+                return Ok(self.sign(coins, outputs, Some(available_value - with_change_fee)));
+            }
+
+            let without_change = self.sign(coins, outputs, None);
+            if available_value >= fee(without_change.size()) {
+                return Ok(without_change);
+            }
+        }
+
+        Err(WalletErr::InsufficientFunds)
+    }
+
+    /**
+     * Returns the transaction that spends the given coins, pays the given
+     * outputs and the given change (if any), signed.
+     */
+    fn sign(
+        &self,
+        coins: &[&(Input, SigningKey)],
+        outputs: &[Output],
+        change: Option<u64>,
+    ) -> Transaction {
+        let mut outputs = outputs.to_vec();
+        if let Some(change) = change {
+            outputs.push(Output {
+                to_address: self.change_address.clone(),
+                value: change,
+                locking_script: self.change_script.clone(),
+            });
+        }
+        let mut transaction = Transaction {
+            inputs: coins.iter().map(|(coin, _)| coin.clone()).collect(),
+            outputs,
+            lock_time: None,
+        };
+
+        sign_pay_to_public_key_hash_inputs(
+            &mut transaction,
+            coins.iter().map(|(_, signing_key)| signing_key),
+            self.algorithm,
+        );
+
+        transaction
+    }
+}
+
+/**
+ * Sets the unlocking scripts of the transaction's inputs, which spend
+ * pay-to-public-key-hash outputs, to the signatures of the given signing keys
+ * (one per input, in order).
+ */
+fn sign_pay_to_public_key_hash_inputs<'a>(
+    transaction: &mut Transaction,
+    signing_keys: impl Iterator<Item = &'a SigningKey>,
+    algorithm: HashAlgorithm,
+) {
+    let signature_hash = transaction.signature_hash(algorithm);
+    for (input, signing_key) in transaction.inputs.iter_mut().zip(signing_keys) {
+        input.unlocking_script = Script::pay_to_public_key_hash_unlocking(
+            &sign(signing_key, &signature_hash),
+            signing_key.verifying_key(),
+        );
+    }
+}
+
+/**
+ * Collects the signatures that spending a multisig (M-of-N) output requires.
+ *
+ * The parties that hold the output's keys sign the transaction one by one,
+ * each on their own machine: a party either signs with their signing key
+ * (sign), or hands over a signature they made elsewhere (add_signature),
+ * which is checked against their public key before it's accepted. Once at
+ * least M of the N parties have signed, finalize puts the signatures in the
+ * order of the public keys and sets the input's unlocking script.
+ *
+ * The signatures sign the transaction's signature hash, so the transaction's
+ * inputs and outputs can't change while the signatures are being collected.
+ */
+pub struct MultisigSpend {
+    transaction: Transaction,
+    input_index: usize,
+    signature_hash: Hash256,
+    threshold: usize,
+    public_keys: Vec<VerifyingKey>,
+    signatures: Vec<Option<Vec<u8>>>,
+}
+
+impl MultisigSpend {
+    /**
+     * Returns a collector for the signatures of the given input of the
+     * transaction, which must spend a multisig output. The signature hash is
+     * computed with the given algorithm (the chain's transaction algorithm).
+     */
+    pub fn new(
+        transaction: Transaction,
+        input_index: usize,
+        algorithm: HashAlgorithm,
+    ) -> Result<Self, WalletErr> {
+        let input = transaction
+            .inputs
+            .get(input_index)
+            .ok_or(WalletErr::InvalidInputIndex)?;
+        let (threshold, public_keys) = input
+            .previous_output
+            .locking_script
+            .parse_multisig()
+            .ok_or(WalletErr::NotMultisig)?;
+
+        Ok(MultisigSpend {
+            signature_hash: transaction.signature_hash(algorithm),
+            signatures: vec![None; public_keys.len()],
+            transaction,
+            input_index,
+            threshold,
+            public_keys,
+        })
+    }
+
+    /**
+     * Returns the hash that each party signs.
+     */
+    pub fn signature_hash(&self) -> &Hash256 {
+        &self.signature_hash
+    }
+
+    /**
+     * Returns the number of signatures that spending the output requires (M).
+     */
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /**
+     * Returns the public keys that can sign the spend (N), in order.
+     */
+    pub fn public_keys(&self) -> &[VerifyingKey] {
+        &self.public_keys
+    }
+
+    /**
+     * Signs the spend with the given signing key, whose public key must be
+     * one of the output's.
+     */
+    pub fn sign(&mut self, signing_key: &SigningKey) -> Result<(), WalletErr> {
+        let signature = sign(signing_key, &self.signature_hash);
+
+        self.add_signature(signing_key.verifying_key(), signature)
+    }
+
+    /**
+     * Adds a signature made by another party with the signing key of the
+     * given public key, which must be one of the output's. Adding a signature
+     * for a key that already signed replaces the previous one.
+     */
+    pub fn add_signature(
+        &mut self,
+        public_key: &VerifyingKey,
+        signature: Vec<u8>,
+    ) -> Result<(), WalletErr> {
+        let position = self
+            .public_keys
+            .iter()
+            .position(|key| key == public_key)
+            .ok_or(WalletErr::UnknownPublicKey)?;
+        let is_valid = Signature::from_slice(&signature).is_ok_and(|parsed_signature| {
+            public_key
+                .verify(self.signature_hash.as_bytes(), &parsed_signature)
+                .is_ok()
+        });
+        if !is_valid {
+            return Err(WalletErr::InvalidSignature);
+        }
+
+        self.signatures[position] = Some(signature);
+
+        Ok(())
+    }
+
+    /**
+     * Returns the number of parties that have signed so far.
+     */
+    pub fn signature_count(&self) -> usize {
+        self.signatures
+            .iter()
+            .filter(|signature| signature.is_some())
+            .count()
+    }
+
+    /**
+     * Returns a flag that states whether enough parties have signed.
+     */
+    pub fn is_complete(&self) -> bool {
+        self.signature_count() >= self.threshold
+    }
+
+    /**
+     * Returns the transaction with the input's unlocking script set to the
+     * first M signatures in the order of the public keys, or an error if
+     * fewer than M parties have signed.
+     */
+    pub fn finalize(self) -> Result<Transaction, WalletErr> {
+        if !self.is_complete() {
+            return Err(WalletErr::ThresholdNotMet);
+        }
+
+        let signatures: Vec<Vec<u8>> = self
+            .signatures
+            .into_iter()
+            .flatten()
+            .take(self.threshold)
+            .collect();
+        let mut transaction = self.transaction;
+        transaction.inputs[self.input_index].unlocking_script =
+            Script::multisig_unlocking(&signatures);
+
+        Ok(transaction)
+    }
+}
+
+#[cfg(test)]
+mod multisig_spend_tests {
+    use super::{MultisigSpend, WalletErr};
+    use crate::test_support::{outpoint, signing_key};
+    use crate::transaction::{Input, Output};
+    use crate::{
+        sign, verify_script, HashAlgorithm, Script, ScriptContext, ScriptErr, Transaction,
+        VerifyingKey,
+    };
+
+    fn public_keys() -> Vec<VerifyingKey> {
+        (1..=3)
+            .map(|byte| *signing_key(byte).verifying_key())
+            .collect()
+    }
+
+    fn create_transaction() -> Transaction {
+        Transaction {
+            inputs: vec![Input::new(
+                outpoint(0),
+                Output::multisig("treasury".to_owned(), 10, 2, &public_keys()).unwrap(),
+            )],
+            outputs: vec![Output {
+                to_address: "Bob".to_owned(),
+                value: 10,
+                locking_script: Script::new(),
+            }],
+            lock_time: None,
+        }
+    }
+
+    fn verify(transaction: &Transaction) -> Result<(), ScriptErr> {
+        let input = &transaction.inputs[0];
+        let context = ScriptContext {
+            signature_hash: transaction.signature_hash(HashAlgorithm::default()),
+            block_index: 0,
+        };
+
+        verify_script(
+            &input.unlocking_script,
+            &input.previous_output.locking_script,
+            &context,
+        )
+    }
+
+    #[test]
+    fn new() {
+        let transaction = create_transaction();
+
+        let result = MultisigSpend::new(transaction.clone(), 0, HashAlgorithm::default()).unwrap();
+
+        assert_eq!(2, result.threshold());
+        assert_eq!(public_keys(), result.public_keys());
+        assert_eq!(
+            &transaction.signature_hash(HashAlgorithm::default()),
+            result.signature_hash()
+        );
+        assert_eq!(0, result.signature_count());
+        assert!(!result.is_complete());
+    }
+
+    #[test]
+    fn new_with_invalid_input_index() {
+        let result = MultisigSpend::new(create_transaction(), 1, HashAlgorithm::default());
+
+        assert_eq!(Some(WalletErr::InvalidInputIndex), result.err());
+    }
+
+    #[test]
+    fn new_with_other_locking_script() {
+        let mut transaction = create_transaction();
+        transaction.inputs[0].previous_output.locking_script =
+            Script::pay_to_public_key_hash(signing_key(1).verifying_key());
+
+        let result = MultisigSpend::new(transaction, 0, HashAlgorithm::default());
+
+        assert_eq!(Some(WalletErr::NotMultisig), result.err());
+    }
+
+    #[test]
+    fn collect_threshold_signatures() {
+        let mut spend =
+            MultisigSpend::new(create_transaction(), 0, HashAlgorithm::default()).unwrap();
+        spend.sign(&signing_key(3)).unwrap();
+        let signature = sign(&signing_key(1), spend.signature_hash());
+        spend
+            .add_signature(signing_key(1).verifying_key(), signature)
+            .unwrap();
+
+        assert!(spend.is_complete());
+        let result = spend.finalize().unwrap();
+
+        assert_eq!(Ok(()), verify(&result));
+    }
+
+    #[test]
+    fn collect_all_signatures() {
+        let mut spend =
+            MultisigSpend::new(create_transaction(), 0, HashAlgorithm::default()).unwrap();
+        for byte in [2, 1, 3].iter() {
+            spend.sign(&signing_key(*byte)).unwrap();
+        }
+
+        assert_eq!(3, spend.signature_count());
+        let result = spend.finalize().unwrap();
+
+        assert_eq!(Ok(()), verify(&result));
+    }
+
+    #[test]
+    fn sign_twice_with_same_key() {
+        let mut spend =
+            MultisigSpend::new(create_transaction(), 0, HashAlgorithm::default()).unwrap();
+        spend.sign(&signing_key(1)).unwrap();
+        spend.sign(&signing_key(1)).unwrap();
+
+        assert_eq!(1, spend.signature_count());
+        assert!(!spend.is_complete());
+    }
+
+    #[test]
+    fn sign_with_unknown_key() {
+        let mut spend =
+            MultisigSpend::new(create_transaction(), 0, HashAlgorithm::default()).unwrap();
+
+        let result = spend.sign(&signing_key(4));
+
+        assert_eq!(Err(WalletErr::UnknownPublicKey), result);
+        assert_eq!(0, spend.signature_count());
+    }
+
+    #[test]
+    fn add_signature_of_other_key() {
+        let mut spend =
+            MultisigSpend::new(create_transaction(), 0, HashAlgorithm::default()).unwrap();
+        let signature = sign(&signing_key(2), spend.signature_hash());
+
+        let result = spend.add_signature(signing_key(1).verifying_key(), signature);
+
+        assert_eq!(Err(WalletErr::InvalidSignature), result);
+        assert_eq!(0, spend.signature_count());
+    }
+
+    #[test]
+    fn add_malformed_signature() {
+        let mut spend =
+            MultisigSpend::new(create_transaction(), 0, HashAlgorithm::default()).unwrap();
+
+        let result = spend.add_signature(signing_key(1).verifying_key(), vec![1, 2, 3]);
+
+        assert_eq!(Err(WalletErr::InvalidSignature), result);
+    }
+
+    #[test]
+    fn finalize_below_threshold() {
+        let mut spend =
+            MultisigSpend::new(create_transaction(), 0, HashAlgorithm::default()).unwrap();
+        spend.sign(&signing_key(2)).unwrap();
+
+        let result = spend.finalize();
+
+        assert_eq!(Err(WalletErr::ThresholdNotMet), result);
+    }
+}
+
+#[cfg(test)]
+mod payment_builder_tests {
+    use super::{PaymentBuilder, WalletErr};
+    use crate::test_support::{add_block, outpoint, output, signing_key, spend};
+    use crate::transaction::Output;
+    use crate::{
+        verify_script, Blockchain, FeeEstimator, FeeRate, HashAlgorithm, Mempool, Script,
+        ScriptContext, Transaction,
+    };
+
+    const FEE_RATE: FeeRate = FeeRate(100);
+
+    fn coin(value: u64, key_byte: u8) -> Output {
+        Output {
+            to_address: "Alice".to_owned(),
+            value,
+            locking_script: Script::pay_to_public_key_hash(signing_key(key_byte).verifying_key()),
+        }
+    }
+
+    /**
+     * Returns the coinbase transaction of a genesis block that pays coins of
+     * the given values (locked to keys 1, 2 and so on).
+     */
+    fn genesis_coinbase(coin_values: &[u64]) -> Transaction {
+        let outputs = (1..)
+            .zip(coin_values)
+            .map(|(byte, coin_value)| coin(*coin_value, byte))
+            .collect();
+
+        Transaction::coinbase(0, outputs)
+    }
+
+    fn create_blockchain(coin_values: &[u64]) -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        add_block(&mut blockchain, vec![genesis_coinbase(coin_values)]);
+
+        blockchain
+    }
+
+    /**
+     * Returns a builder with the coins of genesis_coinbase that pays Bob the
+     * given value.
+     */
+    fn create_builder(fee_rate: FeeRate, coin_values: &[u64], value: u64) -> PaymentBuilder {
+        let mut builder =
+            PaymentBuilder::new(fee_rate, "Alice".to_owned(), signing_key(9).verifying_key());
+        let coinbase = genesis_coinbase(coin_values);
+        for (byte, index) in (1..).zip(0..coin_values.len()) {
+            let input = spend(&coinbase, index);
+            builder
+                .add_coin(input.outpoint, input.previous_output, signing_key(byte))
+                .unwrap();
+        }
+        builder.add_output(output("Bob", value));
+
+        builder
+    }
+
+    fn verify(transaction: &Transaction) {
+        let context = ScriptContext {
+            signature_hash: transaction.signature_hash(HashAlgorithm::default()),
+            block_index: 0,
+        };
+        for input in &transaction.inputs {
+            verify_script(
+                &input.unlocking_script,
+                &input.previous_output.locking_script,
+                &context,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn build_payment_with_change() {
+        let builder = create_builder(FEE_RATE, &[500, 500], 300);
+
+        let result = builder.build().unwrap();
+
+        assert_eq!(1, result.inputs.len());
+        assert_eq!(coin(500, 1), result.inputs[0].previous_output);
+        assert_eq!(output("Bob", 300), result.outputs[0]);
+        assert_eq!("Alice", result.outputs[1].to_address);
+        assert_eq!(
+            Script::pay_to_public_key_hash(signing_key(9).verifying_key()),
+            result.outputs[1].locking_script
+        );
+        assert_eq!(FEE_RATE.fee(result.size()), result.fee());
+        assert!(result.fee_rate() >= FEE_RATE);
+        verify(&result);
+    }
+
+    #[test]
+    fn spend_more_coins_to_cover_fee() {
+        let builder = create_builder(FEE_RATE, &[300, 300], 300);
+
+        let result = builder.build().unwrap();
+
+        assert_eq!(2, result.inputs.len());
+        assert_eq!(FEE_RATE.fee(result.size()), result.fee());
+        verify(&result);
+    }
+
+    #[test]
+    fn leave_change_to_miner_if_it_does_not_cover_its_fee() {
+        let with_change = create_builder(FEE_RATE, &[1_000], 300).build().unwrap();
+        let fee = with_change.fee();
+        let builder = create_builder(FEE_RATE, &[300 + fee], 300);
+
+        let result = builder.build().unwrap();
+
+        assert_eq!(2, with_change.outputs.len());
+        assert_eq!(vec![output("Bob", 300)], result.outputs);
+        assert_eq!(fee, result.fee());
+        assert!(result.fee_rate() >= FEE_RATE);
+        verify(&result);
+    }
+
+    #[test]
+    fn build_payment_with_insufficient_funds() {
+        let builder = create_builder(FEE_RATE, &[100, 200], 300);
+
+        assert_eq!(Err(WalletErr::InsufficientFunds), builder.build());
+    }
+
+    #[test]
+    fn add_coin_with_other_key() {
+        let mut builder = create_builder(FEE_RATE, &[], 300);
+
+        let result = builder.add_coin(outpoint(1), coin(500, 1), signing_key(2));
+
+        assert_eq!(Err(WalletErr::MismatchedSigningKey), result);
+    }
+
+    #[test]
+    fn pay_estimated_fee_rate() {
+        let mut estimator = FeeEstimator::new();
+        let confirmed = create_builder(FeeRate(3_000), &[10_000], 300)
+            .build()
+            .unwrap();
+        estimator.add_confirmed_transactions(&[Transaction::coinbase(1, vec![]), confirmed]);
+        let fee_rate = estimator.estimate_fee_rate(1);
+
+        let result = create_builder(fee_rate, &[10_000], 500).build().unwrap();
+
+        assert!(fee_rate >= FeeRate(3_000));
+        assert!(result.fee_rate() >= fee_rate);
+        assert!(Mempool::new()
+            .add_transaction(result, &create_blockchain(&[10_000]))
+            .is_ok());
+    }
+
+    #[test]
+    fn bump_fee_out_of_change() {
+        let payment = create_builder(FEE_RATE, &[500, 500], 300).build().unwrap();
+        let builder = create_builder(FeeRate(300), &[500, 500], 300);
+
+        let result = builder.bump_fee(&payment).unwrap();
+
+        assert_eq!(payment.inputs.len(), result.inputs.len());
+        assert_eq!(
+            payment.inputs[0].previous_output,
+            result.inputs[0].previous_output
+        );
+        assert_eq!(payment.outputs[0], result.outputs[0]);
+        assert!(result.outputs[1].value < payment.outputs[1].value);
+        assert!(result.fee_rate() >= FeeRate(300));
+        verify(&result);
+    }
+
+    #[test]
+    fn bump_fee_with_more_coins() {
+        let payment = create_builder(FEE_RATE, &[330, 500], 300).build().unwrap();
+        let builder = create_builder(FeeRate(300), &[330, 500], 300);
+
+        let result = builder.bump_fee(&payment).unwrap();
+
+        assert_eq!(1, payment.inputs.len());
+        assert_eq!(2, result.inputs.len());
+        assert_eq!(
+            payment.inputs[0].previous_output,
+            result.inputs[0].previous_output
+        );
+        assert!(result.fee_rate() >= FeeRate(300));
+        verify(&result);
+    }
+
+    #[test]
+    fn bump_fee_replaces_payment_in_mempool() {
+        let blockchain = create_blockchain(&[500]);
+        let mut mempool = Mempool::new();
+        let payment = create_builder(FeeRate(10), &[500], 300).build().unwrap();
+        let payment_id = mempool
+            .add_transaction(payment.clone(), &blockchain)
+            .unwrap();
+        let replacement = create_builder(FeeRate(20), &[500], 300)
+            .bump_fee(&payment)
+            .unwrap();
+
+        let result = mempool.add_transaction(replacement.clone(), &blockchain);
+
+        assert_eq!(Ok(replacement.id()), result);
+        assert!(!mempool.contains(&payment_id));
+    }
+
+    #[test]
+    fn bump_fee_of_payment_to_change_address() {
+        let mut builder = create_builder(FEE_RATE, &[1_000], 300);
+        let payment_to_self = Output {
+            to_address: "Alice".to_owned(),
+            value: 200,
+            locking_script: Script::pay_to_public_key_hash(signing_key(9).verifying_key()),
+        };
+        builder.add_output(payment_to_self.clone());
+        let payment = builder.build().unwrap();
+        let builder = PaymentBuilder {
+            fee_rate: FeeRate(300),
+            ..builder
+        };
+
+        let result = builder.bump_fee(&payment).unwrap();
+
+        assert_eq!(3, payment.outputs.len());
+        assert_eq!(3, result.outputs.len());
+        assert_eq!(payment_to_self, result.outputs[1]);
+        assert!(result.outputs[2].value < payment.outputs[2].value);
+        assert!(result.fee_rate() >= FeeRate(300));
+        verify(&result);
+    }
+
+    #[test]
+    fn bump_fee_with_mismatched_outputs() {
+        let payment = create_builder(FEE_RATE, &[500], 300).build().unwrap();
+        let builder = create_builder(FeeRate(300), &[500], 200);
+
+        assert_eq!(
+            Err(WalletErr::MismatchedOutputs),
+            builder.bump_fee(&payment)
+        );
+    }
+
+    #[test]
+    fn bump_fee_with_lower_fee_rate() {
+        let payment = create_builder(FEE_RATE, &[500], 300).build().unwrap();
+        let builder = create_builder(FeeRate(50), &[500], 300);
+
+        assert_eq!(
+            Err(WalletErr::InsufficientFeeRate),
+            builder.bump_fee(&payment)
+        );
+    }
+
+    #[test]
+    fn bump_fee_of_payment_with_unknown_coins() {
+        let payment = create_builder(FEE_RATE, &[500], 300).build().unwrap();
+        let mut builder = create_builder(FeeRate(300), &[], 300);
+        builder
+            .add_coin(outpoint(7), coin(500, 1), signing_key(1))
+            .unwrap();
+
+        assert_eq!(Err(WalletErr::UnknownCoin), builder.bump_fee(&payment));
+    }
+
+    #[test]
+    fn bump_fee_with_insufficient_funds() {
+        let payment = create_builder(FEE_RATE, &[330], 300).build().unwrap();
+        let builder = create_builder(FeeRate(1_000), &[330], 300);
+
+        assert_eq!(
+            Err(WalletErr::InsufficientFunds),
+            builder.bump_fee(&payment)
+        );
+    }
+}